@@ -5,7 +5,7 @@
 use bstr::ByteSlice;
 use clap::Arg;
 
-use crate::branchloc::BranchLocator;
+use crate::{branchloc::BranchLocator, stack::DependencyPolicy};
 
 /// The `--branch`/`-b` option for selecting an alternative branch.
 pub(crate) fn branch_arg() -> Arg {
@@ -66,6 +66,40 @@ pub(crate) fn push_conflicts_arg() -> clap::Arg {
         .action(clap::ArgAction::Set)
 }
 
+/// The --deps option determining how patch dependencies are handled.
+pub(crate) fn deps_arg() -> clap::Arg {
+    clap::Arg::new("deps")
+        .long("deps")
+        .help("\"carry\", \"check\", or \"ignore\" patch dependencies")
+        .long_help(
+            "Either \"carry\", \"check\", or \"ignore\" patch dependencies.\n\
+             \n\
+             A patch depends on another patch if it modifies lines changed by, or \
+             adjacent to lines changed by, the other patch. Dependencies may also be \
+             declared explicitly using `stg deps --add`. See `stg deps` to inspect \
+             dependencies.\n\
+             \n\
+             Using `--deps=carry` (or just `--deps`) carries dependencies along with \
+             the patches being operated on. For example, pushing a patch also pushes \
+             the patches it depends on and popping a patch also pops the patches \
+             that depend on it.\n\
+             \n\
+             Using `--deps=check` refuses any operation that would leave a patch \
+             applied without its dependencies applied below it.\n\
+             \n\
+             Using `--deps=ignore` does not consider dependencies. This is the \
+             default behavior unless the \"stgit.deps\" variable is set to \
+             \"carry\" or \"check\".",
+        )
+        .hide_possible_values(true)
+        .value_name("policy")
+        .value_parser(["carry", "check", "ignore"])
+        .num_args(0..=1)
+        .require_equals(true)
+        .default_missing_value("carry")
+        .action(clap::ArgAction::Set)
+}
+
 pub(crate) fn committer_date_is_author_date_arg() -> clap::Arg {
     Arg::new("committer-date-is-author-date")
         .long("committer-date-is-author-date")
@@ -78,6 +112,44 @@ pub(crate) fn committer_date_is_author_date_arg() -> clap::Arg {
         .action(clap::ArgAction::SetTrue)
 }
 
+/// The `--continue`, `--skip`, and `--abort` options for resuming an operation halted
+/// by push conflicts.
+pub(crate) fn operation_args() -> [Arg; 3] {
+    [
+        Arg::new("continue")
+            .long("continue")
+            .help("Continue the operation after resolving conflicts")
+            .long_help(
+                "Continue an operation that was halted by push conflicts. Resolved \
+                 changes in the index are refreshed into the conflicting patch and \
+                 the remaining patches are pushed.",
+            )
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with_all(["skip", "abort"]),
+        Arg::new("skip")
+            .long("skip")
+            .help("Skip the conflicting patch and continue the operation")
+            .long_help(
+                "Continue an operation that was halted by push conflicts, but \
+                 without the conflicting patch. The conflicting patch is popped and \
+                 restored to its state from before it was pushed, any changes in the \
+                 index and worktree are discarded, and the remaining patches are \
+                 pushed.",
+            )
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with("abort"),
+        Arg::new("abort")
+            .long("abort")
+            .help("Abort the operation and restore the original stack")
+            .long_help(
+                "Abort an operation that was halted by push conflicts. The stack is \
+                 reset to its state prior to the operation and any changes in the \
+                 index and worktree are discarded.",
+            )
+            .action(clap::ArgAction::SetTrue),
+    ]
+}
+
 /// The `--diff-opt`/`-O` option for pass-through to subordinate `git` processes.
 pub(crate) fn diff_opts_arg() -> Arg {
     Arg::new("git-diff-opt")
@@ -98,6 +170,16 @@ pub(crate) fn diff_opts_arg() -> Arg {
         .value_hint(clap::ValueHint::Other)
 }
 
+/// Determine whether `--continue`, `--skip`, or `--abort` was used.
+///
+/// The provided `matches` must come from a [`clap::Command`] setup with
+/// [`operation_args()`].
+pub(crate) fn resume_operation_requested(matches: &clap::ArgMatches) -> bool {
+    ["continue", "skip", "abort"]
+        .iter()
+        .any(|id| matches.get_flag(id))
+}
+
 /// Get a `&str` from a `clap::ArgMatches` instance for the given `id`.
 ///
 /// This function may be cleaner than calling `ArgMatches::get_one::<String>()` directly
@@ -167,3 +249,20 @@ pub(crate) fn resolve_allow_push_conflicts(
         .map(|s| s == "allow")
         .unwrap_or_else(|| config.boolean("stgit.push.allow-conflicts").unwrap_or(true))
 }
+
+pub(crate) fn resolve_deps_policy(
+    config: &gix::config::Snapshot,
+    matches: &clap::ArgMatches,
+) -> anyhow::Result<DependencyPolicy> {
+    if let Some(policy) = get_one_str(matches, "deps") {
+        policy.parse()
+    } else if let Some(policy) = config.string("stgit.deps") {
+        policy
+            .to_str()
+            .map_err(|_| anyhow::anyhow!("invalid `stgit.deps` value"))?
+            .parse()
+            .map_err(|e| anyhow::anyhow!("`stgit.deps`: {e}"))
+    } else {
+        Ok(DependencyPolicy::Ignore)
+    }
+}
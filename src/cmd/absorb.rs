@@ -0,0 +1,308 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg absorb` implementation.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use bstr::{BString, ByteSlice};
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    color::get_color_stdout,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::PatchName,
+    print_warning_message,
+    stack::{patch_diff, InitializationPolicy, LineOwners, Stack, StackAccess, StackStateAccess},
+    stupid::{parse_diff, FileDiff, Stupid, StupidContext},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "absorb",
+    category: super::CommandCategory::PatchManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Fold local changes into the applied patches they belong with")
+        .long_about(
+            "Fold each hunk of the local changes in the worktree into the applied patch \
+             that introduced the lines surrounding the hunk.\n\
+             \n\
+             A hunk that modifies or removes lines is absorbed into the patch that last \
+             modified all of those lines. A hunk that only adds lines is absorbed into \
+             the patch that last modified the lines immediately before and after the \
+             added lines. Hunks that cannot be unambiguously attributed to a single \
+             applied patch remain in the worktree, as do changes to binary files, file \
+             modes, and added, deleted, or renamed files.\n\
+             \n\
+             All affected patches are updated in a single transaction, such that the \
+             absorb may be reverted with `stg undo`. If any patch above an updated \
+             patch would not apply cleanly, no changes are made.",
+        )
+        .arg(
+            Arg::new("dry-run")
+                .long("dry-run")
+                .short('n')
+                .help("Show which patch each hunk would be absorbed into")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(argset::committer_date_is_author_date_arg())
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
+    let stupid = repo.stupid();
+
+    repo.check_repository_state()?;
+    let statuses = stupid.statuses(None)?;
+    statuses.check_conflicts()?;
+    statuses.check_index_clean()?;
+    stack.check_head_top_mismatch()?;
+
+    if stack.applied().is_empty() {
+        return Err(super::Error::NoAppliedPatches.into());
+    }
+    let applied: Vec<PatchName> = stack.applied().to_vec();
+    let head_tree_id = stack.get_branch_head().tree_id()?.detach();
+
+    stupid.update_index_refresh()?;
+    let diff = stupid.diff_index(
+        head_tree_id,
+        ["-U0", "--no-renames", "--no-ext-diff", "--no-color"],
+        None::<Vec<&str>>,
+    )?;
+    let files = parse_diff(&diff)?;
+    if files.is_empty() {
+        return Err(anyhow!("no local changes to absorb"));
+    }
+
+    let mut owners = LineOwners::default();
+    for (index, patchname) in applied.iter().enumerate() {
+        let patch_diff = patch_diff(&stupid, stack.get_patch_commit(patchname))?;
+        owners.record(&parse_diff(&patch_diff)?, index);
+    }
+
+    // Absorbing patch, if any, for each hunk of each file.
+    let mut assignments: Vec<Vec<Option<usize>>> = files
+        .iter()
+        .map(|file| {
+            file.hunks
+                .iter()
+                .map(|hunk| {
+                    if is_plain_modification(file) {
+                        owners.owner_of_hunk(file.path, hunk)
+                    } else {
+                        None
+                    }
+                })
+                .collect()
+        })
+        .collect();
+
+    // Determine the tree with each patch's absorbed hunks applied to HEAD. Hunks for
+    // patches whose trees do not accommodate the hunks remain in the worktree.
+    let mut absorbed_trees: BTreeMap<usize, gix::ObjectId> = BTreeMap::new();
+    for (index, patchname) in applied.iter().enumerate() {
+        let subset = subset_diff(&files, &assignments, |owner| owner == Some(index));
+        if subset.is_empty() {
+            continue;
+        }
+        let absorbed_tree_id = apply_to_tree(&stupid, head_tree_id, subset)?;
+        let patch_tree_id = stack.get_patch_commit(patchname).tree_id()?.detach();
+        let applies = stupid.with_temp_index(|stupid_temp| {
+            stupid_temp.read_tree(patch_tree_id)?;
+            stupid_temp.apply_treediff_to_index(head_tree_id, absorbed_tree_id, true)
+        })?;
+        if applies {
+            absorbed_trees.insert(index, absorbed_tree_id);
+        } else {
+            for owner in assignments.iter_mut().flatten() {
+                if *owner == Some(index) {
+                    *owner = None;
+                }
+            }
+        }
+    }
+
+    let remaining = files
+        .iter()
+        .zip(&assignments)
+        .map(|(file, owners)| {
+            if file.hunks.is_empty() {
+                1
+            } else {
+                owners.iter().filter(|owner| owner.is_none()).count()
+            }
+        })
+        .sum::<usize>();
+
+    if matches.get_flag("dry-run") {
+        for (file, owners) in files.iter().zip(&assignments) {
+            let path = file.path.strip_prefix(b"a/").unwrap_or(file.path).as_bstr();
+            for (hunk, owner) in file.hunks.iter().zip(owners) {
+                if let Some(index) = owner {
+                    println!("{path}:{} {}", hunk.old_start, applied[*index]);
+                }
+            }
+        }
+    }
+
+    if remaining > 0 {
+        print_warning_message(
+            matches,
+            &format!(
+                "{remaining} hunk{} could not be absorbed and remain{} in the worktree",
+                if remaining == 1 { "" } else { "s" },
+                if remaining == 1 { "s" } else { "" },
+            ),
+        );
+    }
+
+    let Some(&lowest) = absorbed_trees.keys().next() else {
+        return Err(anyhow!("no hunks could be absorbed"));
+    };
+
+    if matches.get_flag("dry-run") {
+        return Ok(());
+    }
+
+    let all_absorbed = subset_diff(&files, &assignments, |owner| owner.is_some());
+    let new_top_tree_id = apply_to_tree(&stupid, head_tree_id, all_absorbed)?;
+    let committer_date_is_author_date = matches.get_flag("committer-date-is-author-date");
+
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(false)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            let to_pop = applied[lowest + 1..].to_vec();
+            let popped_extra = trans.pop_patches(|pn| to_pop.contains(pn))?;
+            assert!(popped_extra.is_empty());
+
+            for (index, patchname) in applied.iter().enumerate().skip(lowest) {
+                if index > lowest {
+                    // Halting would leave a partially absorbed stack, so turn any halt
+                    // into an error to have the whole transaction rolled back.
+                    trans
+                        .push_patches(&[patchname], false)
+                        .map_err(|e| anyhow!("{e:#}"))?;
+                }
+                if let Some(&absorbed_tree_id) = absorbed_trees.get(&index) {
+                    let commit_id = absorb_into_patch(
+                        &repo,
+                        patchname,
+                        trans.get_patch_commit(patchname),
+                        head_tree_id,
+                        absorbed_tree_id,
+                        committer_date_is_author_date,
+                    )?;
+                    trans.update_patch(patchname, commit_id)?;
+                }
+            }
+
+            if trans.top().tree_id()?.detach() != new_top_tree_id {
+                return Err(anyhow!(
+                    "absorbed changes could not be cleanly folded into the stack"
+                ));
+            }
+            Ok(())
+        })
+        .execute("absorb")?;
+
+    // The index matched HEAD prior to the absorb; it now needs to match the new HEAD
+    // such that only the unabsorbed changes remain in the worktree.
+    stupid.read_tree(new_top_tree_id)?;
+    stupid.update_index_refresh()?;
+
+    Ok(())
+}
+
+/// Determine whether a file's diff only modifies the content of an existing file.
+fn is_plain_modification(file: &FileDiff) -> bool {
+    file.header.lines().skip(1).all(|line| {
+        line.starts_with(b"index ") || line.starts_with(b"--- ") || line.starts_with(b"+++ ")
+    }) && !file.binary
+}
+
+/// Assemble a diff from the hunks whose assigned owner is selected.
+fn subset_diff(
+    files: &[FileDiff],
+    assignments: &[Vec<Option<usize>>],
+    select: impl Fn(Option<usize>) -> bool,
+) -> BString {
+    let mut diff = BString::default();
+    for (file, owners) in files.iter().zip(assignments) {
+        let mut hunks = file
+            .hunks
+            .iter()
+            .zip(owners)
+            .filter(|(_, owner)| select(**owner))
+            .peekable();
+        if hunks.peek().is_some() {
+            diff.extend_from_slice(file.header);
+            for (hunk, _) in hunks {
+                diff.extend_from_slice(hunk.text);
+            }
+        }
+    }
+    diff
+}
+
+/// Write the tree resulting from applying a zero-context diff to a tree.
+fn apply_to_tree(
+    stupid: &StupidContext,
+    tree_id: gix::ObjectId,
+    diff: BString,
+) -> Result<gix::ObjectId> {
+    stupid.with_temp_index(|stupid_temp| {
+        stupid_temp.read_tree(tree_id)?;
+        stupid_temp.apply_unidiff_zero_to_index(diff.as_ref())?;
+        stupid_temp.write_tree()
+    })
+}
+
+/// Create a replacement patch commit with the changes between `head_tree_id` and
+/// `absorbed_tree_id` merged into the patch's tree.
+fn absorb_into_patch(
+    repo: &gix::Repository,
+    patchname: &PatchName,
+    patch_commit: &gix::Commit,
+    head_tree_id: gix::ObjectId,
+    absorbed_tree_id: gix::ObjectId,
+    committer_date_is_author_date: bool,
+) -> Result<gix::ObjectId> {
+    let patch_commit_ref = patch_commit.decode()?;
+    let tree_id = repo.stupid().with_temp_index(|stupid_temp| {
+        stupid_temp.read_tree(patch_commit_ref.tree())?;
+        if stupid_temp.apply_treediff_to_index(head_tree_id, absorbed_tree_id, true)? {
+            stupid_temp.write_tree()
+        } else {
+            Err(anyhow!(
+                "absorbed changes do not apply to patch `{patchname}`"
+            ))
+        }
+    })?;
+
+    let author = patch_commit.author_strict()?;
+    let default_committer = repo.get_committer()?;
+    let committer = if committer_date_is_author_date {
+        let mut committer = default_committer.to_owned();
+        committer.time = author.time;
+        committer
+    } else {
+        default_committer.to_owned()
+    };
+
+    repo.commit_ex(
+        &author,
+        &committer,
+        &patch_commit.message_ex(),
+        tree_id,
+        patch_commit_ref.parents(),
+    )
+}
@@ -0,0 +1,290 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg bisect` implementation.
+
+use std::ffi::OsString;
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::{range, PatchName, PatchRange, RangeConstraint},
+    print_info_message,
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stupid::Stupid,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "bisect",
+    category: super::CommandCategory::StackInspection,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Find the first patch for which a command fails")
+        .long_about(
+            "Find the first patch in the stack for which a command fails.\n\
+             \n\
+             Patches are binary-searched by moving the top of the stack, in the \
+             manner of `stg goto`, and running the command with each candidate patch \
+             as the topmost applied patch.\n\
+             \n\
+             The original stack state is restored once the first failing patch is \
+             found, when the command cannot be run, or when bisecting is interrupted.",
+        )
+        .subcommand_required(true)
+        .subcommand(
+            clap::Command::new("run")
+                .about("Bisect patches by running a command")
+                .long_about(
+                    "Bisect patches by running a command.\n\
+                     \n\
+                     The command is run from the root of the worktree with each \
+                     candidate patch as the topmost applied patch. An exit status of \
+                     0 indicates that the patch is good, and an exit status of 1 \
+                     through 127, except 125, indicates that the patch is bad. As \
+                     with `git bisect run`, an exit status of 125 indicates that the \
+                     patch cannot be tested and should be skipped. Any other exit \
+                     status, e.g. from the command being terminated by a signal, \
+                     aborts the bisection.\n\
+                     \n\
+                     The patches below the first patch in the range are assumed to be \
+                     good and the last patch in the range must be bad.\n\
+                     \n\
+                     By default, all applied patches are bisected. Use '--range' to \
+                     bisect a different, contiguous range of patches which may \
+                     include unapplied patches.\n\
+                     \n\
+                     When the command consists of a single argument, it is run with \
+                     the shell. This allows shell constructs such as `stg bisect run \
+                     \"make && make test\"`.",
+                )
+                .arg(
+                    Arg::new("range")
+                        .long("range")
+                        .short('r')
+                        .help("Bisect patches in <patch-range>")
+                        .value_name("patch-range")
+                        .value_hint(ValueHint::Other)
+                        .allow_hyphen_values(true)
+                        .value_parser(clap::value_parser!(PatchRange)),
+                )
+                .arg(
+                    Arg::new("command")
+                        .help("Command to run for each patch")
+                        .value_name("command")
+                        .required(true)
+                        .num_args(1..)
+                        .trailing_var_arg(true)
+                        .allow_hyphen_values(true)
+                        .value_hint(ValueHint::CommandWithArguments)
+                        .value_parser(clap::value_parser!(OsString)),
+                ),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let Some(("run", sub_matches)) = matches.subcommand() else {
+        panic!("valid subcommand is expected")
+    };
+
+    let repo = gix::Repository::open()?;
+    let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
+    let stupid = repo.stupid();
+
+    repo.check_repository_state()?;
+    let statuses = stupid.statuses(None)?;
+    statuses.check_conflicts()?;
+    stack.check_head_top_mismatch()?;
+    statuses.check_index_and_worktree_clean()?;
+
+    let patchnames = if let Some(range) = sub_matches.get_one::<PatchRange>("range") {
+        let patchnames = range::resolve_names_contiguous(
+            &stack,
+            [range],
+            RangeConstraint::VisibleWithAppliedBoundary,
+        )?;
+        let positions: Vec<usize> = patchnames
+            .iter()
+            .map(|patchname| {
+                stack
+                    .applied()
+                    .iter()
+                    .chain(stack.unapplied())
+                    .position(|pn| pn == patchname)
+                    .expect("range already constrained to visible patches")
+            })
+            .collect();
+        if positions.windows(2).any(|w| w[0] > w[1]) {
+            return Err(anyhow!("patch range `{range}` is not in stack order"));
+        }
+        patchnames
+    } else {
+        stack.applied().to_vec()
+    };
+
+    if patchnames.is_empty() {
+        return Err(super::Error::NoAppliedPatches.into());
+    }
+
+    let command: Vec<&OsString> = sub_matches
+        .get_many::<OsString>("command")
+        .expect("required argument")
+        .collect();
+
+    let orig_applied = stack.applied().to_vec();
+    let orig_unapplied = stack.unapplied().to_vec();
+
+    let first_bad = crate::signal::critical(|| {
+        let outcome = bisect(&repo, sub_matches, &patchnames, &command);
+        let restored = restore(&repo, sub_matches, &orig_applied, &orig_unapplied);
+        let first_bad = outcome?;
+        restored?;
+        Ok(first_bad)
+    })?;
+
+    if let Some(patchname) = first_bad {
+        println!("`{patchname}` is the first failing patch");
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "command succeeds for the last patch in the range, `{}`",
+            patchnames.last().unwrap()
+        ))
+    }
+}
+
+/// Outcome of running the command against a patch.
+enum Verdict {
+    Good,
+    Bad,
+    Skip,
+}
+
+/// Binary search for the first patch for which the command fails.
+///
+/// Skipped patches are avoided by testing the untested patch nearest to the midpoint
+/// of the remaining range instead.
+///
+/// Returns `None` if the command succeeds for the last patch.
+fn bisect(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    patchnames: &[PatchName],
+    command: &[&OsString],
+) -> Result<Option<PatchName>> {
+    let mut good = 0;
+    let mut bad = patchnames.len() - 1;
+    let mut skipped = vec![false; patchnames.len()];
+
+    match test_patch(repo, matches, &patchnames[bad], command, bad - good + 1)? {
+        Verdict::Good => return Ok(None),
+        Verdict::Skip => {
+            return Err(anyhow!(
+                "cannot test the last patch in the range, `{}`",
+                patchnames[bad]
+            ))
+        }
+        Verdict::Bad => {}
+    }
+
+    while good < bad {
+        let mid = good + (bad - good) / 2;
+        let Some(candidate) = (good..bad)
+            .filter(|&i| !skipped[i])
+            .min_by_key(|&i| i.abs_diff(mid))
+        else {
+            let candidates = patchnames[good..=bad]
+                .iter()
+                .map(|pn| format!("`{pn}`"))
+                .collect::<Vec<_>>()
+                .join(", ");
+            return Err(anyhow!(
+                "only skipped patches are left to test; \
+                 the first failing patch could be any of: {candidates}"
+            ));
+        };
+        let remaining = (good..=bad).filter(|&i| !skipped[i]).count();
+        match test_patch(repo, matches, &patchnames[candidate], command, remaining)? {
+            Verdict::Good => good = candidate + 1,
+            Verdict::Bad => bad = candidate,
+            Verdict::Skip => skipped[candidate] = true,
+        }
+    }
+
+    Ok(Some(patchnames[bad].clone()))
+}
+
+/// Go to the given patch and run the command.
+fn test_patch(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    patchname: &PatchName,
+    command: &[&OsString],
+    remaining: usize,
+) -> Result<Verdict> {
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+    if stack.applied().last() != Some(patchname) {
+        stack
+            .setup_transaction()
+            .use_index_and_worktree(true)
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| super::goto::goto_patch(trans, patchname, false))
+            .execute("bisect run")?;
+    }
+
+    print_info_message(
+        matches,
+        &format!("Testing `{patchname}` ({remaining} patches left to bisect)"),
+    );
+
+    let display_command = super::exec::display_command(command);
+    let mut shell_command = super::exec::shell_command(command);
+    if let Some(work_dir) = repo.work_dir() {
+        shell_command.current_dir(work_dir);
+    }
+
+    let status = shell_command
+        .status()
+        .map_err(|e| anyhow!("could not execute `{display_command}`: {e}"))?;
+
+    if crate::signal::is_signaled() {
+        return Err(anyhow!("`{display_command}` interrupted at `{patchname}`"));
+    }
+
+    match status.code() {
+        Some(0) => Ok(Verdict::Good),
+        Some(125) => {
+            print_info_message(matches, &format!("Skipping `{patchname}`"));
+            Ok(Verdict::Skip)
+        }
+        Some(1..=127) => Ok(Verdict::Bad),
+        _ => Err(anyhow!(
+            "`{display_command}` failed ({status}) at `{patchname}`; bisect aborted"
+        )),
+    }
+}
+
+/// Restore the original applied and unapplied patches.
+fn restore(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    applied: &[PatchName],
+    unapplied: &[PatchName],
+) -> Result<()> {
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+    if stack.applied() == applied && stack.unapplied() == unapplied {
+        return Ok(());
+    }
+    stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| trans.reorder_patches(Some(applied), Some(unapplied), None))
+        .execute("bisect run (restore)")?;
+    Ok(())
+}
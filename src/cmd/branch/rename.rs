@@ -5,10 +5,14 @@
 use std::str::FromStr;
 
 use anyhow::Result;
+use bstr::ByteSlice;
 
 use crate::{
     ext::RepositoryExtended,
-    stack::{state_refname_from_branch_name, InitializationPolicy, Stack, StackAccess},
+    stack::{
+        get_checkpoint_refname_prefix, get_version_refname_prefix, state_refname_from_branch_name,
+        InitializationPolicy, Stack, StackAccess,
+    },
     stupid::Stupid,
     wrap::PartialRefName,
 };
@@ -56,27 +60,31 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
         old_branchname,
         InitializationPolicy::RequireInitialized,
     ) {
-        let state_commit = repo
+        let message = format!("rename {old_branchname} to {new_branchname}");
+        let state_id = repo
             .find_reference(stack.get_stack_refname())
             .expect("just found this stack state reference")
             .into_fully_peeled_id()?
-            .object()?
-            .try_into_commit()?;
-        repo.edit_reference(gix::refs::transaction::RefEdit {
-            change: gix::refs::transaction::Change::Update {
-                log: gix::refs::transaction::LogChange {
-                    mode: gix::refs::transaction::RefLog::AndReference,
-                    force_create_reflog: false,
-                    message: format!("rename {old_branchname} to {new_branchname}").into(),
-                },
-                expected: gix::refs::transaction::PreviousValue::MustNotExist,
-                new: gix::refs::Target::Peeled(state_commit.id),
-            },
-            name: gix::refs::FullName::try_from(state_refname_from_branch_name(
-                new_branchname.as_ref(),
-            ))?,
-            deref: false,
-        })?;
+            .detach();
+        let mut edits = vec![ref_update(
+            state_refname_from_branch_name(new_branchname.as_ref()),
+            state_id,
+            gix::refs::transaction::PreviousValue::MustNotExist,
+            &message,
+        )?];
+        edits.extend(prefixed_ref_updates(
+            repo,
+            &get_version_refname_prefix(old_branchname.as_ref()),
+            &get_version_refname_prefix(new_branchname.as_ref()),
+            &message,
+        )?);
+        edits.extend(prefixed_ref_updates(
+            repo,
+            &get_checkpoint_refname_prefix(old_branchname.as_ref()),
+            &get_checkpoint_refname_prefix(new_branchname.as_ref()),
+            &message,
+        )?);
+        repo.edit_references(edits)?;
         stupid
             .config_rename_section(
                 &format!("branch.{old_branchname}.stgit"),
@@ -91,3 +99,60 @@ pub(super) fn dispatch(repo: &gix::Repository, matches: &clap::ArgMatches) -> Re
     super::set_stgit_parent(repo, new_branchname, parent_branchname.as_ref())?;
     Ok(())
 }
+
+/// Make a reference edit that points `name` at `id`.
+fn ref_update(
+    name: String,
+    id: gix::ObjectId,
+    expected: gix::refs::transaction::PreviousValue,
+    message: &str,
+) -> Result<gix::refs::transaction::RefEdit> {
+    Ok(gix::refs::transaction::RefEdit {
+        change: gix::refs::transaction::Change::Update {
+            log: gix::refs::transaction::LogChange {
+                mode: gix::refs::transaction::RefLog::AndReference,
+                force_create_reflog: false,
+                message: message.into(),
+            },
+            expected,
+            new: gix::refs::Target::Peeled(id),
+        },
+        name: gix::refs::FullName::try_from(name)?,
+        deref: false,
+    })
+}
+
+/// Make reference edits that copy every reference under `old_prefix` to the same
+/// name under `new_prefix`.
+///
+/// The references under `old_prefix` are left in place.
+fn prefixed_ref_updates(
+    repo: &gix::Repository,
+    old_prefix: &str,
+    new_prefix: &str,
+    message: &str,
+) -> Result<Vec<gix::refs::transaction::RefEdit>> {
+    let mut edits = Vec::new();
+    for mut reference in repo
+        .references()?
+        .prefixed(old_prefix)?
+        .filter_map(Result::ok)
+    {
+        let Some(suffix) = reference
+            .name()
+            .as_bstr()
+            .strip_prefix(old_prefix.as_bytes())
+            .and_then(|suffix| suffix.to_str().ok())
+            .map(ToString::to_string)
+        else {
+            continue;
+        };
+        edits.push(ref_update(
+            format!("{new_prefix}{suffix}"),
+            reference.peel_to_id_in_place()?.detach(),
+            gix::refs::transaction::PreviousValue::Any,
+            message,
+        )?);
+    }
+    Ok(edits)
+}
@@ -0,0 +1,107 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg checkpoint` implementation.
+
+use std::io::Write;
+
+use anyhow::Result;
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::RepositoryExtended,
+    print_info_message,
+    stack::{InitializationPolicy, Stack},
+    wrap::PartialRefName,
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "checkpoint",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Record a named checkpoint of the stack state")
+        .long_about(
+            "Record the stack's current state as a named checkpoint. A checkpoint \
+             entry is added to the stack log, as shown by 'stg log', and the \
+             checkpoint may later be returned to with 'stg undo --to <name>' or 'stg \
+             reset --checkpoint <name>', regardless of how many operations have been \
+             performed since.\n\
+             \n\
+             Each checkpoint is stored as a reference, `refs/checkpoints/<branch>/<name>`, \
+             to a stack state commit. Checkpoints are kept when the stack log is cleared \
+             with 'stg log --clear'.",
+        )
+        .override_usage(super::make_usage(
+            "stg checkpoint",
+            &["[OPTIONS] <name>", "--delete <name>", "--list"],
+        ))
+        .arg(
+            Arg::new("name")
+                .help("Name of the checkpoint")
+                .value_name("name")
+                .required_unless_present("list")
+                .value_parser(clap::value_parser!(PartialRefName))
+                .value_hint(ValueHint::Other),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("Replace an existing checkpoint with the same name")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("delete")
+                .long("delete")
+                .short('d')
+                .help("Delete the named checkpoint")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("force"),
+        )
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .short('l')
+                .help("List checkpoints")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["name", "force", "delete"]),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+
+    if matches.get_flag("list") {
+        let mut stdout = std::io::stdout().lock();
+        for (name, _) in stack.get_checkpoints()? {
+            writeln!(stdout, "{name}")?;
+        }
+        return Ok(());
+    }
+
+    let name = matches
+        .get_one::<PartialRefName>("name")
+        .expect("name is required unless listing");
+
+    if matches.get_flag("delete") {
+        stack.delete_checkpoint(name.as_ref())?;
+        print_info_message(matches, &format!("Deleted checkpoint `{name}`"));
+    } else {
+        stack.check_head_top_mismatch()?;
+        stack.create_checkpoint(name.as_ref(), matches.get_flag("force"))?;
+        print_info_message(matches, &format!("Recorded checkpoint `{name}`"));
+    }
+    Ok(())
+}
@@ -9,7 +9,7 @@ use crate::{
     color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended},
     patch::PatchName,
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{InitializationPolicy, Operation, Stack, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -85,7 +85,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     }
 
     if !to_delete.is_empty() {
-        stack
+        let stack = stack
             .setup_transaction()
             .allow_conflicts(true)
             .use_index_and_worktree(false)
@@ -96,6 +96,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 Ok(())
             })
             .execute("delete")?;
+        Operation::clear_stale(&stack)?;
     }
 
     Ok(())
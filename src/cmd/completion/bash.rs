@@ -143,6 +143,21 @@ fn write_command_func(script: &mut ShStream, fname: &str, command: &clap::Comman
         let num_vals = (value_range.max_values() > 1 && value_range.max_values() < usize::MAX)
             .then_some(value_range.max_values());
         script.ensure_blank_line();
+        if arg.get_value_hint() == clap::ValueHint::CommandWithArguments {
+            // The command's name is completed, followed by default completion of
+            // its arguments.
+            script.line(&f!("if (( pos_index == {pos_index} )); then"));
+            script.indent();
+            insert_compreply(script, arg);
+            script.dedent();
+            script.line(&f!("elif (( pos_index > {pos_index} )); then"));
+            script.indent();
+            script.line("mapfile -t COMPREPLY < <(compgen -o default -- \"$cur\")");
+            script.dedent();
+            script.line("fi");
+            pos_index += 1;
+            continue;
+        }
         if matches!(arg.get_action(), clap::ArgAction::Append)
             || (num_vals.is_some() && arg.get_value_delimiter() == Some(' '))
         {
@@ -302,7 +317,9 @@ fn insert_compreply(script: &mut ShStream, arg: &clap::Arg) {
             clap::ValueHint::EmailAddress => {
                 script.line(":");
             }
-            clap::ValueHint::CommandName => {
+            clap::ValueHint::CommandName
+            | clap::ValueHint::CommandString
+            | clap::ValueHint::CommandWithArguments => {
                 script.line("mapfile -t COMPREPLY < <(compgen -A command -- \"$cur\")");
             }
             clap::ValueHint::Username => {
@@ -312,8 +329,6 @@ fn insert_compreply(script: &mut ShStream, arg: &clap::Arg) {
                 script.line("mapfile -t COMPREPLY < <(compgen -A hostname -- \"$cur\")");
             }
             clap::ValueHint::ExecutablePath => todo!(),
-            clap::ValueHint::CommandString => todo!(),
-            clap::ValueHint::CommandWithArguments => todo!(),
             clap::ValueHint::Url => todo!(),
             _ => todo!(),
         };
@@ -0,0 +1,230 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg cover` implementation.
+
+use std::{io::Read, path::PathBuf};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+use clap::{Arg, ArgMatches};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::patchedit,
+    stack::{CoverLetter, InitializationPolicy, Stack, StackAccess, StackStateAccess},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "cover",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+/// Line separating the cover letter's description from its changes section.
+const CHANGES_HEADING: &str = "Changes:";
+
+static EDIT_INSTRUCTION: &str = "\
+    # Please enter the cover letter for the patch series. Lines starting\n\
+    # with '#' will be ignored. The first line is the title of the series,\n\
+    # which may be followed by a blank line and a description of the series.\n\
+    # Changes since the previous version of the series may be described\n\
+    # after a line containing only \"Changes:\". An empty cover letter\n\
+    # aborts the edit.\n";
+
+/// Default file name for interactively editable cover letter.
+static EDIT_FILE_NAME: &str = ".stgit-cover.txt";
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Manage the patch series cover letter")
+        .long_about(
+            "Manage the cover letter for the stack's patch series.\n\
+             \n\
+             The cover letter is composed of a title, a description of the patch \
+             series, and an optional description of the changes since the previous \
+             version of the series. It is used by `stg email format --cover-letter` \
+             in place of git's cover letter template.\n\
+             \n\
+             The cover letter is recorded in the stack state and is thus tracked by \
+             `stg log`, may be reverted with `stg undo` and `stg redo`, and is \
+             recorded with each version of the series (see `stg snapshot`).",
+        )
+        .arg(argset::branch_arg().global(true))
+        .subcommand_required(true)
+        .subcommand(
+            clap::Command::new("edit")
+                .about("Edit the cover letter")
+                .long_about(
+                    "Edit the cover letter using the configured editor, or replace it \
+                     with the contents of a file.\n\
+                     \n\
+                     The first line of the cover letter is the series title, which \
+                     may be followed by a blank line and a description of the series. \
+                     Changes since the previous version of the series may be described \
+                     after a line containing only \"Changes:\".",
+                )
+                .arg(
+                    Arg::new("file")
+                        .long("file")
+                        .short('f')
+                        .help("Use the contents of <path> instead of invoking the editor")
+                        .long_help(
+                            "Use the contents of <path> as the cover letter instead of \
+                             invoking the editor. Use '-' to read from stdin.",
+                        )
+                        .num_args(1)
+                        .value_name("path")
+                        .value_hint(clap::ValueHint::FilePath)
+                        .value_parser(clap::value_parser!(PathBuf)),
+                ),
+        )
+        .subcommand(clap::Command::new("show").about("Print the cover letter"))
+        .subcommand(clap::Command::new("delete").about("Delete the cover letter"))
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+
+    match matches.subcommand() {
+        Some(("edit", sub_matches)) => {
+            let cover = if let Some(path) = sub_matches.get_one::<PathBuf>("file") {
+                let mut buf = Vec::new();
+                if path == &PathBuf::from("-") {
+                    std::io::stdin().read_to_end(&mut buf)?;
+                } else {
+                    buf = std::fs::read(path)?;
+                }
+                parse_cover(&buf, false)?
+            } else {
+                let mut template = stack.cover().map(format_cover).unwrap_or_default();
+                template.push_str(EDIT_INSTRUCTION);
+                std::fs::write(EDIT_FILE_NAME, template)?;
+                let buf = patchedit::call_editor(EDIT_FILE_NAME, &repo.config_snapshot())?;
+                parse_cover(&buf, true)?
+            };
+            let cover = cover.ok_or_else(|| anyhow!("aborting due to empty cover letter"))?;
+            if stack.cover() == Some(&cover) {
+                return Ok(());
+            }
+            update_cover(stack, Some(cover), "cover edit", sub_matches)
+        }
+        Some(("show", _)) => {
+            let cover = stack.cover().ok_or_else(|| {
+                anyhow!("no cover letter for branch `{}`", stack.get_branch_name())
+            })?;
+            print!("{}", format_cover(cover));
+            Ok(())
+        }
+        Some(("delete", sub_matches)) => {
+            if stack.cover().is_none() {
+                return Err(anyhow!(
+                    "no cover letter for branch `{}`",
+                    stack.get_branch_name()
+                ));
+            }
+            update_cover(stack, None, "cover delete", sub_matches)
+        }
+        _ => panic!("valid subcommand is expected"),
+    }
+}
+
+fn update_cover(
+    stack: Stack,
+    cover: Option<CoverLetter>,
+    reflog_msg: &str,
+    matches: &ArgMatches,
+) -> Result<()> {
+    stack
+        .setup_transaction()
+        .allow_conflicts(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            trans.set_cover(cover);
+            Ok(())
+        })
+        .execute(reflog_msg)?;
+    Ok(())
+}
+
+/// Format the cover letter in its editable form.
+fn format_cover(cover: &CoverLetter) -> String {
+    let mut text = format!("{}\n", cover.title);
+    if !cover.body.is_empty() {
+        text.push('\n');
+        text.push_str(&cover.body);
+        text.push('\n');
+    }
+    if !cover.changes.is_empty() {
+        text.push('\n');
+        text.push_str(CHANGES_HEADING);
+        text.push('\n');
+        text.push_str(&cover.changes);
+        text.push('\n');
+    }
+    text
+}
+
+/// Parse the editable form of a cover letter.
+///
+/// `None` is returned if the cover letter is empty.
+fn parse_cover(buf: &[u8], strip_comments: bool) -> Result<Option<CoverLetter>> {
+    let text = buf
+        .to_str()
+        .map_err(|_| anyhow!("cover letter is not valid UTF-8"))?;
+    let mut lines = text
+        .lines()
+        .filter(|line| !(strip_comments && line.starts_with('#')))
+        .skip_while(|line| line.trim().is_empty());
+
+    let Some(title) = lines.next() else {
+        return Ok(None);
+    };
+    let title = title.trim();
+    if title == CHANGES_HEADING {
+        return Err(anyhow!("cover letter title is empty"));
+    }
+
+    let mut body = Vec::new();
+    let mut changes = Vec::new();
+    let mut in_changes = false;
+    for line in lines {
+        if !in_changes && line.trim_end() == CHANGES_HEADING {
+            in_changes = true;
+        } else if in_changes {
+            changes.push(line);
+        } else {
+            body.push(line);
+        }
+    }
+
+    Ok(Some(CoverLetter {
+        title: title.to_string(),
+        body: trim_blank_lines(&body),
+        changes: trim_blank_lines(&changes),
+    }))
+}
+
+/// Join lines, omitting leading and trailing blank lines.
+fn trim_blank_lines(lines: &[&str]) -> String {
+    let is_blank = |line: &&str| line.trim().is_empty();
+    let start = lines.iter().position(|line| !is_blank(line));
+    let end = lines.iter().rposition(|line| !is_blank(line));
+    if let (Some(start), Some(end)) = (start, end) {
+        lines[start..=end]
+            .iter()
+            .map(|line| line.trim_end())
+            .collect::<Vec<_>>()
+            .join("\n")
+    } else {
+        String::new()
+    }
+}
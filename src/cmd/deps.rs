@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg deps` implementation.
+
+use std::collections::BTreeSet;
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::{PatchLocator, PatchName},
+    stack::{
+        declared_dependencies, format_declared_dependencies, Dependencies, DependencyKind,
+        InitializationPolicy, Stack, StackStateAccess, DEPENDS_META_KEY,
+    },
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "deps",
+    category: super::CommandCategory::StackInspection,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Show and declare patch dependencies")
+        .long_about(
+            "Show the dependencies of a patch or declare additional dependencies.\n\
+             \n\
+             A patch depends on another patch when it modifies lines that were \
+             modified by the other patch, or lines adjacent to them, such that the \
+             patch would not apply without the other patch. These dependencies are \
+             reported as \"overlap\" dependencies. Overlap dependencies are only \
+             determined between applied and unapplied patches.\n\
+             \n\
+             Dependencies that cannot be determined from the patches' contents may be \
+             declared using '--add' and removed using '--remove'. These dependencies \
+             are reported as \"declared\" dependencies and are recorded in the \
+             patch's \"depends\" metadata (see `stg meta`).\n\
+             \n\
+             By default, the tree of patches that the topmost patch transitively \
+             depends on is shown. Use '--dependents' to instead show the tree of \
+             patches that depend on the patch.\n\
+             \n\
+             The `stg push`, `stg pop`, `stg float`, and `stg sink` commands may \
+             carry or check dependencies using their '--deps' option or the \
+             \"stgit.deps\" configuration variable.",
+        )
+        .override_usage(super::make_usage(
+            "stg deps",
+            &[
+                "[OPTIONS] [patch]",
+                "[OPTIONS] --all",
+                "[OPTIONS] --add <dependency>... [patch]",
+                "[OPTIONS] --remove <dependency>... [patch]",
+            ],
+        ))
+        .arg(
+            Arg::new("patch")
+                .help("Patch to show or update dependencies for")
+                .value_name("patch")
+                .value_hint(ValueHint::Other)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator)),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("dependents")
+                .long("dependents")
+                .short('r')
+                .help("Show patches that depend on the patch")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .short('a')
+                .help("Show the direct dependencies of all visible patches")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["patch", "dependents"]),
+        )
+        .arg(
+            Arg::new("add")
+                .long("add")
+                .help("Declare that the patch depends on <dependency>")
+                .value_name("dependency")
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_hint(ValueHint::Other)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with_all(["all", "dependents"]),
+        )
+        .arg(
+            Arg::new("remove")
+                .long("remove")
+                .help("Remove declared dependency on <dependency>")
+                .value_name("dependency")
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_hint(ValueHint::Other)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .conflicts_with_all(["all", "dependents"]),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+
+    if matches.get_flag("all") {
+        let deps = Dependencies::compute(&repo, &stack)?;
+        for patchname in stack.applied_and_unapplied() {
+            print!("{patchname}:");
+            for (dep, _) in deps.direct(patchname) {
+                print!(" {dep}");
+            }
+            println!();
+        }
+        return Ok(());
+    }
+
+    let patchname = if let Some(patch_loc) = matches.get_one::<PatchLocator>("patch") {
+        patch_loc.resolve_name(&stack)?
+    } else if let Some(top_patchname) = stack.applied().last() {
+        top_patchname.clone()
+    } else {
+        return Err(super::Error::NoAppliedPatches.into());
+    };
+
+    if matches.contains_id("add") || matches.contains_id("remove") {
+        return update_declared(stack, &patchname, matches);
+    }
+
+    let deps = Dependencies::compute(&repo, &stack)?;
+    println!("{patchname}");
+    let mut path = vec![patchname];
+    print_tree(&deps, &mut path, matches.get_flag("dependents"));
+    Ok(())
+}
+
+fn print_tree(deps: &Dependencies, path: &mut Vec<PatchName>, reverse: bool) {
+    let patchname = path.last().expect("path is not empty").clone();
+    let children: Vec<(PatchName, BTreeSet<DependencyKind>)> = if reverse {
+        deps.stack_ordered(deps.direct_dependents(&patchname).map(|(pn, _)| pn.clone()))
+            .into_iter()
+            .rev()
+            .map(|pn| {
+                let kinds = deps
+                    .direct(&pn)
+                    .find(|(dep, _)| *dep == &patchname)
+                    .map(|(_, kinds)| kinds.clone())
+                    .unwrap_or_default();
+                (pn, kinds)
+            })
+            .collect()
+    } else {
+        deps.stack_ordered(deps.direct(&patchname).map(|(pn, _)| pn.clone()))
+            .into_iter()
+            .rev()
+            .map(|pn| {
+                let kinds = deps
+                    .direct(&patchname)
+                    .find(|(dep, _)| *dep == &pn)
+                    .map(|(_, kinds)| kinds.clone())
+                    .unwrap_or_default();
+                (pn, kinds)
+            })
+            .collect()
+    };
+
+    let indent = "  ".repeat(path.len());
+    for (child, kinds) in children {
+        let kinds = kinds
+            .iter()
+            .map(ToString::to_string)
+            .collect::<Vec<_>>()
+            .join(", ");
+        if path.contains(&child) {
+            println!("{indent}{child} [{kinds}] (cycle)");
+        } else {
+            println!("{indent}{child} [{kinds}]");
+            path.push(child);
+            print_tree(deps, path, reverse);
+            path.pop();
+        }
+    }
+}
+
+fn update_declared(stack: Stack, patchname: &PatchName, matches: &ArgMatches) -> Result<()> {
+    let mut declared = declared_dependencies(&stack, patchname);
+
+    if let Some(locators) = matches.get_many::<PatchLocator>("remove") {
+        for loc in locators {
+            let dep = loc.resolve_name(&stack)?;
+            if let Some(pos) = declared.iter().position(|pn| pn == &dep) {
+                declared.remove(pos);
+            } else {
+                return Err(anyhow!(
+                    "patch `{patchname}` does not declare a dependency on `{dep}`"
+                ));
+            }
+        }
+    }
+
+    if let Some(locators) = matches.get_many::<PatchLocator>("add") {
+        for loc in locators {
+            let dep = loc.resolve_name(&stack)?;
+            if &dep == patchname {
+                return Err(anyhow!("patch `{patchname}` cannot depend on itself"));
+            } else if !declared.contains(&dep) {
+                declared.push(dep);
+            }
+        }
+    }
+
+    let value = format_declared_dependencies(&declared);
+    stack
+        .setup_transaction()
+        .allow_conflicts(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            trans.set_patch_meta(
+                patchname,
+                DEPENDS_META_KEY,
+                (!value.is_empty()).then_some(value.as_str()),
+            );
+            Ok(())
+        })
+        .execute(&format!("deps {patchname}"))?;
+    Ok(())
+}
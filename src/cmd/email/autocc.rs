@@ -0,0 +1,204 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Automatic collection of Cc addresses for patch emails.
+//!
+//! Addresses are collected from the trailers of each patch's commit message, as found
+//! in the email body, and from the output of a user-supplied command such as the Linux
+//! kernel's `get_maintainer.pl`.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Context, Result};
+use bstr::ByteSlice;
+
+use super::mbox;
+use crate::patch::edit::parse_trailers;
+
+/// Trailers whose addresses are collected when `stgit.email.auto-cc-trailers` is not
+/// set.
+const DEFAULT_TRAILERS: [&str; 3] = ["Signed-off-by", "Reviewed-by", "Acked-by"];
+
+/// Configuration for automatically collecting Cc addresses.
+pub(super) struct AutoCc {
+    /// Trailer keys whose values are collected.
+    trailers: Vec<String>,
+
+    /// Shell command run with the path to each email.
+    cc_cmd: Option<String>,
+}
+
+impl AutoCc {
+    /// Get the automatic Cc configuration from the command line and configuration.
+    ///
+    /// Returns `None` if automatic Cc collection is not enabled.
+    pub(super) fn from_matches(
+        repo: &gix::Repository,
+        matches: &clap::ArgMatches,
+    ) -> Result<Option<Self>> {
+        if matches.get_flag("no-auto-cc") {
+            return Ok(None);
+        }
+        let config = repo.config_snapshot();
+        let cc_cmd = matches.get_one::<String>("cc-cmd").cloned().or_else(|| {
+            config
+                .string("stgit.email.cc-cmd")
+                .map(|cmd| cmd.to_str_lossy().into_owned())
+        });
+        let enabled = matches.get_flag("auto-cc")
+            || matches.contains_id("cc-cmd")
+            || config.boolean("stgit.email.auto-cc").unwrap_or(false);
+        if !enabled {
+            return Ok(None);
+        }
+        let trailers = if let Some(values) = config
+            .plumbing()
+            .strings_by_key("stgit.email.auto-cc-trailers")
+        {
+            values
+                .iter()
+                .flat_map(|value| {
+                    value
+                        .to_str_lossy()
+                        .split(|c: char| c == ',' || c.is_whitespace())
+                        .filter(|key| !key.is_empty())
+                        .map(ToString::to_string)
+                        .collect::<Vec<_>>()
+                })
+                .collect()
+        } else {
+            DEFAULT_TRAILERS.iter().map(ToString::to_string).collect()
+        };
+        Ok(Some(Self { trailers, cc_cmd }))
+    }
+
+    /// Add automatically collected Cc addresses to each email.
+    ///
+    /// Cover letters, i.e. emails numbered 0, are Cc'd to the addresses collected from
+    /// all the other emails. Addresses already in the `To` or `Cc` headers, and the
+    /// `sender`'s address, are not added again.
+    ///
+    /// Returns whether any addresses were added to each email.
+    pub(super) fn apply(
+        &self,
+        repo: &gix::Repository,
+        emails: &mut [&mut mbox::Email],
+        sender: &str,
+    ) -> Result<Vec<bool>> {
+        let mut collected: Vec<Option<Vec<String>>> = Vec::with_capacity(emails.len());
+        for email in emails.iter() {
+            if email.is_cover_letter() {
+                collected.push(None);
+            } else {
+                collected.push(Some(self.collect(repo, email)?));
+            }
+        }
+        let all_addresses: Vec<String> = collected.iter().flatten().flatten().cloned().collect();
+
+        let sender = mbox::address_email(sender);
+        let mut added = Vec::with_capacity(emails.len());
+        for (email, addresses) in emails.iter_mut().zip(collected) {
+            let addresses = addresses.as_ref().unwrap_or(&all_addresses);
+            let to = email
+                .get_header("To")
+                .map(mbox::split_addresses)
+                .unwrap_or_default();
+            let mut cc = email
+                .get_header("Cc")
+                .map(mbox::split_addresses)
+                .unwrap_or_default();
+            let num_cc = cc.len();
+            for address in addresses {
+                let email_address = mbox::address_email(address);
+                if !email_address.eq_ignore_ascii_case(sender)
+                    && !to.iter().chain(cc.iter()).any(|existing| {
+                        mbox::address_email(existing).eq_ignore_ascii_case(email_address)
+                    })
+                {
+                    cc.push(address.clone());
+                }
+            }
+            if cc.len() > num_cc {
+                email.set_header("Cc", mbox::join_addresses(&cc));
+            }
+            added.push(cc.len() > num_cc);
+        }
+
+        Ok(added)
+    }
+
+    /// Collect the Cc addresses for a single patch email.
+    fn collect(&self, repo: &gix::Repository, email: &mbox::Email) -> Result<Vec<String>> {
+        let mut addresses = Vec::new();
+
+        // The commit message is reconstituted from the subject and the portion of the
+        // body before the three-dash line.
+        let mut message = email
+            .get_header("Subject")
+            .map(mbox::decode_header)
+            .unwrap_or_default()
+            .into_bytes();
+        message.extend_from_slice(b"\n\n");
+        message.extend(
+            email
+                .body
+                .lines_with_terminator()
+                .take_while(|line| line.trim_end() != b"---")
+                .flatten(),
+        );
+        for (key, value) in parse_trailers(repo, &message)? {
+            if self
+                .trailers
+                .iter()
+                .any(|trailer| trailer.eq_ignore_ascii_case(&key))
+                && value.contains('@')
+            {
+                addresses.push(mbox::encode_mailbox(&value));
+            }
+        }
+
+        if let Some(cc_cmd) = self.cc_cmd.as_ref() {
+            let mut file = tempfile::Builder::new()
+                .prefix("stg-email-")
+                .suffix(".patch")
+                .tempfile()?;
+            email.write_to(&mut file)?;
+            file.flush()?;
+            let output = std::process::Command::new("sh")
+                .arg("-c")
+                .arg(format!(r#"{cc_cmd} "$@""#))
+                .arg(cc_cmd)
+                .arg(file.path())
+                .stdin(std::process::Stdio::null())
+                .stderr(std::process::Stdio::inherit())
+                .output()
+                .with_context(|| format!("running `{cc_cmd}`"))?;
+            if !output.status.success() {
+                return Err(anyhow!("`{cc_cmd}` failed with {}", output.status));
+            }
+            for line in output.stdout.lines() {
+                let line = line.to_str_lossy();
+                let line = line.trim();
+                let address = if let Some(end) = line.rfind('>') {
+                    &line[..=end]
+                } else {
+                    line.split_whitespace().next().unwrap_or_default()
+                };
+                if address.contains('@') {
+                    addresses.push(mbox::encode_mailbox(address));
+                }
+            }
+        }
+
+        let mut deduped: Vec<String> = Vec::with_capacity(addresses.len());
+        for address in addresses {
+            let email_address = mbox::address_email(&address);
+            if !deduped
+                .iter()
+                .any(|existing| mbox::address_email(existing).eq_ignore_ascii_case(email_address))
+            {
+                deduped.push(address);
+            }
+        }
+        Ok(deduped)
+    }
+}
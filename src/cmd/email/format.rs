@@ -2,27 +2,57 @@
 
 //! `stg email format` implementation.
 
-use anyhow::{anyhow, Result};
+use std::{
+    borrow::Cow,
+    collections::{BTreeMap, HashMap},
+    io::Write,
+    path::{Path, PathBuf},
+};
+
+use anyhow::{anyhow, Context, Result};
+use bstr::{BStr, BString, ByteSlice, ByteVec};
 use clap::Arg;
 
+use super::{autocc::AutoCc, mbox};
 use crate::{
     argset,
     branchloc::BranchLocator,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
+    stack::{CoverLetter, InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
+/// Subject placeholder in the cover letter template.
+const SUBJECT_PLACEHOLDER: &str = "*** SUBJECT HERE ***";
+
+/// Body placeholder in the cover letter template.
+const BLURB_PLACEHOLDER: &str = "*** BLURB HERE ***";
+
 pub(super) fn command() -> clap::Command {
     clap::Command::new("format")
         .about("Format patches as email files")
         .long_about(
             "Format selected patches as email files, one patch per file. The files are \
              formatted to resemble a UNIX mailbox (mbox) and may be sent with the `stg \
-             email send` command. The first line of the patch's commit message is used \
-             to form the email's subject with the remainder of the message in the \
-             email's body.\n\
+             email send` command or applied with `git am`. The first line of the \
+             patch's commit message is used to form the email's subject with the \
+             remainder of the message in the email's body. Any notes attached to the \
+             patch (see git-notes(1)) are included after the three-dash line.\n\
+             \n\
+             Each email file is named after its patch, e.g. '0001-<patch>.patch'. The \
+             subject of each email may be customized with a template file named \
+             \"emailsubject.tmpl\", which is searched for in \"$GIT_DIR/\", \
+             \"$XDG_CONFIG_HOME/stgit/templates/\", and \"~/.stgit/templates/\". The \
+             following variables are supported in the template: %(prefix)s, e.g. \
+             \"PATCH v2 1/3\"; %(shortdescr)s, the first line of the patch \
+             description; %(patchname)s; %(number)s; %(total)s; %(version)s, the \
+             series version, if any; and %(branch)s. The default template is \
+             \"[%(prefix)s] %(shortdescr)s\".\n\
+             \n\
+             Each email is given a deterministic `Message-Id` header derived from the \
+             patch's commit id and the series version, such that formatting the same \
+             version of a patch again yields the same message id.\n\
              \n\
              The patches to format may be specified as individual patch names or patch \
              ranges of the form 'p0..p3', or '--all' may be used to format all applied \
@@ -43,9 +73,17 @@ pub(super) fn command() -> clap::Command {
              Recipients may be specified using the '--to' and '--cc', or setting \
              recipients may be deferred to `stg email send`.\n\
              \n\
+             Emails are generated by StGit itself, except when '--attach', '--inline', \
+             or '--git-opt' are used, in which case `git format-patch` is used to \
+             generate the emails.\n\
+             \n\
              Many aspects of the format behavior may be controlled via `format.*` \
-             configuration values. Refer to the git-config(1) and git-format-patch(1) \
-             man pages for more details.",
+             configuration values, including `format.subjectPrefix`, \
+             `format.coverLetter`, `format.numbered`, `format.signOff`, \
+             `format.thread`, `format.to`, `format.cc`, `format.headers`, \
+             `format.signature`, `format.signatureFile`, `format.suffix`, and \
+             `format.outputDirectory`. Refer to the git-config(1) and \
+             git-format-patch(1) man pages for more details.",
         )
         .override_usage(super::super::make_usage(
             "stg email format",
@@ -68,13 +106,16 @@ pub(super) fn command() -> clap::Command {
                 .help("Format all applied patches")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(super::no_snapshot_arg())
         .arg(
             Arg::new("git-format-patch-opt")
                 .long("git-opt")
                 .short('G')
                 .help("Pass additional <option> to `git format-patch`")
                 .long_help(
-                    "Pass additional <option> to `git format-patch`.\n\
+                    "Pass additional <option> to `git format-patch`. Use of this \
+                     option causes the emails to be generated with `git format-patch` \
+                     instead of by StGit.\n\
                      \n\
                      See the git-format-patch(1) man page. This option may be \
                      specified multiple times.",
@@ -87,6 +128,7 @@ pub(super) fn command() -> clap::Command {
         .args(format_options())
         .next_help_heading("Message Options")
         .args(message_options())
+        .args(super::auto_cc_args())
     // DIFF OPTIONS ???
 }
 
@@ -109,8 +151,10 @@ fn format_options() -> Vec<Arg> {
             .help("Generate a cover letter")
             .long_help(
                 "In addition to the patches, generate a cover letter file containing \
-                 the branch description, shortlog and the overall diffstat. You can \
-                 fill in a description in the file before sending it out.",
+                 the branch description, shortlog and the overall diffstat. If the \
+                 stack has a cover letter (see `stg cover`), its title, description, \
+                 and changes are filled in. Otherwise you can fill in a description \
+                 in the file before sending it out.",
             )
             .action(clap::ArgAction::SetTrue),
         Arg::new("numbered")
@@ -350,6 +394,24 @@ fn message_options() -> Vec<Arg> {
             .long("no-thread")
             .help("Disable message threading")
             .action(clap::ArgAction::SetTrue),
+        Arg::new("notes")
+            .long("notes")
+            .help("Include notes from <ref> after the three-dash line")
+            .long_help(
+                "Include the patch's notes after the three-dash line of each email. \
+                 Notes are included by default from the default notes ref (see \
+                 git-notes(1)). The optional <ref> specifies an alternative notes \
+                 ref to use instead.",
+            )
+            .value_name("ref")
+            .num_args(0..=1)
+            .default_missing_value("")
+            .require_equals(true),
+        Arg::new("no-notes")
+            .long("no-notes")
+            .help("Do not include notes")
+            .conflicts_with("notes")
+            .action(clap::ArgAction::SetTrue),
         Arg::new("signature")
             .long("signature")
             .help("Add a signature to each email")
@@ -439,6 +501,15 @@ fn message_options() -> Vec<Arg> {
     ]
 }
 
+/// Threading style for formatted emails.
+#[derive(Clone, Copy, PartialEq, Eq)]
+enum ThreadStyle {
+    /// Every email is a reply to the head of the series.
+    Shallow,
+    /// Every email is a reply to the previous email.
+    Deep,
+}
+
 pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::from_branch_locator(
@@ -473,6 +544,34 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
         }
     }
 
+    let diff_specified = matches.contains_id("interdiff") || matches.contains_id("range-diff");
+    let version = super::prepare_version(&stack, &patches, matches, diff_specified)?;
+
+    // MIME attachments and arbitrary `git format-patch` options are only supported
+    // by `git format-patch` itself.
+    if matches.get_flag("attach")
+        || matches.get_flag("inline")
+        || matches.contains_id("git-format-patch-opt")
+    {
+        format_with_git(&stack, &patches, matches, version.as_ref())?;
+    } else {
+        format_native(&stack, &patches, matches, version.as_ref())?;
+    }
+
+    if let Some(version) = version {
+        stack.snapshot_version(version.number, true)?;
+    }
+
+    Ok(())
+}
+
+/// Format the patches as emails using `git format-patch`.
+fn format_with_git(
+    stack: &Stack,
+    patches: &[PatchName],
+    matches: &clap::ArgMatches,
+    version: Option<&super::SeriesVersion>,
+) -> Result<()> {
     let mut format_args: Vec<(usize, String)> = Vec::new();
 
     // This dummy command is constructed with just the Args that are to be
@@ -484,6 +583,11 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
 
     for arg in dummy_command.get_arguments() {
         let arg_id = arg.get_id().as_str();
+        // The list of generated files is needed to fill in the cover letter, so
+        // quiet output is handled here instead of by `git format-patch`.
+        if arg_id == "quiet" {
+            continue;
+        }
         if matches!(
             matches.value_source(arg_id),
             Some(clap::parser::ValueSource::CommandLine)
@@ -495,7 +599,7 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
                 let values = matches.get_many::<String>(arg_id).unwrap();
                 assert!(indices.len() == values.len());
                 indices.into_iter().zip(values).for_each(|(index, value)| {
-                    if arg_id == "thread" && value.is_empty() {
+                    if value.is_empty() {
                         format_args.push((index, format!("--{long}")));
                     } else {
                         format_args.push((index, format!("--{long}={value}")));
@@ -511,10 +615,18 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
 
     let mut format_args = format_args.drain(..).map(|(_, s)| s).collect::<Vec<_>>();
 
+    if !matches.contains_id("notes") && !matches.get_flag("no-notes") {
+        format_args.push("--notes".to_string());
+    }
+
     if let Some(values) = matches.get_many::<String>("git-format-patch-opt") {
         format_args.extend(values.cloned());
     }
 
+    if let Some(version) = version {
+        version.push_git_args(&mut format_args);
+    }
+
     {
         let base = stack
             .get_patch_commit(&patches[0])
@@ -526,5 +638,742 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
         format_args.push(format!("{base}..{last}"));
     }
 
-    repo.stupid().format_patch(format_args)
+    let output = stack.repo.stupid().format_patch(format_args)?;
+    if !matches.get_flag("quiet") {
+        std::io::stdout().write_all(&output)?;
+    }
+
+    if let Some(auto_cc) = AutoCc::from_matches(stack.repo, matches)? {
+        let paths = output
+            .lines()
+            .map(|line| line.to_os_str().map(PathBuf::from))
+            .collect::<Result<Vec<_>, _>>()?;
+        let mut emails = paths
+            .iter()
+            .map(|path| {
+                let data =
+                    std::fs::read(path).with_context(|| format!("reading `{}`", path.display()))?;
+                mbox::Email::parse(&data).with_context(|| format!("parsing `{}`", path.display()))
+            })
+            .collect::<Result<Vec<_>>>()?;
+        let committer = stack.repo.get_committer()?;
+        auto_cc.apply(
+            stack.repo,
+            &mut emails.iter_mut().collect::<Vec<_>>(),
+            &committer.email.to_str_lossy(),
+        )?;
+        for (path, email) in paths.iter().zip(&emails) {
+            let mut file = std::fs::File::create(path)
+                .with_context(|| format!("creating `{}`", path.display()))?;
+            email.write_to(&mut file)?;
+        }
+    }
+
+    if let (Some(cover), Some(first_line)) = (stack.cover(), output.lines().next()) {
+        let path = Path::new(first_line.to_os_str()?);
+        if path.is_file() {
+            fill_cover_letter(path, cover, version.map(|version| version.number))?;
+        }
+    }
+
+    Ok(())
+}
+
+/// Format the patches as emails without the aid of `git format-patch`.
+fn format_native(
+    stack: &Stack,
+    patches: &[PatchName],
+    matches: &clap::ArgMatches,
+    version: Option<&super::SeriesVersion>,
+) -> Result<()> {
+    let mut emails = make_emails(stack, patches, matches, version)?;
+    if let Some(auto_cc) = AutoCc::from_matches(stack.repo, matches)? {
+        let committer = stack.repo.get_committer()?;
+        auto_cc.apply(
+            stack.repo,
+            &mut emails
+                .iter_mut()
+                .map(|(_, email)| email)
+                .collect::<Vec<_>>(),
+            &committer.email.to_str_lossy(),
+        )?;
+    }
+
+    let output_dir = matches
+        .get_one::<String>("output-directory")
+        .cloned()
+        .or_else(|| {
+            stack
+                .repo
+                .config_snapshot()
+                .string("format.outputDirectory")
+                .map(|s| s.to_str_lossy().into_owned())
+        })
+        .map(PathBuf::from);
+    if let Some(output_dir) = output_dir.as_ref() {
+        std::fs::create_dir_all(output_dir).with_context(|| format!("creating {output_dir:?}"))?;
+    }
+
+    let progress_flag = matches.get_flag("progress");
+    let quiet_flag = matches.get_flag("quiet");
+    let num_emails = emails.len();
+    for (i, (file_name, email)) in emails.iter().enumerate() {
+        let path = if let Some(output_dir) = output_dir.as_ref() {
+            output_dir.join(file_name)
+        } else {
+            PathBuf::from(file_name)
+        };
+        let mut file = std::fs::File::create(&path)
+            .with_context(|| format!("creating `{}`", path.display()))?;
+        email.write_to(&mut file)?;
+        if progress_flag {
+            eprint!(
+                "\rGenerating patches: {:3}% ({}/{num_emails})",
+                (i + 1) * 100 / num_emails,
+                i + 1
+            );
+        }
+        if !quiet_flag {
+            println!("{}", path.display());
+        }
+    }
+    if progress_flag {
+        eprintln!(", done.");
+    }
+
+    Ok(())
+}
+
+/// Generate emails for the patches, along with each email's file name.
+///
+/// The `matches` must be from this `format` command.
+pub(super) fn make_emails(
+    stack: &Stack,
+    patches: &[PatchName],
+    matches: &clap::ArgMatches,
+    version: Option<&super::SeriesVersion>,
+) -> Result<Vec<(String, mbox::Email)>> {
+    let repo = stack.repo;
+    let config = repo.config_snapshot();
+    let config_string = |key: &str| config.string(key).map(|s| s.to_str_lossy().into_owned());
+    let stupid = repo.stupid();
+
+    let committer = repo.get_committer()?;
+    let sender_name = committer.name.to_str_lossy().into_owned();
+    let sender_email = committer.email.to_str_lossy().into_owned();
+
+    let suffix = matches
+        .get_one::<String>("suffix")
+        .cloned()
+        .or_else(|| config_string("format.suffix"))
+        .unwrap_or_else(|| ".patch".to_string());
+    let numbered_files = matches.get_flag("numbered-files");
+
+    let reroll_count = matches
+        .get_one::<String>("reroll-count")
+        .cloned()
+        .or_else(|| {
+            version
+                .and_then(|version| version.reroll_count)
+                .map(|reroll_count| reroll_count.to_string())
+        });
+    let against_previous = reroll_count
+        .as_ref()
+        .and_then(|reroll_count| reroll_count.parse::<u32>().ok())
+        .filter(|reroll_count| *reroll_count > 1)
+        .map(|reroll_count| format!(" against v{}", reroll_count - 1))
+        .unwrap_or_default();
+    let file_prefix = reroll_count
+        .as_ref()
+        .map(|reroll_count| format!("v{reroll_count}-"))
+        .unwrap_or_default();
+
+    let mut subject_prefix = matches
+        .get_one::<String>("subject-prefix")
+        .cloned()
+        .or_else(|| config_string("format.subjectPrefix"))
+        .unwrap_or_else(|| "PATCH".to_string());
+    if matches.get_flag("rfc") {
+        subject_prefix.insert_str(0, "RFC ");
+    }
+    if let Some(reroll_count) = reroll_count.as_ref() {
+        subject_prefix.push_str(&format!(" v{reroll_count}"));
+    }
+
+    let total = patches.len();
+    let cover_flag = matches.get_flag("cover-letter")
+        || match config_string("format.coverLetter").as_deref() {
+            Some("auto") => total > 1,
+            Some(_) => config.boolean("format.coverLetter").unwrap_or(false),
+            None => false,
+        };
+    let numbered = if matches.get_flag("numbered") {
+        true
+    } else if matches.get_flag("no-numbered") {
+        false
+    } else if let Some(Ok(numbered)) = config.try_boolean("format.numbered") {
+        numbered
+    } else {
+        total > 1 || cover_flag
+    };
+    let start_number = if let Some(start_number) = matches.get_one::<String>("start-number") {
+        start_number
+            .parse::<usize>()
+            .map_err(|_| anyhow!("invalid start number `{start_number}`"))?
+    } else {
+        1
+    };
+    let last_number = start_number + total - 1;
+    let number_width = last_number.to_string().len();
+
+    let signoff = matches.get_flag("signoff") || config.boolean("format.signOff").unwrap_or(false);
+    let signoff_trailer = format!("Signed-off-by: {sender_name} <{sender_email}>");
+
+    let to = recipients(matches, config.plumbing(), "to");
+    let cc = recipients(matches, config.plumbing(), "cc");
+    let mut extra_headers = Vec::new();
+    if let Some(values) = config.plumbing().strings_by_key("format.headers") {
+        for value in values {
+            extra_headers.extend(
+                value
+                    .to_str_lossy()
+                    .lines()
+                    .map(str::trim_end)
+                    .filter(|line| !line.is_empty())
+                    .map(ToString::to_string),
+            );
+        }
+    }
+    if let Some(headers) = matches.get_many::<String>("add-header") {
+        extra_headers.extend(headers.cloned());
+    }
+
+    let in_reply_to = matches.get_one::<String>("in-reply-to").map(|id| {
+        let id = id.trim();
+        if id.starts_with('<') {
+            id.to_string()
+        } else {
+            format!("<{id}>")
+        }
+    });
+    let config_thread = || match config_string("format.thread").as_deref() {
+        Some("shallow") => Some(ThreadStyle::Shallow),
+        Some("deep") => Some(ThreadStyle::Deep),
+        Some(_) => config
+            .boolean("format.thread")
+            .unwrap_or(false)
+            .then_some(ThreadStyle::Shallow),
+        None => None,
+    };
+    let thread = if matches.get_flag("no-thread") {
+        None
+    } else if let Some(style) = matches.get_one::<String>("thread") {
+        match style.as_str() {
+            "shallow" => Some(ThreadStyle::Shallow),
+            "deep" => Some(ThreadStyle::Deep),
+            _ => Some(config_thread().unwrap_or(ThreadStyle::Shallow)),
+        }
+    } else {
+        config_thread()
+    };
+
+    let signature = if matches.get_flag("no-signature") {
+        None
+    } else if let Some(signature) = matches.get_one::<String>("signature") {
+        Some(signature.clone())
+    } else if let Some(path) = matches
+        .get_one::<String>("signature-file")
+        .cloned()
+        .or_else(|| config_string("format.signatureFile"))
+    {
+        Some(
+            std::fs::read_to_string(&path)
+                .with_context(|| format!("reading signature file `{path}`"))?,
+        )
+    } else if let Some(signature) = config_string("format.signature") {
+        Some(signature).filter(|signature| !signature.is_empty())
+    } else {
+        let version = stupid.version()?;
+        Some(
+            version
+                .strip_prefix("git version ")
+                .unwrap_or(&version)
+                .to_string(),
+        )
+    };
+    let signature = signature.map(|signature| {
+        let mut text = format!("-- \n{signature}");
+        if !signature.ends_with('\n') {
+            text.push('\n');
+        }
+        text.push('\n');
+        text
+    });
+
+    let notes_ref = if matches.get_flag("no-notes") {
+        None
+    } else {
+        Some(
+            matches
+                .get_one::<String>("notes")
+                .map(String::as_str)
+                .filter(|notes_ref| !notes_ref.is_empty()),
+        )
+    };
+
+    let subject_template = match crate::templates::get_template(repo, "emailsubject.tmpl")? {
+        Some(template) => Cow::Owned(template),
+        None => Cow::Borrowed(crate::templates::EMAILSUBJECT_TMPL),
+    };
+
+    let first_commit = stack.get_patch_commit(&patches[0]);
+    let base_id = first_commit
+        .parent_ids()
+        .next()
+        .expect("patch commit has one parent")
+        .detach();
+    let base_tree_id = first_commit.get_parent_commit()?.tree_id()?.detach();
+    let last_commit = stack.get_patch_commit(patches.last().unwrap());
+    let top_id = last_commit.id;
+    let top_tree_id = last_commit.tree_id()?.detach();
+    let null_id = gix::ObjectId::null(repo.object_hash());
+    let zero_commit = matches.get_flag("zero-commit");
+
+    // Interdiffs and range-diffs are included in the cover letter, or in the lone
+    // patch's commentary. Each is a label, the content, and whether the content is
+    // indented when included in a patch.
+    let mut commentaries: Vec<(String, BString, bool)> = Vec::new();
+    if let Some(rev) = matches.get_one::<String>("interdiff") {
+        let rev_tree_id = repo
+            .rev_parse_single(rev.as_str())?
+            .object()?
+            .peel_to_kind(gix::object::Kind::Tree)?
+            .id;
+        let diff =
+            stupid.diff_tree_patch(rev_tree_id, top_tree_id, None::<Vec<&str>>, false, ["-M"])?;
+        commentaries.push((format!("Interdiff{against_previous}"), diff, true));
+    }
+    if let Some(refspec) = matches
+        .get_one::<String>("range-diff")
+        .or_else(|| version.and_then(|version| version.range_diff.as_ref()))
+    {
+        let old_range = if refspec.contains("..") {
+            refspec.clone()
+        } else {
+            format!("{base_id}..{refspec}")
+        };
+        let range_diff = stupid.range_diff(
+            &old_range,
+            &format!("{base_id}..{top_id}"),
+            matches
+                .get_one::<String>("creation-factor")
+                .map(String::as_str),
+        )?;
+        commentaries.push((format!("Range-diff{against_previous}"), range_diff, false));
+    }
+
+    let base_info = if let Some(spec) = matches.get_one::<String>("base") {
+        let base_commit_id = if spec == "auto" {
+            base_id
+        } else {
+            repo.rev_parse_single(spec.as_str())?
+                .object()?
+                .peel_to_kind(gix::object::Kind::Commit)?
+                .id
+        };
+        if !stupid
+            .merge_bases(base_commit_id, base_id)?
+            .contains(&base_commit_id)
+        {
+            return Err(anyhow!(
+                "base commit `{spec}` is not an ancestor of the patches"
+            ));
+        }
+        let mut info = format!("\nbase-commit: {base_commit_id}\n");
+        for commit_id in stupid
+            .rev_list(base_commit_id, base_id, None::<Vec<&str>>)?
+            .iter()
+            .rev()
+        {
+            if let Some(patch_id) = stupid.patch_id(*commit_id)? {
+                info.push_str(&format!("prerequisite-patch-id: {patch_id}\n"));
+            }
+        }
+        Some(info)
+    } else {
+        None
+    };
+
+    let add_common_headers = |email: &mut mbox::Email| {
+        for header in &extra_headers {
+            email.raw_header(header);
+        }
+        if !to.is_empty() {
+            email.header("To", mbox::join_addresses(&to));
+        }
+        if !cc.is_empty() {
+            email.header("Cc", mbox::join_addresses(&cc));
+        }
+    };
+
+    let mut thread_refs: Vec<String> = in_reply_to.iter().cloned().collect();
+    let mut add_thread_headers = |email: &mut mbox::Email, message_id: String| {
+        email.header("Message-Id", message_id.as_str());
+        if let Some(thread) = thread {
+            if let Some(parent) = thread_refs.last() {
+                email.header("In-Reply-To", parent.as_str());
+                email.header("References", thread_refs.join("\n "));
+            }
+            if thread == ThreadStyle::Deep
+                || thread_refs.len() == usize::from(in_reply_to.is_some())
+            {
+                thread_refs.push(message_id);
+            }
+        } else if let Some(in_reply_to) = in_reply_to.as_ref() {
+            email.header("In-Reply-To", in_reply_to.as_str());
+            email.header("References", in_reply_to.as_str());
+        }
+    };
+
+    let mut emails: Vec<(String, mbox::Email)> = Vec::with_capacity(total + 1);
+
+    if cover_flag {
+        let mut email = mbox::Email::new(if zero_commit { null_id } else { top_id });
+        add_thread_headers(
+            &mut email,
+            mbox::message_id(
+                top_id,
+                Some("cover"),
+                reroll_count.as_deref(),
+                &sender_email,
+            ),
+        );
+        email.header("From", mbox::encode_address(&sender_name, &sender_email));
+        email.header(
+            "Date",
+            gix::date::Time::now_local_or_utc().format(gix::date::time::format::GIT_RFC2822),
+        );
+
+        let title = stack
+            .cover()
+            .map_or(SUBJECT_PLACEHOLDER, |cover| cover.title.as_str());
+        let subject = if numbered {
+            format!(
+                "[{subject_prefix} {:0number_width$}/{last_number}] {title}",
+                0
+            )
+        } else {
+            format!("[{subject_prefix}] {title}")
+        };
+        email.header("Subject", mbox::encode_subject(&subject));
+
+        let mut body = BString::from(stack.cover().map_or_else(
+            || BLURB_PLACEHOLDER.to_string(),
+            |cover| cover_blurb(cover, version.map(|version| version.number)),
+        ));
+        if !body.is_empty() {
+            body.push_str("\n\n");
+        }
+
+        let mut shortlog: BTreeMap<String, Vec<String>> = BTreeMap::new();
+        for patchname in patches {
+            let commit = stack.get_patch_commit(patchname);
+            let author = commit.author_strict()?;
+            let (title, _) = split_message(&commit.message_ex().decode()?);
+            shortlog
+                .entry(author.name.to_str_lossy().into_owned())
+                .or_default()
+                .push(title);
+        }
+        for (i, (author, titles)) in shortlog.iter().enumerate() {
+            if i > 0 {
+                body.push(b'\n');
+            }
+            body.push_str(format!("{author} ({}):\n", titles.len()));
+            for title in titles {
+                body.push_str(format!("  {title}\n"));
+            }
+        }
+        body.push(b'\n');
+        body.push_str(stupid.diff_tree_stat(base_tree_id, top_tree_id)?);
+        body.push(b'\n');
+
+        for (label, content, _) in &commentaries {
+            body.push_str(format!("{label}:\n"));
+            body.push_str(content);
+        }
+        if let Some(base_info) = base_info.as_ref() {
+            body.push_str(base_info);
+        }
+        if let Some(signature) = signature.as_ref() {
+            body.push_str(signature);
+        }
+
+        if mbox::has_non_ascii(subject.as_bytes()) || mbox::has_non_ascii(&body) {
+            email.mime_headers();
+        }
+        add_common_headers(&mut email);
+        email.body = body;
+
+        let file_name = if numbered_files {
+            "0".to_string()
+        } else {
+            format!("{file_prefix}0000-cover-letter{suffix}")
+        };
+        emails.push((file_name, email));
+    }
+
+    for (i, patchname) in patches.iter().enumerate() {
+        let number = start_number + i;
+        let commit = stack.get_patch_commit(patchname);
+        let parent_tree_id = commit.get_parent_commit()?.tree_id()?.detach();
+        let author = commit.author_strict()?;
+        let (title, mut message_body) = split_message(&commit.message_ex().decode()?);
+        if signoff {
+            add_signoff(&mut message_body, &signoff_trailer);
+        }
+
+        let subject = if matches.get_flag("keep-subject") {
+            title
+        } else {
+            let prefix = if numbered {
+                format!("{subject_prefix} {number:0number_width$}/{last_number}")
+            } else {
+                subject_prefix.clone()
+            };
+            let number = number.to_string();
+            let last_number = last_number.to_string();
+            let mut replacements: HashMap<&str, Cow<'_, BStr>> = HashMap::new();
+            replacements.insert("prefix", Cow::Borrowed(prefix.as_str().into()));
+            replacements.insert("shortdescr", Cow::Borrowed(title.as_str().into()));
+            replacements.insert(
+                "patchname",
+                Cow::Borrowed(AsRef::<str>::as_ref(patchname).into()),
+            );
+            replacements.insert("number", Cow::Borrowed(number.as_str().into()));
+            replacements.insert("total", Cow::Borrowed(last_number.as_str().into()));
+            replacements.insert(
+                "version",
+                Cow::Borrowed(reroll_count.as_deref().unwrap_or_default().into()),
+            );
+            replacements.insert("branch", Cow::Borrowed(stack.get_branch_name().into()));
+            let subject = crate::templates::specialize_template(&subject_template, &replacements);
+            subject
+                .to_str_lossy()
+                .lines()
+                .next()
+                .unwrap_or_default()
+                .trim()
+                .to_string()
+        };
+
+        let mut email = mbox::Email::new(if zero_commit { null_id } else { commit.id });
+        add_thread_headers(
+            &mut email,
+            mbox::message_id(commit.id, None, reroll_count.as_deref(), &sender_email),
+        );
+        email.header(
+            "From",
+            mbox::encode_address(&author.name.to_str_lossy(), &author.email.to_str_lossy()),
+        );
+        email.header(
+            "Date",
+            author.time.format(gix::date::time::format::GIT_RFC2822),
+        );
+        email.header("Subject", mbox::encode_subject(&subject));
+        if mbox::has_non_ascii(subject.as_bytes()) || mbox::has_non_ascii(message_body.as_bytes()) {
+            email.mime_headers();
+        }
+        add_common_headers(&mut email);
+
+        let mut body = BString::default();
+        if !message_body.is_empty() {
+            body.push_str(&message_body);
+            body.push(b'\n');
+        }
+        body.push_str("---\n");
+
+        if let Some(notes_ref) = notes_ref {
+            if let Some(note) = stupid.notes_show(commit.id, notes_ref)? {
+                if let Some(notes_ref) = notes_ref {
+                    body.push_str(format!("\nNotes ({notes_ref}):\n"));
+                } else {
+                    body.push_str("\nNotes:\n");
+                }
+                for line in note.trim_end().lines() {
+                    body.push_str("    ");
+                    body.push_str(line);
+                    body.push(b'\n');
+                }
+                body.push(b'\n');
+            }
+        }
+
+        if total == 1 && !cover_flag {
+            for (label, content, indent) in &commentaries {
+                body.push_str(format!("{label}:\n"));
+                for line in content.lines_with_terminator() {
+                    if *indent {
+                        body.push_str("  ");
+                    }
+                    body.push_str(line);
+                }
+                body.push(b'\n');
+            }
+        }
+
+        body.push_str(stupid.diff_tree_patch(
+            parent_tree_id,
+            commit.tree_id()?.detach(),
+            None::<Vec<&str>>,
+            false,
+            [
+                "-M",
+                "--stat=72",
+                "--summary",
+                if matches.get_flag("no-binary") {
+                    "--no-binary"
+                } else {
+                    "--binary"
+                },
+            ],
+        )?);
+
+        if i == 0 && !cover_flag {
+            if let Some(base_info) = base_info.as_ref() {
+                body.push_str(base_info);
+            }
+        }
+        if let Some(signature) = signature.as_ref() {
+            body.push_str(signature);
+        }
+        email.body = body;
+
+        let file_name = if numbered_files {
+            number.to_string()
+        } else {
+            format!("{file_prefix}{number:04}-{patchname}{suffix}")
+        };
+        emails.push((file_name, email));
+    }
+
+    Ok(emails)
+}
+
+/// Get the recipients for the `to` or `cc` option.
+///
+/// Recipients from the `format.to` or `format.cc` configuration are used unless
+/// discarded with the corresponding '--no-to' or '--no-cc' option. Recipients specified
+/// on the command line before the '--no-to' or '--no-cc' option are also discarded.
+fn recipients(matches: &clap::ArgMatches, config: &gix::config::File, arg_id: &str) -> Vec<String> {
+    let no_index = if matches.get_flag(&format!("no-{arg_id}")) {
+        matches
+            .indices_of(format!("no-{arg_id}").as_str())
+            .and_then(Iterator::max)
+            .or(Some(0))
+    } else {
+        None
+    };
+
+    let mut recipients = Vec::new();
+    if no_index.is_none() {
+        if let Some(values) = config.strings_by_key(format!("format.{arg_id}").as_str()) {
+            recipients.extend(values.iter().map(|value| value.to_str_lossy().into_owned()));
+        }
+    }
+    if let (Some(indices), Some(values)) = (
+        matches.indices_of(arg_id),
+        matches.get_many::<String>(arg_id),
+    ) {
+        for (index, value) in indices.zip(values) {
+            if no_index.map_or(true, |no_index| index > no_index) {
+                recipients.push(value.clone());
+            }
+        }
+    }
+    recipients
+}
+
+/// Split a commit message into its subject and body.
+///
+/// As with `git format-patch`, the subject is formed by joining the lines of the
+/// message's first paragraph. The body is the remainder of the message, without
+/// leading blank lines or trailing whitespace.
+fn split_message(message: &str) -> (String, String) {
+    let mut lines = message.lines().skip_while(|line| line.trim().is_empty());
+    let mut subject_lines = Vec::new();
+    for line in lines.by_ref() {
+        if line.trim().is_empty() {
+            break;
+        }
+        subject_lines.push(line.trim());
+    }
+    let body = lines
+        .skip_while(|line| line.trim().is_empty())
+        .collect::<Vec<_>>()
+        .join("\n");
+    (subject_lines.join(" "), body.trim_end().to_string())
+}
+
+/// Add a Signed-off-by trailer to a message body.
+///
+/// The trailer is not added if it is already the message's last trailer.
+fn add_signoff(body: &mut String, trailer: &str) {
+    if body.lines().last() == Some(trailer) {
+        return;
+    }
+    let is_trailer = |line: &str| {
+        line.split_once(": ").map_or(false, |(token, _)| {
+            !token.is_empty() && token.chars().all(|c| c.is_ascii_alphanumeric() || c == '-')
+        })
+    };
+    if !body.is_empty() {
+        let last_paragraph = body.rsplit("\n\n").next().unwrap_or_default();
+        if last_paragraph.lines().all(is_trailer) {
+            body.push('\n');
+        } else {
+            body.push_str("\n\n");
+        }
+    }
+    body.push_str(trailer);
+}
+
+/// Make the cover letter blurb from the stack's cover letter.
+///
+/// The blurb is composed of the cover letter's description and its changes, which are
+/// described as changes since the previous version when the series version is known.
+fn cover_blurb(cover: &CoverLetter, version: Option<u32>) -> String {
+    let mut blurb = cover.body.clone();
+    if !cover.changes.is_empty() {
+        if !blurb.is_empty() {
+            blurb.push_str("\n\n");
+        }
+        if let Some(version) = version.filter(|version| *version > 1) {
+            blurb.push_str(&format!("Changes since v{}:\n", version - 1));
+        } else {
+            blurb.push_str("Changes:\n");
+        }
+        blurb.push_str(&cover.changes);
+    }
+    blurb
+}
+
+/// Fill in the cover letter template generated by `git format-patch`.
+///
+/// The template's subject and blurb placeholders are replaced with the stack's cover
+/// letter. The file is left unchanged if it is not a cover letter template.
+fn fill_cover_letter(path: &Path, cover: &CoverLetter, version: Option<u32>) -> Result<()> {
+    let template = std::fs::read(path)?;
+    let Ok(template) = template.to_str() else {
+        return Ok(());
+    };
+    if !template.contains(SUBJECT_PLACEHOLDER) || !template.contains(BLURB_PLACEHOLDER) {
+        return Ok(());
+    }
+
+    let letter = template
+        .replacen(SUBJECT_PLACEHOLDER, &cover.title, 1)
+        .replacen(BLURB_PLACEHOLDER, &cover_blurb(cover, version), 1);
+    std::fs::write(path, letter)?;
+    Ok(())
 }
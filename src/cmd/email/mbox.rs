@@ -0,0 +1,506 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Native generation of patch emails.
+//!
+//! Emails are formatted as UNIX mailbox (mbox) messages in the same manner as `git
+//! format-patch` such that they may be applied with `git am`. Header values containing
+//! non-ASCII characters are encoded according to RFC 2047 and long header values are
+//! folded.
+//!
+//! Existing email files, such as those generated by `stg email format`, may also be
+//! parsed such that their headers may be adjusted before being sent.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use bstr::{BString, ByteSlice};
+
+/// Date used in the mbox "From " line of each email.
+///
+/// This fixed date is what `git format-patch` uses, which allows tools such as `git
+/// mailsplit` to recognize the start of each message.
+const MBOX_FROM_DATE: &str = "Mon Sep 17 00:00:00 2001";
+
+/// Maximum length of an RFC 2047 encoded header line.
+const MAX_ENCODED_LENGTH: usize = 76;
+
+/// Maximum length of an unencoded header line.
+const MAX_HEADER_LENGTH: usize = 78;
+
+/// An email message to be written to an mbox file.
+pub(super) struct Email {
+    /// Commit id used in the message's mbox "From " line.
+    from_id: gix::ObjectId,
+
+    /// Header lines with already encoded values.
+    headers: Vec<String>,
+
+    /// Message body, including any diff.
+    pub(super) body: BString,
+}
+
+impl Email {
+    pub(super) fn new(from_id: gix::ObjectId) -> Self {
+        Self {
+            from_id,
+            headers: Vec::new(),
+            body: BString::default(),
+        }
+    }
+
+    /// Append a header with an already encoded value.
+    pub(super) fn header(&mut self, name: &str, value: impl AsRef<str>) {
+        self.headers.push(format!("{name}: {}", value.as_ref()));
+    }
+
+    /// Append a complete header line, e.g. "Organization: git-foo".
+    pub(super) fn raw_header(&mut self, line: &str) {
+        self.headers.push(line.to_string());
+    }
+
+    /// Parse an email from the contents of an mbox file.
+    ///
+    /// Only the first message of the mbox is parsed. The mbox "From " line is optional.
+    pub(super) fn parse(data: &[u8]) -> Result<Self> {
+        let mut from_id = gix::ObjectId::null(gix::hash::Kind::Sha1);
+        let mut headers: Vec<String> = Vec::new();
+        let mut rest = data;
+        let mut is_first_line = true;
+        loop {
+            let (line, remainder) = match rest.find_byte(b'\n') {
+                Some(pos) => (&rest[..pos], &rest[pos + 1..]),
+                None => (rest, &rest[rest.len()..]),
+            };
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            rest = remainder;
+            if is_first_line {
+                is_first_line = false;
+                if let Some(from_line) = line.strip_prefix(b"From ") {
+                    if let Some(id) = from_line.split_str(" ").next() {
+                        if let Ok(id) = gix::ObjectId::from_hex(id) {
+                            from_id = id;
+                        }
+                    }
+                    continue;
+                }
+            }
+            if line.is_empty() {
+                break;
+            }
+            let line = line
+                .to_str()
+                .map_err(|_| anyhow!("email header is not valid UTF-8"))?;
+            if line.starts_with([' ', '\t']) {
+                let Some(last) = headers.last_mut() else {
+                    return Err(anyhow!("email begins with a header continuation line"));
+                };
+                last.push('\n');
+                last.push_str(line);
+            } else if line.contains(':') {
+                headers.push(line.to_string());
+            } else {
+                return Err(anyhow!("invalid email header line `{line}`"));
+            }
+            if rest.is_empty() {
+                break;
+            }
+        }
+        if headers.is_empty() {
+            return Err(anyhow!("no email headers found"));
+        }
+        Ok(Self {
+            from_id,
+            headers,
+            body: BString::from(rest),
+        })
+    }
+
+    /// Get the commit id from the message's mbox "From " line.
+    ///
+    /// The id is null when the message does not correspond to a commit.
+    pub(super) fn commit_id(&self) -> gix::ObjectId {
+        self.from_id
+    }
+
+    /// Determine whether the email is a cover letter.
+    ///
+    /// Cover letters are recognized by their subject being numbered 0 or by their
+    /// message id having been generated for a cover letter by [`message_id()`].
+    pub(super) fn is_cover_letter(&self) -> bool {
+        if self
+            .get_header("Message-Id")
+            .map_or(false, |id| id.trim().starts_with("<stgit.cover."))
+        {
+            return true;
+        }
+        let subject = self
+            .get_header("Subject")
+            .map(decode_header)
+            .unwrap_or_default();
+        subject
+            .strip_prefix('[')
+            .and_then(|rest| rest.split_once(']'))
+            .and_then(|(prefix, _)| prefix.rsplit(' ').next())
+            .and_then(|numbering| numbering.split_once('/'))
+            .map_or(false, |(number, _)| {
+                !number.is_empty() && number.bytes().all(|b| b == b'0')
+            })
+    }
+
+    /// Get the header lines, with already encoded values.
+    pub(super) fn headers(&self) -> &[String] {
+        &self.headers
+    }
+
+    /// Get the (still encoded) value of the first header with the given name.
+    pub(super) fn get_header(&self, name: &str) -> Option<&str> {
+        self.headers
+            .iter()
+            .find_map(|line| header_value(line, name))
+    }
+
+    /// Remove all headers with the given name, returning the first header's value.
+    pub(super) fn remove_header(&mut self, name: &str) -> Option<String> {
+        let value = self.get_header(name).map(ToString::to_string);
+        self.headers
+            .retain(|line| header_value(line, name).is_none());
+        value
+    }
+
+    /// Replace any headers with the given name with a single, already encoded, value.
+    pub(super) fn set_header(&mut self, name: &str, value: impl AsRef<str>) {
+        let new_line = format!("{name}: {}", value.as_ref());
+        let mut found = false;
+        self.headers.retain_mut(|line| {
+            if header_value(line, name).is_none() {
+                true
+            } else if found {
+                false
+            } else {
+                found = true;
+                *line = new_line.clone();
+                true
+            }
+        });
+        if !found {
+            self.headers.push(new_line);
+        }
+    }
+
+    /// Append the MIME headers declaring the body's UTF-8 charset.
+    pub(super) fn mime_headers(&mut self) {
+        self.header("MIME-Version", "1.0");
+        self.header("Content-Type", "text/plain; charset=UTF-8");
+        self.header("Content-Transfer-Encoding", "8bit");
+    }
+
+    /// Write the message in mbox format.
+    pub(super) fn write_to(&self, output: &mut impl Write) -> std::io::Result<()> {
+        writeln!(output, "From {} {MBOX_FROM_DATE}", self.from_id)?;
+        self.write_message_to(output)
+    }
+
+    /// Write the message's headers and body without the mbox "From " line.
+    pub(super) fn write_message_to(&self, output: &mut impl Write) -> std::io::Result<()> {
+        for header in &self.headers {
+            writeln!(output, "{header}")?;
+        }
+        writeln!(output)?;
+        output.write_all(&self.body)
+    }
+}
+
+/// Determine whether the text contains any non-ASCII characters.
+pub(super) fn has_non_ascii(text: &[u8]) -> bool {
+    !text.is_ascii()
+}
+
+/// Encode a `Subject` header value.
+///
+/// A leading bracketed prefix, such as `[PATCH n/m]`, is kept as-is.
+pub(super) fn encode_subject(subject: &str) -> String {
+    let prefix_len = "Subject: ".len();
+    if has_non_ascii(subject.as_bytes()) {
+        let split = if subject.starts_with('[') {
+            subject
+                .find("] ")
+                .filter(|pos| subject[..*pos].is_ascii())
+                .map_or(0, |pos| pos + 2)
+        } else {
+            0
+        };
+        let (plain, rest) = subject.split_at(split);
+        let mut value = plain.to_string();
+        value.push_str(&encode_rfc2047(rest, prefix_len + plain.len(), false));
+        value
+    } else {
+        fold_words(subject, prefix_len)
+    }
+}
+
+/// Encode a name and email address for an address header such as `From`.
+pub(super) fn encode_address(name: &str, email: &str) -> String {
+    if has_non_ascii(name.as_bytes()) {
+        format!("{} <{email}>", encode_rfc2047(name, "From: ".len(), true))
+    } else if name.contains(|c| "()<>@,;:\\\".[]".contains(c)) {
+        let quoted = name.replace('\\', "\\\\").replace('"', "\\\"");
+        format!("\"{quoted}\" <{email}>")
+    } else {
+        format!("{name} <{email}>")
+    }
+}
+
+/// Encode an address of the form "Name <email>", as may be supplied by the user.
+///
+/// Addresses without non-ASCII characters are used as-is.
+pub(super) fn encode_mailbox(mailbox: &str) -> String {
+    let mailbox = mailbox.trim();
+    if has_non_ascii(mailbox.as_bytes()) {
+        if let Some((name, rest)) = mailbox.rsplit_once('<') {
+            let name = name.trim().trim_matches('"');
+            if let Some(email) = rest.strip_suffix('>') {
+                return encode_address(name, email.trim());
+            }
+        }
+    }
+    mailbox.to_string()
+}
+
+/// Split the value of an address header into its individual addresses.
+pub(super) fn split_addresses(value: &str) -> Vec<String> {
+    let mut addresses = Vec::new();
+    let mut current = String::new();
+    let mut in_quotes = false;
+    let mut in_angle = false;
+    let mut escaped = false;
+    for c in value.chars() {
+        match c {
+            _ if escaped => escaped = false,
+            '\\' if in_quotes => escaped = true,
+            '"' => in_quotes = !in_quotes,
+            '<' if !in_quotes => in_angle = true,
+            '>' if !in_quotes => in_angle = false,
+            ',' if !in_quotes && !in_angle => {
+                addresses.push(std::mem::take(&mut current));
+                continue;
+            }
+            '\n' | '\r' => continue,
+            _ => {}
+        }
+        current.push(c);
+    }
+    addresses.push(current);
+    addresses
+        .into_iter()
+        .map(|address| address.trim().to_string())
+        .filter(|address| !address.is_empty())
+        .collect()
+}
+
+/// Get the bare email address, e.g. "user@example.com", from an address.
+pub(super) fn address_email(address: &str) -> &str {
+    address
+        .rsplit_once('<')
+        .and_then(|(_, rest)| rest.split_once('>'))
+        .map_or(address, |(email, _)| email)
+        .trim()
+}
+
+/// Decode an encoded header value.
+///
+/// Folded lines are unfolded and any RFC 2047 encoded-words are decoded.
+pub(super) fn decode_header(value: &str) -> String {
+    let unfolded = value.replace("\r\n", "\n").replace("\n", "");
+    let mut decoded = String::with_capacity(unfolded.len());
+    let mut pending_space = String::new();
+    let mut prev_was_encoded = false;
+    let mut rest = unfolded.as_str();
+    while !rest.is_empty() {
+        let word_end = rest.find([' ', '\t']).unwrap_or(rest.len());
+        let (word, remainder) = rest.split_at(word_end);
+        let space_end = remainder
+            .find(|c: char| c != ' ' && c != '\t')
+            .unwrap_or(remainder.len());
+        let (space, remainder) = remainder.split_at(space_end);
+        rest = remainder;
+
+        if let Some(text) = decode_encoded_word(word) {
+            if !prev_was_encoded {
+                decoded.push_str(&pending_space);
+            }
+            decoded.push_str(&text);
+            prev_was_encoded = true;
+        } else {
+            decoded.push_str(&pending_space);
+            decoded.push_str(word);
+            prev_was_encoded = false;
+        }
+        pending_space = space.to_string();
+    }
+    decoded
+}
+
+/// Decode a single RFC 2047 encoded-word, e.g. "=?UTF-8?q?caf=C3=A9?=".
+fn decode_encoded_word(word: &str) -> Option<String> {
+    let inner = word.strip_prefix("=?")?.strip_suffix("?=")?;
+    let mut parts = inner.splitn(3, '?');
+    let charset = parts.next()?;
+    let encoding = parts.next()?;
+    let text = parts.next()?;
+    let bytes = match encoding {
+        "q" | "Q" => {
+            let mut bytes = Vec::with_capacity(text.len());
+            let mut iter = text.bytes();
+            while let Some(b) = iter.next() {
+                match b {
+                    b'_' => bytes.push(b' '),
+                    b'=' => {
+                        let hex = [iter.next()?, iter.next()?];
+                        let hex = std::str::from_utf8(&hex).ok()?;
+                        bytes.push(u8::from_str_radix(hex, 16).ok()?);
+                    }
+                    _ => bytes.push(b),
+                }
+            }
+            bytes
+        }
+        "b" | "B" => base64_decode(text)?,
+        _ => return None,
+    };
+    let charset = charset.split('*').next().unwrap_or(charset);
+    let encoding =
+        encoding_rs::Encoding::for_label(charset.as_bytes()).unwrap_or(encoding_rs::UTF_8);
+    Some(encoding.decode_without_bom_handling(&bytes).0.into_owned())
+}
+
+/// Decode base64 text, returning `None` if the text is not valid base64.
+fn base64_decode(text: &str) -> Option<Vec<u8>> {
+    let mut bytes = Vec::with_capacity(text.len() * 3 / 4);
+    let mut acc: u32 = 0;
+    let mut bits = 0;
+    for c in text.bytes().filter(|&c| c != b'=') {
+        let value = match c {
+            b'A'..=b'Z' => c - b'A',
+            b'a'..=b'z' => c - b'a' + 26,
+            b'0'..=b'9' => c - b'0' + 52,
+            b'+' => 62,
+            b'/' => 63,
+            _ => return None,
+        };
+        acc = (acc << 6) | u32::from(value);
+        bits += 6;
+        if bits >= 8 {
+            bits -= 8;
+            bytes.push((acc >> bits) as u8);
+            acc &= (1 << bits) - 1;
+        }
+    }
+    Some(bytes)
+}
+
+/// Join multiple values of an address header, folding each onto its own line.
+pub(super) fn join_addresses(addresses: &[String]) -> String {
+    addresses.join(",\n    ")
+}
+
+/// Make a deterministic `Message-Id` for an email.
+///
+/// The id is derived from the given commit id and optional series version such that
+/// formatting the same patch for the same version of the series always yields the same
+/// id. The `label` distinguishes messages, such as the cover letter, that would
+/// otherwise use the same commit id. The domain is taken from the sender's email address.
+pub(super) fn message_id(
+    commit_id: gix::ObjectId,
+    label: Option<&str>,
+    version: Option<&str>,
+    sender_email: &str,
+) -> String {
+    let mut local = String::from("stgit.");
+    if let Some(label) = label {
+        local.push_str(label);
+        local.push('.');
+    }
+    local.push_str(&commit_id.to_string());
+    if let Some(version) = version {
+        local.push_str(".v");
+        local.extend(
+            version
+                .chars()
+                .filter(|c| c.is_ascii_alphanumeric() || matches!(c, '.' | '-')),
+        );
+    }
+    let domain = sender_email
+        .rsplit_once('@')
+        .map(|(_, domain)| domain)
+        .filter(|domain| !domain.is_empty())
+        .unwrap_or("localhost");
+    format!("<{local}@{domain}>")
+}
+
+/// Get the value of a header line if the header has the given name.
+fn header_value<'a>(line: &'a str, name: &str) -> Option<&'a str> {
+    let (line_name, value) = line.split_once(':')?;
+    line_name
+        .eq_ignore_ascii_case(name)
+        .then(|| value.strip_prefix(' ').unwrap_or(value))
+}
+
+/// Encode text as RFC 2047 "Q" encoded-words.
+///
+/// The encoded-words are folded such that no line exceeds [`MAX_ENCODED_LENGTH`]
+/// characters, given that the first line already has `line_len` characters. Address
+/// encoding is stricter about which characters may appear unencoded.
+fn encode_rfc2047(text: &str, mut line_len: usize, is_address: bool) -> String {
+    const WORD_START: &str = "=?UTF-8?q?";
+    const WORD_END: &str = "?=";
+
+    let mut encoded = String::from(WORD_START);
+    line_len += WORD_START.len();
+    let mut buf = [0u8; 4];
+    for c in text.chars() {
+        let mut encoded_char = String::new();
+        let is_special = |b: u8| {
+            !b.is_ascii()
+                || b.is_ascii_whitespace()
+                || matches!(b, b'=' | b'?' | b'_')
+                || (is_address && !b.is_ascii_alphanumeric() && !b"!*+-/".contains(&b))
+        };
+        for &b in c.encode_utf8(&mut buf).as_bytes() {
+            if is_special(b) {
+                encoded_char.push_str(&format!("={b:02X}"));
+            } else {
+                encoded_char.push(char::from(b));
+            }
+        }
+        if line_len + encoded_char.len() + WORD_END.len() > MAX_ENCODED_LENGTH
+            && encoded.len() > WORD_START.len()
+        {
+            encoded.push_str(WORD_END);
+            encoded.push_str("\n ");
+            encoded.push_str(WORD_START);
+            line_len = 1 + WORD_START.len();
+        }
+        encoded.push_str(&encoded_char);
+        line_len += encoded_char.len();
+    }
+    encoded.push_str(WORD_END);
+    encoded
+}
+
+/// Fold an ASCII header value at word boundaries.
+fn fold_words(text: &str, mut line_len: usize) -> String {
+    let mut folded = String::with_capacity(text.len());
+    for (i, word) in text.split(' ').enumerate() {
+        if i > 0 {
+            if line_len + 1 + word.len() > MAX_HEADER_LENGTH && !word.is_empty() {
+                folded.push('\n');
+                line_len = 0;
+            }
+            folded.push(' ');
+            line_len += 1;
+        }
+        folded.push_str(word);
+        line_len += word.len();
+    }
+    folded
+}
@@ -2,10 +2,19 @@
 
 //! `stg email` implementation.
 
+mod autocc;
 mod format;
+mod mbox;
 mod send;
+mod transport;
 
 use anyhow::Result;
+use clap::Arg;
+
+use crate::{
+    patch::PatchName,
+    stack::{parse_version, Stack, StackStateAccess},
+};
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
     name: "email",
@@ -26,10 +35,19 @@ fn make() -> clap::Command {
              send`. This workflow may be condensed to one step by specifying patch \
              names to `stg email send` instead of email files.\n\
              \n\
-             The `format` and `send` subcommands are thin wrappers over `git \
-             format-patch` and `git send-email`, respectively. Refer to the \
-             git-format-patch(1) and git-send-email(1) manpages for more details about \
-             configuration and options.",
+             The `format` subcommand generates emails compatible with those generated \
+             by `git format-patch`, and the `send` subcommand is, by default, a thin \
+             wrapper over `git send-email`. Refer to the git-format-patch(1) and git-send-email(1) \
+             manpages for more details about configuration and options.\n\
+             \n\
+             When formatting or sending patches from the stack, the stack's state is \
+             recorded as a numbered version of the patch series; see `stg series \
+             snapshot`. Unless '--reroll-count' is specified, the series version is \
+             that of the latest snapshot if none of the patches have changed since, \
+             otherwise the next version. For versions after the first, \
+             '--reroll-count' is filled in automatically and, unless '--interdiff' or \
+             '--range-diff' is specified, a range-diff against the previous version \
+             is included.",
         )
         .subcommand_required(true)
         .subcommand(format::command())
@@ -43,3 +61,150 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         _ => panic!("valid subcommand is expected"),
     }
 }
+
+/// Option to disable automatic series version snapshots.
+fn no_snapshot_arg() -> Arg {
+    Arg::new("no-snapshot")
+        .long("no-snapshot")
+        .help("Do not record a snapshot of the series version")
+        .long_help(
+            "Do not record a snapshot of the patch series version, and do not \
+             automatically fill in '--reroll-count' and '--range-diff' from \
+             previous versions.",
+        )
+        .action(clap::ArgAction::SetTrue)
+}
+
+/// Options for automatically collecting Cc addresses.
+fn auto_cc_args() -> Vec<Arg> {
+    vec![
+        Arg::new("auto-cc")
+            .long("auto-cc")
+            .help("Cc addresses from trailers and the Cc command")
+            .long_help(
+                "Automatically add Cc addresses to each patch's email. Addresses are \
+                 collected from the patch's `Signed-off-by`, `Reviewed-by`, and \
+                 `Acked-by` trailers, or the trailers listed in \
+                 `stgit.email.auto-cc-trailers`, and from the output of the Cc command, \
+                 if any. The cover letter is Cc'd to the addresses collected for all \
+                 patches. The sender is never automatically Cc'd.\n\
+                 \n\
+                 The default is the value of `stgit.email.auto-cc`.",
+            )
+            .action(clap::ArgAction::SetTrue),
+        Arg::new("no-auto-cc")
+            .long("no-auto-cc")
+            .help("Do not automatically add Cc addresses")
+            .action(clap::ArgAction::SetTrue)
+            .conflicts_with_all(["auto-cc", "cc-cmd"]),
+        Arg::new("cc-cmd")
+            .long("cc-cmd")
+            .help("Run <command> to get each patch's Cc addresses")
+            .long_help(
+                "Run <command> with the shell to get additional Cc addresses for each \
+                 patch, e.g. a script such as the Linux kernel's \
+                 `scripts/get_maintainer.pl`. The command is run with the path to a \
+                 file containing the patch's email as its argument and should output \
+                 one address per line. Any text following the address, such as \
+                 \"(maintainer:SUBSYSTEM)\", is ignored. Implies '--auto-cc'.\n\
+                 \n\
+                 The default is the value of `stgit.email.cc-cmd`.",
+            )
+            .value_name("command")
+            .num_args(1)
+            .value_parser(clap::builder::NonEmptyStringValueParser::new())
+            .value_hint(clap::ValueHint::CommandString),
+    ]
+}
+
+/// Series version of the patches being formatted or sent.
+struct SeriesVersion {
+    /// Version number to be recorded with [`Stack::snapshot_version()`] once the
+    /// patches are successfully formatted or sent.
+    number: u32,
+
+    /// Reroll count implied by the version, if not specified by the user.
+    reroll_count: Option<u32>,
+
+    /// Range-diff against the previous version, if the user did not specify an
+    /// interdiff or range-diff.
+    range_diff: Option<String>,
+}
+
+impl SeriesVersion {
+    /// Append the `git format-patch` options implied by the version to `args`.
+    fn push_git_args(&self, args: &mut Vec<String>) {
+        if let Some(reroll_count) = self.reroll_count {
+            args.push(format!("--reroll-count={reroll_count}"));
+        }
+        if let Some(range_diff) = self.range_diff.as_ref() {
+            args.push(format!("--range-diff={range_diff}"));
+        }
+    }
+}
+
+/// Determine the series version of the patches being formatted or sent.
+///
+/// `diff_specified` indicates whether the user already specified an interdiff or
+/// range-diff.
+fn prepare_version(
+    stack: &Stack,
+    patches: &[PatchName],
+    matches: &clap::ArgMatches,
+    diff_specified: bool,
+) -> Result<Option<SeriesVersion>> {
+    if matches.get_flag("no-snapshot") {
+        return Ok(None);
+    }
+
+    let (number, reroll_count) =
+        if let Some(reroll_count) = matches.get_one::<String>("reroll-count") {
+            if let Ok(version) = parse_version(reroll_count) {
+                (version, None)
+            } else {
+                return Ok(None);
+            }
+        } else {
+            let version = if let Some((latest, _)) = stack.get_versions()?.last() {
+                let latest_state = stack
+                    .get_version_state(*latest)?
+                    .expect("version was just listed");
+                let unchanged = patches.iter().all(|pn| {
+                    latest_state.has_patch(pn)
+                        && latest_state.get_patch_commit_id(pn) == stack.get_patch_commit_id(pn)
+                });
+                if unchanged {
+                    *latest
+                } else {
+                    latest + 1
+                }
+            } else {
+                1
+            };
+            (version, Some(version).filter(|version| *version > 1))
+        };
+
+    let mut range_diff = None;
+    if number > 1 && !diff_specified {
+        if let Some(prev_state) = stack.get_version_state(number - 1)? {
+            if let (Some(first), Some(last)) =
+                (prev_state.applied().first(), prev_state.applied().last())
+            {
+                let base = prev_state
+                    .get_patch_commit(first)
+                    .parent_ids()
+                    .next()
+                    .expect("patch commit has one parent")
+                    .detach();
+                let last = prev_state.get_patch_commit_id(last);
+                range_diff = Some(format!("{base}..{last}"));
+            }
+        }
+    }
+
+    Ok(Some(SeriesVersion {
+        number,
+        reroll_count,
+        range_diff,
+    }))
+}
@@ -2,17 +2,23 @@
 
 //! `stg email send` implementation.
 
-use std::{path::Path, str::FromStr};
+use std::{ffi::OsString, io::Write, path::Path, str::FromStr};
 
-use anyhow::{anyhow, Result};
+use anyhow::{anyhow, Context, Result};
+use bstr::{BString, ByteSlice, ByteVec};
 use clap::Arg;
 
+use super::{
+    autocc::AutoCc,
+    mbox,
+    transport::{Sendmail, SmtpClient, Transport},
+};
 use crate::{
     argset,
     branchloc::BranchLocator,
     ext::{CommitExtended, RepositoryExtended},
-    patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
+    stack::{InitializationPolicy, SentRecord, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -22,14 +28,35 @@ pub(super) fn command() -> clap::Command {
         .long_about(
             "Send patches as emails.\n\
              \n\
-             This is a wrapper for `git send-email`. Refer to the git-send-email(1) \
-             man page for additional details.\n\
+             By default, this is a wrapper for `git send-email`. Refer to the \
+             git-send-email(1) man page for additional details.\n\
+             \n\
+             Alternatively, emails may be sent without `git send-email` by setting \
+             `stgit.email.transport` to `smtp` to send emails directly to the SMTP \
+             server, or to `sendmail` to pipe emails to a sendmail-compatible \
+             program. The built-in SMTP client uses the `sendemail.smtpServer`, \
+             `sendemail.smtpServerPort`, `sendemail.smtpDomain`, \
+             `sendemail.smtpUser`, and `sendemail.smtpPass` configuration; \
+             encrypted connections are not supported. Because the password would be \
+             sent in cleartext, authenticating with `sendemail.smtpUser` is refused \
+             unless `stgit.email.smtp-plaintext-auth` is set to true. The sendmail \
+             program is \
+             `sendemail.sendmailCmd`, `sendemail.smtpServer` if it is an absolute \
+             path, or `sendmail`. The built-in transports do not support \
+             '--compose', '--annotate', '--subject', or '--git-opt'.\n\
              \n\
              The patches to send may be specified as files or directories generated by \
              `stg email format`, or as patch names/ranges as would be supplied to `stg \
              email format`. Specifying a directory will send all files in that \
              directory.\n\
              \n\
+             When patches are sent, the message id, date, and series version of each \
+             patch's email are recorded in the patch's sent history. When \
+             `stgit.email.chain-versions` is true and '--in-reply-to' is not \
+             specified, a new version of the patch series is sent as a reply to the \
+             previous version's email for the first patch. `stg series --sent` shows \
+             whether patches have changed since they were last sent.\n\
+             \n\
              The header of the email is configurable via command line options. The \
              user will be prompted for any necessary information not specified on the \
              command line or in the configuration.\n\
@@ -78,6 +105,7 @@ pub(super) fn command() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with_all(["patchranges-or-paths", "dump-aliases"]),
         )
+        .arg(super::no_snapshot_arg())
         .arg(
             Arg::new("git-send-email-opt")
                 .long("git-opt")
@@ -95,6 +123,7 @@ pub(super) fn command() -> clap::Command {
         )
         .next_help_heading("Compose Options")
         .args(compose_options())
+        .args(super::auto_cc_args())
         .next_help_heading("Send Options")
         .next_help_heading("Automate Options")
         .args(automate_options())
@@ -381,15 +410,16 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
     )?;
 
     let source_args = matches.get_many::<String>("patchranges-or-paths");
-    let sources = if let Some(patchranges_or_paths) = source_args {
+    let (sources, patches) = if let Some(patchranges_or_paths) = source_args {
         let patchranges_or_paths = patchranges_or_paths.collect::<Vec<_>>();
         if patchranges_or_paths.iter().all(|s| Path::new(s).is_dir())
             || patchranges_or_paths.iter().all(|s| Path::new(s).is_file())
         {
-            patchranges_or_paths
+            let paths = patchranges_or_paths
                 .iter()
                 .map(ToString::to_string)
-                .collect::<Vec<_>>()
+                .collect::<Vec<_>>();
+            (paths, None)
         } else {
             let mut ranges = Vec::new();
             for arg in patchranges_or_paths {
@@ -422,7 +452,7 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
                 .unwrap()
                 .detach();
             let last = stack.get_patch_commit_id(patches.last().unwrap());
-            vec![format!("{base}..{last}")]
+            (vec![format!("{base}..{last}")], Some(patches))
         }
     } else if matches.get_flag("all") {
         let applied = stack.applied();
@@ -437,18 +467,230 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
         }
         let base = stack.base().id;
         let last = stack.get_patch_commit_id(applied.last().unwrap());
-        vec![format!("{base}..{last}")]
+        (vec![format!("{base}..{last}")], Some(applied.to_vec()))
     } else {
         panic!("expect either patchranges or -a/--all")
     };
 
-    let mut send_args = Vec::new();
+    let version = if let Some(patches) = patches.as_ref() {
+        super::prepare_version(&stack, patches, matches, false)?
+    } else {
+        None
+    };
+
+    let in_reply_to = if let Some(in_reply_to) = matches.get_one::<String>("in-reply-to") {
+        Some(in_reply_to.clone())
+    } else if let (Some(patches), Some(version), true) = (
+        patches.as_deref(),
+        version.as_ref(),
+        repo.config_snapshot()
+            .boolean("stgit.email.chain-versions")
+            .unwrap_or(false),
+    ) {
+        previous_version_message_id(&stack, patches, version.number)
+    } else {
+        None
+    };
+
+    let transport = repo
+        .config_snapshot()
+        .string("stgit.email.transport")
+        .map(|transport| transport.to_str_lossy().into_owned());
+    let mut sent = Vec::new();
+    let result = match transport.as_deref() {
+        None | Some("git") => {
+            let auto_cc = AutoCc::from_matches(&repo, matches)?;
+
+            // Emails for patches, and emails with automatic Cc addresses, are prepared
+            // here and the resulting email files are given to `git send-email`.
+            let prepare = patches.is_some() || auto_cc.is_some();
+
+            let mut dummy_command = clap::Command::new("dummy")
+                .args(compose_options())
+                .args(automate_options())
+                .args(administer_options());
+            if !prepare {
+                dummy_command = dummy_command.args(format_options());
+            }
+            let mut send_args: Vec<OsString> = passthrough_args(matches, &mut dummy_command)
+                .into_iter()
+                .map(OsString::from)
+                .collect();
+            if let Some(in_reply_to) = in_reply_to
+                .as_ref()
+                .filter(|_| !matches.contains_id("in-reply-to"))
+            {
+                send_args.push(format!("--in-reply-to={in_reply_to}").into());
+            }
+
+            if let Some(values) = matches.get_many::<String>("git-send-email-opt") {
+                send_args.extend(values.map(OsString::from));
+            }
+
+            let (temp_dir, emails) = if prepare {
+                let mut emails = load_emails(
+                    &stack,
+                    &sources,
+                    patches.as_deref(),
+                    matches,
+                    version.as_ref(),
+                )?;
+                if let Some(auto_cc) = auto_cc {
+                    let sender = SendEmailConfig::new(&repo, matches).sender(&repo, matches)?;
+                    auto_cc.apply(&repo, &mut emails.iter_mut().collect::<Vec<_>>(), &sender)?;
+                }
+                let temp_dir = tempfile::Builder::new().prefix("stg-email-").tempdir()?;
+                for (i, email) in emails.iter().enumerate() {
+                    let path = temp_dir.path().join(format!("{:04}.patch", i + 1));
+                    let mut file = std::fs::File::create(&path)
+                        .with_context(|| format!("creating `{}`", path.display()))?;
+                    email.write_to(&mut file)?;
+                }
+                send_args.push(temp_dir.path().as_os_str().to_os_string());
+                (Some(temp_dir), emails)
+            } else {
+                send_args.extend(sources.iter().map(OsString::from));
+                // The email files are only parsed to record the sent patches.
+                let emails = load_emails(&stack, &sources, None, matches, None).unwrap_or_default();
+                (None, emails)
+            };
+
+            let mut output = Vec::new();
+            let result = repo.stupid().send_email(send_args, &mut output);
+            drop(temp_dir);
+            sent = sent_by_git(&emails, &output);
+            result
+        }
+        Some(kind @ ("smtp" | "sendmail")) => send_builtin(
+            &stack,
+            &sources,
+            patches.as_deref(),
+            matches,
+            version.as_ref(),
+            in_reply_to,
+            kind,
+            &mut sent,
+        ),
+        Some(kind) => {
+            return Err(anyhow!(
+                "invalid stgit.email.transport `{kind}`; expected `git`, `smtp`, or `sendmail`"
+            ));
+        }
+    };
+
+    // Emails sent before any failure are recorded before the failure is reported.
+    if !matches.get_flag("dry-run") {
+        let stack = record_sent(stack, &sent, version.as_ref())?;
+        if let (Ok(()), Some(version)) = (&result, version) {
+            stack.snapshot_version(version.number, true)?;
+        }
+    }
+
+    result
+}
+
+/// Determine which emails `git send-email` reported sending in its output.
+///
+/// Each sent email is reported either with a log of its headers, which includes its
+/// message id, or with `--quiet`, with its subject. Emails declined at a confirmation
+/// prompt are not reported, although their headers are shown with the prompt.
+fn sent_by_git(emails: &[mbox::Email], output: &[u8]) -> Vec<(gix::ObjectId, String)> {
+    let normalize = |subject: &str| subject.split_whitespace().collect::<Vec<_>>().join(" ");
+    let mut message_ids: Vec<String> = Vec::new();
+    let mut subjects: Vec<String> = Vec::new();
+    let mut in_log = false;
+    for line in output.lines() {
+        let line = line.to_str_lossy();
+        if line == "OK. Log says:" {
+            in_log = true;
+        } else if line.starts_with("Result: ") {
+            in_log = false;
+        } else if let Some(subject) = line.strip_prefix("Sent ") {
+            subjects.push(normalize(subject));
+        } else if let Some((name, value)) = line.split_once(':').filter(|_| in_log) {
+            if name.eq_ignore_ascii_case("Message-Id") {
+                message_ids.push(value.trim().to_string());
+            }
+        }
+    }
+
+    emails
+        .iter()
+        .filter(|email| !email.is_cover_letter())
+        .filter_map(|email| {
+            let message_id = email.get_header("Message-Id")?.trim().to_string();
+            let reported = message_ids.contains(&message_id)
+                || email
+                    .get_header("Subject")
+                    .map_or(false, |subject| subjects.contains(&normalize(subject)));
+            reported.then(|| (email.commit_id(), message_id))
+        })
+        .collect()
+}
+
+/// Record the sent emails in the sent history of the patches they were generated from.
+///
+/// Each email is given as the id of the commit it was generated from and its message
+/// id. Emails that do not correspond to any patch's current commit are ignored.
+fn record_sent<'repo>(
+    stack: Stack<'repo>,
+    sent: &[(gix::ObjectId, String)],
+    version: Option<&super::SeriesVersion>,
+) -> Result<Stack<'repo>> {
+    let time = gix::date::Time::now_local_or_utc();
+    let mut records: Vec<(PatchName, SentRecord)> = Vec::new();
+    for (commit_id, message_id) in sent {
+        if let Some(patchname) = stack
+            .all_patches()
+            .find(|pn| stack.get_patch_commit_id(pn) == *commit_id)
+        {
+            records.push((
+                patchname.clone(),
+                SentRecord {
+                    commit_id: *commit_id,
+                    message_id: message_id.clone(),
+                    time,
+                    version: version.map(|version| version.number),
+                },
+            ));
+        }
+    }
+
+    if records.is_empty() {
+        return Ok(stack);
+    }
+
+    stack.record_sent(records, "email send")
+}
+
+/// Get the message id of the previous version of the patch series.
+///
+/// The message id is that of the email for the first of the `patches` that was sent
+/// as the version preceding `version`.
+fn previous_version_message_id(
+    stack: &Stack,
+    patches: &[PatchName],
+    version: u32,
+) -> Option<String> {
+    let previous = version.checked_sub(1)?;
+    patches.iter().find_map(|patchname| {
+        stack
+            .get_patch(patchname)
+            .sent
+            .iter()
+            .rev()
+            .find(|record| record.version == Some(previous))
+            .map(|record| record.message_id.clone())
+    })
+}
+
+/// Get the command line options, for the args of `dummy_command`, to be passed
+/// through to another command.
+///
+/// The options are returned in the order they were specified on the command line.
+fn passthrough_args(matches: &clap::ArgMatches, dummy_command: &mut clap::Command) -> Vec<String> {
+    let mut args = Vec::new();
 
-    let mut dummy_command = clap::Command::new("dummy")
-        .args(compose_options())
-        .args(automate_options())
-        .args(administer_options())
-        .args(format_options());
     dummy_command.build();
 
     for arg in dummy_command.get_arguments() {
@@ -464,24 +706,504 @@ pub(super) fn dispatch(matches: &clap::ArgMatches) -> Result<()> {
                 let values = matches.get_many::<String>(arg_id).unwrap();
                 assert!(indices.len() == values.len());
                 indices.into_iter().zip(values).for_each(|(index, value)| {
-                    send_args.push((index, format!("--{long}={value}")));
+                    args.push((index, format!("--{long}={value}")));
                 });
             } else {
-                indices.for_each(|index| send_args.push((index, format!("--{long}"))));
+                indices.for_each(|index| args.push((index, format!("--{long}"))));
             }
         }
     }
 
-    send_args.sort_by_key(|(index, _)| *index);
+    args.sort_by_key(|(index, _)| *index);
 
-    let mut send_args = send_args.drain(..).map(|(_, s)| s).collect::<Vec<_>>();
+    args.drain(..).map(|(_, s)| s).collect()
+}
 
-    if let Some(values) = matches.get_many::<String>("git-send-email-opt") {
-        send_args.extend(values.cloned());
+/// Send emails with one of the built-in transports instead of `git send-email`.
+///
+/// Emails for patches are generated as by `stg email format`. Email files are sent
+/// as-is other than having their sender, date, recipient, and threading headers
+/// filled in.
+///
+/// The commit and message ids of the patch emails successfully delivered are pushed to
+/// `sent`, including when delivery of a later email fails.
+#[allow(clippy::too_many_arguments)]
+fn send_builtin(
+    stack: &Stack,
+    sources: &[String],
+    patches: Option<&[PatchName]>,
+    matches: &clap::ArgMatches,
+    version: Option<&super::SeriesVersion>,
+    in_reply_to: Option<String>,
+    kind: &str,
+    sent: &mut Vec<(gix::ObjectId, String)>,
+) -> Result<()> {
+    for (arg_id, long) in [
+        ("compose", "compose"),
+        ("annotate", "annotate"),
+        ("subject", "subject"),
+        ("git-send-email-opt", "git-opt"),
+    ] {
+        if matches!(
+            matches.value_source(arg_id),
+            Some(clap::parser::ValueSource::CommandLine)
+        ) {
+            return Err(anyhow!(
+                "'--{long}' is not supported with the `{kind}` email transport"
+            ));
+        }
     }
 
-    let mut sources = sources;
-    send_args.append(&mut sources);
+    let repo = stack.repo;
+    let config = SendEmailConfig::new(repo, matches);
+    let addresses = |arg_id: &str| {
+        let values: Vec<String> = if let Some(values) = matches.get_many::<String>(arg_id) {
+            values.cloned().collect()
+        } else {
+            config.strings(arg_id)
+        };
+        values
+            .iter()
+            .flat_map(|value| mbox::split_addresses(value))
+            .map(|address| mbox::encode_mailbox(&address))
+            .collect::<Vec<_>>()
+    };
+
+    let from = config.sender(repo, matches)?;
+    let envelope_sender = config
+        .string("envelopeSender")
+        .filter(|sender| sender != "auto");
+    let envelope_from = envelope_sender
+        .clone()
+        .unwrap_or_else(|| mbox::address_email(&from).to_string());
+    let to = addresses("to");
+    let cc = addresses("cc");
+    let bcc = addresses("bcc");
+    let reply_to = matches
+        .get_one::<String>("reply-to")
+        .map(|reply_to| mbox::encode_mailbox(reply_to));
+    let in_reply_to = in_reply_to.map(|id| {
+        let id = id.trim();
+        if id.starts_with('<') {
+            id.to_string()
+        } else {
+            format!("<{id}>")
+        }
+    });
+    let thread = !matches.get_flag("no-thread") && config.boolean("thread").unwrap_or(true);
+    let chain_reply_to = config.boolean("chainReplyTo").unwrap_or(false);
+    let confirm = matches
+        .get_one::<String>("confirm")
+        .cloned()
+        .or_else(|| config.string("confirm"))
+        .unwrap_or_else(|| "auto".to_string());
+    if !["always", "never", "cc", "compose", "auto"].contains(&confirm.as_str()) {
+        return Err(anyhow!("invalid sendemail.confirm `{confirm}`"));
+    }
+    let dry_run = matches.get_flag("dry-run");
+    let quiet = matches.get_flag("quiet");
+
+    let smtp_server = config.string("smtpServer");
+    let sendmail_cmd = config.string("sendmailCmd");
+    let mut transport = if kind == "sendmail"
+        || smtp_server
+            .as_ref()
+            .map_or(sendmail_cmd.is_some(), |server| server.starts_with('/'))
+    {
+        let set_sender = envelope_sender.is_some();
+        if let Some(command) = sendmail_cmd {
+            Transport::Sendmail(Sendmail::new(command.into(), true, set_sender))
+        } else if let Some(program) = smtp_server.filter(|server| server.starts_with('/')) {
+            Transport::Sendmail(Sendmail::new(program.into(), false, set_sender))
+        } else {
+            let program = ["/usr/sbin/sendmail", "/usr/lib/sendmail"]
+                .into_iter()
+                .find(|path| Path::new(path).is_file())
+                .unwrap_or("sendmail");
+            Transport::Sendmail(Sendmail::new(program.into(), false, set_sender))
+        }
+    } else {
+        if let Some(encryption) = config.string("smtpEncryption") {
+            if matches!(encryption.as_str(), "ssl" | "tls") {
+                return Err(anyhow!(
+                    "sendemail.smtpEncryption `{encryption}` is not supported by the \
+                     built-in SMTP client; use the `git` or `sendmail` email transport"
+                ));
+            }
+        }
+        let port = if let Some(port) = config.string("smtpServerPort") {
+            port.parse::<u16>()
+                .map_err(|_| anyhow!("invalid sendemail.smtpServerPort `{port}`"))?
+        } else {
+            25
+        };
+        let credentials = if let Some(user) = config.string("smtpUser") {
+            let password = config
+                .string("smtpPass")
+                .ok_or_else(|| anyhow!("sendemail.smtpPass must be set with sendemail.smtpUser"))?;
+            if !repo
+                .config_snapshot()
+                .boolean("stgit.email.smtp-plaintext-auth")
+                .unwrap_or(false)
+            {
+                return Err(anyhow!(
+                    "refusing to send SMTP credentials over an unencrypted connection; \
+                     set `stgit.email.smtp-plaintext-auth` to allow it, or use the `git` \
+                     or `sendmail` email transport"
+                ));
+            }
+            Some((user, password))
+        } else {
+            None
+        };
+        Transport::Smtp(SmtpClient::new(
+            smtp_server.unwrap_or_else(|| "localhost".to_string()),
+            port,
+            config
+                .string("smtpDomain")
+                .unwrap_or_else(|| "localhost.localdomain".to_string()),
+            credentials,
+        ))
+    };
+
+    let mut emails = load_emails(stack, sources, patches, matches, version)?;
+    let auto_cc_added = if let Some(auto_cc) = AutoCc::from_matches(repo, matches)? {
+        auto_cc.apply(repo, &mut emails.iter_mut().collect::<Vec<_>>(), &from)?
+    } else {
+        vec![false; emails.len()]
+    };
+
+    let now = gix::date::Time::now_local_or_utc();
+    let first_seconds =
+        now.seconds - emails.len().saturating_sub(1) as gix::date::SecondsSinceUnixEpoch;
+    let id_domain = envelope_from
+        .rsplit_once('@')
+        .map_or("localhost", |(_, domain)| domain);
+    let mut thread_refs: Vec<String> = in_reply_to.iter().cloned().collect();
+    let mut send_all = dry_run || confirm == "never";
+    let stdin = std::io::stdin();
 
-    repo.stupid().send_email(send_args)
+    for (i, mut email) in emails.into_iter().enumerate() {
+        if let Some(author) = email.remove_header("From") {
+            let author = mbox::decode_header(&author);
+            if author != mbox::decode_header(&from) {
+                let mut body = BString::from(format!("From: {author}\n\n"));
+                body.push_str(&email.body);
+                email.body = body;
+                if mbox::has_non_ascii(author.as_bytes())
+                    && email.get_header("Content-Type").is_none()
+                {
+                    email.mime_headers();
+                }
+            }
+        }
+        email.set_header("From", &from);
+
+        let message_to = merge_addresses(email.get_header("To"), &to);
+        let message_cc = merge_addresses(email.get_header("Cc"), &cc);
+        let message_bcc = merge_addresses(email.remove_header("Bcc").as_deref(), &bcc);
+        if !message_to.is_empty() {
+            email.set_header("To", mbox::join_addresses(&message_to));
+        }
+        if !message_cc.is_empty() {
+            email.set_header("Cc", mbox::join_addresses(&message_cc));
+        }
+        if let Some(reply_to) = reply_to.as_ref() {
+            email.set_header("Reply-To", reply_to);
+        }
+        email.set_header(
+            "Date",
+            gix::date::Time {
+                seconds: first_seconds + i as gix::date::SecondsSinceUnixEpoch,
+                ..now
+            }
+            .format(gix::date::time::format::GIT_RFC2822),
+        );
+
+        let message_id = if let Some(message_id) = email.get_header("Message-Id") {
+            message_id.trim().to_string()
+        } else {
+            let message_id = format!(
+                "<{}.{}-{}-stgit@{id_domain}>",
+                now.seconds,
+                std::process::id(),
+                i + 1
+            );
+            email.set_header("Message-Id", &message_id);
+            message_id
+        };
+        if email.get_header("In-Reply-To").is_none() {
+            if thread {
+                if let Some(parent) = thread_refs.last() {
+                    email.set_header("In-Reply-To", parent);
+                    email.set_header("References", thread_refs.join("\n "));
+                }
+            } else if let Some(in_reply_to) = in_reply_to.as_ref() {
+                email.set_header("In-Reply-To", in_reply_to);
+                email.set_header("References", in_reply_to);
+            }
+        }
+        if chain_reply_to || thread_refs.len() == usize::from(in_reply_to.is_some()) {
+            thread_refs.push(message_id.clone());
+        }
+
+        let subject = email
+            .get_header("Subject")
+            .map(mbox::decode_header)
+            .unwrap_or_default();
+        let mut recipients: Vec<String> = Vec::new();
+        for address in message_to.iter().chain(&message_cc).chain(&message_bcc) {
+            let address = mbox::address_email(address);
+            if !recipients
+                .iter()
+                .any(|existing| existing.eq_ignore_ascii_case(address))
+            {
+                recipients.push(address.to_string());
+            }
+        }
+        if recipients.is_empty() {
+            return Err(anyhow!(
+                "no recipients for email `{subject}`; use '--to' or sendemail.to"
+            ));
+        }
+
+        let mut log = format!("{}\nMAIL FROM:<{envelope_from}>\n", transport.describe());
+        for recipient in &recipients {
+            log.push_str(&format!("RCPT TO:<{recipient}>\n"));
+        }
+        for header in email.headers() {
+            log.push_str(header);
+            log.push('\n');
+        }
+
+        // As with `git send-email`, the "cc" and "auto" modes only confirm emails with
+        // automatically added Cc addresses.
+        let needs_confirm = confirm == "always" || (confirm != "compose" && auto_cc_added[i]);
+        if !send_all && needs_confirm {
+            print!("{log}\nSend this email? ([y]es|(n)o|(q)uit|(a)ll): ");
+            std::io::stdout().flush()?;
+            let mut answer = String::new();
+            if stdin.read_line(&mut answer)? == 0 {
+                answer.push('q');
+            }
+            match answer.trim().to_ascii_lowercase().as_str() {
+                "" | "y" | "yes" => {}
+                "a" | "all" => send_all = true,
+                "n" | "no" => continue,
+                _ => break,
+            }
+        }
+
+        if !dry_run {
+            let mut message = Vec::new();
+            email.write_message_to(&mut message)?;
+            transport.deliver(&envelope_from, &recipients, &message)?;
+            if !email.is_cover_letter() {
+                sent.push((email.commit_id(), message_id));
+            }
+        }
+
+        let dry = if dry_run { "Dry-" } else { "" };
+        if quiet {
+            println!("{dry}Sent {subject}");
+        } else {
+            println!("{dry}OK. Log says:\n{log}\nResult: OK");
+        }
+    }
+
+    transport.finish()
+}
+
+/// Load the emails to be sent, either by generating them for the patches, or by
+/// reading them from the source files and directories.
+fn load_emails(
+    stack: &Stack,
+    sources: &[String],
+    patches: Option<&[PatchName]>,
+    matches: &clap::ArgMatches,
+    version: Option<&super::SeriesVersion>,
+) -> Result<Vec<mbox::Email>> {
+    if let Some(patches) = patches {
+        let mut format_args = vec!["format".to_string()];
+        format_args.extend(passthrough_args(
+            matches,
+            &mut clap::Command::new("dummy").args(format_options()),
+        ));
+        format_args.extend(patches.iter().map(ToString::to_string));
+        let format_matches = super::format::command().try_get_matches_from(format_args)?;
+        Ok(
+            super::format::make_emails(stack, patches, &format_matches, version)?
+                .into_iter()
+                .map(|(_, email)| email)
+                .collect(),
+        )
+    } else {
+        let mut paths = Vec::new();
+        for source in sources {
+            let path = Path::new(source);
+            if path.is_dir() {
+                let mut dir_paths = std::fs::read_dir(path)?
+                    .map(|entry| entry.map(|entry| entry.path()))
+                    .collect::<std::io::Result<Vec<_>>>()?;
+                dir_paths.retain(|path| path.is_file());
+                dir_paths.sort();
+                paths.extend(dir_paths);
+            } else {
+                paths.push(path.to_path_buf());
+            }
+        }
+        paths
+            .iter()
+            .map(|path| {
+                let data =
+                    std::fs::read(path).with_context(|| format!("reading `{}`", path.display()))?;
+                mbox::Email::parse(&data).with_context(|| format!("parsing `{}`", path.display()))
+            })
+            .collect()
+    }
+}
+
+/// Access to `sendemail.*` configuration.
+///
+/// Values in the `sendemail.<identity>` subsection take precedence when an identity
+/// is specified with '--identity' or `sendemail.identity`.
+struct SendEmailConfig<'repo> {
+    config: gix::config::Snapshot<'repo>,
+    identity: Option<String>,
+}
+
+impl<'repo> SendEmailConfig<'repo> {
+    fn new(repo: &'repo gix::Repository, matches: &clap::ArgMatches) -> Self {
+        let config = repo.config_snapshot();
+        let identity = matches.get_one::<String>("identity").cloned().or_else(|| {
+            config
+                .string("sendemail.identity")
+                .map(|s| s.to_str_lossy().into_owned())
+        });
+        Self { config, identity }
+    }
+
+    fn keys(&self, name: &str) -> Vec<String> {
+        let mut keys = Vec::with_capacity(2);
+        if let Some(identity) = self.identity.as_ref() {
+            keys.push(format!("sendemail.{identity}.{name}"));
+        }
+        keys.push(format!("sendemail.{name}"));
+        keys
+    }
+
+    fn string(&self, name: &str) -> Option<String> {
+        self.keys(name)
+            .iter()
+            .find_map(|key| self.config.string(key.as_str()))
+            .map(|s| s.to_str_lossy().into_owned())
+    }
+
+    fn boolean(&self, name: &str) -> Option<bool> {
+        self.keys(name)
+            .iter()
+            .find_map(|key| self.config.boolean(key.as_str()))
+    }
+
+    fn strings(&self, name: &str) -> Vec<String> {
+        self.keys(name)
+            .iter()
+            .find_map(|key| self.config.plumbing().strings_by_key(key.as_str()))
+            .unwrap_or_default()
+            .iter()
+            .map(|value| value.to_str_lossy().into_owned())
+            .collect()
+    }
+
+    /// Get the encoded "From" address from '--from', `sendemail.from`, or the
+    /// committer identity.
+    fn sender(&self, repo: &gix::Repository, matches: &clap::ArgMatches) -> Result<String> {
+        if let Some(from) = matches
+            .get_one::<String>("from")
+            .cloned()
+            .or_else(|| self.string("from"))
+        {
+            Ok(mbox::encode_mailbox(&from))
+        } else {
+            let committer = repo.get_committer()?;
+            Ok(mbox::encode_address(
+                &committer.name.to_str_lossy(),
+                &committer.email.to_str_lossy(),
+            ))
+        }
+    }
+}
+
+/// Merge an email's existing address header value with additional addresses.
+///
+/// Addresses already present in the header are not duplicated.
+fn merge_addresses(existing: Option<&str>, additional: &[String]) -> Vec<String> {
+    let mut addresses = existing.map(mbox::split_addresses).unwrap_or_default();
+    for address in additional {
+        let email = mbox::address_email(address);
+        if !addresses
+            .iter()
+            .any(|existing| mbox::address_email(existing).eq_ignore_ascii_case(email))
+        {
+            addresses.push(address.clone());
+        }
+    }
+    addresses
+}
+
+#[cfg(test)]
+mod test {
+    use super::{mbox::Email, sent_by_git};
+
+    fn email(n: u8) -> Email {
+        let mut email = Email::new(gix::ObjectId::from([n; 20]));
+        email.header("Subject", format!("[PATCH {n}/3] p{n}"));
+        email.header("Message-Id", format!("<{n}@example.com>"));
+        email
+    }
+
+    #[test]
+    fn declined_emails_are_not_sent() {
+        let emails = [email(1), email(2), email(3)];
+        let output = b"\
+            From: A U Thor <author@example.com>\n\
+            Subject: [PATCH 1/3] p1\n\
+            Message-ID: <1@example.com>\n\
+            \n\
+            OK. Log says:\n\
+            Server: localhost\n\
+            Subject: [PATCH 1/3] p1\n\
+            Message-ID: <1@example.com>\n\
+            \n\
+            Result: 250\n\
+            \n\
+            Subject: [PATCH 2/3] p2\n\
+            Message-ID: <2@example.com>\n\
+            \n\
+            OK. Log says:\n\
+            Server: localhost\n\
+            Subject: [PATCH 3/3] p3\n\
+            Message-ID: <3@example.com>\n\
+            \n\
+            Result: 250\n";
+        let sent = sent_by_git(&emails, output);
+        assert_eq!(
+            sent,
+            [
+                (gix::ObjectId::from([1; 20]), "<1@example.com>".to_string()),
+                (gix::ObjectId::from([3; 20]), "<3@example.com>".to_string()),
+            ]
+        );
+    }
+
+    #[test]
+    fn quiet_sent_emails() {
+        let emails = [email(1), email(2), email(3)];
+        let output = b"Sent [PATCH 2/3] p2\nDry-Sent [PATCH 3/3] p3\n";
+        let sent = sent_by_git(&emails, output);
+        assert_eq!(
+            sent,
+            [(gix::ObjectId::from([2; 20]), "<2@example.com>".to_string())]
+        );
+    }
 }
@@ -0,0 +1,302 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Built-in email transports for `stg email send`.
+//!
+//! Emails may be delivered either directly to an SMTP server or by piping them to a
+//! sendmail-compatible program. Only unencrypted SMTP connections are supported; TLS
+//! connections require `git send-email` or a sendmail program that supports them.
+
+use std::{
+    ffi::OsString,
+    io::{BufRead, BufReader, Write},
+    net::TcpStream,
+    time::Duration,
+};
+
+use anyhow::{anyhow, Context, Result};
+
+/// Time to wait for the SMTP server to respond before giving up.
+const SMTP_TIMEOUT: Duration = Duration::from_secs(300);
+
+/// Means of delivering emails.
+pub(super) enum Transport {
+    Smtp(SmtpClient),
+    Sendmail(Sendmail),
+}
+
+impl Transport {
+    /// Describe where emails are delivered, e.g. "Server: localhost".
+    pub(super) fn describe(&self) -> String {
+        match self {
+            Transport::Smtp(client) => format!("Server: {}", client.server),
+            Transport::Sendmail(sendmail) => {
+                format!("Sendmail: {}", sendmail.program.to_string_lossy())
+            }
+        }
+    }
+
+    /// Deliver `message` from the envelope `sender` to the envelope `recipients`.
+    ///
+    /// The message uses LF line endings and is transformed as needed by the
+    /// transport.
+    pub(super) fn deliver(
+        &mut self,
+        sender: &str,
+        recipients: &[String],
+        message: &[u8],
+    ) -> Result<()> {
+        match self {
+            Transport::Smtp(client) => client.deliver(sender, recipients, message),
+            Transport::Sendmail(sendmail) => sendmail.deliver(sender, recipients, message),
+        }
+    }
+
+    /// Finish delivering emails, closing any connection to the server.
+    pub(super) fn finish(self) -> Result<()> {
+        match self {
+            Transport::Smtp(client) => client.quit(),
+            Transport::Sendmail(_) => Ok(()),
+        }
+    }
+}
+
+/// Minimal SMTP client.
+///
+/// The connection to the server is only established when the first email is
+/// delivered, and is reused for subsequent emails.
+pub(super) struct SmtpClient {
+    server: String,
+    port: u16,
+    domain: String,
+    credentials: Option<(String, String)>,
+    connection: Option<SmtpConnection>,
+}
+
+struct SmtpConnection {
+    reader: BufReader<TcpStream>,
+    writer: TcpStream,
+    eight_bit_mime: bool,
+}
+
+impl SmtpClient {
+    pub(super) fn new(
+        server: String,
+        port: u16,
+        domain: String,
+        credentials: Option<(String, String)>,
+    ) -> Self {
+        Self {
+            server,
+            port,
+            domain,
+            credentials,
+            connection: None,
+        }
+    }
+
+    fn deliver(&mut self, sender: &str, recipients: &[String], message: &[u8]) -> Result<()> {
+        if self.connection.is_none() {
+            self.connect()?;
+        }
+        let connection = self.connection.as_mut().expect("connection was just made");
+
+        let body_param = if connection.eight_bit_mime {
+            " BODY=8BITMIME"
+        } else {
+            ""
+        };
+        connection.command(&format!("MAIL FROM:<{sender}>{body_param}"), 250)?;
+        for recipient in recipients {
+            connection.command(&format!("RCPT TO:<{recipient}>"), 250)?;
+        }
+        connection.command("DATA", 354)?;
+
+        let mut data = Vec::with_capacity(message.len() + message.len() / 32);
+        for line in message.split_inclusive(|&b| b == b'\n') {
+            let line = line.strip_suffix(b"\n").unwrap_or(line);
+            let line = line.strip_suffix(b"\r").unwrap_or(line);
+            if line.starts_with(b".") {
+                data.push(b'.');
+            }
+            data.extend_from_slice(line);
+            data.extend_from_slice(b"\r\n");
+        }
+        data.extend_from_slice(b".\r\n");
+        connection.writer.write_all(&data)?;
+        connection.writer.flush()?;
+        connection.expect(250)?;
+        Ok(())
+    }
+
+    fn connect(&mut self) -> Result<()> {
+        let writer = TcpStream::connect((self.server.as_str(), self.port)).with_context(|| {
+            format!(
+                "connecting to SMTP server `{}` port {}",
+                self.server, self.port
+            )
+        })?;
+        writer.set_read_timeout(Some(SMTP_TIMEOUT))?;
+        writer.set_write_timeout(Some(SMTP_TIMEOUT))?;
+        let reader = BufReader::new(writer.try_clone()?);
+        let mut connection = SmtpConnection {
+            reader,
+            writer,
+            eight_bit_mime: false,
+        };
+        connection.expect(220)?;
+        let capabilities = connection.command(&format!("EHLO {}", self.domain), 250)?;
+        connection.eight_bit_mime = capabilities
+            .iter()
+            .any(|line| line.eq_ignore_ascii_case("8BITMIME"));
+
+        if let Some((user, password)) = self.credentials.as_ref() {
+            let mechanisms: Vec<String> = capabilities
+                .iter()
+                .filter_map(|line| {
+                    let (keyword, rest) = line.split_once(' ')?;
+                    keyword.eq_ignore_ascii_case("AUTH").then_some(rest)
+                })
+                .flat_map(|rest| rest.split_whitespace())
+                .map(str::to_ascii_uppercase)
+                .collect();
+            if mechanisms.iter().any(|m| m == "PLAIN") {
+                let token = base64_encode(format!("\0{user}\0{password}").as_bytes());
+                connection.command(&format!("AUTH PLAIN {token}"), 235)?;
+            } else if mechanisms.iter().any(|m| m == "LOGIN") {
+                connection.command("AUTH LOGIN", 334)?;
+                connection.command(&base64_encode(user.as_bytes()), 334)?;
+                connection.command(&base64_encode(password.as_bytes()), 235)?;
+            } else {
+                return Err(anyhow!(
+                    "SMTP server `{}` does not support PLAIN or LOGIN authentication",
+                    self.server
+                ));
+            }
+        }
+
+        self.connection = Some(connection);
+        Ok(())
+    }
+
+    fn quit(self) -> Result<()> {
+        if let Some(mut connection) = self.connection {
+            connection.command("QUIT", 221)?;
+        }
+        Ok(())
+    }
+}
+
+impl SmtpConnection {
+    /// Send a command to the server and check its reply.
+    fn command(&mut self, command: &str, expected_code: u16) -> Result<Vec<String>> {
+        self.writer.write_all(command.as_bytes())?;
+        self.writer.write_all(b"\r\n")?;
+        self.writer.flush()?;
+        self.expect(expected_code)
+    }
+
+    /// Read a reply from the server, which must have the expected code.
+    ///
+    /// The text of each line of the reply is returned.
+    fn expect(&mut self, expected_code: u16) -> Result<Vec<String>> {
+        let mut lines = Vec::new();
+        loop {
+            let mut line = String::new();
+            if self.reader.read_line(&mut line)? == 0 {
+                return Err(anyhow!("SMTP server closed the connection"));
+            }
+            let line = line.trim_end();
+            let code = line
+                .get(..3)
+                .and_then(|code| code.parse::<u16>().ok())
+                .ok_or_else(|| anyhow!("invalid SMTP server reply `{line}`"))?;
+            let is_last = line.as_bytes().get(3) != Some(&b'-');
+            lines.push(line.get(4..).unwrap_or_default().to_string());
+            if is_last {
+                if code == expected_code {
+                    return Ok(lines);
+                } else {
+                    return Err(anyhow!("SMTP server replied `{line}`"));
+                }
+            }
+        }
+    }
+}
+
+/// Sendmail-compatible program to pipe emails to.
+pub(super) struct Sendmail {
+    program: OsString,
+    use_shell: bool,
+    set_sender: bool,
+}
+
+impl Sendmail {
+    /// Use the given program to deliver emails.
+    ///
+    /// When `use_shell` is true, the program is a command line to be run with the
+    /// shell. The envelope sender is only passed to the program, with '-f', when
+    /// `set_sender` is true.
+    pub(super) fn new(program: OsString, use_shell: bool, set_sender: bool) -> Self {
+        Self {
+            program,
+            use_shell,
+            set_sender,
+        }
+    }
+
+    fn deliver(&mut self, sender: &str, recipients: &[String], message: &[u8]) -> Result<()> {
+        let mut command = if self.use_shell {
+            let mut command_line = self.program.clone();
+            command_line.push(r#" "$@""#);
+            let mut command = std::process::Command::new("sh");
+            command.arg("-c").arg(command_line).arg(&self.program);
+            command
+        } else {
+            std::process::Command::new(&self.program)
+        };
+        command.arg("-i");
+        if self.set_sender {
+            command.arg("-f").arg(sender);
+        }
+        command.args(recipients).stdin(std::process::Stdio::piped());
+        let mut child = command
+            .spawn()
+            .with_context(|| format!("running `{}`", self.program.to_string_lossy()))?;
+        child
+            .stdin
+            .take()
+            .expect("stdin is piped")
+            .write_all(message)?;
+        let status = child.wait()?;
+        if status.success() {
+            Ok(())
+        } else {
+            Err(anyhow!(
+                "`{}` failed with {status}",
+                self.program.to_string_lossy()
+            ))
+        }
+    }
+}
+
+/// Encode bytes as base64 text.
+fn base64_encode(data: &[u8]) -> String {
+    const ALPHABET: &[u8; 64] = b"ABCDEFGHIJKLMNOPQRSTUVWXYZabcdefghijklmnopqrstuvwxyz0123456789+/";
+    let mut encoded = String::with_capacity((data.len() + 2) / 3 * 4);
+    for chunk in data.chunks(3) {
+        let b = [
+            chunk[0],
+            chunk.get(1).copied().unwrap_or(0),
+            chunk.get(2).copied().unwrap_or(0),
+        ];
+        let n = (u32::from(b[0]) << 16) | (u32::from(b[1]) << 8) | u32::from(b[2]);
+        for i in 0..4 {
+            if i <= chunk.len() {
+                encoded.push(char::from(ALPHABET[(n >> (18 - 6 * i)) as usize & 0x3f]));
+            } else {
+                encoded.push('=');
+            }
+        }
+    }
+    encoded
+}
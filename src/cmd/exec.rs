@@ -0,0 +1,383 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg exec` implementation.
+
+use std::{
+    ffi::OsString,
+    io::Write,
+    path::{Path, PathBuf},
+    process::{ExitStatus, Output, Stdio},
+    sync::atomic::{AtomicUsize, Ordering},
+};
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, ValueHint};
+use termcolor::WriteColor;
+
+use crate::{
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::{range, PatchName, PatchRange, RangeConstraint},
+    print_info_message,
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stupid::{Stupid, StupidContext},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "exec",
+    category: super::CommandCategory::StackInspection,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Run a command against each patch")
+        .long_about(
+            "Run a command against each patch in a range of patches.\n\
+             \n\
+             Each patch's commit is checked out, in turn, into a linked worktree \
+             and the command is run from the root of that worktree. Since the linked \
+             worktree belongs to the repository, the command may run git and other \
+             commands that need the repository. The repository's own index and \
+             worktree are left untouched, so there is no need for the worktree to be \
+             clean and the stack is not modified.\n\
+             \n\
+             The linked worktree is reused for each patch such that only the files \
+             that differ between patches are updated. This allows build artifacts to \
+             be reused from one patch to the next. The linked worktree is created \
+             under the repository's git directory and is removed once the command \
+             has been run against all patches. Interrupting the command with ctrl-c \
+             leaves the linked worktree in place; it may be removed with `git \
+             worktree remove`.\n\
+             \n\
+             With '--jobs', the command is run against several patches concurrently, \
+             each in its own linked worktree.\n\
+             \n\
+             The command is run for every patch, even when it fails for some patches. \
+             A table of the outcome for each patch is printed once the command has \
+             been run for all patches.\n\
+             \n\
+             By default, the command is run against the applied patches. Use \
+             '--range' to select a different range of patches.\n\
+             \n\
+             When the command consists of a single argument, it is run with the \
+             shell. This allows shell constructs such as `stg exec \"make && make \
+             test\"`.",
+        )
+        .arg(
+            Arg::new("range")
+                .long("range")
+                .short('r')
+                .help("Run command against patches in <patch-range>")
+                .value_name("patch-range")
+                .value_hint(ValueHint::Other)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchRange)),
+        )
+        .arg(
+            Arg::new("jobs")
+                .long("jobs")
+                .short('j')
+                .help("Run command against up to <n> patches in parallel")
+                .long_help(
+                    "Run the command against up to <n> patches in parallel. A linked \
+                     worktree is created, under the repository's git directory, for \
+                     each job. The linked worktrees are removed once the command has \
+                     been run against all patches. The output of \
+                     each run of the command is captured and reported in stack order. \
+                     A value of 0 uses as many jobs as there are available CPUs.",
+                )
+                .value_name("n")
+                .value_hint(ValueHint::Other)
+                .value_parser(clap::value_parser!(usize)),
+        )
+        .arg(
+            Arg::new("record")
+                .long("record")
+                .help("Record each patch's outcome in patch metadata <key>")
+                .long_help(
+                    "Record the outcome of running the command against each patch as \
+                     patch metadata. The value associated with <key> will be either \
+                     \"pass\" or \"fail\". See stg-meta(1).",
+                )
+                .value_name("key")
+                .value_hint(ValueHint::Other)
+                .value_parser(super::meta::parse_key),
+        )
+        .arg(
+            Arg::new("command")
+                .help("Command to run for each patch")
+                .value_name("command")
+                .required(true)
+                .num_args(1..)
+                .trailing_var_arg(true)
+                .allow_hyphen_values(true)
+                .value_hint(ValueHint::CommandWithArguments)
+                .value_parser(clap::value_parser!(OsString)),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
+
+    let patchnames = if let Some(range) = matches.get_one::<PatchRange>("range") {
+        range::resolve_names(&stack, [range], RangeConstraint::VisibleWithAppliedBoundary)?
+    } else {
+        stack.applied().to_vec()
+    };
+
+    if patchnames.is_empty() {
+        return Err(super::Error::NoAppliedPatches.into());
+    }
+
+    let command: Vec<&OsString> = matches
+        .get_many::<OsString>("command")
+        .expect("required argument")
+        .collect();
+    let display_command = display_command(&command);
+
+    let patches: Vec<(&PatchName, gix::ObjectId)> = patchnames
+        .iter()
+        .map(|patchname| (patchname, stack.get_patch_commit(patchname).id))
+        .collect();
+
+    let jobs = match matches.get_one::<usize>("jobs").copied().unwrap_or(1) {
+        0 => std::thread::available_parallelism().map_or(1, usize::from),
+        jobs => jobs,
+    }
+    .min(patches.len());
+
+    let outcomes = exec_in_worktrees(&repo, matches, &patches, &command, jobs)?;
+
+    let mut stdout = get_color_stdout(matches);
+    let mut color_spec = termcolor::ColorSpec::new();
+    for (patchname, status) in patchnames.iter().zip(&outcomes) {
+        if status.success() {
+            stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Green)))?;
+            write!(stdout, "pass")?;
+            stdout.reset()?;
+            writeln!(stdout, " {patchname}")?;
+        } else {
+            stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Red)))?;
+            write!(stdout, "fail")?;
+            stdout.reset()?;
+            writeln!(stdout, " {patchname} ({status})")?;
+        }
+    }
+
+    if let Some(key) = matches.get_one::<String>("record") {
+        stack
+            .setup_transaction()
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| {
+                for (patchname, status) in patchnames.iter().zip(&outcomes) {
+                    let outcome = if status.success() { "pass" } else { "fail" };
+                    trans.set_patch_meta(patchname, key, Some(outcome));
+                }
+                Ok(())
+            })
+            .execute(&format!("exec --record {key}"))?;
+    }
+
+    let failed = outcomes.iter().filter(|status| !status.success()).count();
+    if failed == 0 {
+        Ok(())
+    } else {
+        Err(anyhow!(
+            "`{display_command}` failed for {failed} of {} patches",
+            patchnames.len()
+        ))
+    }
+}
+
+/// Run the command against the patches in linked worktrees.
+///
+/// A linked worktree is created for each job. The linked worktrees are removed before
+/// returning, even when running the command fails. Only creating and removing the
+/// linked worktrees is shielded from ctrl-c; the command itself may be interrupted.
+fn exec_in_worktrees(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    patches: &[(&PatchName, gix::ObjectId)],
+    command: &[&OsString],
+    jobs: usize,
+) -> Result<Vec<ExitStatus>> {
+    let stupid = repo.stupid();
+    let worktrees_dir = repo.git_dir().join("stgit").join("worktrees");
+    let pid = std::process::id();
+
+    let mut worktrees: Vec<PathBuf> = Vec::with_capacity(jobs);
+    let added = crate::signal::critical(|| {
+        for (i, &(_, commit_id)) in patches.iter().take(jobs).enumerate() {
+            let path = worktrees_dir.join(format!("exec-{pid}-{i}"));
+            stupid.worktree_add(&path, commit_id)?;
+            worktrees.push(path);
+        }
+        Ok(())
+    });
+
+    let outcomes = added.and_then(|()| {
+        if let [worktree] = worktrees.as_slice() {
+            exec_sequential(matches, patches, command, worktree)
+        } else {
+            exec_parallel(matches, patches, command, &worktrees)
+        }
+    });
+
+    let removed = crate::signal::critical(|| {
+        let mut removed = Ok(());
+        for path in &worktrees {
+            let result = stupid.worktree_remove(path);
+            if removed.is_ok() {
+                removed = result;
+            }
+        }
+        removed
+    });
+
+    let outcomes = outcomes?;
+    removed?;
+    Ok(outcomes)
+}
+
+/// Run the command against each patch, in turn, in a single linked worktree.
+fn exec_sequential(
+    matches: &ArgMatches,
+    patches: &[(&PatchName, gix::ObjectId)],
+    command: &[&OsString],
+    worktree: &Path,
+) -> Result<Vec<ExitStatus>> {
+    let display_command = display_command(command);
+    let stupid = StupidContext::default();
+    let mut outcomes = Vec::with_capacity(patches.len());
+    for &(patchname, commit_id) in patches {
+        stupid.worktree_checkout(worktree, commit_id)?;
+        print_info_message(
+            matches,
+            &format!("Executing `{display_command}` for `{patchname}`"),
+        );
+        let status = shell_command(command)
+            .current_dir(worktree)
+            .status()
+            .map_err(|e| anyhow!("could not execute `{display_command}`: {e}"))?;
+        outcomes.push(status);
+    }
+    Ok(outcomes)
+}
+
+/// Run the command against the patches concurrently in several linked worktrees.
+///
+/// The output of each run of the command is reported in stack order once the command
+/// has been run against all patches.
+fn exec_parallel(
+    matches: &ArgMatches,
+    patches: &[(&PatchName, gix::ObjectId)],
+    command: &[&OsString],
+    worktrees: &[PathBuf],
+) -> Result<Vec<ExitStatus>> {
+    let outputs = run_jobs(matches, patches, command, worktrees)?;
+
+    let display_command = display_command(command);
+    let mut stdout = std::io::stdout();
+    let mut stderr = std::io::stderr();
+    for (&(patchname, _), output) in patches.iter().zip(&outputs) {
+        if !output.stdout.is_empty() || !output.stderr.is_empty() {
+            print_info_message(
+                matches,
+                &format!("Output of `{display_command}` for `{patchname}`"),
+            );
+            stdout.write_all(&output.stdout)?;
+            stdout.flush()?;
+            stderr.write_all(&output.stderr)?;
+        }
+    }
+
+    Ok(outputs.into_iter().map(|output| output.status).collect())
+}
+
+/// Run the command against all patches using a thread per worktree.
+///
+/// The command's output is captured such that it may be reported in stack order.
+fn run_jobs(
+    matches: &ArgMatches,
+    patches: &[(&PatchName, gix::ObjectId)],
+    command: &[&OsString],
+    worktrees: &[PathBuf],
+) -> Result<Vec<Output>> {
+    let display_command = display_command(command);
+    let next_index = AtomicUsize::new(0);
+
+    let job_outputs = std::thread::scope(|scope| {
+        let handles: Vec<_> = worktrees
+            .iter()
+            .map(|worktree| {
+                let next_index = &next_index;
+                let display_command = &display_command;
+                scope.spawn(move || -> Result<Vec<(usize, Output)>> {
+                    let stupid = StupidContext::default();
+                    let mut outputs = Vec::new();
+                    loop {
+                        let index = next_index.fetch_add(1, Ordering::SeqCst);
+                        let Some(&(patchname, commit_id)) = patches.get(index) else {
+                            break;
+                        };
+                        stupid.worktree_checkout(worktree, commit_id)?;
+                        print_info_message(
+                            matches,
+                            &format!("Executing `{display_command}` for `{patchname}`"),
+                        );
+                        let output = shell_command(command)
+                            .current_dir(worktree)
+                            .stdin(Stdio::null())
+                            .output()
+                            .map_err(|e| anyhow!("could not execute `{display_command}`: {e}"))?;
+                        outputs.push((index, output));
+                    }
+                    Ok(outputs)
+                })
+            })
+            .collect();
+
+        handles
+            .into_iter()
+            .map(|handle| handle.join().expect("job thread should not panic"))
+            .collect::<Result<Vec<_>>>()
+    })?;
+
+    let mut outputs: Vec<Option<Output>> = vec![None; patches.len()];
+    for (index, output) in job_outputs.into_iter().flatten() {
+        outputs[index] = Some(output);
+    }
+    Ok(outputs
+        .into_iter()
+        .map(|output| output.expect("command run for every patch"))
+        .collect())
+}
+
+/// Make a [`std::process::Command`] for running a user-supplied command.
+///
+/// A single-argument command is run with the shell. Otherwise, the first argument is
+/// run with the shell with any remaining arguments passed through unmodified.
+pub(super) fn shell_command(command: &[&OsString]) -> std::process::Command {
+    let mut shell_command = std::process::Command::new("sh");
+    shell_command.arg("-c");
+    if let [command] = command {
+        shell_command.arg(command);
+    } else {
+        let mut program = command[0].clone();
+        program.push(r#" "$@""#);
+        shell_command.arg(program).args(command);
+    }
+    shell_command
+}
+
+/// Format a user-supplied command for display.
+pub(super) fn display_command(command: &[&OsString]) -> String {
+    command
+        .iter()
+        .map(|arg| arg.to_string_lossy())
+        .collect::<Vec<_>>()
+        .join(" ")
+}
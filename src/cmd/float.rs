@@ -15,7 +15,7 @@ use crate::{
     color::get_color_stdout,
     ext::RepositoryExtended,
     patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{Dependencies, DependencyPolicy, InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -69,6 +69,7 @@ fn make() -> clap::Command {
         )
         .arg(argset::keep_arg())
         .arg(argset::committer_date_is_author_date_arg())
+        .arg(argset::deps_arg())
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -98,6 +99,31 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Err(anyhow!("no patches to float"));
     }
 
+    let deps_policy = argset::resolve_deps_policy(&repo.config_snapshot(), matches)?;
+    let deps = if deps_policy == DependencyPolicy::Ignore {
+        None
+    } else {
+        Some(Dependencies::compute(&repo, &stack)?)
+    };
+
+    let patches = if let (DependencyPolicy::Carry, Some(deps)) = (deps_policy, &deps) {
+        let mut extra: Vec<PatchName> = deps
+            .dependents_of(&patches)
+            .into_iter()
+            .filter(|pn| stack.is_applied(pn))
+            .collect();
+        if !noapply_flag {
+            extra.extend(
+                deps.dependencies_of(&patches)
+                    .into_iter()
+                    .filter(|pn| stack.is_unapplied(pn)),
+            );
+        }
+        deps.carry(&patches, extra)
+    } else {
+        patches
+    };
+
     if !keep_flag && (!noapply_flag || patches.iter().any(|pn| stack.is_applied(pn))) {
         statuses.check_index_and_worktree_clean()?;
     }
@@ -132,6 +158,10 @@ fn run(matches: &ArgMatches) -> Result<()> {
         (applied, unapplied)
     };
 
+    if let Some(deps) = &deps {
+        deps.check_applied(stack.applied(), &applied)?;
+    }
+
     stack
         .setup_transaction()
         .use_index_and_worktree(true)
@@ -10,7 +10,7 @@ use crate::{
     color::get_color_stdout,
     ext::RepositoryExtended,
     patch::{LocationConstraint, PatchLocator, PatchName},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{InitializationPolicy, Operation, Stack, StackStateAccess, StackTransaction},
     stupid::Stupid,
 };
 
@@ -28,16 +28,21 @@ fn make() -> clap::Command {
         .arg(argset::merged_arg())
         .arg(argset::committer_date_is_author_date_arg())
         .arg(argset::push_conflicts_arg())
+        .args(argset::operation_args())
         .arg(
             Arg::new("patch")
                 .help("Patch to go to")
-                .required(true)
+                .required_unless_present_any(["continue", "skip", "abort"])
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchLocator)),
         )
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
+    if argset::resume_operation_requested(matches) {
+        return super::push::resume_operation(matches);
+    }
+
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
@@ -62,31 +67,40 @@ fn run(matches: &ArgMatches) -> Result<()> {
         .resolve_name(&stack)?
         .constrain(&stack, LocationConstraint::Visible)?;
 
+    let operation = Operation::new(&stack, "goto")?;
+
     stack
         .setup_transaction()
         .use_index_and_worktree(true)
         .allow_push_conflicts(allow_push_conflicts)
         .committer_date_is_author_date(committer_date_is_author_date)
+        .record_operation(operation)
         .with_output_stream(get_color_stdout(matches))
-        .transact(|trans| {
-            if let Some(pos) = trans.applied().iter().position(|pn| pn == &patchname) {
-                let applied = trans.applied()[0..=pos].to_vec();
-                let mut unapplied = trans.applied()[pos + 1..].to_vec();
-                unapplied.extend(trans.unapplied().iter().cloned());
-                trans.reorder_patches(Some(&applied), Some(&unapplied), None)
-            } else {
-                let pos = trans
-                    .unapplied()
-                    .iter()
-                    .position(|pn| pn == &patchname)
-                    .expect("already determined patch exists and not hidden or applied");
-
-                let to_apply: Vec<PatchName> = trans.unapplied()[0..=pos].to_vec();
-                trans.push_patches(&to_apply, merged_flag)?;
-                Ok(())
-            }
-        })
+        .transact(|trans| goto_patch(trans, &patchname, merged_flag))
         .execute("goto")?;
 
     Ok(())
 }
+
+/// Push or pop patches such that the given patch becomes the topmost applied patch.
+pub(super) fn goto_patch(
+    trans: &mut StackTransaction,
+    patchname: &PatchName,
+    check_merged: bool,
+) -> Result<()> {
+    if let Some(pos) = trans.applied().iter().position(|pn| pn == patchname) {
+        let applied = trans.applied()[0..=pos].to_vec();
+        let mut unapplied = trans.applied()[pos + 1..].to_vec();
+        unapplied.extend(trans.unapplied().iter().cloned());
+        trans.reorder_patches(Some(&applied), Some(&unapplied), None)
+    } else {
+        let pos = trans
+            .unapplied()
+            .iter()
+            .position(|pn| pn == patchname)
+            .expect("already determined patch exists and not hidden or applied");
+
+        let to_apply: Vec<PatchName> = trans.unapplied()[0..=pos].to_vec();
+        trans.push_patches(&to_apply, check_merged)
+    }
+}
@@ -3,6 +3,7 @@
 //! `stg import` implementation.
 
 use std::{
+    collections::{BTreeMap, HashMap},
     io::Read,
     path::{Path, PathBuf},
 };
@@ -13,10 +14,11 @@ use clap::{Arg, ArgGroup};
 
 use crate::{
     color::get_color_stdout,
-    ext::{RepositoryExtended, TimeExtended},
+    ext::{CommitExtended, RepositoryExtended, TimeExtended},
     patch::{patchedit, PatchName},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
+    wrap::Message,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -47,6 +49,10 @@ fn make() -> clap::Command {
              If a patch does not apply cleanly, the failed diff is written to a \
              .stgit-failed.patch file and an empty patch is added to the stack.\n\
              \n\
+             Instead of importing patches, the '--trailers-from' option adds review \
+             trailers found in replies to sent patch emails to the existing patches. \
+             See '--trailers-from' for details.\n\
+             \n\
              The patch description must be separated from the diff with a \"---\" line.",
         )
         .override_usage(super::make_usage(
@@ -57,6 +63,7 @@ fn make() -> clap::Command {
                     "[OPTIONS] -m [<mail-path>|<Maildir-path>]",
                     "[OPTIONS] -M [<mbox-path>]",
                     "[OPTIONS] -S [<series-path>]",
+                    "[OPTIONS] --trailers-from <mbox-path>",
                     "[OPTIONS] -u <diff-url>",
                     "[OPTIONS] -u -m <mail-url>",
                     "[OPTIONS] -u -M <mbox-url>",
@@ -68,6 +75,7 @@ fn make() -> clap::Command {
                     "[OPTIONS] -m [<mail-path>|<Maildir-path>]",
                     "[OPTIONS] -M [<mbox-path>]",
                     "[OPTIONS] -S [<series-path>]",
+                    "[OPTIONS] --trailers-from <mbox-path>",
                 ]
             },
         ))
@@ -105,7 +113,36 @@ fn make() -> clap::Command {
                 .long_help("Import patch series from a series file are tar archive.")
                 .action(clap::ArgAction::SetTrue),
         )
-        .group(ArgGroup::new("whence").args(["mail", "mbox", "series"]));
+        .arg(
+            Arg::new("trailers-from")
+                .long("trailers-from")
+                .help("Add review trailers from replies in <mbox> to patches")
+                .long_help(
+                    "Add review trailers from the replies to sent patch emails found in \
+                    <mbox> to the patches in the stack. No new patches are imported.\n\
+                    \n\
+                    Each reply is matched to the patch it replies to using the \
+                    message ids in its \"In-Reply-To\" and \"References\" headers. \
+                    Message ids are matched against those recorded in the patches' \
+                    sent history by `stg email send`. The message ids generated by \
+                    `stg email format` also identify the patch's commit, which may be \
+                    the patch's current commit or its commit in a recorded series \
+                    version. Replies that cannot be matched by message id are matched \
+                    by subject instead.\n\
+                    \n\
+                    The \"Acked-by\", \"Reviewed-by\", \"Tested-by\", and \
+                    \"Reported-by\" lines found in the replies, excluding quoted \
+                    lines, are added as trailers to the matched patches unless the \
+                    patches already have the same trailers. Replies to cover letters \
+                    and replies that cannot be matched to any patch are ignored. All \
+                    the patches are updated in a single transaction.",
+                )
+                .value_name("mbox")
+                .value_parser(clap::value_parser!(PathBuf))
+                .value_hint(clap::ValueHint::FilePath)
+                .conflicts_with("source"),
+        )
+        .group(ArgGroup::new("whence").args(["mail", "mbox", "series", "trailers-from"]));
 
     let app = if cfg!(feature = "import-url") {
         app.arg(
@@ -241,7 +278,9 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     stack.check_head_top_mismatch()?;
     //stupid.update_index_refresh()?;
 
-    if cfg!(feature = "import-url") && matches.get_flag("url") {
+    if let Some(mbox_path) = matches.get_one::<PathBuf>("trailers-from") {
+        import_trailers(stack, matches, mbox_path)
+    } else if cfg!(feature = "import-url") && matches.get_flag("url") {
         import_url(stack, matches)
     } else if matches.get_flag("series") {
         import_series(stack, matches, source_path.as_deref())
@@ -454,6 +493,285 @@ fn import_mail(stack: Stack, matches: &clap::ArgMatches, source_path: Option<&Pa
     Ok(())
 }
 
+/// Trailers collected from replies by `--trailers-from`.
+const REVIEW_TRAILERS: [&str; 4] = ["Acked-by", "Reviewed-by", "Tested-by", "Reported-by"];
+
+fn import_trailers(stack: Stack, matches: &clap::ArgMatches, source_path: &Path) -> Result<()> {
+    let out_dir = tempfile::tempdir()?;
+    let stupid = stack.repo.stupid();
+    let num_emails = stupid.mailsplit(Some(source_path), out_dir.path(), false, true)?;
+    let finder = PatchFinder::new(&stack)?;
+
+    let mut found: BTreeMap<PatchName, Vec<(String, String)>> = BTreeMap::new();
+    for i in 1..=num_emails {
+        let email_path = out_dir.path().join(format!("{i:04}"));
+        let content = std::fs::read(&email_path)?;
+        let email_file = std::fs::File::open(&email_path)?;
+        let (mailinfo, message, diff) = stupid.mailinfo(Some(email_file), false)?;
+        if diff
+            .lines()
+            .any(|line| line.starts_with(b"diff ") || line.starts_with(b"+++ "))
+        {
+            // Patch emails, as opposed to replies, are not sources of trailers.
+            continue;
+        }
+        let trailers = review_trailers(message.as_bstr());
+        if trailers.is_empty() {
+            continue;
+        }
+        let subject = Headers::parse_mailinfo(mailinfo.as_bstr())
+            .and_then(|headers| headers.subject)
+            .unwrap_or_default();
+        if let Some(patchname) = finder.find(&referenced_message_ids(&content), &subject) {
+            let patch_trailers = found.entry(patchname.clone()).or_default();
+            for trailer in trailers {
+                if !patch_trailers.contains(&trailer) {
+                    patch_trailers.push(trailer);
+                }
+            }
+        } else {
+            eprintln!("info: ignoring reply `{subject}` that does not match any patch");
+        }
+    }
+
+    let committer = stack.repo.get_committer()?;
+    let mut updates: Vec<(PatchName, gix::ObjectId)> = Vec::new();
+    for (patchname, trailers) in found {
+        let patch_commit = stack.get_patch_commit(&patchname);
+        let message = patch_commit.message_ex();
+        let message = message.decode()?;
+        let existing = patchedit::parse_trailers(stack.repo, message.as_bytes())?;
+        let trailers: Vec<(String, String)> = trailers
+            .into_iter()
+            .filter(|(key, value)| {
+                !existing.iter().any(|(existing_key, existing_value)| {
+                    existing_key.eq_ignore_ascii_case(key) && existing_value == value
+                })
+            })
+            .collect();
+        if trailers.is_empty() {
+            continue;
+        }
+        let new_message = stupid.interpret_trailers(
+            message.as_bytes(),
+            trailers
+                .iter()
+                .map(|(key, value)| (key.as_str(), value.as_str())),
+        )?;
+        let new_message = String::from_utf8(new_message)
+            .map_err(|_| anyhow!("could not decode message after adding trailers"))?;
+        let patch_commit_ref = patch_commit.decode()?;
+        let commit_id = stack.repo.commit_ex(
+            &patch_commit.author_strict()?,
+            committer,
+            &Message::from(new_message),
+            patch_commit_ref.tree(),
+            patch_commit_ref.parents(),
+        )?;
+        updates.push((patchname, commit_id));
+    }
+
+    if updates.is_empty() {
+        eprintln!("info: no new trailers found");
+        return Ok(());
+    }
+
+    stack
+        .setup_transaction()
+        .with_output_stream(get_color_stdout(matches))
+        .use_index_and_worktree(true)
+        .allow_conflicts(false)
+        .transact(|trans| {
+            let popped = if let Some(pos) = trans
+                .applied()
+                .iter()
+                .position(|pn| updates.iter().any(|(patchname, _)| patchname == pn))
+            {
+                let to_pop = trans.applied()[pos..].to_vec();
+                trans.pop_patches(|pn| to_pop.contains(pn))?;
+                to_pop
+            } else {
+                vec![]
+            };
+            for (patchname, commit_id) in &updates {
+                trans.update_patch(patchname, *commit_id)?;
+            }
+            trans.push_patches(&popped, false)
+        })
+        .execute("import: trailers")?;
+
+    Ok(())
+}
+
+/// Find the review trailers in the body of a reply.
+///
+/// Quoted lines are ignored, as is everything after a signature separator line.
+fn review_trailers(message: &BStr) -> Vec<(String, String)> {
+    let mut trailers: Vec<(String, String)> = Vec::new();
+    for line in message.lines() {
+        if line == b"-- " {
+            break;
+        }
+        let Ok(line) = line.to_str() else {
+            continue;
+        };
+        let Some((key, value)) = line.split_once(':') else {
+            continue;
+        };
+        let value = value.trim();
+        if let Some(key) = REVIEW_TRAILERS
+            .iter()
+            .find(|trailer| trailer.eq_ignore_ascii_case(key))
+        {
+            let trailer = (key.to_string(), value.to_string());
+            if value.contains('@') && !trailers.contains(&trailer) {
+                trailers.push(trailer);
+            }
+        }
+    }
+    trailers
+}
+
+/// Get the message ids an email refers to, from nearest to furthest.
+///
+/// The ids from the "In-Reply-To" header come first, followed by the ids from the
+/// "References" header in reverse order.
+fn referenced_message_ids(content: &[u8]) -> Vec<String> {
+    let mut in_reply_to = String::new();
+    let mut references = String::new();
+    let mut current: Option<&mut String> = None;
+    for line in content.lines() {
+        if line.is_empty() {
+            break;
+        }
+        let line = line.to_str_lossy();
+        if line.starts_with([' ', '\t']) {
+            if let Some(value) = current.as_mut() {
+                value.push(' ');
+                value.push_str(line.trim());
+            }
+            continue;
+        }
+        current = match line.split_once(':') {
+            Some((name, value)) if name.eq_ignore_ascii_case("in-reply-to") => {
+                in_reply_to.push_str(value.trim());
+                Some(&mut in_reply_to)
+            }
+            Some((name, value)) if name.eq_ignore_ascii_case("references") => {
+                references.push_str(value.trim());
+                Some(&mut references)
+            }
+            _ => None,
+        };
+    }
+
+    let message_ids = |value: &str| -> Vec<String> {
+        value
+            .split('<')
+            .skip(1)
+            .filter_map(|rest| rest.split_once('>'))
+            .map(|(id, _)| format!("<{id}>"))
+            .collect()
+    };
+    let mut ids = message_ids(&in_reply_to);
+    for id in message_ids(&references).into_iter().rev() {
+        if !ids.contains(&id) {
+            ids.push(id);
+        }
+    }
+    ids
+}
+
+/// Matches replies to the patches they reply to.
+struct PatchFinder {
+    /// Patches by the message ids recorded in their sent history.
+    by_message_id: HashMap<String, PatchName>,
+
+    /// Patches by their current commit id or their commit id in a series version.
+    by_commit_id: HashMap<gix::ObjectId, PatchName>,
+
+    /// Patches by the subject line of their message.
+    by_subject: HashMap<String, PatchName>,
+}
+
+impl PatchFinder {
+    fn new(stack: &Stack) -> Result<Self> {
+        let mut by_message_id = HashMap::new();
+        let mut by_commit_id = HashMap::new();
+        let mut by_subject = HashMap::new();
+        for patchname in stack.all_patches() {
+            for record in &stack.get_patch(patchname).sent {
+                by_message_id.insert(record.message_id.clone(), patchname.clone());
+            }
+            let commit = stack.get_patch_commit(patchname);
+            by_commit_id.insert(commit.id, patchname.clone());
+            by_subject
+                .entry(patch_subject(commit))
+                .or_insert_with(|| patchname.clone());
+        }
+        for (version, _) in stack.get_versions()?.into_iter().rev() {
+            let Some(state) = stack.get_version_state(version)? else {
+                continue;
+            };
+            for patchname in state.all_patches().filter(|pn| stack.has_patch(pn)) {
+                let commit = state.get_patch_commit(patchname);
+                by_commit_id
+                    .entry(commit.id)
+                    .or_insert_with(|| patchname.clone());
+                by_subject
+                    .entry(patch_subject(commit))
+                    .or_insert_with(|| patchname.clone());
+            }
+        }
+        Ok(Self {
+            by_message_id,
+            by_commit_id,
+            by_subject,
+        })
+    }
+
+    /// Find the patch replied to given the referenced message ids and the reply's
+    /// subject, as normalized by `git mailinfo`.
+    fn find(&self, message_ids: &[String], subject: &str) -> Option<&PatchName> {
+        for message_id in message_ids {
+            if let Some(patchname) = self.by_message_id.get(message_id) {
+                return Some(patchname);
+            }
+            let Some(local) = message_id
+                .strip_prefix("<stgit.")
+                .and_then(|rest| rest.split_once('@'))
+                .map(|(local, _)| local)
+            else {
+                continue;
+            };
+            if local.starts_with("cover.") {
+                return None;
+            }
+            if let Some(patchname) = local
+                .split('.')
+                .next()
+                .and_then(|hex| gix::ObjectId::from_hex(hex.as_bytes()).ok())
+                .and_then(|commit_id| self.by_commit_id.get(&commit_id))
+            {
+                return Some(patchname);
+            }
+        }
+        self.by_subject.get(subject.trim())
+    }
+}
+
+/// Get the subject line of a patch's commit message.
+fn patch_subject(commit: &gix::Commit) -> String {
+    commit
+        .message_raw_sloppy()
+        .lines()
+        .next()
+        .unwrap_or_default()
+        .to_str_lossy()
+        .trim()
+        .to_string()
+}
+
 fn read_gz(source_file: std::fs::File, content: &mut Vec<u8>) -> Result<()> {
     flate2::read::GzDecoder::new(source_file).read_to_end(content)?;
     Ok(())
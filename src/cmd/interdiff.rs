@@ -0,0 +1,317 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg interdiff` implementation.
+
+use std::io::Write;
+
+use anyhow::{Context, Result};
+use bstr::{BString, ByteSlice, ByteVec};
+use clap::{Arg, ArgMatches, ValueHint};
+use termcolor::WriteColor;
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    color::get_color_stdout,
+    ext::{CommitExtended, RepositoryExtended},
+    patch::PatchName,
+    stack::{
+        parse_version, InitializationPolicy, Stack, StackAccess, StackState, StackStateAccess,
+    },
+    stupid::{Stupid, StupidContext},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "interdiff",
+    category: super::CommandCategory::StackInspection,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Compare the patch series between two stack states")
+        .long_about(
+            "Compare the patch series from two states in the stack's history, in the \
+             manner of `git range-diff`.\n\
+             \n\
+             Stack states are specified using series versions recorded with `stg \
+             snapshot`, e.g. 'v2', the commit ids shown by `stg log`, or any other \
+             git revision that refers to a stack state commit. The new state defaults \
+             to the stack's current state.\n\
+             \n\
+             Patches are matched between the two states by name. Each patch is listed \
+             in the new state's order along with its position and abbreviated commit id \
+             in each state. Patches that only exist in the old state are listed with \
+             '<', patches that only exist in the new state are listed with '>', \
+             unchanged patches are listed with '=', and changed patches are listed with \
+             '!'. For each changed patch, a diff of the patch's author, message, and \
+             diff is shown.\n\
+             \n\
+             Only applied patches are compared by default. Use '--all' to also compare \
+             unapplied and hidden patches.",
+        )
+        .arg(
+            Arg::new("old")
+                .help("Old stack state")
+                .value_name("old-state")
+                .value_hint(ValueHint::Other)
+                .required(true),
+        )
+        .arg(
+            Arg::new("new")
+                .help("New stack state")
+                .value_name("new-state")
+                .value_hint(ValueHint::Other),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("all")
+                .long("all")
+                .short('a')
+                .help("Compare all patches, including unapplied and hidden patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("stat")
+                .long("stat")
+                .short('s')
+                .help("Only list patches, without diffs of changed patches")
+                .action(clap::ArgAction::SetTrue),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+
+    let old_spec = matches.get_one::<String>("old").expect("required argument");
+    let old_state = resolve_state(&stack, old_spec)?;
+    let new_state = if let Some(new_spec) = matches.get_one::<String>("new") {
+        resolve_state(&stack, new_spec)?
+    } else {
+        resolve_state(&stack, stack.get_stack_refname())?
+    };
+
+    let all_flag = matches.get_flag("all");
+    let series = |state: &StackState| -> Vec<PatchName> {
+        if all_flag {
+            state.all_patches().cloned().collect()
+        } else {
+            state.applied().to_vec()
+        }
+    };
+
+    let mut stdout = get_color_stdout(matches);
+    write_range_diff(
+        &repo,
+        &mut stdout,
+        (&old_state, &series(&old_state)),
+        (&new_state, &series(&new_state)),
+        !matches.get_flag("stat"),
+        crate::color::use_color(matches),
+    )
+}
+
+/// Resolve a series version or git revision to a stack state.
+fn resolve_state<'repo>(stack: &Stack<'repo>, spec: &str) -> Result<StackState<'repo>> {
+    if let Ok(version) = parse_version(spec) {
+        if let Some(state) = stack.get_version_state(version)? {
+            return Ok(state);
+        }
+    }
+    let repo = stack.repo;
+    let commit = repo.rev_parse_single(spec)?.object()?.try_into_commit()?;
+    StackState::from_commit(repo, &commit).with_context(|| format!("`{spec}` is not a stack state"))
+}
+
+/// Write a range-diff comparing two patch series.
+///
+/// The patches in each series are given in order and matched between the old and new
+/// series by name.
+pub(super) fn write_range_diff(
+    repo: &gix::Repository,
+    output: &mut impl WriteColor,
+    old: (&StackState, &[PatchName]),
+    new: (&StackState, &[PatchName]),
+    show_diffs: bool,
+    use_color: bool,
+) -> Result<()> {
+    let stupid = repo.stupid();
+    let (old_state, old_series) = old;
+    let (new_state, new_series) = new;
+    let mut old_shown = vec![false; old_series.len()];
+
+    let write_removed = |output: &mut dyn WriteColor, old_index: usize| -> Result<()> {
+        let patchname = &old_series[old_index];
+        let commit_id = old_state.get_patch_commit(patchname).id;
+        output.set_color(termcolor::ColorSpec::new().set_fg(Some(termcolor::Color::Red)))?;
+        write!(
+            output,
+            "{}:  {} < -:  ------- {patchname}",
+            old_index + 1,
+            short_id(commit_id)
+        )?;
+        output.reset()?;
+        writeln!(output)?;
+        Ok(())
+    };
+
+    for (new_index, patchname) in new_series.iter().enumerate() {
+        let new_commit = new_state.get_patch_commit(patchname);
+        let Some(old_index) = old_series.iter().position(|pn| pn == patchname) else {
+            output.set_color(termcolor::ColorSpec::new().set_fg(Some(termcolor::Color::Green)))?;
+            write!(
+                output,
+                "-:  ------- > {}:  {} {patchname}",
+                new_index + 1,
+                short_id(new_commit.id)
+            )?;
+            output.reset()?;
+            writeln!(output)?;
+            continue;
+        };
+
+        for i in 0..old_index {
+            if !old_shown[i] && !new_series.contains(&old_series[i]) {
+                write_removed(output, i)?;
+                old_shown[i] = true;
+            }
+        }
+        old_shown[old_index] = true;
+
+        let old_commit = old_state.get_patch_commit(patchname);
+        let old_text = patch_text(&stupid, old_commit)?;
+        let new_text = patch_text(&stupid, new_commit)?;
+        let sigil = if old_text == new_text { '=' } else { '!' };
+
+        if sigil == '!' {
+            output.set_color(termcolor::ColorSpec::new().set_fg(Some(termcolor::Color::Yellow)))?;
+        }
+        write!(
+            output,
+            "{}:  {} {sigil} {}:  {} {patchname}",
+            old_index + 1,
+            short_id(old_commit.id),
+            new_index + 1,
+            short_id(new_commit.id),
+        )?;
+        output.reset()?;
+        writeln!(output)?;
+
+        if show_diffs && sigil == '!' {
+            write_text_diff(output, &stupid, &old_text, &new_text, use_color)?;
+        }
+    }
+
+    for (i, shown) in old_shown.iter().enumerate() {
+        if !shown {
+            write_removed(output, i)?;
+        }
+    }
+
+    Ok(())
+}
+
+pub(super) fn short_id(commit_id: gix::ObjectId) -> String {
+    commit_id.to_string()[..7].to_string()
+}
+
+/// Make a textual representation of a patch for comparison purposes.
+///
+/// The text is composed of the patch's author, message, and diff. Diff details that
+/// vary depending on the patch's parent, such as blob ids and hunk line numbers, are
+/// omitted such that a patch that is only rebased compares as unchanged.
+pub(super) fn patch_text(stupid: &StupidContext, commit: &gix::Commit) -> Result<BString> {
+    let mut text = BString::default();
+    let author = commit.author_strict()?;
+    writeln!(text, "Author: {} <{}>", author.name, author.email)?;
+    writeln!(text)?;
+    for line in commit.message_ex().decode()?.lines() {
+        if line.is_empty() {
+            writeln!(text)?;
+        } else {
+            writeln!(text, "    {line}")?;
+        }
+    }
+    writeln!(text)?;
+
+    let parent_tree_id = commit.get_parent_commit()?.tree_id()?.detach();
+    let tree_id = commit.tree_id()?.detach();
+    let diff = stupid.diff_tree_patch(
+        parent_tree_id,
+        tree_id,
+        None::<Vec<&str>>,
+        false,
+        ["--no-ext-diff"],
+    )?;
+    for line in diff.lines_with_terminator() {
+        if line.starts_with(b"index ") {
+            continue;
+        } else if let Some(rest) = line.strip_prefix(b"@@ ") {
+            text.push_str("@@");
+            if let Some(pos) = rest.find(b"@@") {
+                text.push_str(&rest[pos + 2..]);
+            } else {
+                text.push(b'\n');
+            }
+        } else {
+            text.push_str(line);
+        }
+    }
+    Ok(text)
+}
+
+/// Write an indented diff of two patch texts, omitting the diff's header.
+pub(super) fn write_text_diff(
+    output: &mut dyn WriteColor,
+    stupid: &StupidContext,
+    old_text: &[u8],
+    new_text: &[u8],
+    use_color: bool,
+) -> Result<()> {
+    let diff = diff_texts(stupid, old_text, new_text, use_color)?;
+    let mut in_header = true;
+    for line in diff.lines_with_terminator() {
+        if in_header {
+            if strip_color(line).starts_with(b"@@") {
+                in_header = false;
+            } else {
+                continue;
+            }
+        }
+        output.write_all(b"    ")?;
+        output.write_all(line)?;
+    }
+    Ok(())
+}
+
+/// Diff two patch texts.
+fn diff_texts(
+    stupid: &StupidContext,
+    old_text: &[u8],
+    new_text: &[u8],
+    use_color: bool,
+) -> Result<BString> {
+    let mut old_file = tempfile::NamedTempFile::new()?;
+    let mut new_file = tempfile::NamedTempFile::new()?;
+    old_file.write_all(old_text)?;
+    new_file.write_all(new_text)?;
+    stupid.diff_no_index(old_file.path(), new_file.path(), use_color)
+}
+
+/// Strip any leading ANSI color escape sequences from a line.
+fn strip_color(mut line: &[u8]) -> &[u8] {
+    while let Some(rest) = line.strip_prefix(b"\x1b[") {
+        if let Some(end) = rest.find_byte(b'm') {
+            line = &rest[end + 1..];
+        } else {
+            break;
+        }
+    }
+    line
+}
@@ -2,16 +2,20 @@
 
 //! `stg log` implementation.
 
+use std::io::Write;
+
 use anyhow::{anyhow, Result};
-use clap::{Arg, ArgMatches};
+use bstr::ByteSlice;
+use clap::{Arg, ArgMatches, ValueHint};
 
 use crate::{
     argset,
     branchloc::BranchLocator,
-    ext::RepositoryExtended,
-    patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackAccess},
-    stupid::Stupid,
+    ext::{RepositoryExtended, TimeExtended},
+    patch::{patchrange, PatchName, PatchRange, RangeConstraint},
+    print_info_message, print_warning_message,
+    stack::{InitializationPolicy, PrunePolicy, RawStackState, Stack, StackAccess},
+    stupid::{LogFilter, Stupid},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -28,17 +32,41 @@ fn make() -> clap::Command {
             "Show the history of changes to the stack. If one or more patch names are \
              given, only the changes affecting those patches are shown.\n\
              \n\
+             Entries may also be selected by the StGit command that recorded them with \
+             '--command', by who performed the operation with '--author', and by when \
+             the operation was performed with '--since' and '--until'. When more than \
+             one command or author is given, entries matching any of them are shown. \
+             With '--json', the selected entries are output as JSON for further \
+             processing.\n\
+             \n\
              The 'stg undo' and 'stg redo' commands may be used to step back and forth \
              through historical stack states. The 'stg reset' command may be used to \
-             reset the stack directly to a historic state.\n\
+             reset the stack directly to a historic state. Named checkpoints recorded \
+             with 'stg checkpoint' appear in the log as \"checkpoint <name>\" entries.\n\
              \n\
              The '--clear' option may be used to delete the stack's change history. \
              Undo and redo are unavailable on a stack without change history. Clearing \
-             the stack state history cannot be undone.",
+             the stack state history cannot be undone. Checkpoints are kept when the \
+             history is cleared and may still be used with 'stg undo --to'.\n\
+             \n\
+             The '--prune' option may be used to remove older entries from the stack's \
+             change history while retaining recent entries. By default, the number of \
+             entries given by the `stgit.log.keep` configuration variable are retained, \
+             or 100 entries if it is not set. Entries recorded since the \
+             `stgit.log.keep-since` date are also retained. The '--keep' and \
+             '--keep-since' options override these variables. Entries referred to by \
+             checkpoints or by sent patch series snapshots are always retained. Setting \
+             `stgit.log.auto` prunes the history automatically whenever it grows beyond \
+             the given number of entries, or beyond `stgit.log.keep` entries if that is \
+             greater.",
         )
         .override_usage(super::make_usage(
             "stg log",
-            &["[OPTIONS] [--] [patch]...", "--clear"],
+            &[
+                "[OPTIONS] [--] [patch]...",
+                "--clear",
+                "--prune [--keep <n>] [--keep-since <date>]",
+            ],
         ))
         .arg(
             Arg::new("patchranges-all")
@@ -71,13 +99,68 @@ fn make() -> clap::Command {
                 .help("Show using full commit log format")
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("command")
+                .long("command")
+                .help("Only show entries recorded by <command>")
+                .long_help(
+                    "Only show entries recorded by <command>, e.g. \"refresh\". This \
+                     option may be used multiple times.",
+                )
+                .value_name("command")
+                .action(clap::ArgAction::Append)
+                .value_hint(ValueHint::Other),
+        )
+        .arg(
+            Arg::new("author")
+                .long("author")
+                .help("Only show entries whose author matches <pattern>")
+                .long_help(
+                    "Only show entries whose author matches the regular expression \
+                     <pattern>. This option may be used multiple times.",
+                )
+                .value_name("pattern")
+                .action(clap::ArgAction::Append)
+                .value_hint(ValueHint::Other),
+        )
+        .arg(
+            Arg::new("since")
+                .long("since")
+                .help("Only show entries more recent than <date>")
+                .value_name("date")
+                .value_hint(ValueHint::Other),
+        )
+        .arg(
+            Arg::new("until")
+                .long("until")
+                .help("Only show entries older than <date>")
+                .value_name("date")
+                .value_hint(ValueHint::Other),
+        )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Display entries as JSON")
+                .long_help(
+                    "Display the selected entries as a JSON array with one object per \
+                     entry, most recent first. Each object contains the entry's \
+                     \"commit\" id, the \"command\" that recorded the entry, the \
+                     entry's full \"message\", the \"author\" of the operation (with \
+                     \"name\" and \"email\"), its \"timestamp\", and the stack's state \
+                     \"before\" and \"after\" the operation. Each state contains the \
+                     \"applied\", \"unapplied\", and \"hidden\" patch names. The state \
+                     before the stack's first entry is null.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["diff", "full"]),
+        )
         .arg(
             Arg::new("graphical")
                 .long("graphical")
                 .short('g')
                 .help("Run gitk instead of printing to stdout")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["diff", "number", "full"]),
+                .conflicts_with_all(["diff", "number", "full", "json"]),
         )
         .arg(
             Arg::new("clear")
@@ -85,7 +168,53 @@ fn make() -> clap::Command {
                 .help("Clear the stack history")
                 // .exclusive(true),
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["patchranges-all", "diff", "number", "full", "graphical"]),
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "diff",
+                    "number",
+                    "full",
+                    "graphical",
+                    "command",
+                    "author",
+                    "since",
+                    "until",
+                    "json",
+                ]),
+        )
+        .arg(
+            Arg::new("prune")
+                .long("prune")
+                .help("Remove older entries from the stack history")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "patchranges-all",
+                    "diff",
+                    "number",
+                    "full",
+                    "graphical",
+                    "command",
+                    "author",
+                    "since",
+                    "until",
+                    "json",
+                    "clear",
+                ]),
+        )
+        .arg(
+            Arg::new("keep")
+                .long("keep")
+                .help("Retain the <n> most recent entries when pruning")
+                .value_name("n")
+                .requires("prune")
+                .value_parser(argset::parse_usize),
+        )
+        .arg(
+            Arg::new("keep-since")
+                .long("keep-since")
+                .help("Retain entries recorded since <date> when pruning")
+                .value_name("date")
+                .requires("prune")
+                .value_parser(|s: &str| gix::date::Time::parse_time(s)),
         )
 }
 
@@ -98,7 +227,39 @@ fn run(matches: &ArgMatches) -> Result<()> {
     )?;
 
     if matches.get_flag("clear") {
-        stack.clear_state_log("clear log")
+        stack.clear_state_log("clear log")?;
+        let checkpoints = stack.get_checkpoints()?;
+        if !checkpoints.is_empty() {
+            let names: Vec<String> = checkpoints
+                .iter()
+                .map(|(name, _)| format!("`{name}`"))
+                .collect();
+            print_warning_message(
+                matches,
+                &format!(
+                    "checkpoints {} are kept, but are no longer part of the stack log",
+                    names.join(", ")
+                ),
+            );
+        }
+        Ok(())
+    } else if matches.get_flag("prune") {
+        let mut policy = PrunePolicy::from_config(&repo.config_snapshot())?;
+        if let Some(keep) = matches.get_one::<usize>("keep") {
+            policy.keep = *keep;
+        }
+        if let Some(since) = matches.get_one::<gix::date::Time>("keep-since") {
+            policy.keep_since = Some(*since);
+        }
+        let (_, num_removed) = stack.prune_state_log(&policy)?;
+        print_info_message(
+            matches,
+            &format!(
+                "Pruned {num_removed} stack log entr{}",
+                if num_removed == 1 { "y" } else { "ies" }
+            ),
+        );
+        Ok(())
     } else {
         let pathspecs: Option<Vec<String>> =
             if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-all") {
@@ -122,16 +283,57 @@ fn run(matches: &ArgMatches) -> Result<()> {
             .next()
             .ok_or_else(|| anyhow!("`{}` does not have any parents", stack.get_stack_refname()))?;
 
+        let mut filter = LogFilter::default();
+        if let Some(authors) = matches.get_many::<String>("author") {
+            filter.authors(authors.cloned());
+        }
+        filter
+            .since(matches.get_one::<String>("since").map(String::as_str))
+            .until(matches.get_one::<String>("until").map(String::as_str));
+
         let stupid = repo.stupid();
+        let num_commits = matches.get_one::<usize>("number").copied();
+
+        if let Some(commands) = matches.get_many::<String>("command") {
+            let commands: Vec<&String> = commands.collect();
+            let mut commit_ids = Vec::new();
+            for commit_id in
+                stupid.log_ids(simplified_parent_id, pathspecs.as_ref(), None, &filter)?
+            {
+                let commit = repo.find_commit(commit_id)?;
+                let message = commit.message_raw_sloppy().to_str_lossy();
+                if commands
+                    .iter()
+                    .any(|command| *command == log_command(&message))
+                {
+                    commit_ids.push(commit_id);
+                    if num_commits.map_or(false, |n| commit_ids.len() >= n) {
+                        break;
+                    }
+                }
+            }
+            if commit_ids.is_empty() {
+                return if matches.get_flag("json") {
+                    write_json(&repo, &commit_ids)
+                } else {
+                    Ok(())
+                };
+            }
+            filter.commit_ids(commit_ids);
+        }
 
         if matches.get_flag("graphical") {
-            stupid.gitk(simplified_parent_id, pathspecs)
+            stupid.gitk(simplified_parent_id, pathspecs, &filter)
+        } else if matches.get_flag("json") {
+            let commit_ids =
+                stupid.log_ids(simplified_parent_id, pathspecs, num_commits, &filter)?;
+            write_json(&repo, &commit_ids)
         } else {
-            let num_commits = matches.get_one::<usize>("number").copied();
             stupid.log(
                 simplified_parent_id,
                 pathspecs,
                 num_commits,
+                &filter,
                 crate::color::use_color(matches),
                 matches.get_flag("full"),
                 matches.get_flag("diff"),
@@ -139,3 +341,92 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 }
+
+#[derive(serde::Serialize)]
+struct JsonEntry {
+    commit: String,
+    command: String,
+    message: String,
+    author: JsonAuthor,
+    timestamp: String,
+    before: Option<JsonState>,
+    after: JsonState,
+}
+
+#[derive(serde::Serialize)]
+struct JsonAuthor {
+    name: String,
+    email: String,
+}
+
+#[derive(serde::Serialize)]
+struct JsonState {
+    applied: Vec<String>,
+    unapplied: Vec<String>,
+    hidden: Vec<String>,
+}
+
+impl JsonState {
+    fn from_commit(commit: &gix::Commit) -> Result<Self> {
+        let mut tree = commit.tree()?;
+        let stack_json = tree
+            .peel_to_entry_by_path("stack.json")?
+            .ok_or_else(|| anyhow!("stack metadata not found in `{}`", commit.id))?
+            .object()?;
+        let state = RawStackState::from_stack_json(&stack_json.data)?;
+        let names = |patchnames: Vec<PatchName>| -> Vec<String> {
+            patchnames.iter().map(ToString::to_string).collect()
+        };
+        Ok(Self {
+            applied: names(state.applied),
+            unapplied: names(state.unapplied),
+            hidden: names(state.hidden),
+        })
+    }
+}
+
+/// Get the name of the command that recorded a stack log entry.
+///
+/// The command is the first word of the entry's message, e.g. "refresh" for
+/// "refresh: p1".
+fn log_command(message: &str) -> &str {
+    message
+        .lines()
+        .next()
+        .and_then(|line| line.split_whitespace().next())
+        .unwrap_or_default()
+        .trim_end_matches(':')
+}
+
+fn write_json(repo: &gix::Repository, commit_ids: &[gix::ObjectId]) -> Result<()> {
+    let mut entries: Vec<JsonEntry> = Vec::with_capacity(commit_ids.len());
+    for commit_id in commit_ids {
+        let commit = repo.find_commit(*commit_id)?;
+        let commit_ref = commit.decode()?;
+        let author = commit_ref.author();
+        let message = commit_ref.message.to_str_lossy().trim_end().to_string();
+        let command = log_command(&message).to_string();
+        let before = if let Some(parent_id) = commit.parent_ids().next() {
+            Some(JsonState::from_commit(&repo.find_commit(parent_id)?)?)
+        } else {
+            None
+        };
+        entries.push(JsonEntry {
+            commit: commit_id.to_string(),
+            command,
+            message,
+            author: JsonAuthor {
+                name: author.name.to_str_lossy().to_string(),
+                email: author.email.to_str_lossy().to_string(),
+            },
+            timestamp: author.time.format(gix::date::time::format::ISO8601_STRICT),
+            before,
+            after: JsonState::from_commit(&commit)?,
+        });
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &entries)?;
+    writeln!(stdout)?;
+    Ok(())
+}
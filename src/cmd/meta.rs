@@ -0,0 +1,235 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg meta` implementation.
+
+use anyhow::{anyhow, Result};
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::{PatchLocator, PatchName},
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "meta",
+    category: super::CommandCategory::PatchManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Manage per-patch metadata")
+        .long_about(
+            "Manage arbitrary key/value metadata associated with patches.\n\
+             \n\
+             Metadata may be used to track information about a patch that does not \
+             belong in the patch's commit message, such as review status, a tracker \
+             ticket, or the target upstream branch. Metadata is recorded in the stack \
+             state and is thus tracked by `stg log` and may be reverted with `stg \
+             undo` and `stg redo`.\n\
+             \n\
+             Keys must start with an ASCII letter or digit and may otherwise only \
+             contain ASCII letters, digits, '-', '_', and '.'. Values may not \
+             contain control characters such as newlines.\n\
+             \n\
+             By default, the topmost patch's metadata is used. Use '--patch' to \
+             select a different patch.",
+        )
+        .arg(argset::branch_arg().global(true))
+        .arg(
+            Arg::new("patch")
+                .long("patch")
+                .short('p')
+                .help("Use <patch> instead of the topmost patch")
+                .global(true)
+                .num_args(1)
+                .value_name("patch")
+                .value_hint(ValueHint::Other)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator)),
+        )
+        .subcommand_required(true)
+        .subcommand(
+            clap::Command::new("get")
+                .about("Print the value of a metadata key")
+                .arg(
+                    Arg::new("key")
+                        .help("Metadata key to print")
+                        .required(true)
+                        .value_parser(parse_key),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("set")
+                .about("Set the value of a metadata key")
+                .arg(
+                    Arg::new("key")
+                        .help("Metadata key to set")
+                        .required(true)
+                        .value_parser(parse_key),
+                )
+                .arg(
+                    Arg::new("value")
+                        .help("Value to associate with key")
+                        .required(true)
+                        .allow_hyphen_values(true)
+                        .value_parser(parse_value),
+                ),
+        )
+        .subcommand(
+            clap::Command::new("unset")
+                .about("Remove one or more metadata keys")
+                .arg(
+                    Arg::new("keys")
+                        .help("Metadata keys to remove")
+                        .value_name("key")
+                        .required(true)
+                        .num_args(1..)
+                        .value_parser(parse_key),
+                ),
+        )
+        .subcommand(clap::Command::new("list").about("List all metadata keys and values"))
+}
+
+/// Parse and validate a metadata key.
+pub(crate) fn parse_key(s: &str) -> Result<String> {
+    let mut chars = s.chars();
+    match chars.next() {
+        None => Err(anyhow!("key must not be empty")),
+        Some(c) if !c.is_ascii_alphanumeric() => {
+            Err(anyhow!("key must start with an ASCII letter or digit"))
+        }
+        _ => {
+            if chars.all(|c| c.is_ascii_alphanumeric() || matches!(c, '-' | '_' | '.')) {
+                Ok(s.to_string())
+            } else {
+                Err(anyhow!(
+                    "key may only contain ASCII letters, digits, '-', '_', and '.'"
+                ))
+            }
+        }
+    }
+}
+
+/// Parse and validate a metadata value.
+///
+/// Control characters are rejected since values are recorded one per line in the
+/// stack state's patch metadata.
+fn parse_value(s: &str) -> Result<String> {
+    if s.chars().any(char::is_control) {
+        Err(anyhow!("value must not contain control characters"))
+    } else {
+        Ok(s.to_string())
+    }
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::AllowUninitialized,
+    )?;
+
+    let (sub_name, sub_matches) = matches.subcommand().expect("valid subcommand is expected");
+
+    let patchname = if let Some(patch_loc) = sub_matches.get_one::<PatchLocator>("patch") {
+        patch_loc.resolve_name(&stack)?
+    } else if let Some(top_patchname) = stack.applied().last() {
+        top_patchname.clone()
+    } else {
+        return Err(super::Error::NoAppliedPatches.into());
+    };
+
+    match sub_name {
+        "get" => {
+            let key = sub_matches
+                .get_one::<String>("key")
+                .expect("required argument");
+            let value = stack
+                .get_patch(&patchname)
+                .meta
+                .get(key)
+                .ok_or_else(|| anyhow!("patch `{patchname}` does not have metadata `{key}`"))?;
+            println!("{value}");
+            Ok(())
+        }
+        "list" => {
+            for (key, value) in &stack.get_patch(&patchname).meta {
+                println!("{key}={value}");
+            }
+            Ok(())
+        }
+        "set" => {
+            let key = sub_matches
+                .get_one::<String>("key")
+                .expect("required argument");
+            let value = sub_matches
+                .get_one::<String>("value")
+                .expect("required argument");
+            update_meta(
+                stack,
+                &patchname,
+                &[(key, Some(value.as_str()))],
+                &format!("meta set {patchname} {key}"),
+                matches,
+            )
+        }
+        "unset" => {
+            let keys: Vec<&String> = sub_matches
+                .get_many::<String>("keys")
+                .expect("required argument")
+                .collect();
+            let patch_meta = &stack.get_patch(&patchname).meta;
+            if let Some(key) = keys
+                .iter()
+                .find(|key| !patch_meta.contains_key(key.as_str()))
+            {
+                return Err(anyhow!(
+                    "patch `{patchname}` does not have metadata `{key}`"
+                ));
+            }
+            let changes: Vec<(&String, Option<&str>)> =
+                keys.iter().map(|key| (*key, None)).collect();
+            let keys_str = keys
+                .iter()
+                .map(|key| key.as_str())
+                .collect::<Vec<_>>()
+                .join(" ");
+            update_meta(
+                stack,
+                &patchname,
+                &changes,
+                &format!("meta unset {patchname} {keys_str}"),
+                matches,
+            )
+        }
+        _ => panic!("valid subcommand is expected"),
+    }
+}
+
+fn update_meta(
+    stack: Stack,
+    patchname: &PatchName,
+    changes: &[(&String, Option<&str>)],
+    reflog_msg: &str,
+    matches: &ArgMatches,
+) -> Result<()> {
+    stack
+        .setup_transaction()
+        .allow_conflicts(true)
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            for (key, value) in changes {
+                trans.set_patch_meta(patchname, key, *value);
+            }
+            Ok(())
+        })
+        .execute(reflog_msg)?;
+    Ok(())
+}
@@ -7,14 +7,20 @@
 
 use clap::builder::StyledStr;
 
+pub(crate) mod absorb;
+pub(crate) mod bisect;
 pub(crate) mod branch;
+pub(crate) mod checkpoint;
 pub(crate) mod clean;
 pub(crate) mod commit;
 pub(crate) mod completion;
+pub(crate) mod cover;
 pub(crate) mod delete;
+pub(crate) mod deps;
 pub(crate) mod diff;
 pub(crate) mod edit;
 pub(crate) mod email;
+pub(crate) mod exec;
 pub(crate) mod export;
 pub(crate) mod files;
 pub(crate) mod float;
@@ -24,9 +30,12 @@ pub(crate) mod hide;
 pub(crate) mod id;
 pub(crate) mod import;
 pub(crate) mod init;
+pub(crate) mod interdiff;
 pub(crate) mod log;
+pub(crate) mod meta;
 pub(crate) mod new;
 pub(crate) mod next;
+pub(crate) mod patch_log;
 pub(crate) mod patches;
 pub(crate) mod pick;
 pub(crate) mod pop;
@@ -42,7 +51,9 @@ pub(crate) mod reset;
 pub(crate) mod series;
 pub(crate) mod show;
 pub(crate) mod sink;
+pub(crate) mod snapshot;
 pub(crate) mod spill;
+pub(crate) mod split;
 pub(crate) mod squash;
 pub(crate) mod sync;
 pub(crate) mod top;
@@ -81,14 +92,20 @@ pub(crate) struct StGitCommand {
 /// This is used in [`crate::main`] for command line argument parsing and eventual
 /// dispatch of a subcommand.
 pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
+    absorb::STGIT_COMMAND,
+    bisect::STGIT_COMMAND,
     branch::STGIT_COMMAND,
+    checkpoint::STGIT_COMMAND,
     clean::STGIT_COMMAND,
     commit::STGIT_COMMAND,
     completion::STGIT_COMMAND,
+    cover::STGIT_COMMAND,
     delete::STGIT_COMMAND,
+    deps::STGIT_COMMAND,
     diff::STGIT_COMMAND,
     edit::STGIT_COMMAND,
     email::STGIT_COMMAND,
+    exec::STGIT_COMMAND,
     export::STGIT_COMMAND,
     files::STGIT_COMMAND,
     float::STGIT_COMMAND,
@@ -98,9 +115,12 @@ pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
     id::STGIT_COMMAND,
     import::STGIT_COMMAND,
     init::STGIT_COMMAND,
+    interdiff::STGIT_COMMAND,
     log::STGIT_COMMAND,
+    meta::STGIT_COMMAND,
     new::STGIT_COMMAND,
     next::STGIT_COMMAND,
+    patch_log::STGIT_COMMAND,
     patches::STGIT_COMMAND,
     pick::STGIT_COMMAND,
     pop::STGIT_COMMAND,
@@ -116,7 +136,9 @@ pub(crate) const STGIT_COMMANDS: &[StGitCommand] = &[
     series::STGIT_COMMAND,
     show::STGIT_COMMAND,
     sink::STGIT_COMMAND,
+    snapshot::STGIT_COMMAND,
     spill::STGIT_COMMAND,
+    split::STGIT_COMMAND,
     squash::STGIT_COMMAND,
     sync::STGIT_COMMAND,
     top::STGIT_COMMAND,
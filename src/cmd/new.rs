@@ -106,6 +106,19 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with("save-template"),
         )
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .short('I')
+                .help("Interactively select hunks to refresh the new patch with")
+                .long_help(
+                    "Interactively select which hunks of the changes in the work \
+                     tree to refresh the new patch with, in the manner of `git add \
+                     --patch`. Implies '--refresh'.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with("save-template"),
+        )
         .arg(
             Arg::new("index")
                 .long("index")
@@ -118,7 +131,7 @@ fn make() -> clap::Command {
                 )
                 .requires("refresh")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["pathspecs", "submodules", "force"]),
+                .conflicts_with_all(["pathspecs", "submodules", "force", "interactive"]),
         )
         .arg(
             Arg::new("force")
@@ -179,7 +192,9 @@ fn run(matches: &ArgMatches) -> Result<()> {
         Ok(None)
     }?;
 
-    let is_refreshing = matches.get_flag("refresh") || matches.contains_id("pathspecs");
+    let is_refreshing = matches.get_flag("refresh")
+        || matches.get_flag("interactive")
+        || matches.contains_id("pathspecs");
 
     let tree_id = if is_refreshing {
         refresh::assemble_refresh_tree(&stack, matches, None)?
@@ -0,0 +1,139 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg patch-log` implementation.
+
+use std::io::Write;
+
+use anyhow::Result;
+use clap::{Arg, ArgMatches, ValueHint};
+use termcolor::WriteColor;
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    color::get_color_stdout,
+    ext::RepositoryExtended,
+    patch::PatchLocator,
+    stack::{patch_versions, InitializationPolicy, Stack, StackStateAccess},
+    stupid::Stupid,
+};
+
+use super::interdiff::{patch_text, short_id, write_text_diff};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "patch-log",
+    category: super::CommandCategory::PatchInspection,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Show the evolution of a patch")
+        .long_about(
+            "Show each version of a patch found in the stack log, most recent first. \
+             The topmost patch is used by default.\n\
+             \n\
+             Each version is listed with its number, the abbreviated id of the \
+             patch's commit, when the version was recorded, and the stack log \
+             message of the operation that produced it, e.g. \"refresh\" or \"edit\". \
+             A new version is listed whenever the patch's commit changes; renaming \
+             a patch does not produce a new version. Renames are followed such that \
+             versions recorded under a previous name are included; those versions are \
+             annotated with the name the patch had at the time.\n\
+             \n\
+             With '--diff', each version is followed by a diff of the patch's author, \
+             message, and diff compared to the previous version, in the manner of \
+             'stg interdiff'.\n\
+             \n\
+             Only versions recorded in the stack log are shown. Versions from before \
+             the stack log was cleared or pruned are not available.",
+        )
+        .arg(
+            Arg::new("patch")
+                .help("Patch to show the history of")
+                .value_name("patch")
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator))
+                .value_hint(ValueHint::Other),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("diff")
+                .long("diff")
+                .short('d')
+                .help("Show the changes between consecutive versions")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("number")
+                .long("number")
+                .short('n')
+                .help("Limit output to the <n> most recent versions")
+                .value_name("n")
+                .value_parser(argset::parse_usize),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+
+    let patchname = if let Some(patch_loc) = matches.get_one::<PatchLocator>("patch") {
+        patch_loc.resolve_name(&stack)?
+    } else if let Some(top_patchname) = stack.applied().last() {
+        top_patchname.clone()
+    } else {
+        return Err(super::Error::NoAppliedPatches.into());
+    };
+
+    let versions = patch_versions(&repo, &stack, &patchname)?;
+    let num_shown = matches
+        .get_one::<usize>("number")
+        .copied()
+        .unwrap_or(versions.len())
+        .min(versions.len());
+
+    let stupid = repo.stupid();
+    let use_color = crate::color::use_color(matches);
+    let show_diff = matches.get_flag("diff");
+    let mut stdout = get_color_stdout(matches);
+
+    for index in (versions.len() - num_shown..versions.len()).rev() {
+        let version = &versions[index];
+        write!(stdout, "{}  ", index + 1)?;
+        stdout.set_color(termcolor::ColorSpec::new().set_fg(Some(termcolor::Color::Yellow)))?;
+        write!(stdout, "{}", short_id(version.commit_id))?;
+        stdout.reset()?;
+        write!(stdout, "   ")?;
+        stdout.set_color(termcolor::ColorSpec::new().set_fg(Some(termcolor::Color::Blue)))?;
+        write!(
+            stdout,
+            "{}",
+            version.time.format(gix::date::time::format::GIT_RFC2822)
+        )?;
+        stdout.reset()?;
+        write!(stdout, "   {}", version.message)?;
+        if version.patchname != patchname {
+            write!(stdout, " (as {})", version.patchname)?;
+        }
+        writeln!(stdout)?;
+
+        if show_diff && index > 0 {
+            let prev_commit_id = versions[index - 1].commit_id;
+            if prev_commit_id != version.commit_id {
+                let old_text = patch_text(&stupid, &repo.find_commit(prev_commit_id)?)?;
+                let new_text = patch_text(&stupid, &repo.find_commit(version.commit_id)?)?;
+                if old_text != new_text {
+                    write_text_diff(&mut stdout, &stupid, &old_text, &new_text, use_color)?;
+                }
+            }
+        }
+    }
+
+    Ok(())
+}
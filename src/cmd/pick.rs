@@ -20,7 +20,7 @@ use crate::{
     patch::{
         revspec, PatchName, RangeConstraint, RangeRevisionSpec, SingleRevisionSpec, StGitRevision,
     },
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stack::{InitializationPolicy, Operation, Stack, StackAccess, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -56,13 +56,14 @@ fn make() -> clap::Command {
                 "[OPTIONS] [--name NAME] [--parent COMMITTISH] <source>",
                 "[OPTIONS] --fold [--file PATH]... <source>...",
                 "[OPTIONS] --update <source>...",
+                "--continue | --skip | --abort",
             ],
         ))
         .arg(
             Arg::new("stgit-revision")
                 .help("Patch name or committish to import")
                 .value_name("source")
-                .required(true)
+                .required_unless_present_any(["continue", "skip", "abort"])
                 .num_args(1..)
                 .value_parser(clap::value_parser!(RangeRevisionSpec)),
         )
@@ -139,9 +140,14 @@ fn make() -> clap::Command {
                 .value_name("path")
                 .requires("fold"),
         )
+        .args(argset::operation_args())
 }
 
 fn run(matches: &clap::ArgMatches) -> Result<()> {
+    if argset::resume_operation_requested(matches) {
+        return super::push::resume_operation(matches);
+    }
+
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AutoInitialize)?;
     let ref_stack = Stack::from_branch_locator(
@@ -345,10 +351,13 @@ fn pick_picks(
         disallow.push(&new_patches[new_patches.len() - 1].0);
     }
 
+    let operation = Operation::new(&stack, "pick")?;
+
     stack
         .setup_transaction()
         .with_output_stream(get_color_stdout(matches))
         .use_index_and_worktree(true)
+        .record_operation(operation)
         .transact(|trans| {
             let mut to_push = Vec::new();
             for (i, (patchname, commit_id)) in new_patches.iter().enumerate() {
@@ -12,7 +12,7 @@ use crate::{
     color::get_color_stdout,
     ext::RepositoryExtended,
     patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{Dependencies, DependencyPolicy, InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -85,6 +85,7 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue),
         )
         .arg(argset::keep_arg())
+        .arg(argset::deps_arg())
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -155,6 +156,22 @@ fn run(matches: &ArgMatches) -> Result<()> {
         statuses.check_index_and_worktree_clean()?;
     }
 
+    let deps_policy = argset::resolve_deps_policy(&repo.config_snapshot(), matches)?;
+    let deps = if deps_policy == DependencyPolicy::Ignore {
+        None
+    } else {
+        Some(Dependencies::compute(&repo, &stack)?)
+    };
+
+    if let (DependencyPolicy::Carry, Some(deps)) = (deps_policy, &deps) {
+        let dependents: Vec<PatchName> = deps
+            .dependents_of(&patches)
+            .into_iter()
+            .filter(|pn| stack.is_applied(pn))
+            .collect();
+        patches.extend(dependents);
+    }
+
     let mut new_unapplied: Vec<PatchName> = vec![];
     let mut new_applied: Vec<PatchName> = vec![];
 
@@ -180,6 +197,10 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 
+    if let Some(deps) = &deps {
+        deps.check_applied(stack.applied(), &new_applied)?;
+    }
+
     new_unapplied.reserve(stack.unapplied().len());
     stack
         .unapplied()
@@ -2,15 +2,20 @@
 
 //! `stg push` implementation.
 
+use std::rc::Rc;
+
 use anyhow::{anyhow, Result};
 use clap::{Arg, ArgMatches};
 
 use crate::{
     argset,
     color::get_color_stdout,
-    ext::RepositoryExtended,
+    ext::{CommitExtended, RepositoryExtended},
     patch::{patchrange, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{
+        Dependencies, DependencyPolicy, InitializationPolicy, Operation, Stack, StackState,
+        StackStateAccess,
+    },
     stupid::Stupid,
 };
 
@@ -34,7 +39,13 @@ fn make() -> clap::Command {
              while pushing a patch, the conflicts are written to the work tree \
              and the push command halts. Conflicts may then be resolved using \
              the normal Git methods, or alternatively the push may be undone \
-             using 'stg undo'.",
+             using 'stg undo'.\n\
+             \n\
+             After resolving conflicts and adding the resolved files with 'stg add', \
+             use '--continue' to refresh the conflicting patch and push the remaining \
+             patches. Alternatively, '--skip' leaves the conflicting patch unapplied \
+             and pushes the remaining patches, and '--abort' returns the stack to its \
+             state before the push.",
         )
         .override_usage(super::make_usage(
             "stg push",
@@ -42,6 +53,7 @@ fn make() -> clap::Command {
                 "[OPTIONS] [patch]...",
                 "[OPTIONS] -n <number>",
                 "[OPTIONS] --all",
+                "--continue | --skip | --abort",
             ],
         ))
         .arg(
@@ -114,9 +126,15 @@ fn make() -> clap::Command {
         .arg(argset::merged_arg())
         .arg(argset::committer_date_is_author_date_arg())
         .arg(argset::push_conflicts_arg())
+        .arg(argset::deps_arg())
+        .args(argset::operation_args())
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
+    if argset::resume_operation_requested(matches) {
+        return resume_operation(matches);
+    }
+
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
     let stupid = repo.stupid();
@@ -124,6 +142,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let opt_number = matches.get_one::<isize>("number").copied();
     let allow_push_conflicts =
         argset::resolve_allow_push_conflicts(&repo.config_snapshot(), matches);
+    let deps_policy = argset::resolve_deps_policy(&repo.config_snapshot(), matches)?;
 
     if Some(0) == opt_number {
         return Ok(());
@@ -187,11 +206,29 @@ fn run(matches: &ArgMatches) -> Result<()> {
         patches.reverse();
     }
 
+    if deps_policy != DependencyPolicy::Ignore && !noapply_flag {
+        let deps = Dependencies::compute(&repo, &stack)?;
+        if deps_policy == DependencyPolicy::Carry {
+            let missing: Vec<PatchName> = deps
+                .dependencies_of(&patches)
+                .into_iter()
+                .filter(|pn| stack.is_unapplied(pn))
+                .collect();
+            patches = deps.carry(&patches, missing);
+        }
+        let mut applied = stack.applied().to_vec();
+        applied.extend(patches.iter().cloned());
+        deps.check_applied(stack.applied(), &applied)?;
+    }
+
+    let operation = Operation::new(&stack, "push")?;
+
     stack
         .setup_transaction()
         .use_index_and_worktree(true)
         .allow_push_conflicts(allow_push_conflicts)
         .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .record_operation(operation)
         .with_output_stream(get_color_stdout(matches))
         .transact(|trans| {
             if settree_flag {
@@ -218,3 +255,133 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     Ok(())
 }
+
+/// Continue, skip, or abort an operation that was halted by push conflicts.
+///
+/// This is shared by all of the commands which record their operation when a push
+/// results in conflicts (i.e. `push`, `goto`, `pick`, and `rebase`), such that any of
+/// those commands may be used to resume an in-progress operation.
+pub(super) fn resume_operation(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
+    let stupid = repo.stupid();
+    let operation = Operation::load(&stack)?.ok_or_else(|| anyhow!("no operation in progress"))?;
+    let command = operation.command.as_str();
+
+    if operation.is_stale(&stack)? {
+        Operation::clear(&stack)?;
+        return Err(anyhow!(
+            "the stack has changed since `{command}` halted; \
+             the halted operation can no longer be resumed and has been discarded"
+        ));
+    }
+
+    if matches.get_flag("abort") {
+        let stack = stack
+            .setup_transaction()
+            .use_index_and_worktree(true)
+            .allow_bad_head(true)
+            .discard_changes(true)
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| {
+                let repo = trans.stack().repo;
+                let state_commit = Rc::new(repo.find_commit(operation.orig_state)?);
+                let state = StackState::from_commit(repo, &state_commit)?;
+                trans.reset_to_state(state)
+            })
+            .execute(&format!("{command} --abort"))?;
+        Operation::clear(&stack)?;
+        return pop_autostash(&operation, &stupid);
+    }
+
+    let stack = if matches.get_flag("skip") {
+        let conflicting = operation.conflicting.as_ref();
+        stack
+            .setup_transaction()
+            .use_index_and_worktree(true)
+            .discard_changes(true)
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| {
+                if let Some(conflicting) = conflicting {
+                    let patchname = &conflicting.patchname;
+                    if trans.applied().contains(patchname) {
+                        trans.pop_patches(|pn| pn == patchname)?;
+                    }
+                    if trans.unapplied().contains(patchname) {
+                        trans.update_patch(patchname, conflicting.orig_commit_id)?;
+                    }
+                }
+                Ok(())
+            })
+            .execute(&format!("{command} --skip"))?
+    } else {
+        repo.check_repository_state()?;
+        stupid.statuses(None)?.check_conflicts()?;
+        stack.check_head_top_mismatch()?;
+
+        // Refresh the conflicting patch with the resolved changes from the index.
+        let resolved = operation
+            .conflicting
+            .as_ref()
+            .map(|conflicting| &conflicting.patchname)
+            .filter(|pn| stack.applied().last() == Some(*pn));
+        let stack = if let Some(conflicting) = resolved {
+            let patch_commit = stack.get_patch_commit(conflicting);
+            let tree_id = stupid.write_tree()?;
+            if tree_id == patch_commit.tree_id()?.detach() {
+                stack
+            } else {
+                let author = patch_commit.author_strict()?;
+                let mut committer = repo.get_committer()?.to_owned();
+                if operation.committer_date_is_author_date {
+                    committer.time = author.time;
+                }
+                let commit_id = repo.commit_ex(
+                    &author,
+                    &committer,
+                    &patch_commit.message_ex(),
+                    tree_id,
+                    patch_commit.parent_ids().map(|id| id.detach()),
+                )?;
+                stack
+                    .setup_transaction()
+                    .with_output_stream(get_color_stdout(matches))
+                    .transact(|trans| trans.update_patch(conflicting, commit_id))
+                    .execute(&format!("{command} --continue (refresh)"))?
+            }
+        } else {
+            stack
+        };
+        stupid.statuses(None)?.check_index_and_worktree_clean()?;
+        stack
+    };
+
+    let remaining: Vec<PatchName> = operation
+        .remaining
+        .iter()
+        .filter(|pn| stack.is_unapplied(pn))
+        .cloned()
+        .collect();
+
+    super::rebase::push_patches_with_stops(
+        stack,
+        &repo,
+        matches,
+        &operation,
+        &remaining,
+        &operation.stops,
+        operation.check_merged,
+        argset::resolve_allow_push_conflicts(&repo.config_snapshot(), matches),
+        &format!("{command} --continue"),
+    )?;
+
+    pop_autostash(&operation, &stupid)
+}
+
+fn pop_autostash(operation: &Operation, stupid: &crate::stupid::StupidContext) -> Result<()> {
+    if !operation.autostash || stupid.stash_pop()? {
+        Ok(())
+    } else {
+        Err(super::Error::CausedConflicts("stash pop resulted in conflicts".to_string()).into())
+    }
+}
@@ -14,7 +14,9 @@ use crate::{
     ext::RepositoryExtended,
     patch::{patchedit, PatchName, SingleRevisionSpec},
     print_info_message,
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stack::{
+        InitializationPolicy, Operation, PatchStops, Stack, StackAccess, StackStateAccess, Stop,
+    },
     stupid::Stupid,
 };
 
@@ -33,24 +35,25 @@ fn make() -> clap::Command {
             new base and push the patches back.\n\
             \n\
             Merge conflicts may arise when patches are being pushed-back onto the \
-            stack. If this occurs, resolve the conflicts and then continue the rebase \
-            with the following sequence:\n\
+            stack. If this occurs, resolve the conflicts, add the resolved files with \
+            'stg add', and then continue the rebase with:\n\
             \n    \
-            stg add --update\n    \
-            stg refresh\n    \
-            stg goto top-patch\n\
+            stg rebase --continue\n\
             \n\
-            Or to skip the conflicting patch:\n\
+            Or to skip the conflicting patch, leaving it unapplied:\n\
             \n    \
-            stg undo --hard\n    \
-            stg push next-patch..top-patch\n\
+            stg rebase --skip\n\
+            \n\
+            Or to return the stack to its state before the rebase:\n\
+            \n    \
+            stg rebase --abort\n\
             ",
         )
         .arg(
             Arg::new("committish")
                 .help("New base commit for the stack")
                 .value_parser(clap::value_parser!(SingleRevisionSpec))
-                .required_unless_present("interactive"),
+                .required_unless_present_any(["interactive", "continue", "skip", "abort"]),
         )
         .arg(
             Arg::new("interactive")
@@ -89,9 +92,14 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue),
         )
         .arg(argset::push_conflicts_arg())
+        .args(argset::operation_args())
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
+    if argset::resume_operation_requested(matches) {
+        return super::push::resume_operation(matches);
+    }
+
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
     let config = repo.config_snapshot();
@@ -147,6 +155,8 @@ fn run(matches: &ArgMatches) -> Result<()> {
             .unwrap_or(false)
     };
 
+    let mut operation = Operation::new(&stack, "rebase")?;
+
     let using_stash = if autostash && clean_result.is_err() {
         stupid.stash_push()?;
         true
@@ -156,6 +166,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
         false
     };
 
+    operation.autostash = using_stash;
     let applied = stack.applied().to_vec();
 
     stack
@@ -197,7 +208,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
             matches,
             &applied,
             allow_push_conflicts,
-            committer_date_is_author_date,
+            operation,
         )?;
     } else if !matches.get_flag("nopush") {
         stack.check_head_top_mismatch()?;
@@ -207,6 +218,7 @@ fn run(matches: &ArgMatches) -> Result<()> {
             .use_index_and_worktree(true)
             .allow_push_conflicts(allow_push_conflicts)
             .committer_date_is_author_date(committer_date_is_author_date)
+            .record_operation(operation)
             .with_output_stream(get_color_stdout(matches))
             .transact(|trans| trans.push_patches(&applied, check_merged))
             .execute("rebase (reapply)")?;
@@ -229,14 +241,19 @@ const INTERACTIVE_HELP_LINES: &str = "\
 #
 #   k, keep <patch> = do not modify this patch
 #   e, edit <patch> = interactively edit this patch
+#   r, reword <patch> = interactively edit this patch's message
+#   rename <patch> <new-name> = rename this patch
 #   s, squash <patch> = squash patch into the previous patch
 #   f, fixup <patch> = like \"squash\", but discard this patch's commit message
 #   h, hide <patch> = hide patch
 #   d, delete <patch> = delete patch
+#   x, exec <command> = run command after pushing the previous patch; stop if it fails
+#   b, break = stop after pushing the previous patch
 #
 # These lines can be reordered; they are executed from top to bottom.
 #
 # Patches above the APPLY_LINE are applied; other patches are kept unapplied.
+# The exec and break commands may only be used above the APPLY_LINE.
 ";
 
 #[derive(Debug, Clone)]
@@ -244,18 +261,44 @@ struct Instruction {
     patchname: PatchName,
     action: Action,
     apply: bool,
+
+    /// Stops to be performed after this instruction's patch is pushed.
+    stops: Vec<Stop>,
 }
 
-#[derive(Debug, Clone, Copy, PartialEq, Eq)]
+#[derive(Debug, Clone, PartialEq, Eq)]
 enum Action {
     Keep,
     Edit,
+    Reword,
+    Rename(PatchName),
     Squash,
     Fixup,
     Hide,
     Delete,
 }
 
+/// Instructions parsed from the interactive rebase instruction sheet.
+struct Instructions {
+    /// Stops to be performed before any patches are pushed.
+    leading_stops: Vec<Stop>,
+    instructions: Vec<Instruction>,
+}
+
+impl Instructions {
+    /// Remove an instruction, moving any of its stops to the preceding instruction.
+    fn remove(&mut self, index: usize) -> Instruction {
+        let mut instruction = self.instructions.remove(index);
+        let stops = std::mem::take(&mut instruction.stops);
+        if index > 0 {
+            self.instructions[index - 1].stops.extend(stops);
+        } else {
+            self.leading_stops.extend(stops);
+        }
+        instruction
+    }
+}
+
 fn interactive_pushback(
     stack: Stack,
     repo: &gix::Repository,
@@ -263,9 +306,10 @@ fn interactive_pushback(
     matches: &ArgMatches,
     previously_applied: &[PatchName],
     allow_push_conflicts: bool,
-    committer_date_is_author_date: bool,
+    operation: Operation,
 ) -> Result<()> {
     let mut stack = stack;
+    let committer_date_is_author_date = matches.get_flag("committer-date-is-author-date");
 
     if stack.all_patches().next().is_none() {
         return Ok(());
@@ -281,14 +325,37 @@ fn interactive_pushback(
     let buf = buf
         .to_str()
         .map_err(|_| anyhow!("`{filename}` is not valid UTF-8"))?;
-    let mut instructions = parse_instructions(buf)?;
+    let mut sheet = parse_instructions(buf)?;
 
-    validate_instructions(&stack, &instructions)?;
+    validate_instructions(&stack, &sheet.instructions)?;
+
+    // Order the unapplied patches to match the instruction sheet such that the
+    // instructions operate on, and the stack is left with, the patch order chosen by
+    // the user. Patches without instructions are kept after those with instructions.
+    let mut unapplied: Vec<PatchName> = sheet
+        .instructions
+        .iter()
+        .map(|inst| &inst.patchname)
+        .filter(|pn| stack.unapplied().contains(pn))
+        .cloned()
+        .collect();
+    for patchname in stack.unapplied() {
+        if !unapplied.contains(patchname) {
+            unapplied.push(patchname.clone());
+        }
+    }
+    if unapplied != stack.unapplied() {
+        stack = stack
+            .setup_transaction()
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| trans.reorder_patches(None, Some(&unapplied), None))
+            .execute("rebase (reorder)")?;
+    }
 
     let mut index: usize = 0;
 
-    while index < instructions.len() {
-        let instruction = instructions[index].clone();
+    while index < sheet.instructions.len() {
+        let instruction = sheet.instructions[index].clone();
         let patchname = &instruction.patchname;
 
         match instruction.action {
@@ -299,8 +366,10 @@ fn interactive_pushback(
             Action::Delete => {
                 // Find contiguous delete instructions in order to delete in batches.
                 let mut delete_instructions: Vec<Instruction> = Vec::new();
-                while index < instructions.len() && instructions[index].action == Action::Delete {
-                    delete_instructions.push(instructions.remove(index));
+                while index < sheet.instructions.len()
+                    && sheet.instructions[index].action == Action::Delete
+                {
+                    delete_instructions.push(sheet.remove(index));
                 }
                 assert!(!delete_instructions.is_empty());
                 assert!(delete_instructions
@@ -323,8 +392,10 @@ fn interactive_pushback(
 
             Action::Hide => {
                 let mut hide_instructions: Vec<Instruction> = Vec::new();
-                while index < instructions.len() && instructions[index].action == Action::Hide {
-                    hide_instructions.push(instructions.remove(index));
+                while index < sheet.instructions.len()
+                    && sheet.instructions[index].action == Action::Hide
+                {
+                    hide_instructions.push(sheet.remove(index));
                 }
                 assert!(!hide_instructions.is_empty());
                 assert!(hide_instructions
@@ -341,16 +412,21 @@ fn interactive_pushback(
                     .execute("hide")?;
             }
 
-            Action::Edit => {
+            Action::Edit | Action::Reword => {
                 let dummy_edit_command = clap::Command::new("dummy-edit");
                 let dummy_edit_command = patchedit::add_args(dummy_edit_command, false, false);
+                let edit_args: &[&str] = if instruction.action == Action::Edit {
+                    &["dummy-edit", "--edit", "--diff"]
+                } else {
+                    &["dummy-edit", "--edit"]
+                };
                 let edit_matches = dummy_edit_command
-                    .try_get_matches_from(["dummy-edit", "--edit", "--diff"])
+                    .try_get_matches_from(edit_args)
                     .expect("dummy command has valid arguments");
                 match patchedit::EditBuilder::default()
                     .original_patchname(Some(patchname))
                     .existing_patch_commit(stack.get_patch_commit(patchname))
-                    .allow_diff_edit(true)
+                    .allow_diff_edit(instruction.action == Action::Edit)
                     .edit(&stack, repo, &edit_matches)?
                 {
                     patchedit::EditOutcome::TemplateSaved(_) => panic!("template save not enabled"),
@@ -379,17 +455,31 @@ fn interactive_pushback(
                                 .execute(&format!("edit: {patchname}"))?;
                         }
 
-                        instructions[index] = Instruction {
-                            action: Action::Keep,
-                            patchname: new_patchname.unwrap_or_else(|| patchname.clone()),
-                            apply: instruction.apply,
-                        };
+                        let instruction = &mut sheet.instructions[index];
+                        instruction.action = Action::Keep;
+                        if let Some(new_patchname) = new_patchname {
+                            instruction.patchname = new_patchname;
+                        }
 
                         index += 1;
                     }
                 }
             }
 
+            Action::Rename(new_patchname) => {
+                stack = stack
+                    .setup_transaction()
+                    .with_output_stream(get_color_stdout(matches))
+                    .transact(|trans| trans.rename_patch(patchname, &new_patchname))
+                    .execute(&format!("rename {patchname} {new_patchname}"))?;
+
+                let instruction = &mut sheet.instructions[index];
+                instruction.action = Action::Keep;
+                instruction.patchname = new_patchname;
+
+                index += 1;
+            }
+
             Action::Squash | Action::Fixup => {
                 let action_str = match instruction.action {
                     Action::Squash => "squash",
@@ -399,11 +489,11 @@ fn interactive_pushback(
 
                 let squash_patchnames: Vec<PatchName> = if index > 0 {
                     let mut patchnames: Vec<PatchName> =
-                        vec![instructions[index - 1].patchname.clone()];
-                    while index < instructions.len()
-                        && instructions[index].action == instruction.action
+                        vec![sheet.instructions[index - 1].patchname.clone()];
+                    while index < sheet.instructions.len()
+                        && sheet.instructions[index].action == instruction.action
                     {
-                        let Instruction { patchname, .. } = instructions.remove(index);
+                        let Instruction { patchname, .. } = sheet.remove(index);
                         patchnames.push(patchname);
                     }
                     patchnames
@@ -447,11 +537,10 @@ fn interactive_pushback(
                             Some(target_patchname),
                             false,
                         )?;
-                        instructions[index - 1] = Instruction {
-                            action: Action::Keep,
-                            patchname: new_patchname,
-                            apply: instruction.apply,
-                        };
+                        let target = &mut sheet.instructions[index - 1];
+                        target.action = Action::Keep;
+                        target.patchname = new_patchname;
+                        target.apply = instruction.apply;
                         Ok(())
                     })
                     .execute("squash")?;
@@ -459,31 +548,184 @@ fn interactive_pushback(
         }
     }
 
-    let to_push: Vec<PatchName> = instructions
+    let check_merged = matches.get_flag("merged");
+    let last_to_push = sheet
+        .instructions
         .iter()
-        .filter_map(|inst| {
-            if inst.apply {
-                Some(inst.patchname.clone())
-            } else {
-                None
-            }
+        .rev()
+        .find(|inst| inst.apply)
+        .map(|inst| inst.patchname.clone());
+
+    if !run_stops(repo, matches, &sheet.leading_stops, last_to_push.as_ref())? {
+        return Ok(());
+    }
+
+    let to_push: Vec<PatchName> = sheet
+        .instructions
+        .iter()
+        .filter(|inst| inst.apply)
+        .map(|inst| inst.patchname.clone())
+        .collect();
+    let stops: Vec<PatchStops> = sheet
+        .instructions
+        .into_iter()
+        .filter(|inst| inst.apply && !inst.stops.is_empty())
+        .map(|inst| PatchStops {
+            patchname: inst.patchname,
+            stops: inst.stops,
         })
         .collect();
-    let check_merged = matches.get_flag("merged");
 
-    stack.check_head_top_mismatch()?;
-    stack
-        .setup_transaction()
-        .use_index_and_worktree(true)
-        .allow_push_conflicts(allow_push_conflicts)
-        .committer_date_is_author_date(committer_date_is_author_date)
-        .with_output_stream(get_color_stdout(matches))
-        .transact(|trans| trans.push_patches(&to_push, check_merged))
-        .execute("rebase (reapply)")?;
+    let mut operation = operation;
+    operation.committer_date_is_author_date = committer_date_is_author_date;
+    push_patches_with_stops(
+        stack,
+        repo,
+        matches,
+        &operation,
+        &to_push,
+        &stops,
+        check_merged,
+        allow_push_conflicts,
+        "rebase (reapply)",
+    )
+}
+
+/// Push patches, performing each patch's stops once the patch is pushed.
+///
+/// Stops for patches not in `patches` are performed before any patch is pushed. When
+/// a push results in conflicts, the patches and stops not yet reached are recorded with
+/// the halted operation such that they are pushed and performed when the operation is
+/// continued.
+#[allow(clippy::too_many_arguments)]
+pub(super) fn push_patches_with_stops(
+    stack: Stack,
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    operation: &Operation,
+    patches: &[PatchName],
+    stops: &[PatchStops],
+    check_merged: bool,
+    allow_push_conflicts: bool,
+    reflog_msg: &str,
+) -> Result<()> {
+    let mut stack = stack;
+    let last_to_push = patches.last();
+    let stops_for = |patchname: &PatchName| {
+        stops
+            .iter()
+            .filter(|patch_stops| &patch_stops.patchname == patchname)
+            .flat_map(|patch_stops| patch_stops.stops.iter().cloned())
+            .collect::<Vec<Stop>>()
+    };
+
+    let leading_stops: Vec<Stop> = stops
+        .iter()
+        .filter(|patch_stops| !patches.contains(&patch_stops.patchname))
+        .flat_map(|patch_stops| patch_stops.stops.iter().cloned())
+        .collect();
+    if !run_stops(repo, matches, &leading_stops, last_to_push)? {
+        return Ok(());
+    }
+
+    let mut to_push: Vec<PatchName> = Vec::new();
+    for (i, patchname) in patches.iter().enumerate() {
+        to_push.push(patchname.clone());
+        let patch_stops = stops_for(patchname);
+        if !patch_stops.is_empty() || Some(patchname) == last_to_push {
+            stack.check_head_top_mismatch()?;
+            let result = stack
+                .setup_transaction()
+                .use_index_and_worktree(true)
+                .allow_push_conflicts(allow_push_conflicts)
+                .committer_date_is_author_date(operation.committer_date_is_author_date)
+                .record_operation(operation.clone())
+                .with_output_stream(get_color_stdout(matches))
+                .transact(|trans| trans.push_patches(&to_push, check_merged))
+                .execute(reflog_msg);
+            if let Err(e) = result {
+                // The recorded operation only knows about this batch of patches, so
+                // the patches and stops from subsequent batches must be added.
+                let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+                if let Some(mut halted) = Operation::load(&stack)?
+                    .filter(|halted| halted.orig_state == operation.orig_state)
+                {
+                    let later = &patches[i + 1..];
+                    halted.remaining.extend(later.iter().cloned());
+                    halted.stops = stops
+                        .iter()
+                        .filter(|patch_stops| {
+                            &patch_stops.patchname == patchname
+                                || later.contains(&patch_stops.patchname)
+                        })
+                        .cloned()
+                        .collect();
+                    halted.save(&stack)?;
+                }
+                return Err(e);
+            }
+            to_push.clear();
+
+            let proceed = run_stops(repo, matches, &patch_stops, last_to_push)?;
+            // Exec commands may have modified the stack.
+            stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+            if !proceed {
+                break;
+            }
+        }
+    }
 
     Ok(())
 }
 
+/// Perform stops from the instruction sheet.
+///
+/// Returns `false` if pushing patches should stop, or an error if an exec command
+/// fails. In either case, the user is informed how to resume pushing the remaining
+/// patches.
+fn run_stops(
+    repo: &gix::Repository,
+    matches: &ArgMatches,
+    stops: &[Stop],
+    last_to_push: Option<&PatchName>,
+) -> Result<bool> {
+    let stack = Stack::current(repo, InitializationPolicy::RequireInitialized)?;
+    let resume_hint = match last_to_push {
+        Some(last) if !stack.is_applied(last) => format!("; resume with `stg goto {last}`"),
+        _ => String::new(),
+    };
+    let position = stack.applied().last().map_or_else(
+        || "before pushing any patches".to_string(),
+        |pn| format!("at `{pn}`"),
+    );
+
+    for stop in stops {
+        match stop {
+            Stop::Exec(command) => {
+                print_info_message(matches, &format!("Executing `{command}`"));
+                let mut sh_command = std::process::Command::new("sh");
+                sh_command.arg("-c").arg(command);
+                if let Some(work_dir) = repo.work_dir() {
+                    sh_command.current_dir(work_dir);
+                }
+                let status = sh_command
+                    .status()
+                    .map_err(|e| anyhow!("could not execute `{command}`: {e}"))?;
+                if !status.success() {
+                    return Err(anyhow!(
+                        "`{command}` failed ({status}); stopped {position}{resume_hint}"
+                    ));
+                }
+            }
+            Stop::Break => {
+                print_info_message(matches, &format!("Stopped {position}{resume_hint}"));
+                return Ok(false);
+            }
+        }
+    }
+    Ok(true)
+}
+
 fn make_instructions_template(stack: &Stack, previously_applied: &[PatchName]) -> String {
     let name_width = stack.all_patches().map(PatchName::len).max().unwrap();
     let mut template = String::with_capacity(4096);
@@ -507,8 +749,9 @@ fn make_instructions_template(stack: &Stack, previously_applied: &[PatchName]) -
     template
 }
 
-fn parse_instructions(buf: &str) -> Result<Vec<Instruction>> {
-    let mut instructions = Vec::new();
+fn parse_instructions(buf: &str) -> Result<Instructions> {
+    let mut leading_stops = Vec::new();
+    let mut instructions: Vec<Instruction> = Vec::new();
     let mut apply = true;
 
     for line in buf.lines() {
@@ -529,6 +772,33 @@ fn parse_instructions(buf: &str) -> Result<Vec<Instruction>> {
         }
         .trim();
 
+        // Exec commands are taken verbatim, i.e. they may contain '#'.
+        let exec_command = line
+            .split_once(|c: char| c.is_ascii_whitespace())
+            .filter(|(action_str, _)| ["x", "exec"].contains(action_str))
+            .map(|(_, command)| command.trim());
+        let stop = if let Some(command) = exec_command {
+            Some(Stop::Exec(command.to_string()))
+        } else if ["b", "break"].contains(&instruction_str) {
+            Some(Stop::Break)
+        } else {
+            None
+        };
+
+        if let Some(stop) = stop {
+            if !apply {
+                return Err(anyhow!(
+                    "exec and break are not allowed below the APPLY_LINE: `{line}`"
+                ));
+            }
+            if let Some(instruction) = instructions.last_mut() {
+                instruction.stops.push(stop);
+            } else {
+                leading_stops.push(stop);
+            }
+            continue;
+        }
+
         if instruction_str.is_empty() {
             continue;
         }
@@ -536,13 +806,24 @@ fn parse_instructions(buf: &str) -> Result<Vec<Instruction>> {
         if let Some((action_str, patchname_str)) =
             instruction_str.split_once(|c: char| c.is_ascii_whitespace())
         {
-            let action = match action_str {
-                "k" | "keep" => Action::Keep,
-                "e" | "edit" => Action::Edit,
-                "s" | "squash" => Action::Squash,
-                "f" | "fix" | "fixup" => Action::Fixup,
-                "h" | "hide" => Action::Hide,
-                "d" | "delete" => Action::Delete,
+            let (patchname_str, action) = match action_str {
+                "k" | "keep" => (patchname_str, Action::Keep),
+                "e" | "edit" => (patchname_str, Action::Edit),
+                "r" | "reword" => (patchname_str, Action::Reword),
+                "rename" => {
+                    if let Some((patchname_str, new_patchname_str)) =
+                        patchname_str.split_once(|c: char| c.is_ascii_whitespace())
+                    {
+                        let new_patchname = PatchName::from_str(new_patchname_str.trim())?;
+                        (patchname_str, Action::Rename(new_patchname))
+                    } else {
+                        return Err(anyhow!("bad instruction line: `{line}`"));
+                    }
+                }
+                "s" | "squash" => (patchname_str, Action::Squash),
+                "f" | "fix" | "fixup" => (patchname_str, Action::Fixup),
+                "h" | "hide" => (patchname_str, Action::Hide),
+                "d" | "delete" => (patchname_str, Action::Delete),
                 _ => return Err(anyhow!("unknown instruction action `{action_str}`")),
             };
 
@@ -552,12 +833,16 @@ fn parse_instructions(buf: &str) -> Result<Vec<Instruction>> {
                 patchname,
                 action,
                 apply,
+                stops: Vec::new(),
             });
         } else {
             return Err(anyhow!("bad instruction line: `{line}`"));
         }
     }
-    Ok(instructions)
+    Ok(Instructions {
+        leading_stops,
+        instructions,
+    })
 }
 
 fn validate_instructions(stack: &Stack, instructions: &[Instruction]) -> Result<()> {
@@ -572,5 +857,20 @@ fn validate_instructions(stack: &Stack, instructions: &[Instruction]) -> Result<
             seen_patchnames.push(patchname);
         }
     }
+    for instruction in instructions {
+        if let Action::Rename(new_patchname) = &instruction.action {
+            if let Some(existing) = stack
+                .all_patches()
+                .chain(seen_patchnames.iter().copied())
+                .find(|pn| new_patchname.collides(pn))
+            {
+                return Err(anyhow!(
+                    "cannot rename `{}` to `{new_patchname}`: patch `{existing}` already exists",
+                    instruction.patchname,
+                ));
+            }
+            seen_patchnames.push(new_patchname);
+        }
+    }
     Ok(())
 }
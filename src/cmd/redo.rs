@@ -10,7 +10,7 @@ use crate::{
     argset,
     color::get_color_stdout,
     ext::RepositoryExtended,
-    stack::{InitializationPolicy, Stack},
+    stack::{InitializationPolicy, Operation, Stack},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -60,7 +60,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
     let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
     let redo_steps = matches.get_one::<usize>("number").copied().unwrap_or(1);
 
-    stack
+    let stack = stack
         .setup_transaction()
         .use_index_and_worktree(true)
         .allow_bad_head(true)
@@ -73,5 +73,5 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         })
         .execute(&format!("redo {redo_steps}"))?;
 
-    Ok(())
+    Operation::clear_stale(&stack)
 }
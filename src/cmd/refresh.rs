@@ -16,6 +16,7 @@ use crate::{
     color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended, SignatureExtended},
     hook::run_pre_commit_hook,
+    hunks::select_hunks,
     patch::{patchedit, LocationConstraint, PatchLocator, PatchName},
     stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
     stupid::{Status, StatusOptions, Statuses, Stupid, StupidContext},
@@ -88,6 +89,21 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .conflicts_with_all(["pathspecs", "update", "submodules", "force"]),
         )
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .short('I')
+                .help("Interactively select hunks to refresh")
+                .long_help(
+                    "Interactively select which hunks of the changes in the \
+                     worktree to refresh into the patch, in the manner of `git add \
+                     --patch`. Hunks may be split into smaller hunks. The index is \
+                     updated to match the refreshed patch while unselected changes \
+                     remain in the worktree.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["index", "update"]),
+        )
         .arg(
             Arg::new("force")
                 .long("force")
@@ -430,29 +446,31 @@ pub(crate) fn assemble_refresh_tree(
 ) -> Result<gix::ObjectId> {
     let stupid = stack.repo.stupid();
     let opt_pathspecs = matches.get_many::<PathBuf>("pathspecs");
-    let is_path_limiting = limit_to_patchname.is_some() || opt_pathspecs.is_some();
+    let is_interactive = matches.get_flag("interactive");
+    let is_path_limiting =
+        !is_interactive && (limit_to_patchname.is_some() || opt_pathspecs.is_some());
+    let submodules_flag = matches.get_flag("submodules");
+    let nosubmodules_flag = matches.get_flag("no-submodules");
+    let use_submodules = if !submodules_flag && !nosubmodules_flag {
+        stack
+            .repo
+            .config_snapshot()
+            .boolean("stgit.refreshsubmodules")
+            .unwrap_or(false)
+    } else {
+        submodules_flag
+    };
     let statuses;
 
-    let refresh_paths = if matches.get_flag("index") {
+    let refresh_paths = if matches.get_flag("index") || is_interactive {
         // When refreshing from the index, no path limiting may be used.
         assert!(!is_path_limiting);
         IndexSet::new()
     } else {
         let maybe_patch_commit = limit_to_patchname.map(|pn| stack.get_patch_commit(pn));
-        let submodules_flag = matches.get_flag("submodules");
-        let nosubmodules_flag = matches.get_flag("no-submodules");
-        let use_submodules = if !submodules_flag && !nosubmodules_flag {
-            stack
-                .repo
-                .config_snapshot()
-                .boolean("stgit.refreshsubmodules")
-                .unwrap_or(false)
-        } else {
-            submodules_flag
-        };
         let mut status_opts = StatusOptions::default();
         status_opts.include_submodules(use_submodules);
-        if let Some(pathspecs) = opt_pathspecs {
+        if let Some(pathspecs) = opt_pathspecs.clone() {
             status_opts.pathspecs(pathspecs);
         }
         statuses = stupid.statuses(Some(&status_opts))?;
@@ -465,7 +483,17 @@ pub(crate) fn assemble_refresh_tree(
         )?
     };
 
-    let tree_id = write_tree(stack, &refresh_paths, is_path_limiting)?;
+    let tree_id = if is_interactive {
+        select_refresh_tree(
+            stack,
+            matches,
+            opt_pathspecs,
+            use_submodules,
+            matches.get_flag("force"),
+        )?
+    } else {
+        write_tree(stack, &refresh_paths, is_path_limiting)?
+    };
 
     let tree_id = if matches.get_flag("no-verify")
         || !run_pre_commit_hook(stack.repo, matches.get_flag("edit"))?
@@ -479,3 +507,48 @@ pub(crate) fn assemble_refresh_tree(
 
     Ok(tree_id)
 }
+
+/// Interactively select worktree hunks and write the resulting tree.
+///
+/// The default index is updated to match the returned tree such that unselected
+/// changes only remain in the worktree.
+fn select_refresh_tree<'a>(
+    stack: &Stack,
+    matches: &ArgMatches,
+    opt_pathspecs: Option<impl IntoIterator<Item = &'a PathBuf>>,
+    use_submodules: bool,
+    force: bool,
+) -> Result<gix::ObjectId> {
+    let stupid = stack.repo.stupid();
+    let statuses = stupid.statuses(None)?;
+    statuses.check_conflicts()?;
+    if !force
+        && statuses
+            .iter()
+            .any(|entry| !matches!(entry.index_status(), Status::Unmodified))
+    {
+        return Err(anyhow!("the index is dirty; consider using `--force`",));
+    }
+
+    let head_tree_id = stack.get_branch_head().tree_id()?.detach();
+    let mut diff_opts = vec!["--binary", "--no-renames", "--no-ext-diff", "--no-color"];
+    if !use_submodules {
+        diff_opts.push("--ignore-submodules=all");
+    }
+    stupid.update_index_refresh()?;
+    let diff = stupid.diff_index(head_tree_id, diff_opts, opt_pathspecs)?;
+    let selection = select_hunks(&diff, "refresh", &mut get_color_stdout(matches))?;
+
+    let tree_id = if selection.is_empty() {
+        head_tree_id
+    } else {
+        stupid.with_temp_index(|stupid_temp| {
+            stupid_temp.read_tree(head_tree_id)?;
+            stupid_temp.apply_to_index(selection.diff(false).as_ref())?;
+            stupid_temp.write_tree()
+        })?
+    };
+    stupid.read_tree(tree_id)?;
+    stupid.update_index_refresh()?;
+    Ok(tree_id)
+}
@@ -13,7 +13,7 @@ use crate::{
     ext::{CommitExtended, RepositoryExtended},
     patch::PatchName,
     print_info_message, print_warning_message,
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    stack::{InitializationPolicy, Operation, Stack, StackAccess, StackStateAccess},
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -181,7 +181,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         .filter(|&pn| !stack.unapplied().contains(pn))
         .for_each(|pn| print_info_message(matches, &format!("`{pn}` is now unapplied")));
 
-    stack
+    let stack = stack
         .setup_transaction()
         .use_index_and_worktree(false)
         .with_output_stream(get_color_stdout(matches))
@@ -212,5 +212,5 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         })
         .execute("repair")?;
 
-    Ok(())
+    Operation::clear_stale(&stack)
 }
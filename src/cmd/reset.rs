@@ -9,8 +9,9 @@ use crate::{
     color::get_color_stdout,
     ext::RepositoryExtended,
     patch::{patchrange, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackState},
+    stack::{InitializationPolicy, Operation, Stack, StackState},
     stupid::Stupid,
+    wrap::PartialRefName,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -28,18 +29,23 @@ fn make() -> clap::Command {
              only the changes in the worktree.\n\
              \n\
              The state is specified with a commit id from the stack log, which may be \
-             viewed with 'stg log'. Patch name arguments may optionally be provided to \
-             limit which patches are reset.",
+             viewed with 'stg log', or with the name of a checkpoint recorded by 'stg \
+             checkpoint'. Patch name arguments may optionally be provided with a commit \
+             id to limit which patches are reset.",
         )
         .override_usage(super::make_usage(
             "stg reset",
-            &["[--hard] [<committish> [<patchname>...]]", "--hard"],
+            &[
+                "[--hard] [<committish> [<patchname>...]]",
+                "[--hard] --checkpoint <name>",
+                "--hard",
+            ],
         ))
         .trailing_var_arg(true)
         .arg(
             Arg::new("committish")
                 .help("Stack state committish")
-                .required_unless_present("hard"),
+                .required_unless_present_any(["hard", "checkpoint"]),
         )
         .arg(
             Arg::new("patchranges-all")
@@ -49,6 +55,14 @@ fn make() -> clap::Command {
                 .allow_hyphen_values(true)
                 .value_parser(clap::value_parser!(PatchRange)),
         )
+        .arg(
+            Arg::new("checkpoint")
+                .long("checkpoint")
+                .help("Reset to the checkpoint <name>")
+                .value_name("name")
+                .value_parser(clap::value_parser!(PartialRefName))
+                .conflicts_with_all(["committish", "patchranges-all"]),
+        )
         .arg(
             Arg::new("hard")
                 .long("hard")
@@ -59,7 +73,11 @@ fn make() -> clap::Command {
 
 fn run(matches: &clap::ArgMatches) -> Result<()> {
     let repo = gix::Repository::open()?;
-    if let Some(committish) = crate::argset::get_one_str(matches, "committish") {
+    if let Some(name) = matches.get_one::<PartialRefName>("checkpoint") {
+        let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
+        let commit_id = stack.get_checkpoint(name.as_ref())?;
+        reset_to_commit(stack, commit_id, matches)
+    } else if let Some(committish) = crate::argset::get_one_str(matches, "committish") {
         let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
         let commit_id = repo
             .rev_parse_single(committish)
@@ -69,25 +87,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
             .try_into_commit()
             .map_err(|_| anyhow!("target `{committish}` is not a commit"))?
             .id;
-        stack
-            .setup_transaction()
-            .use_index_and_worktree(true)
-            .discard_changes(matches.get_flag("hard"))
-            .allow_bad_head(matches.get_many::<PatchRange>("patchranges-all").is_none())
-            .with_output_stream(get_color_stdout(matches))
-            .transact(|trans| {
-                let commit = trans.repo().find_commit(commit_id)?;
-                let reset_state = StackState::from_commit(trans.repo(), &commit)?;
-                if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-all") {
-                    let patchnames =
-                        patchrange::resolve_names(&reset_state, range_specs, RangeConstraint::All)?;
-                    trans.reset_to_state_partially(&reset_state, &patchnames)
-                } else {
-                    trans.reset_to_state(reset_state)
-                }
-            })
-            .execute("reset")?;
-        Ok(())
+        reset_to_commit(stack, commit_id, matches)
     } else if matches.get_flag("hard") {
         let head_tree_id = repo.head_commit()?.tree_id()?.detach();
         repo.stupid().read_tree_checkout_hard(head_tree_id)
@@ -95,3 +95,30 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         unreachable!();
     }
 }
+
+/// Reset the stack, or only the given patches, to the stack state of `commit_id`.
+fn reset_to_commit(
+    stack: Stack,
+    commit_id: gix::ObjectId,
+    matches: &clap::ArgMatches,
+) -> Result<()> {
+    let stack = stack
+        .setup_transaction()
+        .use_index_and_worktree(true)
+        .discard_changes(matches.get_flag("hard"))
+        .allow_bad_head(matches.get_many::<PatchRange>("patchranges-all").is_none())
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            let commit = trans.repo().find_commit(commit_id)?;
+            let reset_state = StackState::from_commit(trans.repo(), &commit)?;
+            if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-all") {
+                let patchnames =
+                    patchrange::resolve_names(&reset_state, range_specs, RangeConstraint::All)?;
+                trans.reset_to_state_partially(&reset_state, &patchnames)
+            } else {
+                trans.reset_to_state(reset_state)
+            }
+        })
+        .execute("reset")?;
+    Operation::clear_stale(&stack)
+}
@@ -128,13 +128,48 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .overrides_with("author"),
         )
+        .arg(
+            Arg::new("meta")
+                .long("meta")
+                .help("Display metadata <key> for each patch")
+                .long_help(
+                    "Display the value of metadata <key> for each patch. This option \
+                     may be specified multiple times to display several metadata \
+                     values. Patches without the metadata key display nothing for the \
+                     key. See `stg meta` for managing patch metadata.",
+                )
+                .value_name("key")
+                .num_args(1)
+                .action(clap::ArgAction::Append)
+                .value_parser(super::meta::parse_key),
+        )
+        .arg(
+            Arg::new("sent")
+                .long("sent")
+                .help("Display when each patch was last sent by email")
+                .long_help(
+                    "Display the version each patch was last sent as by `stg email \
+                     send`. Patches that have changed since they were last sent are \
+                     marked as \"changed\". Patches that have never been sent display \
+                     nothing.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(
             Arg::new("count")
                 .long("count")
                 .short('c')
                 .help("Display the number of selected patches and exit")
                 .action(clap::ArgAction::SetTrue)
-                .conflicts_with_all(["description", "author", "empty", "show-branch", "no-prefix"]),
+                .conflicts_with_all([
+                    "description",
+                    "author",
+                    "meta",
+                    "sent",
+                    "empty",
+                    "show-branch",
+                    "no-prefix",
+                ]),
         )
         .arg(
             Arg::new("commit-id")
@@ -289,6 +324,93 @@ fn make() -> clap::Command {
                 .action(clap::ArgAction::SetTrue)
                 .overrides_with("show-branch"),
         )
+        .arg(
+            Arg::new("json")
+                .long("json")
+                .help("Display patches as JSON")
+                .long_help(
+                    "Display the selected patches as a JSON array with one object per \
+                     patch. Each object contains the patch's \"name\", \"state\" \
+                     (\"applied\", \"unapplied\", or \"hidden\"), whether it is the \
+                     \"top\" patch, its absolute \"index\", its \"offset\" from the \
+                     topmost patch, its \"commit\" id, its \"author\" and \
+                     \"committer\" (each with \"name\", \"email\", and \"date\"), \
+                     whether it is \"empty\", its \"description\" (the first line of \
+                     the patch's message), its \"meta\" data, and its \"sent\" \
+                     history (each with \"commit\", \"message-id\", \"date\", and \
+                     \"version\").\n\
+                     \n\
+                     The other display options do not apply to JSON output.",
+                )
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all([
+                    "count",
+                    "commit-id",
+                    "description",
+                    "author",
+                    "meta",
+                    "sent",
+                    "empty",
+                    "no-prefix",
+                    "indices",
+                    "offsets",
+                    "show-branch",
+                ]),
+        )
+        .arg(
+            Arg::new("format")
+                .long("format")
+                .help("Display patches using <format>")
+                .long_help(
+                    "Display each selected patch using <format>, a string that \
+                     interpolates %(atom) placeholders similar to those of `git \
+                     for-each-ref`. The following atoms are available:\n\
+                     \n\
+                     %(name): patch name\n\
+                     %(state): \"applied\", \"unapplied\", or \"hidden\"\n\
+                     %(prefix): status prefix; one of '>', '+', '-', or '!'\n\
+                     %(index): absolute patch index\n\
+                     %(offset): offset relative to the topmost patch\n\
+                     %(branch): branch name\n\
+                     %(commit): commit id; %(commit:short) or %(commit:short=<n>) for \
+                     an abbreviated commit id\n\
+                     %(author): author name and email\n\
+                     %(author-name), %(author-email): author name or email\n\
+                     %(author-date): author date; optionally %(author-date:<fmt>) where \
+                     <fmt> is one of \"default\", \"iso\", \"iso-strict\", \
+                     \"rfc2822\", \"short\", \"raw\", or \"unix\"\n\
+                     %(committer), %(committer-name), %(committer-email), \
+                     %(committer-date): as above, but for the committer\n\
+                     %(description): first line of the patch's message\n\
+                     %(message): full patch message\n\
+                     %(empty): \"empty\" if the patch is empty, otherwise nothing\n\
+                     %(meta:<key>): value of patch metadata <key>, if any\n\
+                     \n\
+                     Additionally, \"%%\" is replaced with '%', \"%n\" with a \
+                     newline, and \"%xx\", where \"xx\" are two hexadecimal digits, \
+                     with the corresponding byte.\n\
+                     \n\
+                     A newline is output after each formatted patch. The other display \
+                     options do not apply when a format is used.",
+                )
+                .value_name("format")
+                .num_args(1)
+                .value_parser(clap::value_parser!(SeriesFormat))
+                .conflicts_with_all([
+                    "count",
+                    "json",
+                    "commit-id",
+                    "description",
+                    "author",
+                    "meta",
+                    "sent",
+                    "empty",
+                    "no-prefix",
+                    "indices",
+                    "offsets",
+                    "show-branch",
+                ]),
+        )
 }
 
 #[derive(Clone)]
@@ -319,6 +441,175 @@ impl FromStr for CommitIdLength {
     }
 }
 
+/// Format string for `--format`.
+#[derive(Clone)]
+struct SeriesFormat(Vec<FormatItem>);
+
+#[derive(Clone)]
+enum FormatItem {
+    Literal(Vec<u8>),
+    Atom(FormatAtom),
+}
+
+#[derive(Clone)]
+enum FormatAtom {
+    Name,
+    State,
+    Prefix,
+    Index,
+    Offset,
+    Branch,
+    Commit(Option<usize>),
+    Signature(SignatureRole, SignatureField),
+    Description,
+    Message,
+    Empty,
+    Meta(String),
+}
+
+#[derive(Clone, Copy)]
+enum SignatureRole {
+    Author,
+    Committer,
+}
+
+#[derive(Clone, Copy)]
+enum SignatureField {
+    Full,
+    Name,
+    Email,
+    Date(gix::date::time::Format<'static>),
+}
+
+impl FromStr for SeriesFormat {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let mut items: Vec<FormatItem> = Vec::new();
+        let mut literal: Vec<u8> = Vec::new();
+        let mut rest = s;
+
+        while let Some(pos) = rest.find('%') {
+            literal.extend_from_slice(&rest.as_bytes()[..pos]);
+            rest = &rest[pos + 1..];
+            if let Some(after) = rest.strip_prefix('%') {
+                literal.push(b'%');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('n') {
+                literal.push(b'\n');
+                rest = after;
+            } else if let Some(after) = rest.strip_prefix('(') {
+                let end = after
+                    .find(')')
+                    .ok_or_else(|| anyhow!("unterminated format atom `%({after}`"))?;
+                if !literal.is_empty() {
+                    items.push(FormatItem::Literal(std::mem::take(&mut literal)));
+                }
+                items.push(FormatItem::Atom(FormatAtom::from_str(&after[..end])?));
+                rest = &after[end + 1..];
+            } else if let Some(byte) = rest
+                .get(..2)
+                .filter(|hex| hex.bytes().all(|b| b.is_ascii_hexdigit()))
+                .and_then(|hex| u8::from_str_radix(hex, 16).ok())
+            {
+                literal.push(byte);
+                rest = &rest[2..];
+            } else {
+                return Err(anyhow!(
+                    "invalid format escape `%{}`",
+                    rest.chars().next().unwrap_or_default()
+                ));
+            }
+        }
+        literal.extend_from_slice(rest.as_bytes());
+        if !literal.is_empty() {
+            items.push(FormatItem::Literal(literal));
+        }
+
+        Ok(Self(items))
+    }
+}
+
+impl FromStr for FormatAtom {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self, Self::Err> {
+        let (name, modifier) = if let Some((name, modifier)) = s.split_once(':') {
+            (name, Some(modifier))
+        } else {
+            (s, None)
+        };
+
+        let signature_field = |role: SignatureRole, field: &str| -> Result<FormatAtom> {
+            let field = match (field, modifier) {
+                ("", None) => SignatureField::Full,
+                ("-name", None) => SignatureField::Name,
+                ("-email", None) => SignatureField::Email,
+                ("-date", modifier) => SignatureField::Date(match modifier.unwrap_or("default") {
+                    "default" => gix::date::time::format::DEFAULT.into(),
+                    "iso" | "iso8601" => gix::date::time::format::ISO8601.into(),
+                    "iso-strict" | "iso8601-strict" => {
+                        gix::date::time::format::ISO8601_STRICT.into()
+                    }
+                    "rfc2822" | "rfc" => gix::date::time::format::RFC2822.into(),
+                    "short" => gix::date::time::format::SHORT.into(),
+                    "raw" => gix::date::time::format::RAW,
+                    "unix" => gix::date::time::format::UNIX,
+                    other => return Err(anyhow!("invalid date format `{other}` in `%({s})`")),
+                }),
+                _ => return Err(anyhow!("invalid format atom `%({s})`")),
+            };
+            Ok(FormatAtom::Signature(role, field))
+        };
+
+        match (name, modifier) {
+            ("name", None) => Ok(Self::Name),
+            ("state", None) => Ok(Self::State),
+            ("prefix", None) => Ok(Self::Prefix),
+            ("index", None) => Ok(Self::Index),
+            ("offset", None) => Ok(Self::Offset),
+            ("branch", None) => Ok(Self::Branch),
+            ("commit", None) => Ok(Self::Commit(None)),
+            ("commit", Some("short")) => Ok(Self::Commit(Some(7))),
+            ("commit", Some(modifier)) => modifier
+                .strip_prefix("short=")
+                .and_then(|n| usize::from_str(n).ok())
+                .filter(|n| *n >= 4)
+                .map(|n| Self::Commit(Some(n)))
+                .ok_or_else(|| {
+                    anyhow!(
+                        "invalid `%({s})`; abbreviated commit length must be an integer \
+                         greater than or equal to 4"
+                    )
+                }),
+            ("description", None) => Ok(Self::Description),
+            ("message", None) => Ok(Self::Message),
+            ("empty", None) => Ok(Self::Empty),
+            ("meta", Some(key)) => Ok(Self::Meta(
+                super::meta::parse_key(key).map_err(|e| anyhow!("invalid `%({s})`: {e}"))?,
+            )),
+            _ => {
+                if let Some(field) = name.strip_prefix("author") {
+                    signature_field(SignatureRole::Author, field)
+                } else if let Some(field) = name.strip_prefix("committer") {
+                    signature_field(SignatureRole::Committer, field)
+                } else {
+                    Err(anyhow!("invalid format atom `%({s})`"))
+                }
+            }
+        }
+    }
+}
+
+/// A patch selected for display.
+struct Entry {
+    patchname: PatchName,
+    commit_id: gix::ObjectId,
+    sigil: char,
+    index: usize,
+    offset_from_top: isize,
+}
+
 fn run(matches: &ArgMatches) -> Result<()> {
     let repo = gix::Repository::open()?;
     let opt_branch = matches.get_one::<BranchLocator>("branch");
@@ -353,14 +644,6 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let unapplied_flag = matches.get_flag("unapplied");
     let hidden_flag = matches.get_flag("hidden");
 
-    struct Entry {
-        patchname: PatchName,
-        commit_id: gix::ObjectId,
-        sigil: char,
-        index: usize,
-        offset_from_top: isize,
-    }
-
     let mut patches: Vec<Entry> = vec![];
 
     if let Some(range_specs) = matches.get_many::<PatchRange>("patchranges-all") {
@@ -478,9 +761,28 @@ fn run(matches: &ArgMatches) -> Result<()> {
         return Ok(());
     }
 
+    if matches.get_flag("json") {
+        if matches.get_flag("reverse") {
+            patches.reverse();
+        }
+        return write_json(&repo, &stack, &patches);
+    }
+
+    if let Some(format) = matches.get_one::<SeriesFormat>("format") {
+        if matches.get_flag("reverse") {
+            patches.reverse();
+        }
+        return write_formatted(&repo, &stack, &patches, format);
+    }
+
     let opt_commit_id = matches.get_one::<CommitIdLength>("commit-id");
     let description_flag = matches.get_flag("description");
     let author_flag = matches.get_flag("author");
+    let sent_flag = matches.get_flag("sent");
+    let meta_keys: Vec<&String> = matches
+        .get_many::<String>("meta")
+        .map(|keys| keys.collect())
+        .unwrap_or_default();
 
     let branch_prefix = format!("{}:", &stack.get_branch_name());
     let branch_prefix = if matches.get_flag("show-branch") {
@@ -489,7 +791,12 @@ fn run(matches: &ArgMatches) -> Result<()> {
         ""
     };
 
-    let patchname_width = if opt_commit_id.is_some() || description_flag || author_flag {
+    let patchname_width = if opt_commit_id.is_some()
+        || description_flag
+        || author_flag
+        || sent_flag
+        || !meta_keys.is_empty()
+    {
         patches
             .iter()
             .map(|Entry { patchname, .. }| patchname.len())
@@ -629,6 +936,30 @@ fn run(matches: &ArgMatches) -> Result<()> {
                 write!(stdout, "{name:author_width$}")?;
             }
         }
+        if !meta_keys.is_empty() {
+            let patch_meta = &stack.get_patch(&patchname).meta;
+            for key in &meta_keys {
+                if let Some(value) = patch_meta.get(key.as_str()) {
+                    stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Cyan)))?;
+                    write!(stdout, " {key}={value}")?;
+                }
+            }
+        }
+        if sent_flag {
+            if let Some(record) = stack.get_patch(&patchname).last_sent() {
+                stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Green)))?;
+                write!(stdout, " [sent")?;
+                if let Some(version) = record.version {
+                    write!(stdout, " v{version}")?;
+                }
+                if record.commit_id != commit_id {
+                    stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Yellow)))?;
+                    write!(stdout, ", changed")?;
+                    stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Green)))?;
+                }
+                write!(stdout, "]")?;
+            }
+        }
         if description_flag {
             stdout.set_color(color_spec.set_fg(Some(termcolor::Color::Black)))?;
             write!(stdout, " #")?;
@@ -647,3 +978,208 @@ fn run(matches: &ArgMatches) -> Result<()> {
 
     Ok(())
 }
+
+#[derive(serde::Serialize)]
+struct JsonSignature {
+    name: String,
+    email: String,
+    date: String,
+}
+
+impl From<gix::actor::SignatureRef<'_>> for JsonSignature {
+    fn from(sig: gix::actor::SignatureRef<'_>) -> Self {
+        Self {
+            name: sig.name.to_str_lossy().to_string(),
+            email: sig.email.to_str_lossy().to_string(),
+            date: sig.time.format(gix::date::time::format::ISO8601_STRICT),
+        }
+    }
+}
+
+impl From<gix::actor::Signature> for JsonSignature {
+    fn from(sig: gix::actor::Signature) -> Self {
+        Self {
+            name: sig.name.to_str_lossy().to_string(),
+            email: sig.email.to_str_lossy().to_string(),
+            date: sig.time.format(gix::date::time::format::ISO8601_STRICT),
+        }
+    }
+}
+
+#[derive(serde::Serialize)]
+struct JsonPatch<'a> {
+    name: String,
+    state: &'static str,
+    top: bool,
+    index: usize,
+    offset: isize,
+    commit: String,
+    author: JsonSignature,
+    committer: JsonSignature,
+    empty: bool,
+    description: String,
+    meta: &'a std::collections::BTreeMap<String, String>,
+    sent: Vec<JsonSent<'a>>,
+}
+
+#[derive(serde::Serialize)]
+struct JsonSent<'a> {
+    commit: String,
+    #[serde(rename = "message-id")]
+    message_id: &'a str,
+    date: String,
+    version: Option<u32>,
+}
+
+fn write_json(repo: &gix::Repository, stack: &Stack, patches: &[Entry]) -> Result<()> {
+    let mut json_patches: Vec<JsonPatch> = Vec::with_capacity(patches.len());
+    for entry in patches {
+        let commit = repo.find_commit(entry.commit_id)?;
+        let commit_ref = commit.decode()?;
+        let author = commit
+            .author_strict()
+            .map_or_else(|_| commit_ref.author().into(), JsonSignature::from);
+        let committer = commit
+            .committer_strict()
+            .map_or_else(|_| commit_ref.committer().into(), JsonSignature::from);
+        let patch = stack.get_patch(&entry.patchname);
+        json_patches.push(JsonPatch {
+            name: entry.patchname.to_string(),
+            state: match entry.sigil {
+                '+' | '>' => "applied",
+                '-' => "unapplied",
+                '!' => "hidden",
+                _ => panic!("unhandled sigil {:?}", entry.sigil),
+            },
+            top: entry.sigil == '>',
+            index: entry.index,
+            offset: entry.offset_from_top,
+            commit: entry.commit_id.to_string(),
+            author,
+            committer,
+            empty: commit.is_no_change()?,
+            description: commit_ref.message_summary().to_str_lossy().to_string(),
+            meta: &patch.meta,
+            sent: patch
+                .sent
+                .iter()
+                .map(|record| JsonSent {
+                    commit: record.commit_id.to_string(),
+                    message_id: &record.message_id,
+                    date: record.time.format(gix::date::time::format::ISO8601_STRICT),
+                    version: record.version,
+                })
+                .collect(),
+        });
+    }
+
+    let mut stdout = std::io::stdout().lock();
+    serde_json::to_writer_pretty(&mut stdout, &json_patches)?;
+    writeln!(stdout)?;
+    Ok(())
+}
+
+fn write_formatted(
+    repo: &gix::Repository,
+    stack: &Stack,
+    patches: &[Entry],
+    format: &SeriesFormat,
+) -> Result<()> {
+    let mut stdout = std::io::stdout().lock();
+    for entry in patches {
+        let commit = repo.find_commit(entry.commit_id)?;
+        let commit_ref = commit.decode()?;
+        let mut line: Vec<u8> = Vec::new();
+        for item in &format.0 {
+            match item {
+                FormatItem::Literal(bytes) => line.extend_from_slice(bytes),
+                FormatItem::Atom(atom) => match atom {
+                    FormatAtom::Name => write!(line, "{}", entry.patchname)?,
+                    FormatAtom::State => line.extend_from_slice(match entry.sigil {
+                        '+' | '>' => b"applied",
+                        '-' => b"unapplied",
+                        '!' => b"hidden",
+                        _ => panic!("unhandled sigil {:?}", entry.sigil),
+                    }),
+                    FormatAtom::Prefix => write!(line, "{}", entry.sigil)?,
+                    FormatAtom::Index => write!(line, "{}", entry.index)?,
+                    FormatAtom::Offset => write!(line, "{:+}", entry.offset_from_top)?,
+                    FormatAtom::Branch => {
+                        line.extend_from_slice(stack.get_branch_name().as_bytes())
+                    }
+                    FormatAtom::Commit(length) => {
+                        let id_str = entry.commit_id.to_string();
+                        let n = length.unwrap_or(id_str.len()).min(id_str.len());
+                        line.extend_from_slice(&id_str.as_bytes()[..n]);
+                    }
+                    FormatAtom::Signature(role, field) => {
+                        let sig = match role {
+                            SignatureRole::Author => commit.author_strict(),
+                            SignatureRole::Committer => commit.committer_strict(),
+                        }
+                        .unwrap_or_else(|_| {
+                            match role {
+                                SignatureRole::Author => commit_ref.author(),
+                                SignatureRole::Committer => commit_ref.committer(),
+                            }
+                            .to_owned()
+                        });
+                        match field {
+                            SignatureField::Full => {
+                                write!(line, "{} <{}>", sig.name, sig.email)?;
+                            }
+                            SignatureField::Name => line.extend_from_slice(&sig.name),
+                            SignatureField::Email => line.extend_from_slice(&sig.email),
+                            SignatureField::Date(date_format) => {
+                                line.extend_from_slice(sig.time.format(*date_format).as_bytes());
+                            }
+                        }
+                    }
+                    FormatAtom::Description => {
+                        line.extend_from_slice(&commit_ref.message_summary());
+                    }
+                    FormatAtom::Message => {
+                        let message = commit.message_ex();
+                        if let Ok(message) = message.decode() {
+                            line.extend_from_slice(message.trim_end().as_bytes());
+                        } else {
+                            line.extend_from_slice(message.raw_bytes().trim_end());
+                        }
+                    }
+                    FormatAtom::Empty => {
+                        if commit.is_no_change()? {
+                            line.extend_from_slice(b"empty");
+                        }
+                    }
+                    FormatAtom::Meta(key) => {
+                        if let Some(value) = stack.get_patch(&entry.patchname).meta.get(key) {
+                            line.extend_from_slice(value.as_bytes());
+                        }
+                    }
+                },
+            }
+        }
+        line.push(b'\n');
+        stdout.write_all(&line)?;
+    }
+    Ok(())
+}
+
+#[cfg(test)]
+mod test {
+    use std::str::FromStr;
+
+    use super::{FormatItem, SeriesFormat};
+
+    #[test]
+    fn hex_escape() {
+        let SeriesFormat(items) = SeriesFormat::from_str("%0a%7E").unwrap();
+        assert!(matches!(items.as_slice(), [FormatItem::Literal(bytes)] if bytes == b"\n~"));
+    }
+
+    #[test]
+    fn signed_hex_escape() {
+        assert!(SeriesFormat::from_str("%+f").is_err());
+        assert!(SeriesFormat::from_str("%-1").is_err());
+    }
+}
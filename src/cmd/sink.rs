@@ -10,7 +10,7 @@ use crate::{
     color::get_color_stdout,
     ext::RepositoryExtended,
     patch::{patchrange, LocationConstraint, PatchLocator, PatchName, PatchRange, RangeConstraint},
-    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stack::{Dependencies, DependencyPolicy, InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
 };
 
@@ -78,6 +78,7 @@ fn make() -> clap::Command {
         )
         .arg(argset::keep_arg())
         .arg(argset::committer_date_is_author_date_arg())
+        .arg(argset::deps_arg())
 }
 
 fn run(matches: &ArgMatches) -> Result<()> {
@@ -127,6 +128,37 @@ fn run(matches: &ArgMatches) -> Result<()> {
         }
     }
 
+    let deps_policy = argset::resolve_deps_policy(&repo.config_snapshot(), matches)?;
+    let deps = if deps_policy == DependencyPolicy::Ignore {
+        None
+    } else {
+        Some(Dependencies::compute(&repo, &stack)?)
+    };
+
+    let patches = if let (DependencyPolicy::Carry, Some(deps)) = (deps_policy, &deps) {
+        let target_index = opt_target
+            .as_ref()
+            .map_or(0, |target_patch| stack.index_of(target_patch));
+        let extra: Vec<PatchName> = deps
+            .dependencies_of(&patches)
+            .into_iter()
+            .filter(|pn| {
+                stack.is_unapplied(pn)
+                    || (stack.is_applied(pn) && stack.index_of(pn) >= target_index)
+            })
+            .collect();
+        if let Some(target_patch) = &opt_target {
+            if extra.contains(target_patch) {
+                return Err(anyhow!(
+                    "cannot sink below `{target_patch}` since patches being sunk depend on it"
+                ));
+            }
+        }
+        deps.carry(&patches, extra)
+    } else {
+        patches
+    };
+
     let mut remaining_unapplied: Vec<PatchName> = stack
         .unapplied()
         .iter()
@@ -170,6 +202,10 @@ fn run(matches: &ArgMatches) -> Result<()> {
         (applied, remaining_unapplied)
     };
 
+    if let Some(deps) = &deps {
+        deps.check_applied(stack.applied(), &applied)?;
+    }
+
     stack
         .setup_transaction()
         .use_index_and_worktree(true)
@@ -0,0 +1,94 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg snapshot` implementation.
+
+use std::io::Write;
+
+use anyhow::Result;
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    argset,
+    branchloc::BranchLocator,
+    ext::RepositoryExtended,
+    print_info_message,
+    stack::{parse_version, InitializationPolicy, Stack},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "snapshot",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    clap::Command::new(STGIT_COMMAND.name)
+        .about("Record the patch series as a numbered version")
+        .long_about(
+            "Record the stack's current state as a numbered version of the patch \
+             series. Snapshots are typically recorded each time the patch series is \
+             sent for review, which `stg email format` and `stg email send` do \
+             automatically.\n\
+             \n\
+             The version defaults to one more than the latest recorded version. Each \
+             version snapshot is stored as a reference, \
+             `refs/stacks/<branch>.versions/v<N>`, to the stack state commit. Version \
+             names, e.g. 'v2', may be used with `stg interdiff` to compare versions of \
+             the patch series.",
+        )
+        .override_usage(super::make_usage(
+            "stg snapshot",
+            &["[OPTIONS] [version]", "--list"],
+        ))
+        .arg(
+            Arg::new("version")
+                .help("Version to record, e.g. 'v2'")
+                .value_name("version")
+                .value_parser(parse_version)
+                .value_hint(ValueHint::Other),
+        )
+        .arg(argset::branch_arg())
+        .arg(
+            Arg::new("force")
+                .long("force")
+                .short('f')
+                .help("Replace an existing snapshot of the version")
+                .action(clap::ArgAction::SetTrue),
+        )
+        .arg(
+            Arg::new("list")
+                .long("list")
+                .short('l')
+                .help("List recorded versions")
+                .action(clap::ArgAction::SetTrue)
+                .conflicts_with_all(["version", "force"]),
+        )
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::from_branch_locator(
+        &repo,
+        matches.get_one::<BranchLocator>("branch"),
+        InitializationPolicy::RequireInitialized,
+    )?;
+    let versions = stack.get_versions()?;
+
+    if matches.get_flag("list") {
+        let mut stdout = std::io::stdout().lock();
+        for (version, _) in versions {
+            writeln!(stdout, "v{version}")?;
+        }
+        return Ok(());
+    }
+
+    let version = if let Some(version) = matches.get_one::<u32>("version") {
+        *version
+    } else {
+        versions.last().map_or(1, |(version, _)| version + 1)
+    };
+    stack.snapshot_version(version, matches.get_flag("force"))?;
+    print_info_message(matches, &format!("Recorded version v{version}"));
+    Ok(())
+}
@@ -11,6 +11,7 @@ use crate::{
     argset,
     color::get_color_stdout,
     ext::{CommitExtended, RepositoryExtended},
+    hunks::select_hunks,
     stack::{InitializationPolicy, Stack, StackStateAccess},
     stupid::Stupid,
 };
@@ -52,6 +53,18 @@ fn make() -> clap::Command {
                 )
                 .action(clap::ArgAction::SetTrue),
         )
+        .arg(
+            Arg::new("interactive")
+                .long("interactive")
+                .short('I')
+                .help("Interactively select hunks to spill")
+                .long_help(
+                    "Interactively select which hunks of the patch to spill, in the \
+                     manner of `git add --patch`. Hunks may be split into smaller \
+                     hunks.",
+                )
+                .action(clap::ArgAction::SetTrue),
+        )
         .arg(argset::committer_date_is_author_date_arg())
         .arg(
             Arg::new("pathspecs")
@@ -83,7 +96,25 @@ fn run(matches: &ArgMatches) -> Result<()> {
     let parent = patch_commit.get_parent_commit()?;
     let parent_commit_ref = parent.decode()?;
 
-    let tree_id = if let Some(pathspecs) = matches.get_many::<PathBuf>("pathspecs") {
+    let tree_id = if matches.get_flag("interactive") {
+        let diff = stupid.diff_tree_patch(
+            parent_commit_ref.tree(),
+            patch_commit_ref.tree(),
+            matches.get_many::<PathBuf>("pathspecs"),
+            false,
+            ["--full-index", "--binary", "--no-renames", "--no-ext-diff"],
+        )?;
+        let selection = select_hunks(&diff, "spill", &mut get_color_stdout(matches))?;
+        if selection.is_empty() {
+            patch_commit_ref.tree()
+        } else {
+            stupid.with_temp_index(|stupid_temp| {
+                stupid_temp.read_tree(patch_commit_ref.tree())?;
+                stupid_temp.apply_reverse_to_index(selection.diff(true).as_ref())?;
+                stupid_temp.write_tree()
+            })?
+        }
+    } else if let Some(pathspecs) = matches.get_many::<PathBuf>("pathspecs") {
         stupid.with_temp_index(|stupid_temp| {
             stupid_temp.read_tree(patch_commit_ref.tree())?;
             stupid_temp.apply_pathlimited_treediff_to_index(
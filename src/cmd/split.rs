@@ -0,0 +1,237 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! `stg split` implementation.
+
+use std::io::Write;
+
+use anyhow::{anyhow, Result};
+use bstr::BString;
+use clap::{Arg, ArgMatches, ValueHint};
+
+use crate::{
+    color::get_color_stdout,
+    ext::{CommitExtended, RepositoryExtended},
+    hunks::select_hunks,
+    patch::{patchedit, LocationConstraint, PatchLocator, PatchName},
+    stack::{InitializationPolicy, Stack, StackStateAccess},
+    stupid::{parse_diff, Stupid, StupidContext},
+};
+
+pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
+    name: "split",
+    category: super::CommandCategory::StackManipulation,
+    make,
+    run,
+};
+
+fn make() -> clap::Command {
+    let app = clap::Command::new(STGIT_COMMAND.name)
+        .about("Split a patch into several patches")
+        .long_about(
+            "Split a patch's changes into two or more new patches which replace the \
+             original patch in place.\n\
+             \n\
+             By default, the hunks for each new patch are selected interactively, in \
+             the manner of `git add --patch`. Selection repeats for each new patch \
+             until all of the original patch's changes are accounted for. If no \
+             hunks are selected for a new patch, the remaining changes are used for \
+             the final new patch.\n\
+             \n\
+             With '--by-file', a new patch is created for each file modified by the \
+             original patch, without interactive selection.\n\
+             \n\
+             The patch description template is presented in an editor for each new \
+             patch such that each new patch may be given its own name and message. \
+             The template is pre-filled with the original patch's name and message. \
+             The new patches keep the original patch's author. The first new patch \
+             takes the place of the original patch and thus keeps its metadata, \
+             declared dependencies, and sent email history.\n\
+             \n\
+             The split is performed in a single transaction and may thus be reverted \
+             with a single `stg undo`.",
+        )
+        .arg(
+            Arg::new("patch")
+                .help("Patch to split")
+                .value_name("patch")
+                .value_hint(ValueHint::Other)
+                .allow_hyphen_values(true)
+                .value_parser(clap::value_parser!(PatchLocator)),
+        )
+        .arg(
+            Arg::new("by-file")
+                .long("by-file")
+                .help("Create a new patch for each modified file")
+                .action(clap::ArgAction::SetTrue),
+        );
+    patchedit::add_args(app, false, false)
+}
+
+fn run(matches: &ArgMatches) -> Result<()> {
+    let repo = gix::Repository::open()?;
+    let stack = Stack::current(&repo, InitializationPolicy::AllowUninitialized)?;
+    let stupid = repo.stupid();
+
+    repo.check_repository_state()?;
+    let statuses = stupid.statuses(None)?;
+    statuses.check_conflicts()?;
+    statuses.check_index_and_worktree_clean()?;
+    stack.check_head_top_mismatch()?;
+
+    let patchname = if let Some(patch_loc) = matches.get_one::<PatchLocator>("patch") {
+        patch_loc
+            .resolve_name(&stack)?
+            .constrain(&stack, LocationConstraint::Visible)?
+    } else if let Some(top_patchname) = stack.applied().last() {
+        top_patchname.clone()
+    } else {
+        return Err(super::Error::NoAppliedPatches.into());
+    };
+
+    let patch_commit = stack.get_patch_commit(&patchname).clone();
+    let parent = patch_commit.get_parent_commit()?;
+    let parent_tree_id = parent.decode()?.tree();
+    let patch_tree_id = patch_commit.decode()?.tree();
+
+    let trees = if matches.get_flag("by-file") {
+        split_by_file(&stupid, parent_tree_id, patch_tree_id)?
+    } else {
+        split_by_hunk(&stupid, parent_tree_id, patch_tree_id, matches)?
+    };
+
+    if trees.len() < 2 {
+        return Err(anyhow!(
+            "patch `{patchname}` must be split into at least two patches"
+        ));
+    }
+
+    stack
+        .setup_transaction()
+        .allow_conflicts(true)
+        .use_index_and_worktree(true)
+        .committer_date_is_author_date(matches.get_flag("committer-date-is-author-date"))
+        .with_output_stream(get_color_stdout(matches))
+        .transact(|trans| {
+            let was_applied = trans.applied().contains(&patchname);
+            let to_push = trans.pop_patches(|pn| pn == &patchname)?;
+            let insert_pos = trans
+                .unapplied()
+                .iter()
+                .position(|pn| pn == &patchname)
+                .expect("patch to split is unapplied");
+
+            let mut parent_id = parent.id;
+            let mut new_patchnames: Vec<PatchName> = Vec::with_capacity(trees.len());
+
+            for (i, tree_id) in trees.iter().enumerate() {
+                let patchedit::EditOutcome::Edited {
+                    new_patchname,
+                    new_commit_id,
+                } = patchedit::EditBuilder::default()
+                    .original_patchname((i == 0).then_some(&patchname))
+                    .template_patchname(Some(&patchname))
+                    .existing_patch_commit(&patch_commit)
+                    .override_tree_id(*tree_id)
+                    .override_parent_id(parent_id)
+                    .allow_implicit_edit(true)
+                    .allow_diff_edit(false)
+                    .allow_template_save(false)
+                    .edit(trans, &repo, matches)?
+                else {
+                    panic!("expected edit to commit, not save template")
+                };
+                let new_patchname = new_patchname.unwrap_or_else(|| patchname.clone());
+                let commit_id = new_commit_id.unwrap_or(patch_commit.id);
+                if i == 0 {
+                    // The original patch is updated in place, rather than replaced, such
+                    // that its metadata and sent history are retained.
+                    trans.rename_patch(&patchname, &new_patchname)?;
+                    trans.update_patch(&new_patchname, commit_id)?;
+                } else {
+                    trans.new_unapplied(&new_patchname, commit_id, insert_pos + i)?;
+                }
+                new_patchnames.push(new_patchname);
+                parent_id = commit_id;
+            }
+
+            if was_applied {
+                new_patchnames.extend(to_push);
+                trans.push_patches(&new_patchnames, false)?;
+            }
+            Ok(())
+        })
+        .execute(&format!("split {patchname}"))?;
+
+    Ok(())
+}
+
+/// Determine the trees for splitting a patch such that each file is in its own patch.
+fn split_by_file(
+    stupid: &StupidContext,
+    parent_tree_id: gix::ObjectId,
+    patch_tree_id: gix::ObjectId,
+) -> Result<Vec<gix::ObjectId>> {
+    let diff = patch_diff(stupid, parent_tree_id, patch_tree_id)?;
+    let files = parse_diff(&diff)?;
+    let mut trees = Vec::with_capacity(files.len());
+    let mut tree_id = parent_tree_id;
+    for file in &files[..files.len().saturating_sub(1)] {
+        let mut file_diff = BString::from(file.header);
+        for hunk in &file.hunks {
+            file_diff.extend_from_slice(hunk.text);
+        }
+        tree_id = stupid.with_temp_index(|stupid_temp| {
+            stupid_temp.read_tree(tree_id)?;
+            stupid_temp.apply_to_index(file_diff.as_ref())?;
+            stupid_temp.write_tree()
+        })?;
+        trees.push(tree_id);
+    }
+    trees.push(patch_tree_id);
+    Ok(trees)
+}
+
+/// Determine the trees for splitting a patch by interactively selecting hunks.
+fn split_by_hunk(
+    stupid: &StupidContext,
+    parent_tree_id: gix::ObjectId,
+    patch_tree_id: gix::ObjectId,
+    matches: &ArgMatches,
+) -> Result<Vec<gix::ObjectId>> {
+    let mut output = get_color_stdout(matches);
+    let mut trees = Vec::new();
+    let mut tree_id = parent_tree_id;
+    loop {
+        let diff = patch_diff(stupid, tree_id, patch_tree_id)?;
+        if diff.is_empty() {
+            break;
+        }
+        writeln!(output, "Select changes for patch {}:", trees.len() + 1)?;
+        let selection = select_hunks(&diff, "include", &mut output)?;
+        if selection.is_empty() {
+            trees.push(patch_tree_id);
+            break;
+        }
+        tree_id = stupid.with_temp_index(|stupid_temp| {
+            stupid_temp.read_tree(tree_id)?;
+            stupid_temp.apply_to_index(selection.diff(false).as_ref())?;
+            stupid_temp.write_tree()
+        })?;
+        trees.push(tree_id);
+    }
+    Ok(trees)
+}
+
+fn patch_diff(
+    stupid: &StupidContext,
+    tree1: gix::ObjectId,
+    tree2: gix::ObjectId,
+) -> Result<BString> {
+    stupid.diff_tree_patch(
+        tree1,
+        tree2,
+        None::<Vec<&str>>,
+        false,
+        ["--full-index", "--binary", "--no-renames", "--no-ext-diff"],
+    )
+}
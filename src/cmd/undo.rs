@@ -11,7 +11,8 @@ use clap::Arg;
 use crate::{
     color::get_color_stdout,
     ext::RepositoryExtended,
-    stack::{InitializationPolicy, Stack, StackAccess, StackState},
+    stack::{InitializationPolicy, Operation, Stack, StackAccess, StackState},
+    wrap::PartialRefName,
 };
 
 pub(super) const STGIT_COMMAND: super::StGitCommand = super::StGitCommand {
@@ -26,7 +27,11 @@ fn make() -> clap::Command {
         .about("Undo the last command")
         .long_about(
             "Reset the patch stack to the state before the last operation. \
-             Consecutive undos will go back to yet older stack states.",
+             Consecutive undos will go back to yet older stack states.\n\
+             \n\
+             Alternatively, the stack may be reset to a checkpoint recorded by 'stg \
+             checkpoint' using '--to'. Checkpoint entries in the stack log are not \
+             counted as operations to undo.",
         )
         .arg(
             Arg::new("number")
@@ -46,6 +51,14 @@ fn make() -> clap::Command {
                         })
                 }),
         )
+        .arg(
+            Arg::new("to")
+                .long("to")
+                .help("Undo back to the checkpoint <name>")
+                .value_name("name")
+                .value_parser(clap::value_parser!(PartialRefName))
+                .conflicts_with("number"),
+        )
         .arg(
             Arg::new("hard")
                 .long("hard")
@@ -57,9 +70,24 @@ fn make() -> clap::Command {
 fn run(matches: &clap::ArgMatches) -> Result<()> {
     let repo = gix::Repository::open()?;
     let stack = Stack::current(&repo, InitializationPolicy::RequireInitialized)?;
+    if let Some(name) = matches.get_one::<PartialRefName>("to") {
+        let stack = stack
+            .setup_transaction()
+            .use_index_and_worktree(true)
+            .allow_bad_head(true)
+            .discard_changes(matches.get_flag("hard"))
+            .with_output_stream(get_color_stdout(matches))
+            .transact(|trans| {
+                let undo_state = trans.stack().get_checkpoint_state(name.as_ref())?;
+                trans.reset_to_state(undo_state)
+            })
+            .execute(&format!("undo --to {name}"))?;
+        return Operation::clear_stale(&stack);
+    }
+
     let undo_steps = matches.get_one::<isize>("number").copied().unwrap_or(1);
 
-    stack
+    let stack = stack
         .setup_transaction()
         .use_index_and_worktree(true)
         .allow_bad_head(true)
@@ -71,7 +99,7 @@ fn run(matches: &clap::ArgMatches) -> Result<()> {
         })
         .execute(&format!("undo {undo_steps}"))?;
 
-    Ok(())
+    Operation::clear_stale(&stack)
 }
 
 pub(super) fn find_undo_state<'repo>(
@@ -95,7 +123,10 @@ pub(super) fn find_undo_state<'repo>(
         }
         let msg = state_commit.message_raw()?;
         let urstate = parse_undo_redo_message(msg);
-        if undo_steps > 0 {
+        if let Some(URState::Checkpoint) = urstate {
+            // Checkpoint entries do not change the stack state and are thus not
+            // counted as steps in either direction.
+        } else if undo_steps > 0 {
             if let Some(URState::Undo(n)) = urstate {
                 undo_steps += n;
             } else {
@@ -119,10 +150,14 @@ pub(super) fn find_undo_state<'repo>(
 enum URState {
     Undo(isize),
     Redo(isize),
+    Checkpoint,
 }
 
 fn parse_undo_redo_message(msg: &BStr) -> Option<URState> {
     let fields: Vec<_> = msg.fields_with(|c| c.is_ascii_whitespace()).collect();
+    if fields.len() == 2 && fields[0] == b"checkpoint" {
+        return Some(URState::Checkpoint);
+    }
     if fields.len() == 2 {
         if let Some(n) = fields[1]
             .to_str()
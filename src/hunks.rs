@@ -0,0 +1,420 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Interactive selection of diff hunks.
+//!
+//! A diff is presented to the user one hunk at a time, in the style of `git add
+//! --patch`, with the user deciding which hunks to select. Hunks may be split into
+//! smaller hunks at the context lines separating their groups of changed lines.
+//!
+//! The selected hunks are assembled into a new diff that may be applied with `git
+//! apply`. Unselected changes within a partially selected hunk are neutralized such
+//! that the assembled hunk still applies to the original (or, when applying in
+//! reverse, the modified) file content.
+
+use std::io::BufRead;
+
+use anyhow::Result;
+use bstr::{BString, ByteSlice};
+use termcolor::WriteColor;
+
+use crate::stupid::parse_diff;
+
+/// Hunks selected from a diff.
+pub(crate) struct HunkSelection {
+    files: Vec<FileSelection>,
+}
+
+/// A single file's portion of a diff along with the selection state of its hunks.
+struct FileSelection {
+    header: BString,
+    hunks: Vec<Hunk>,
+
+    /// Whether a file diff without any hunks, e.g. a binary or mode change, is
+    /// selected.
+    selected: bool,
+}
+
+/// A hunk that may be selected as a whole or by its groups of changed lines.
+struct Hunk {
+    old_start: usize,
+    new_start: usize,
+
+    /// Remainder of the hunk's header line following the line ranges.
+    suffix: BString,
+
+    /// Lines of the hunk, excluding the header line, including line endings.
+    lines: Vec<BString>,
+
+    /// Runs of changed lines, with `lines[start..end]` and whether the run is
+    /// selected.
+    groups: Vec<(usize, usize, bool)>,
+}
+
+/// A unit presented to the user for selection.
+#[derive(Clone, Copy)]
+enum Item {
+    /// A file diff without any hunks.
+    File,
+
+    /// A hunk, with all of its changed lines.
+    Hunk(usize),
+
+    /// A single group of changed lines from a split hunk.
+    Group(usize, usize),
+}
+
+/// User response to a selection prompt.
+enum Response {
+    Yes,
+    No,
+    Quit,
+    All,
+    Done,
+    Split,
+}
+
+impl HunkSelection {
+    /// Determine whether no changes were selected.
+    pub(crate) fn is_empty(&self) -> bool {
+        self.files.iter().all(|file| {
+            !file.selected
+                && file
+                    .hunks
+                    .iter()
+                    .all(|hunk| hunk.groups.iter().all(|(_, _, selected)| !selected))
+        })
+    }
+
+    /// Assemble a diff from the selected hunks.
+    ///
+    /// Unselected changes within partially selected hunks are converted to context
+    /// lines or dropped. When `reverse` is true, the diff is assembled to be applied
+    /// in reverse (i.e. with `git apply -R`) to the modified file content; otherwise
+    /// it is assembled to be applied to the original file content.
+    pub(crate) fn diff(&self, reverse: bool) -> BString {
+        let mut diff = BString::default();
+        for file in &self.files {
+            if file.hunks.is_empty() {
+                if file.selected {
+                    diff.extend_from_slice(&file.header);
+                }
+                continue;
+            }
+            let mut wrote_header = false;
+            for hunk in &file.hunks {
+                if hunk.groups.iter().all(|(_, _, selected)| !selected) {
+                    continue;
+                }
+                if !wrote_header {
+                    diff.extend_from_slice(&file.header);
+                    wrote_header = true;
+                }
+                hunk.write_selected(&mut diff, reverse);
+            }
+        }
+        diff
+    }
+}
+
+impl Hunk {
+    fn new(hunk: &crate::stupid::DiffHunk) -> Self {
+        let mut lines: Vec<BString> = hunk
+            .text
+            .lines_with_terminator()
+            .map(BString::from)
+            .collect();
+        let header_line = lines.remove(0);
+        let suffix = header_line
+            .get(2..)
+            .and_then(|rest| rest.find(b"@@").map(|pos| BString::from(&rest[pos + 2..])))
+            .unwrap_or_else(|| BString::from("\n"));
+
+        let mut groups = Vec::new();
+        let mut group_start: Option<usize> = None;
+        for (i, line) in lines.iter().enumerate() {
+            let is_change = match line.first() {
+                Some(b'-' | b'+') => true,
+                // "\ No newline at end of file" belongs with the preceding line.
+                Some(b'\\') => group_start.is_some(),
+                _ => false,
+            };
+            if is_change {
+                group_start.get_or_insert(i);
+            } else if let Some(start) = group_start.take() {
+                groups.push((start, i, false));
+            }
+        }
+        if let Some(start) = group_start {
+            groups.push((start, lines.len(), false));
+        }
+
+        Self {
+            old_start: hunk.old_start,
+            new_start: hunk.new_start,
+            suffix,
+            lines,
+            groups,
+        }
+    }
+
+    /// Get the range of lines displayed for a group, including surrounding context.
+    fn group_context(&self, group_index: usize) -> (usize, usize) {
+        let start = if group_index == 0 {
+            0
+        } else {
+            self.groups[group_index - 1].1
+        };
+        let end = self
+            .groups
+            .get(group_index + 1)
+            .map_or(self.lines.len(), |(next_start, _, _)| *next_start);
+        (start, end)
+    }
+
+    /// Write the hunk header and lines for a range of the hunk's lines.
+    fn write_display(&self, output: &mut impl WriteColor, start: usize, end: usize) -> Result<()> {
+        let (old_offset, new_offset) = count_lines(&self.lines[..start]);
+        let (old_len, new_len) = count_lines(&self.lines[start..end]);
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_fg(Some(termcolor::Color::Cyan)))?;
+        write!(
+            output,
+            "@@ -{},{old_len} +{},{new_len} @@",
+            self.old_start + old_offset,
+            self.new_start + new_offset,
+        )?;
+        output.reset()?;
+        output.write_all(&self.suffix)?;
+        for line in &self.lines[start..end] {
+            let color = match line.first() {
+                Some(b'-') => Some(termcolor::Color::Red),
+                Some(b'+') => Some(termcolor::Color::Green),
+                _ => None,
+            };
+            if color.is_some() {
+                output.set_color(color_spec.set_fg(color))?;
+            }
+            output.write_all(line)?;
+            if color.is_some() {
+                output.reset()?;
+            }
+        }
+        Ok(())
+    }
+
+    /// Write the hunk to a diff, neutralizing unselected groups of changes.
+    fn write_selected(&self, diff: &mut BString, reverse: bool) {
+        let (neutral, dropped) = if reverse { (b'+', b'-') } else { (b'-', b'+') };
+        let mut lines: Vec<BString> = Vec::with_capacity(self.lines.len());
+        let mut dropping = false;
+        for (i, line) in self.lines.iter().enumerate() {
+            let selected = self
+                .groups
+                .iter()
+                .find(|(start, end, _)| (*start..*end).contains(&i))
+                .map_or(true, |(_, _, selected)| *selected);
+            match line.first() {
+                Some(&c) if !selected && c == neutral => {
+                    let mut context = line.clone();
+                    context[0] = b' ';
+                    lines.push(context);
+                    dropping = false;
+                }
+                Some(&c) if !selected && c == dropped => dropping = true,
+                Some(b'\\') if dropping => {}
+                _ => {
+                    lines.push(line.clone());
+                    dropping = false;
+                }
+            }
+        }
+        let (old_len, new_len) = count_lines(&lines);
+        diff.extend_from_slice(
+            format!(
+                "@@ -{},{old_len} +{},{new_len} @@",
+                self.old_start, self.new_start
+            )
+            .as_bytes(),
+        );
+        diff.extend_from_slice(&self.suffix);
+        for line in lines {
+            diff.extend_from_slice(&line);
+        }
+    }
+}
+
+/// Count the old and new lines represented by a sequence of hunk lines.
+fn count_lines(lines: &[BString]) -> (usize, usize) {
+    let mut old = 0;
+    let mut new = 0;
+    for line in lines {
+        match line.first() {
+            Some(b'-') => old += 1,
+            Some(b'+') => new += 1,
+            Some(b'\\') => {}
+            _ => {
+                old += 1;
+                new += 1;
+            }
+        }
+    }
+    (old, new)
+}
+
+/// Interactively select hunks from a diff.
+///
+/// The `verb` describes what will be done with the selected hunks, e.g. "refresh",
+/// and is used to prompt the user. Responses are read from stdin; end of input is
+/// treated as quitting, leaving any remaining hunks unselected.
+pub(crate) fn select_hunks(
+    diff: &[u8],
+    verb: &str,
+    output: &mut impl WriteColor,
+) -> Result<HunkSelection> {
+    let mut files: Vec<FileSelection> = parse_diff(diff)?
+        .iter()
+        .map(|file| FileSelection {
+            header: file.header.to_owned(),
+            hunks: file.hunks.iter().map(Hunk::new).collect(),
+            selected: false,
+        })
+        .collect();
+
+    let stdin = std::io::stdin();
+    let mut input = stdin.lock();
+
+    'files: for file in files.iter_mut() {
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(color_spec.set_bold(true))?;
+        output.write_all(&file.header)?;
+        output.reset()?;
+
+        let mut items: Vec<Item> = if file.hunks.is_empty() {
+            vec![Item::File]
+        } else {
+            (0..file.hunks.len()).map(Item::Hunk).collect()
+        };
+
+        let mut pos = 0;
+        while pos < items.len() {
+            let item = items[pos];
+            let can_split = matches!(item, Item::Hunk(h) if file.hunks[h].groups.len() > 1);
+            match item {
+                Item::File => {}
+                Item::Hunk(h) => {
+                    let hunk = &file.hunks[h];
+                    hunk.write_display(output, 0, hunk.lines.len())?;
+                }
+                Item::Group(h, g) => {
+                    let hunk = &file.hunks[h];
+                    let (start, end) = hunk.group_context(g);
+                    hunk.write_display(output, start, end)?;
+                }
+            }
+
+            match prompt(
+                output,
+                &mut input,
+                verb,
+                matches!(item, Item::File),
+                can_split,
+            )? {
+                Response::Yes => {
+                    set_selected(file, item);
+                    pos += 1;
+                }
+                Response::No => pos += 1,
+                Response::All => {
+                    for &item in &items[pos..] {
+                        set_selected(file, item);
+                    }
+                    continue 'files;
+                }
+                Response::Done => continue 'files,
+                Response::Quit => break 'files,
+                Response::Split => {
+                    let Item::Hunk(h) = item else {
+                        unreachable!("only hunks may be split")
+                    };
+                    let groups = file.hunks[h].groups.len();
+                    writeln!(output, "Split into {groups} hunks.")?;
+                    items.splice(pos..=pos, (0..groups).map(|g| Item::Group(h, g)));
+                }
+            }
+        }
+    }
+
+    Ok(HunkSelection { files })
+}
+
+fn set_selected(file: &mut FileSelection, item: Item) {
+    match item {
+        Item::File => file.selected = true,
+        Item::Hunk(h) => {
+            for group in file.hunks[h].groups.iter_mut() {
+                group.2 = true;
+            }
+        }
+        Item::Group(h, g) => file.hunks[h].groups[g].2 = true,
+    }
+}
+
+/// Prompt the user until a valid response is given.
+fn prompt(
+    output: &mut impl WriteColor,
+    input: &mut impl BufRead,
+    verb: &str,
+    is_file: bool,
+    can_split: bool,
+) -> Result<Response> {
+    let mut capitalized = verb.to_string();
+    if let Some(first) = capitalized.get_mut(0..1) {
+        first.make_ascii_uppercase();
+    }
+    let noun = if is_file { "change" } else { "hunk" };
+    let choices = if can_split {
+        "y,n,q,a,d,s,?"
+    } else {
+        "y,n,q,a,d,?"
+    };
+
+    loop {
+        let mut color_spec = termcolor::ColorSpec::new();
+        output.set_color(
+            color_spec
+                .set_fg(Some(termcolor::Color::Blue))
+                .set_bold(true),
+        )?;
+        write!(output, "{capitalized} this {noun} [{choices}]? ")?;
+        output.reset()?;
+        output.flush()?;
+
+        let mut line = String::new();
+        if input.read_line(&mut line)? == 0 {
+            writeln!(output)?;
+            return Ok(Response::Quit);
+        }
+        match line.trim() {
+            "y" => return Ok(Response::Yes),
+            "n" => return Ok(Response::No),
+            "q" => return Ok(Response::Quit),
+            "a" => return Ok(Response::All),
+            "d" => return Ok(Response::Done),
+            "s" if can_split => return Ok(Response::Split),
+            _ => {
+                writeln!(
+                    output,
+                    "y - {verb} this {noun}\n\
+                     n - do not {verb} this {noun}\n\
+                     q - quit; do not {verb} this {noun} or any of the remaining ones\n\
+                     a - {verb} this {noun} and all later hunks in the file\n\
+                     d - do not {verb} this {noun} or any of the later hunks in the file"
+                )?;
+                if can_split {
+                    writeln!(output, "s - split the current hunk into smaller hunks")?;
+                }
+                writeln!(output, "? - print help")?;
+            }
+        }
+    }
+}
@@ -23,7 +23,9 @@ use anyhow::{anyhow, Result};
 use bstr::{BString, ByteSlice};
 use clap::ArgMatches;
 
-pub(crate) use self::{args::add_args, interactive::call_editor, parse::parse_name_email};
+pub(crate) use self::{
+    args::add_args, interactive::call_editor, parse::parse_name_email, trailers::parse_trailers,
+};
 use self::{
     description::{DiffBuffer, EditablePatchDescription, EditedPatchDescription},
     interactive::edit_interactive,
@@ -395,7 +397,7 @@ impl<'a, 'repo> EditBuilder<'a, 'repo> {
                 // since in all other contexts, the patch description's diff shows the
                 // changes actually being recorded to the patch.
                 stupid.update_index_refresh()?;
-                stupid.diff_index(old_tree.id)?
+                stupid.diff_index(old_tree.id, None::<&str>, <Option<Vec<OsString>>>::None)?
             };
             let computed_diff = DiffBuffer(diff_buf);
             (Some(computed_diff.clone()), Some(computed_diff))
@@ -1,6 +1,6 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-//! Add trailers to a commit message.
+//! Add and parse trailers of a commit message.
 
 use anyhow::{anyhow, Result};
 use bstr::ByteSlice;
@@ -75,6 +75,27 @@ pub(crate) fn add_trailers<'a, 'b>(
     }
 }
 
+/// Parse the trailers at the end of a commit message.
+///
+/// Each trailer is returned as a key and value pair, e.g. `("Acked-by", "A U Thor
+/// <author@example.com>")`, in the order they appear in the message.
+pub(crate) fn parse_trailers(
+    repo: &gix::Repository,
+    message: &[u8],
+) -> Result<Vec<(String, String)>> {
+    let output = repo.stupid().interpret_trailers_parse(message)?;
+    let output = output
+        .to_str()
+        .map_err(|_| anyhow!("could not decode message trailers"))?;
+    Ok(output
+        .lines()
+        .filter_map(|line| {
+            let (key, value) = line.split_once(':')?;
+            Some((key.trim().to_string(), value.trim().to_string()))
+        })
+        .collect())
+}
+
 #[cfg(test)]
 mod test {
     use clap::Arg;
@@ -120,7 +120,7 @@ fn git_revision_suffix(input: &str) -> nom::IResult<&str, GitRevisionSuffix> {
     )(input)
 }
 
-fn at_braced(input: &str) -> nom::IResult<&str, &str> {
+pub(in super::super) fn at_braced(input: &str) -> nom::IResult<&str, &str> {
     preceded(the_char('@'), braced)(input)
 }
 
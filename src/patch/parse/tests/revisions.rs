@@ -6,7 +6,7 @@ use super::{name, offsets};
 use crate::{
     branchloc::BranchLocator,
     patch::{
-        parse::{branch_locator, single_revision_spec, tilde_number},
+        parse::{at_braced, branch_locator, single_revision_spec, tilde_number},
         GitRevisionSuffix, PatchId, PatchLikeSpec, PatchLocator, SingleRevisionSpec,
     },
     wrap::PartialRefName,
@@ -26,6 +26,17 @@ fn tilde_stuff() {
     assert!(tilde_number("-1").is_err());
 }
 
+#[test]
+fn at_braced_stuff() {
+    assert_eq!(at_braced("@{2}"), Ok(("", "2")));
+    assert_eq!(at_braced("@{2}^"), Ok(("^", "2")));
+    assert_eq!(at_braced("@{1 week ago}~2"), Ok(("~2", "1 week ago")));
+    assert_eq!(at_braced("@{a\\}b}"), Ok(("", "a\\}b")));
+    assert!(at_braced("^{2}").is_err());
+    assert!(at_braced("@2").is_err());
+    assert!(at_braced("").is_err());
+}
+
 #[test]
 fn single_specs() {
     assert_eq!(
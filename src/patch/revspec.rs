@@ -11,6 +11,10 @@
 //! - Names of patches in the current stack may be specified. E.g. a specification of
 //!   `patch` would refer to the patch `patch`'s commit. This is equivalent to
 //!   specifying `refs/stacks/<branch>/patch`.
+//! - Earlier versions of a patch, as recorded in the stack log, may be specified with
+//!   an `@{<n>}` or `@{<date>}` suffix. E.g. `patch@{2}` refers to the patch's commit
+//!   two versions ago and `patch@{2026-09-01}` refers to the patch's commit as of
+//!   that date.
 
 use std::{rc::Rc, str::FromStr};
 
@@ -21,8 +25,8 @@ use super::{
     SingleRevisionSpec, StGitBoundaryRevisions, StGitRevision,
 };
 use crate::{
-    ext::RepositoryExtended,
-    stack::{InitializationPolicy, Stack, StackAccess, StackStateAccess},
+    ext::{RepositoryExtended, TimeExtended},
+    stack::{patch_versions, InitializationPolicy, Stack, StackAccess, StackStateAccess},
 };
 
 /// StGit revision specification error variants.
@@ -217,11 +221,11 @@ impl PatchLikeSpec {
         repo: &'repo gix::Repository,
         stack: &'a impl StackAccess<'repo>,
     ) -> Result<StGitRevision<'repo>> {
-        let rev = self.patch_loc.resolve_revision(stack)?;
-        if self.suffix.as_ref().is_empty() {
+        let (rev, suffix) = self.resolve_version(repo, stack)?;
+        if suffix.is_empty() {
             Ok(rev)
         } else {
-            let spec = format!("{}{}", rev.commit.id, self.suffix.as_ref());
+            let spec = format!("{}{suffix}", rev.commit.id);
             let object = repo.rev_parse_single_ex(&spec)?.object()?;
             let commit = object.peel_tags_to_end()?.try_into_commit()?;
             Ok(StGitRevision {
@@ -237,14 +241,75 @@ impl PatchLikeSpec {
         repo: &'repo gix::Repository,
         stack: &'a impl StackAccess<'repo>,
     ) -> Result<gix::Object<'repo>> {
-        let rev = self.patch_loc.resolve_revision(stack)?;
-        if self.suffix.as_ref().is_empty() {
+        let (rev, suffix) = self.resolve_version(repo, stack)?;
+        if suffix.is_empty() {
             Ok(rev.commit.id().object()?)
         } else {
-            let spec = format!("{}{}", rev.commit.id, self.suffix.as_ref());
+            let spec = format!("{}{suffix}", rev.commit.id);
             Ok(repo.rev_parse_single_ex(&spec)?.object()?)
         }
     }
+
+    /// Resolve the patch locator, selecting an earlier version of the patch when the
+    /// suffix begins with `@{<n>}` or `@{<date>}`.
+    ///
+    /// The remainder of the suffix, to be interpreted as a git revision suffix, is
+    /// returned along with the resolved revision.
+    fn resolve_version<'a, 'repo>(
+        &'a self,
+        repo: &'repo gix::Repository,
+        stack: &'a impl StackAccess<'repo>,
+    ) -> Result<(StGitRevision<'repo>, &'a str)> {
+        let rev = self.patch_loc.resolve_revision(stack)?;
+        let suffix = self.suffix.as_ref();
+        let Ok((rest, selector)) = super::parse::at_braced(suffix) else {
+            return Ok((rev, suffix));
+        };
+        let Some(patchname) = rev.patchname.as_ref() else {
+            return Err(Error::InvalidRevision(
+                self.to_string(),
+                format!("`@{{{selector}}}` must follow a patch"),
+            )
+            .into());
+        };
+
+        let versions = patch_versions(repo, stack, patchname)?;
+
+        let version = if let Ok(n) = selector.parse::<usize>() {
+            versions.iter().rev().nth(n).ok_or_else(|| {
+                anyhow::anyhow!(
+                    "only {} versions of patch `{patchname}` are recorded in the stack log",
+                    versions.len()
+                )
+            })?
+        } else {
+            let time = gix::date::Time::parse_time(selector).map_err(|_| {
+                Error::InvalidRevision(
+                    self.to_string(),
+                    format!("`{selector}` is not a version number or date"),
+                )
+            })?;
+            versions
+                .iter()
+                .rev()
+                .find(|version| version.time.seconds <= time.seconds)
+                .ok_or_else(|| {
+                    anyhow::anyhow!(
+                        "no version of patch `{patchname}` is recorded in the stack log \
+                         as of `{selector}`"
+                    )
+                })?
+        };
+
+        let commit = repo.find_commit(version.commit_id)?;
+        Ok((
+            StGitRevision {
+                patchname: None,
+                commit: Rc::new(commit),
+            },
+            rest,
+        ))
+    }
 }
 
 /// Resolve git-like revision specification.
@@ -3,7 +3,7 @@
 use std::{collections::BTreeMap, rc::Rc, str::FromStr};
 
 use super::super::*;
-use crate::stack::{PatchState, StackStateAccess};
+use crate::stack::{CoverLetter, PatchState, StackStateAccess};
 
 #[derive(Debug, Default)]
 struct DummyStack {
@@ -45,6 +45,10 @@ impl<'repo> StackStateAccess<'repo> for DummyStack {
     fn head(&self) -> &Rc<gix::Commit<'repo>> {
         panic!()
     }
+
+    fn cover(&self) -> Option<&CoverLetter> {
+        None
+    }
 }
 
 impl DummyStack {
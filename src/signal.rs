@@ -1,17 +1,17 @@
 // SPDX-License-Identifier: GPL-2.0-only
 
-use std::sync::atomic::{AtomicBool, Ordering};
+use std::sync::atomic::{AtomicBool, AtomicUsize, Ordering};
 
 use anyhow::{anyhow, Context, Result};
 
 static SIGNALED: AtomicBool = AtomicBool::new(false);
-static CRITICAL: AtomicBool = AtomicBool::new(false);
+static CRITICAL_DEPTH: AtomicUsize = AtomicUsize::new(0);
 const SIGINT_CODE: i32 = 130;
 
 /// Setup signal/event handler for ctrl-c.
 pub(super) fn setup() -> Result<()> {
     ctrlc::set_handler(|| {
-        if SIGNALED.load(Ordering::SeqCst) || !CRITICAL.load(Ordering::SeqCst) {
+        if SIGNALED.load(Ordering::SeqCst) || CRITICAL_DEPTH.load(Ordering::SeqCst) == 0 {
             std::process::exit(SIGINT_CODE);
         } else {
             SIGNALED.store(true, Ordering::SeqCst);
@@ -26,17 +26,20 @@ pub(super) fn setup() -> Result<()> {
 /// critical section will be allowed to complete before exiting the process. If a second
 /// ctrl-c is received, the process will be terminated immediately.
 ///
+/// Critical sections may be nested. A ctrl-c received during a nested critical section
+/// is held-off until the outermost critical section completes.
+///
 /// Returns a result of the inner function when not interrupted, or an error if
 /// interrupted.
 pub(crate) fn critical<F, T>(f: F) -> Result<T>
 where
     F: FnOnce() -> Result<T>,
 {
-    CRITICAL.store(true, Ordering::SeqCst);
+    CRITICAL_DEPTH.fetch_add(1, Ordering::SeqCst);
     let result = f();
-    CRITICAL.store(false, Ordering::SeqCst);
+    let depth = CRITICAL_DEPTH.fetch_sub(1, Ordering::SeqCst) - 1;
 
-    if SIGNALED.load(Ordering::SeqCst) {
+    if depth == 0 && SIGNALED.load(Ordering::SeqCst) {
         // Resetting SIGNALED allows another critical section to be used if the program
         // is allowed to continue after this critical section.
         SIGNALED.store(false, Ordering::SeqCst);
@@ -49,3 +52,8 @@ where
         result
     }
 }
+
+/// Determine whether a ctrl-c signal/event has been held-off by a critical section.
+pub(crate) fn is_signaled() -> bool {
+    SIGNALED.load(Ordering::SeqCst)
+}
@@ -7,7 +7,7 @@ use std::rc::Rc;
 
 use super::{
     iter::{AllPatches, BothPatches},
-    state::PatchState,
+    state::{CoverLetter, PatchState},
 };
 use crate::patch::{LocationConstraint, LocationGroup, PatchName};
 
@@ -79,6 +79,9 @@ pub(crate) trait StackStateAccess<'repo> {
     /// [`crate::stack::Stack::branch_head`].
     fn head(&self) -> &Rc<gix::Commit<'repo>>;
 
+    /// Get the cover letter for the patch series, if any.
+    fn cover(&self) -> Option<&CoverLetter>;
+
     /// Get the commit for the given patch name.
     fn get_patch_commit(&self, patchname: &PatchName) -> &Rc<gix::Commit<'repo>> {
         &self.get_patch(patchname).commit
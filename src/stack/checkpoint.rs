@@ -0,0 +1,109 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Named checkpoints in a stack's state log.
+//!
+//! Each checkpoint is a reference to a stack state commit, i.e. the same kind of commit
+//! referred to by `refs/stacks/<branch>`. Checkpoints are stored as
+//! `refs/checkpoints/<branch>/<name>`. Since checkpoints are references, the stack
+//! states they refer to are retained even if the stack state log is cleared.
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use super::{Stack, StackAccess, StackState};
+use crate::ext::RepositoryExtended;
+
+/// Get the reference name prefix for the checkpoints of the given branch.
+pub(crate) fn get_checkpoint_refname_prefix(branch_name: &str) -> String {
+    format!("refs/checkpoints/{branch_name}/")
+}
+
+/// Get the reference name for a checkpoint of the given branch.
+pub(crate) fn get_checkpoint_refname(branch_name: &str, name: &str) -> String {
+    format!("{}{name}", get_checkpoint_refname_prefix(branch_name))
+}
+
+impl<'repo> Stack<'repo> {
+    /// Get this stack's checkpoints, ordered by name.
+    ///
+    /// Each checkpoint name is paired with the id of its stack state commit.
+    pub(crate) fn get_checkpoints(&self) -> Result<Vec<(String, gix::ObjectId)>> {
+        let prefix = get_checkpoint_refname_prefix(self.get_branch_name());
+        let mut checkpoints = Vec::new();
+        for mut reference in self
+            .repo
+            .references()?
+            .prefixed(prefix.as_str())?
+            .filter_map(Result::ok)
+        {
+            let Some(name) = reference
+                .name()
+                .as_bstr()
+                .strip_prefix(prefix.as_bytes())
+                .and_then(|name| name.to_str().ok())
+                .map(ToString::to_string)
+            else {
+                continue;
+            };
+            checkpoints.push((name, reference.peel_to_id_in_place()?.detach()));
+        }
+        checkpoints.sort();
+        Ok(checkpoints)
+    }
+
+    /// Get the id of the stack state commit of the named checkpoint.
+    pub(crate) fn get_checkpoint(&self, name: &str) -> Result<gix::ObjectId> {
+        let refname = get_checkpoint_refname(self.get_branch_name(), name);
+        Ok(self
+            .repo
+            .try_find_reference(refname.as_str())?
+            .ok_or_else(|| anyhow!("checkpoint `{name}` not found"))?
+            .into_fully_peeled_id()?
+            .detach())
+    }
+
+    /// Get the stack state recorded by the named checkpoint.
+    pub(crate) fn get_checkpoint_state(&self, name: &str) -> Result<StackState<'repo>> {
+        let commit = self.repo.find_commit(self.get_checkpoint(name)?)?;
+        StackState::from_commit(self.repo, &commit)
+    }
+
+    /// Record the stack's current state as a named checkpoint.
+    ///
+    /// A "checkpoint" entry is added to the stack state log and the checkpoint refers
+    /// to that entry's stack state commit. An existing checkpoint with the same name is
+    /// replaced only when `force` is true.
+    pub(crate) fn create_checkpoint(self, name: &str, force: bool) -> Result<Self> {
+        let refname = get_checkpoint_refname(self.get_branch_name(), name);
+        if !force && self.repo.try_find_reference(refname.as_str())?.is_some() {
+            return Err(anyhow!("checkpoint `{name}` already exists"));
+        }
+        let (stack, state_commit_id) = self.append_state_log(&format!("checkpoint {name}"))?;
+        stack.repo.edit_reference(gix::refs::transaction::RefEdit {
+            change: gix::refs::transaction::Change::Update {
+                log: gix::refs::transaction::LogChange {
+                    mode: gix::refs::transaction::RefLog::AndReference,
+                    force_create_reflog: false,
+                    message: format!("checkpoint {name}").into(),
+                },
+                expected: gix::refs::transaction::PreviousValue::Any,
+                new: gix::refs::Target::Peeled(state_commit_id),
+            },
+            name: gix::refs::FullName::try_from(refname)?,
+            deref: false,
+        })?;
+        Ok(stack)
+    }
+
+    /// Delete the named checkpoint.
+    ///
+    /// The stack state log is unaffected.
+    pub(crate) fn delete_checkpoint(&self, name: &str) -> Result<()> {
+        let refname = get_checkpoint_refname(self.get_branch_name(), name);
+        self.repo
+            .try_find_reference(refname.as_str())?
+            .ok_or_else(|| anyhow!("checkpoint `{name}` not found"))?
+            .delete()?;
+        Ok(())
+    }
+}
@@ -0,0 +1,477 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Dependency tracking between patches in a stack.
+//!
+//! A patch depends on another patch when either:
+//!
+//! - The patch modifies lines that were last modified by the other patch, or lines
+//!   adjacent to them. Such overlapping hunks mean that the patch cannot be applied
+//!   without the other patch. These dependencies are found by following the ownership
+//!   of lines through each patch's diff, from the bottom of the stack upward.
+//! - The dependency is explicitly declared in the patch's `depends` metadata, which
+//!   contains a whitespace-separated list of patch names.
+
+use std::collections::{BTreeMap, BTreeSet};
+
+use anyhow::{anyhow, Result};
+use bstr::{BStr, BString};
+use indexmap::IndexSet;
+
+use super::{StackAccess, StackStateAccess};
+use crate::{
+    ext::CommitExtended,
+    patch::PatchName,
+    stupid::{parse_diff, DiffHunk, FileDiff, Stupid, StupidContext},
+};
+
+/// Metadata key used to explicitly declare a patch's dependencies.
+pub(crate) const DEPENDS_META_KEY: &str = "depends";
+
+/// How a dependency between two patches was determined.
+#[derive(Clone, Copy, Debug, PartialEq, Eq, PartialOrd, Ord)]
+pub(crate) enum DependencyKind {
+    /// The patches' hunks overlap.
+    Overlap,
+
+    /// The dependency is declared in the patch's metadata.
+    Declared,
+}
+
+impl std::fmt::Display for DependencyKind {
+    fn fmt(&self, f: &mut std::fmt::Formatter<'_>) -> std::fmt::Result {
+        match self {
+            DependencyKind::Overlap => f.write_str("overlap"),
+            DependencyKind::Declared => f.write_str("declared"),
+        }
+    }
+}
+
+/// Policy for how dependencies are handled when patches are moved, pushed, or popped.
+#[derive(Clone, Copy, Debug, PartialEq, Eq)]
+pub(crate) enum DependencyPolicy {
+    /// Dependencies are not considered.
+    Ignore,
+
+    /// The operation is refused if it would separate a patch from its dependencies.
+    Check,
+
+    /// Dependencies are carried along with the patches being operated on.
+    Carry,
+}
+
+impl std::str::FromStr for DependencyPolicy {
+    type Err = anyhow::Error;
+
+    fn from_str(s: &str) -> Result<Self> {
+        match s {
+            "ignore" => Ok(Self::Ignore),
+            "check" => Ok(Self::Check),
+            "carry" => Ok(Self::Carry),
+            _ => Err(anyhow!(
+                "invalid dependency policy `{s}`; expected \"ignore\", \"check\", or \"carry\""
+            )),
+        }
+    }
+}
+
+/// Dependency graph for the patches of a stack.
+pub(crate) struct Dependencies {
+    /// Direct dependencies of each patch.
+    deps: BTreeMap<PatchName, BTreeMap<PatchName, BTreeSet<DependencyKind>>>,
+
+    /// Visible patches in stack order.
+    order: Vec<PatchName>,
+}
+
+/// Line ownership for a single file.
+///
+/// Each entry maps a run of consecutive lines, starting at a 1-based line number, to
+/// the index of the patch that last modified those lines. Lines not covered by any run
+/// have not been modified by any tracked patch.
+#[derive(Default)]
+struct FileOwners {
+    runs: Vec<(usize, usize, usize)>,
+    binary_owner: Option<usize>,
+}
+
+impl FileOwners {
+    /// Find owners of lines in the inclusive range `lo..=hi`.
+    fn owners(&self, lo: usize, hi: usize, found: &mut BTreeSet<usize>) {
+        for &(start, len, owner) in &self.runs {
+            if start <= hi && lo < start + len {
+                found.insert(owner);
+            }
+        }
+    }
+
+    /// Replace `old_len` lines at `start` with `new_len` lines owned by `owner`.
+    ///
+    /// When `old_len` is zero, the new lines are inserted after line `start`.
+    fn replace(&mut self, start: usize, old_len: usize, new_len: usize, owner: usize) {
+        let (cut_start, cut_end) = if old_len == 0 {
+            (start + 1, start + 1)
+        } else {
+            (start, start + old_len)
+        };
+        let mut runs = Vec::with_capacity(self.runs.len() + 2);
+        for &(run_start, run_len, run_owner) in &self.runs {
+            let run_end = run_start + run_len;
+            if run_end <= cut_start {
+                runs.push((run_start, run_len, run_owner));
+            } else if run_start >= cut_end {
+                runs.push((run_start + new_len - old_len, run_len, run_owner));
+            } else {
+                if run_start < cut_start {
+                    runs.push((run_start, cut_start - run_start, run_owner));
+                }
+                if run_end > cut_end {
+                    runs.push((cut_start + new_len, run_end - cut_end, run_owner));
+                }
+            }
+        }
+        if new_len > 0 {
+            runs.push((cut_start, new_len, owner));
+        }
+        runs.sort_unstable();
+        self.runs = runs;
+    }
+
+    /// Determine whether every line in the inclusive range `lo..=hi` has an owner.
+    fn is_covered(&self, lo: usize, hi: usize) -> bool {
+        let mut next = lo;
+        for &(start, len, _) in &self.runs {
+            if start > next {
+                break;
+            } else if start + len > next {
+                next = start + len;
+            }
+            if next > hi {
+                return true;
+            }
+        }
+        false
+    }
+}
+
+/// Line ownership for the files modified by a sequence of patches.
+///
+/// Patches' diffs are recorded in order, from the bottom of the stack upward, such
+/// that the line numbers in each subsequent diff correspond to the lines resulting
+/// from the previously recorded diffs.
+#[derive(Default)]
+pub(crate) struct LineOwners {
+    files: BTreeMap<BString, FileOwners>,
+}
+
+impl LineOwners {
+    /// Record a patch's diff, with the patch identified by `owner`.
+    ///
+    /// The owners of lines modified by the diff, or adjacent to lines modified by the
+    /// diff, are returned. Binary files are treated as a single unit.
+    pub(crate) fn record(&mut self, diff: &[FileDiff], owner: usize) -> BTreeSet<usize> {
+        let mut found: BTreeSet<usize> = BTreeSet::new();
+        for file in diff {
+            let file_owners = self.files.entry(file.path.to_owned()).or_default();
+            found.extend(file_owners.binary_owner);
+            if file.binary {
+                file_owners.owners(0, usize::MAX, &mut found);
+                file_owners.binary_owner = Some(owner);
+                continue;
+            }
+            for hunk in file.hunks.iter().rev() {
+                let (lo, hi) = if hunk.old_len == 0 {
+                    (hunk.old_start, hunk.old_start + 1)
+                } else {
+                    (
+                        hunk.old_start.saturating_sub(1),
+                        hunk.old_start + hunk.old_len,
+                    )
+                };
+                file_owners.owners(lo, hi, &mut found);
+            }
+            for hunk in file.hunks.iter().rev() {
+                file_owners.replace(hunk.old_start, hunk.old_len, hunk.new_len, owner);
+            }
+        }
+        found.remove(&owner);
+        found
+    }
+
+    /// Find the single owner that a hunk from a subsequent diff belongs with.
+    ///
+    /// A hunk that modifies or removes lines belongs with the owner of those lines if
+    /// all of the lines have the same owner. A hunk that only adds lines belongs with
+    /// the owner of the surrounding lines, if there is exactly one such owner. `None`
+    /// is returned if the hunk does not belong with exactly one owner.
+    pub(crate) fn owner_of_hunk(&self, path: &BStr, hunk: &DiffHunk) -> Option<usize> {
+        let file_owners = self.files.get(path)?;
+        if file_owners.binary_owner.is_some() {
+            return None;
+        }
+        let (lo, hi) = if hunk.old_len == 0 {
+            (hunk.old_start, hunk.old_start + 1)
+        } else {
+            let hi = hunk.old_start + hunk.old_len - 1;
+            if !file_owners.is_covered(hunk.old_start, hi) {
+                return None;
+            }
+            (hunk.old_start, hi)
+        };
+        let mut found: BTreeSet<usize> = BTreeSet::new();
+        file_owners.owners(lo, hi, &mut found);
+        if found.len() == 1 {
+            found.pop_first()
+        } else {
+            None
+        }
+    }
+}
+
+/// Get a patch's diff with zero lines of context, as is suitable for [`LineOwners`].
+pub(crate) fn patch_diff(stupid: &StupidContext, commit: &gix::Commit) -> Result<BString> {
+    let parent = commit.get_parent_commit()?;
+    stupid.diff_tree_patch(
+        parent.tree_id()?.detach(),
+        commit.tree_id()?.detach(),
+        None::<Vec<&str>>,
+        false,
+        ["-U0", "--no-renames", "--no-ext-diff"],
+    )
+}
+
+impl Dependencies {
+    /// Determine dependencies between the visible patches of a stack.
+    ///
+    /// Overlap dependencies are found for the applied and unapplied patches, in stack
+    /// order. Unapplied patches are not necessarily based on the patches below them,
+    /// so their line numbers are assumed to be close enough to those of the preceding
+    /// patches. Declared dependencies are found for all patches.
+    pub(crate) fn compute<'repo>(
+        repo: &'repo gix::Repository,
+        stack: &impl StackAccess<'repo>,
+    ) -> Result<Self> {
+        let stupid = repo.stupid();
+        let order: Vec<PatchName> = stack.applied_and_unapplied().cloned().collect();
+        let mut deps: BTreeMap<PatchName, BTreeMap<PatchName, BTreeSet<DependencyKind>>> =
+            BTreeMap::new();
+
+        let mut owners = LineOwners::default();
+
+        for (index, patchname) in order.iter().enumerate() {
+            let diff = patch_diff(&stupid, stack.get_patch_commit(patchname))?;
+            let found = owners.record(&parse_diff(&diff)?, index);
+            let patch_deps = deps.entry(patchname.clone()).or_default();
+            for owner in found {
+                patch_deps
+                    .entry(order[owner].clone())
+                    .or_default()
+                    .insert(DependencyKind::Overlap);
+            }
+        }
+
+        for patchname in stack.all_patches() {
+            for dep in declared_dependencies(stack, patchname) {
+                if &dep != patchname {
+                    deps.entry(patchname.clone())
+                        .or_default()
+                        .entry(dep)
+                        .or_default()
+                        .insert(DependencyKind::Declared);
+                }
+            }
+        }
+
+        Ok(Self { deps, order })
+    }
+
+    /// Direct dependencies of a patch along with how each was determined.
+    pub(crate) fn direct(
+        &self,
+        patchname: &PatchName,
+    ) -> impl Iterator<Item = (&PatchName, &BTreeSet<DependencyKind>)> {
+        self.deps.get(patchname).into_iter().flatten()
+    }
+
+    /// Direct dependents of a patch; i.e. patches that directly depend on the patch.
+    pub(crate) fn direct_dependents<'a>(
+        &'a self,
+        patchname: &'a PatchName,
+    ) -> impl Iterator<Item = (&'a PatchName, &'a BTreeSet<DependencyKind>)> + 'a {
+        self.deps
+            .iter()
+            .filter_map(move |(pn, deps)| deps.get(patchname).map(|kinds| (pn, kinds)))
+    }
+
+    /// Transitive closure of the dependencies of the given patches.
+    ///
+    /// The given patches are not included unless one depends on another.
+    pub(crate) fn dependencies_of<'a>(
+        &self,
+        patchnames: impl IntoIterator<Item = &'a PatchName>,
+    ) -> IndexSet<PatchName> {
+        let mut found: IndexSet<PatchName> = IndexSet::new();
+        let mut pending: Vec<&PatchName> = patchnames.into_iter().collect();
+        while let Some(pn) = pending.pop() {
+            for (dep, _) in self.direct(pn) {
+                if found.insert(dep.clone()) {
+                    pending.push(dep);
+                }
+            }
+        }
+        found
+    }
+
+    /// Transitive closure of the dependents of the given patches.
+    ///
+    /// The given patches are not included unless one depends on another.
+    pub(crate) fn dependents_of<'a>(
+        &self,
+        patchnames: impl IntoIterator<Item = &'a PatchName>,
+    ) -> IndexSet<PatchName> {
+        let mut found: IndexSet<PatchName> = IndexSet::new();
+        let mut pending: Vec<PatchName> = patchnames.into_iter().cloned().collect();
+        while let Some(pn) = pending.pop() {
+            for (dependent, _) in self.direct_dependents(&pn) {
+                if found.insert(dependent.clone()) {
+                    pending.push(dependent.clone());
+                }
+            }
+        }
+        found
+    }
+
+    /// Order patches such that each patch comes after any of its dependencies that
+    /// are also in `patchnames`.
+    ///
+    /// The existing order is preserved as much as possible by moving dependencies
+    /// earlier.
+    pub(crate) fn order_dependencies_first(&self, patchnames: &[PatchName]) -> Vec<PatchName> {
+        fn place<'a>(
+            deps: &'a Dependencies,
+            pn: &'a PatchName,
+            members: &IndexSet<&'a PatchName>,
+            placed: &mut IndexSet<&'a PatchName>,
+            visiting: &mut BTreeSet<&'a PatchName>,
+        ) {
+            if placed.contains(pn) || !visiting.insert(pn) {
+                return;
+            }
+            let mut direct: Vec<&PatchName> = deps
+                .direct(pn)
+                .map(|(dep, _)| dep)
+                .filter(|dep| members.contains(dep))
+                .collect();
+            direct.sort_by_key(|dep| members.get_index_of(dep));
+            for dep in direct {
+                place(deps, dep, members, placed, visiting);
+            }
+            placed.insert(pn);
+        }
+
+        let members: IndexSet<&PatchName> = patchnames.iter().collect();
+        let mut placed: IndexSet<&PatchName> = IndexSet::with_capacity(members.len());
+        let mut visiting: BTreeSet<&PatchName> = BTreeSet::new();
+        for pn in patchnames {
+            place(self, pn, &members, &mut placed, &mut visiting);
+        }
+        placed.into_iter().cloned().collect()
+    }
+
+    /// Sort patches according to their order in the stack.
+    pub(crate) fn stack_ordered(
+        &self,
+        patchnames: impl IntoIterator<Item = PatchName>,
+    ) -> Vec<PatchName> {
+        let mut patchnames: Vec<PatchName> = patchnames.into_iter().collect();
+        patchnames.sort_by_key(|pn| self.order.iter().position(|opn| opn == pn));
+        patchnames
+    }
+
+    /// Extend `patchnames` with `extra` patches and order the result such that
+    /// dependencies come first.
+    ///
+    /// Extra patches already in `patchnames` are ignored and the remaining extra
+    /// patches are considered in stack order.
+    pub(crate) fn carry(
+        &self,
+        patchnames: &[PatchName],
+        extra: impl IntoIterator<Item = PatchName>,
+    ) -> Vec<PatchName> {
+        let mut combined = patchnames.to_vec();
+        combined
+            .extend(self.stack_ordered(extra.into_iter().filter(|pn| !patchnames.contains(pn))));
+        self.order_dependencies_first(&combined)
+    }
+
+    /// Check that every patch in a proposed applied list has its dependencies applied
+    /// below it.
+    ///
+    /// Unmet dependencies that are also unmet in the `current` applied list are not
+    /// reported, such that only newly introduced problems cause an error. An error
+    /// explaining the first newly unmet dependency is returned.
+    pub(crate) fn check_applied(
+        &self,
+        current: &[PatchName],
+        proposed: &[PatchName],
+    ) -> Result<()> {
+        let is_met = |applied: &[PatchName], pn: &PatchName, dep: &PatchName| {
+            applied
+                .iter()
+                .position(|opn| opn == pn)
+                .map_or(false, |i| applied[..i].contains(dep))
+        };
+
+        for (i, pn) in proposed.iter().enumerate() {
+            for (dep, kinds) in self.direct(pn) {
+                if proposed[..i].contains(dep)
+                    || (current.contains(pn) && !is_met(current, pn, dep))
+                {
+                    continue;
+                }
+                let kinds = kinds
+                    .iter()
+                    .map(ToString::to_string)
+                    .collect::<Vec<_>>()
+                    .join(", ");
+                return Err(if proposed[i + 1..].contains(dep) {
+                    anyhow!(
+                        "patch `{pn}` depends on `{dep}` ({kinds}) but would be applied below it"
+                    )
+                } else {
+                    anyhow!("patch `{pn}` depends on `{dep}` ({kinds}) which would not be applied")
+                });
+            }
+        }
+        Ok(())
+    }
+}
+
+/// Get the explicitly declared dependencies of a patch that exist in the stack.
+pub(crate) fn declared_dependencies<'repo>(
+    stack: &impl StackStateAccess<'repo>,
+    patchname: &PatchName,
+) -> Vec<PatchName> {
+    stack
+        .get_patch(patchname)
+        .meta
+        .get(DEPENDS_META_KEY)
+        .map(|value| {
+            value
+                .split_whitespace()
+                .filter_map(|s| s.parse::<PatchName>().ok())
+                .filter(|pn| stack.has_patch(pn))
+                .collect()
+        })
+        .unwrap_or_default()
+}
+
+/// Make the `depends` metadata value from a list of patch names.
+pub(crate) fn format_declared_dependencies<'a>(
+    patchnames: impl IntoIterator<Item = &'a PatchName>,
+) -> String {
+    patchnames
+        .into_iter()
+        .map(PatchName::as_ref)
+        .collect::<Vec<&str>>()
+        .join(" ")
+}
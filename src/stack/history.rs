@@ -0,0 +1,99 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! History of individual patches recorded in a stack's state log.
+//!
+//! Each stack state commit records the commit of every patch in the stack at that
+//! time. Walking the state log thus yields each version of a patch along with the
+//! operation that produced it.
+
+use std::collections::BTreeMap;
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use super::{serde::RawStackState, StackAccess};
+use crate::{ext::RepositoryExtended, patch::PatchName};
+
+/// A version of a patch recorded in the stack state log.
+pub(crate) struct PatchVersion {
+    /// Name of the patch when this version was recorded.
+    pub(crate) patchname: PatchName,
+
+    /// Id of the patch's commit.
+    pub(crate) commit_id: gix::ObjectId,
+
+    /// Stack log message of the operation that recorded this version.
+    pub(crate) message: String,
+
+    /// Time the operation was performed.
+    pub(crate) time: gix::date::Time,
+}
+
+/// Find the versions of a patch recorded in the stack state log, oldest first.
+///
+/// A new version is recorded whenever the patch's commit changes. Renaming a patch
+/// does not produce a new version; each version retains the name the patch had when
+/// the version was first recorded. The stack log is walked from the current state
+/// back to its oldest state, following the patch across the renames recorded by each
+/// state's operation.
+pub(crate) fn patch_versions<'repo>(
+    repo: &'repo gix::Repository,
+    stack: &impl StackAccess<'repo>,
+    patchname: &PatchName,
+) -> Result<Vec<PatchVersion>> {
+    let mut tracked = patchname.clone();
+    let mut newer_renamed: BTreeMap<PatchName, PatchName> = BTreeMap::new();
+    let mut found: Vec<PatchVersion> = Vec::new();
+
+    let mut next = Some(
+        repo.find_reference(stack.get_stack_refname())?
+            .into_fully_peeled_id()?
+            .detach(),
+    );
+    while let Some(state_id) = next {
+        let commit = repo.find_commit(state_id)?;
+        let mut tree = commit.tree()?;
+        let stack_json = tree
+            .peel_to_entry_by_path("stack.json")?
+            .ok_or_else(|| anyhow!("stack metadata not found in `{state_id}`"))?
+            .object()?;
+        let state = RawStackState::from_stack_json(&stack_json.data)?;
+        next = state.prev;
+
+        if let Some(previous_name) = newer_renamed.get(&tracked) {
+            tracked = previous_name.clone();
+        }
+
+        if let Some(patch_state) = state.patches.get(&tracked) {
+            found.push(PatchVersion {
+                patchname: tracked.clone(),
+                commit_id: patch_state.oid,
+                message: commit
+                    .message_raw()?
+                    .lines()
+                    .next()
+                    .unwrap_or_default()
+                    .to_str_lossy()
+                    .to_string(),
+                time: commit.time()?,
+            });
+        }
+        newer_renamed = state.renamed;
+    }
+
+    let mut versions: Vec<PatchVersion> = Vec::new();
+    for version in found.into_iter().rev() {
+        if versions
+            .last()
+            .map_or(true, |last| last.commit_id != version.commit_id)
+        {
+            versions.push(version);
+        }
+    }
+
+    if versions.is_empty() {
+        Err(anyhow!("patch `{patchname}` not found in the stack log"))
+    } else {
+        Ok(versions)
+    }
+}
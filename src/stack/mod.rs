@@ -2,15 +2,31 @@
 
 //! The StGit stack data structure.
 mod access;
+mod checkpoint;
+mod deps;
+mod history;
 mod iter;
+mod operation;
+mod prune;
 mod serde;
 #[allow(clippy::module_inception)]
 mod stack;
 mod state;
 mod transaction;
 mod upgrade;
+mod version;
 
 pub(crate) use access::{StackAccess, StackStateAccess};
+pub(crate) use checkpoint::{get_checkpoint_refname, get_checkpoint_refname_prefix};
+pub(crate) use deps::{
+    declared_dependencies, format_declared_dependencies, patch_diff, Dependencies, DependencyKind,
+    DependencyPolicy, LineOwners, DEPENDS_META_KEY,
+};
+pub(crate) use history::patch_versions;
+pub(crate) use operation::{ConflictingPatch, Operation, PatchStops, Stop};
+pub(crate) use prune::PrunePolicy;
+pub(crate) use serde::RawStackState;
 pub(crate) use stack::{state_refname_from_branch_name, InitializationPolicy, Stack};
-pub(crate) use state::{PatchState, StackState};
+pub(crate) use state::{CoverLetter, PatchState, SentRecord, StackState};
 pub(crate) use transaction::{Error as TransactionError, StackTransaction};
+pub(crate) use version::{get_version_refname, get_version_refname_prefix, parse_version};
@@ -0,0 +1,186 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Record of multi-step operations interrupted by push conflicts.
+//!
+//! Commands such as `stg push` and `stg rebase` push a sequence of patches. When
+//! pushing one of those patches results in merge conflicts, the operation halts with
+//! the conflicting patch applied and the remaining patches unapplied. The
+//! [`Operation`] record captures what remains to be done such that the operation may
+//! later be continued, skipped past the conflicting patch, or aborted.
+//!
+//! The record is only valid as long as the stack remains in the state it was left in
+//! when the operation halted. Should the stack be changed by other means, e.g. by `stg
+//! undo`, the record is stale and the operation may no longer be resumed.
+//!
+//! The record is stored, per branch, in `<git-dir>/stgit/operation/<branch>`.
+
+use std::path::PathBuf;
+
+use anyhow::{anyhow, Context, Result};
+use serde::{Deserialize, Serialize};
+
+use super::{Stack, StackAccess};
+use crate::patch::PatchName;
+
+/// An in-progress operation that was halted by push conflicts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct Operation {
+    /// Name of the command that started the operation, e.g. "push".
+    pub(crate) command: String,
+
+    /// Stack state commit from before the operation started.
+    ///
+    /// Aborting the operation resets the stack to this state.
+    #[serde(with = "oid_hex")]
+    pub(crate) orig_state: gix::ObjectId,
+
+    /// Stack state commit recorded when the operation halted.
+    ///
+    /// The operation may only be resumed while the stack is in this state.
+    #[serde(with = "oid_hex")]
+    pub(crate) halted_state: gix::ObjectId,
+
+    /// Patch whose push resulted in conflicts.
+    pub(crate) conflicting: Option<ConflictingPatch>,
+
+    /// Patches remaining to be pushed after the conflicting patch.
+    pub(crate) remaining: Vec<PatchName>,
+
+    /// Stops to be performed after pushing the conflicting and remaining patches.
+    pub(crate) stops: Vec<PatchStops>,
+
+    /// Whether to check for patches merged upstream when pushing remaining patches.
+    pub(crate) check_merged: bool,
+
+    /// Whether to set the committer date to the author date when pushing.
+    pub(crate) committer_date_is_author_date: bool,
+
+    /// Whether the operation stashed local changes which need to be popped when the
+    /// operation completes or is aborted.
+    pub(crate) autostash: bool,
+}
+
+/// Instruction which interrupts pushing patches back onto the stack.
+#[derive(Clone, Debug, PartialEq, Eq, Serialize, Deserialize)]
+pub(crate) enum Stop {
+    /// Run a shell command, stopping if it fails.
+    Exec(String),
+
+    /// Stop unconditionally.
+    Break,
+}
+
+/// Stops to be performed once a patch has been pushed.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct PatchStops {
+    pub(crate) patchname: PatchName,
+    pub(crate) stops: Vec<Stop>,
+}
+
+/// A patch whose push resulted in conflicts.
+#[derive(Clone, Debug, Serialize, Deserialize)]
+pub(crate) struct ConflictingPatch {
+    pub(crate) patchname: PatchName,
+
+    /// The patch's commit from before the conflicting push.
+    ///
+    /// Skipping the patch restores this commit.
+    #[serde(with = "oid_hex")]
+    pub(crate) orig_commit_id: gix::ObjectId,
+}
+
+impl Operation {
+    /// Begin a new operation for the given command, starting from the current stack
+    /// state.
+    pub(crate) fn new(stack: &Stack, command: &str) -> Result<Self> {
+        let orig_state = current_state_id(stack)?;
+        Ok(Self {
+            command: command.to_string(),
+            orig_state,
+            halted_state: orig_state,
+            conflicting: None,
+            remaining: Vec::new(),
+            stops: Vec::new(),
+            check_merged: false,
+            committer_date_is_author_date: false,
+            autostash: false,
+        })
+    }
+
+    /// Load the in-progress operation for the stack's branch, if any.
+    pub(crate) fn load(stack: &Stack) -> Result<Option<Self>> {
+        let path = operation_path(stack);
+        match std::fs::read(&path) {
+            Ok(data) => serde_json::from_slice(&data)
+                .with_context(|| format!("reading operation from `{}`", path.display()))
+                .map(Some),
+            Err(e) if e.kind() == std::io::ErrorKind::NotFound => Ok(None),
+            Err(e) => Err(anyhow!("reading `{}`: {e}", path.display())),
+        }
+    }
+
+    /// Save operation as the in-progress operation for the stack's branch.
+    pub(crate) fn save(&self, stack: &Stack) -> Result<()> {
+        let path = operation_path(stack);
+        if let Some(parent) = path.parent() {
+            std::fs::create_dir_all(parent)?;
+        }
+        std::fs::write(&path, serde_json::to_string_pretty(self)?)
+            .with_context(|| format!("writing `{}`", path.display()))
+    }
+
+    /// Remove any in-progress operation record for the stack's branch.
+    pub(crate) fn clear(stack: &Stack) -> Result<()> {
+        match std::fs::remove_file(operation_path(stack)) {
+            Err(e) if e.kind() != std::io::ErrorKind::NotFound => Err(e.into()),
+            _ => Ok(()),
+        }
+    }
+
+    /// Determine whether the stack has changed since the operation halted.
+    pub(crate) fn is_stale(&self, stack: &Stack) -> Result<bool> {
+        Ok(current_state_id(stack)? != self.halted_state)
+    }
+
+    /// Remove the in-progress operation record for the stack's branch if the stack
+    /// has changed since the operation halted.
+    pub(crate) fn clear_stale(stack: &Stack) -> Result<()> {
+        if let Some(operation) = Self::load(stack)? {
+            if operation.is_stale(stack)? {
+                Self::clear(stack)?;
+            }
+        }
+        Ok(())
+    }
+}
+
+fn current_state_id(stack: &Stack) -> Result<gix::ObjectId> {
+    Ok(stack
+        .repo
+        .find_reference(stack.get_stack_refname())?
+        .into_fully_peeled_id()?
+        .detach())
+}
+
+fn operation_path(stack: &Stack) -> PathBuf {
+    stack
+        .repo
+        .git_dir()
+        .join("stgit")
+        .join("operation")
+        .join(stack.get_branch_name())
+}
+
+mod oid_hex {
+    use serde::{Deserialize, Deserializer, Serializer};
+
+    pub(super) fn serialize<S: Serializer>(oid: &gix::ObjectId, s: S) -> Result<S::Ok, S::Error> {
+        s.serialize_str(&oid.to_string())
+    }
+
+    pub(super) fn deserialize<'de, D: Deserializer<'de>>(d: D) -> Result<gix::ObjectId, D::Error> {
+        let hex = String::deserialize(d)?;
+        gix::ObjectId::from_hex(hex.as_bytes())
+            .map_err(|_| serde::de::Error::custom(format!("invalid oid `{hex}`")))
+    }
+}
@@ -0,0 +1,240 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Pruning of a stack's state log.
+//!
+//! Each stack state commit refers to its predecessor via the `prev` field of its
+//! `stack.json`. Pruning removes states from this chain according to a
+//! [`PrunePolicy`]. The retained states that are newer than any removed state are
+//! recommitted with their original messages and signatures such that the `prev`
+//! chain skips the removed states. Checkpoints and version snapshots are always
+//! retained and their references are updated to refer to the recommitted states.
+
+use std::{collections::HashMap, rc::Rc};
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use super::{serde::RawStackState, Operation, Stack, StackAccess, StackState};
+use crate::ext::{RepositoryExtended, TimeExtended};
+
+/// Number of most recent states retained when `stgit.log.keep` is not set.
+const DEFAULT_KEEP: usize = 100;
+
+/// Retention policy for pruning the stack state log.
+///
+/// A state is retained if any of the policy's criteria apply to it.
+pub(crate) struct PrunePolicy {
+    /// Number of most recent states to retain. The current state is always retained.
+    pub(crate) keep: usize,
+
+    /// Retain states recorded at or after this time.
+    pub(crate) keep_since: Option<gix::date::Time>,
+}
+
+impl PrunePolicy {
+    /// Get the policy configured by `stgit.log.keep` and `stgit.log.keep-since`.
+    pub(crate) fn from_config(config: &gix::config::Snapshot) -> Result<Self> {
+        let keep = if let Some(keep) = config.integer("stgit.log.keep") {
+            usize::try_from(keep).map_err(|_| anyhow!("invalid stgit.log.keep `{keep}`"))?
+        } else {
+            DEFAULT_KEEP
+        };
+        let keep_since = config
+            .string("stgit.log.keep-since")
+            .map(|since| gix::date::Time::parse_time(&since.to_str_lossy()))
+            .transpose()
+            .map_err(|e| anyhow!("invalid stgit.log.keep-since: {e}"))?;
+        Ok(Self { keep, keep_since })
+    }
+}
+
+/// A stack state commit in the stack state log.
+struct LogEntry {
+    commit_id: gix::ObjectId,
+    time: gix::date::Time,
+}
+
+impl<'repo> Stack<'repo> {
+    /// Prune the stack state log according to the given retention policy.
+    ///
+    /// The updated stack is returned along with the number of removed states.
+    pub(crate) fn prune_state_log(self, policy: &PrunePolicy) -> Result<(Self, usize)> {
+        if Operation::load(&self)?.is_some() {
+            return Err(anyhow!(
+                "cannot prune the stack log while an operation is in progress"
+            ));
+        }
+
+        let repo = self.repo;
+        let log = self.read_state_log()?;
+
+        let mut protected: Vec<gix::ObjectId> = Vec::new();
+        protected.extend(self.get_checkpoints()?.into_iter().map(|(_, id)| id));
+        protected.extend(self.get_versions()?.into_iter().map(|(_, id)| id));
+
+        let retained: Vec<bool> = log
+            .iter()
+            .enumerate()
+            .map(|(i, entry)| {
+                i == 0
+                    || i < policy.keep
+                    || policy
+                        .keep_since
+                        .map_or(false, |since| entry.time.seconds >= since.seconds)
+                    || protected.contains(&entry.commit_id)
+            })
+            .collect();
+
+        let Some(oldest_removed) = retained.iter().rposition(|retained| !retained) else {
+            return Ok((self, 0));
+        };
+        let num_removed = retained.iter().filter(|retained| !**retained).count();
+
+        // States older than the oldest removed state are unaffected.
+        let mut prev: Option<Rc<gix::Commit<'repo>>> = log[oldest_removed + 1..]
+            .first()
+            .map(|entry| repo.find_commit(entry.commit_id))
+            .transpose()?
+            .map(Rc::new);
+
+        let mut rewritten: HashMap<gix::ObjectId, gix::ObjectId> = HashMap::new();
+        for (entry, _) in log[..oldest_removed]
+            .iter()
+            .zip(&retained)
+            .rev()
+            .filter(|(_, retained)| **retained)
+        {
+            let commit = repo.find_commit(entry.commit_id)?;
+            let commit_ref = commit.decode()?;
+            let message = commit_ref.message.to_str_lossy();
+            let mut state = StackState::from_commit(repo, &commit)?;
+            state.prev = prev;
+            let new_commit_id = state.commit_as(
+                repo,
+                None,
+                &message,
+                commit_ref.author(),
+                commit_ref.committer(),
+            )?;
+            rewritten.insert(entry.commit_id, new_commit_id);
+            prev = Some(Rc::new(repo.find_commit(new_commit_id)?));
+        }
+
+        let new_head_id = rewritten[&log[0].commit_id];
+        let log_change = gix::refs::transaction::LogChange {
+            mode: gix::refs::transaction::RefLog::AndReference,
+            force_create_reflog: false,
+            message: "prune log".into(),
+        };
+        let mut ref_edits = vec![gix::refs::transaction::RefEdit {
+            change: gix::refs::transaction::Change::Update {
+                log: log_change.clone(),
+                expected: gix::refs::transaction::PreviousValue::ExistingMustMatch(
+                    gix::refs::Target::Peeled(log[0].commit_id),
+                ),
+                new: gix::refs::Target::Peeled(new_head_id),
+            },
+            name: gix::refs::FullName::try_from(self.get_stack_refname())?,
+            deref: false,
+        }];
+        let protected_refnames = self
+            .get_checkpoints()?
+            .into_iter()
+            .map(|(name, id)| {
+                (
+                    super::get_checkpoint_refname(self.get_branch_name(), &name),
+                    id,
+                )
+            })
+            .chain(self.get_versions()?.into_iter().map(|(version, id)| {
+                (
+                    super::get_version_refname(self.get_branch_name(), version),
+                    id,
+                )
+            }));
+        for (refname, id) in protected_refnames {
+            if let Some(new_id) = rewritten.get(&id) {
+                ref_edits.push(gix::refs::transaction::RefEdit {
+                    change: gix::refs::transaction::Change::Update {
+                        log: log_change.clone(),
+                        expected: gix::refs::transaction::PreviousValue::ExistingMustMatch(
+                            gix::refs::Target::Peeled(id),
+                        ),
+                        new: gix::refs::Target::Peeled(*new_id),
+                    },
+                    name: gix::refs::FullName::try_from(refname)?,
+                    deref: false,
+                });
+            }
+        }
+        repo.edit_references(ref_edits)?;
+
+        let mut stack = self;
+        stack.state_mut().prev =
+            StackState::from_commit(repo, &repo.find_commit(new_head_id)?)?.prev;
+        Ok((stack, num_removed))
+    }
+
+    /// Prune the stack state log if it has grown beyond `stgit.log.auto` states.
+    ///
+    /// The log is pruned according to the configured [`PrunePolicy`]. Since pruning
+    /// retains at least `stgit.log.keep` states, the log is only pruned once it has
+    /// more than the greater of `stgit.log.auto` and `stgit.log.keep` states.
+    /// Automatic pruning is disabled when `stgit.log.auto` is not set or is 0, and is
+    /// skipped while an operation is in progress.
+    pub(crate) fn auto_prune_state_log(self) -> Result<Self> {
+        let config = self.repo.config_snapshot();
+        let auto = match config.integer("stgit.log.auto") {
+            Some(auto) if auto > 0 => auto as usize,
+            _ => return Ok(self),
+        };
+        let policy = PrunePolicy::from_config(&config)?;
+        if !self.state_log_exceeds(auto.max(policy.keep))? || Operation::load(&self)?.is_some() {
+            return Ok(self);
+        }
+        let (stack, _) = self.prune_state_log(&policy)?;
+        Ok(stack)
+    }
+
+    /// Determine whether the stack state log has more than `count` states.
+    ///
+    /// The first parent of each stack state commit is a simplified commit whose
+    /// first-parent history has one commit per state in the log. Walking that history
+    /// avoids reading each state's `stack.json`.
+    fn state_log_exceeds(&self, count: usize) -> Result<bool> {
+        let state_commit = self
+            .repo
+            .find_reference(self.get_stack_refname())?
+            .into_fully_peeled_id()?
+            .object()?
+            .try_into_commit()?;
+        let Some(simplified_id) = state_commit.parent_ids().next() else {
+            return Ok(false);
+        };
+        let mut walk = simplified_id.ancestors().first_parent_only().all()?;
+        Ok(walk.nth(count).transpose()?.is_some())
+    }
+
+    /// Read the stack state log, newest first.
+    fn read_state_log(&self) -> Result<Vec<LogEntry>> {
+        let mut log = Vec::new();
+        let mut next = Some(
+            self.repo
+                .find_reference(self.get_stack_refname())?
+                .into_fully_peeled_id()?
+                .detach(),
+        );
+        while let Some(commit_id) = next {
+            let commit = self.repo.find_commit(commit_id)?;
+            let time = commit.time()?;
+            let mut tree = commit.tree()?;
+            let stack_json = tree
+                .peel_to_entry_by_path("stack.json")?
+                .ok_or_else(|| anyhow!("stack metadata not found in `{commit_id}`"))?
+                .object()?;
+            next = RawStackState::from_stack_json(&stack_json.data)?.prev;
+            log.push(LogEntry { commit_id, time });
+        }
+        Ok(log)
+    }
+}
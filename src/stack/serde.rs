@@ -6,7 +6,8 @@ use std::collections::BTreeMap;
 
 use anyhow::{Context, Result};
 
-use crate::patch::PatchName;
+use super::state::{CoverLetter, SentRecord};
+use crate::{ext::TimeExtended, patch::PatchName};
 
 /// Raw state deserialization representation.
 ///
@@ -18,12 +19,23 @@ pub(crate) struct RawStackState {
     pub unapplied: Vec<PatchName>,
     pub hidden: Vec<PatchName>,
     pub patches: BTreeMap<PatchName, RawPatchState>,
+    pub cover: Option<CoverLetter>,
+
+    /// Patches renamed by the operation that recorded this state, mapping each new
+    /// patch name to the patch's previous name.
+    pub renamed: BTreeMap<PatchName, PatchName>,
 }
 
 /// Raw patch state representation.
 pub(crate) struct RawPatchState {
     /// The commit id of the patch.
     pub oid: gix::ObjectId,
+
+    /// User-defined key/value metadata associated with the patch.
+    pub meta: BTreeMap<String, String>,
+
+    /// Each time the patch was sent by email, oldest first.
+    pub sent: Vec<SentRecord>,
 }
 
 impl RawStackState {
@@ -50,11 +62,29 @@ impl<'de> serde::Deserialize<'de> for RawStackState {
             pub unapplied: Vec<PatchName>,
             pub hidden: Vec<PatchName>,
             pub patches: BTreeMap<PatchName, DeserPatchState>,
+            #[serde(default)]
+            pub cover: Option<CoverLetter>,
+            #[serde(default)]
+            pub renamed: BTreeMap<PatchName, PatchName>,
         }
 
         #[derive(serde::Deserialize)]
         struct DeserPatchState {
             pub oid: String,
+            #[serde(default)]
+            pub meta: BTreeMap<String, String>,
+            #[serde(default)]
+            pub sent: Vec<DeserSentRecord>,
+        }
+
+        #[derive(serde::Deserialize)]
+        struct DeserSentRecord {
+            pub oid: String,
+            #[serde(rename = "message-id")]
+            pub message_id: String,
+            pub date: String,
+            #[serde(default)]
+            pub version: Option<u32>,
         }
 
         let ds = DeserState::deserialize(deserializer)?;
@@ -90,7 +120,36 @@ impl<'de> serde::Deserialize<'de> for RawStackState {
                     patchname, &raw_patch.oid
                 ))
             })?;
-            patches.insert(patchname, RawPatchState { oid });
+            let mut sent = Vec::with_capacity(raw_patch.sent.len());
+            for raw_record in raw_patch.sent {
+                let commit_id =
+                    gix::ObjectId::from_hex(raw_record.oid.as_bytes()).map_err(|_| {
+                        D::Error::custom(format!(
+                            "invalid sent oid for patch `{patchname}`: '{}'",
+                            &raw_record.oid
+                        ))
+                    })?;
+                let time = gix::date::Time::parse_time(&raw_record.date).map_err(|_| {
+                    D::Error::custom(format!(
+                        "invalid sent date for patch `{patchname}`: '{}'",
+                        &raw_record.date
+                    ))
+                })?;
+                sent.push(SentRecord {
+                    commit_id,
+                    message_id: raw_record.message_id,
+                    time,
+                    version: raw_record.version,
+                });
+            }
+            patches.insert(
+                patchname,
+                RawPatchState {
+                    oid,
+                    meta: raw_patch.meta,
+                    sent,
+                },
+            );
         }
 
         Ok(RawStackState {
@@ -100,6 +159,8 @@ impl<'de> serde::Deserialize<'de> for RawStackState {
             unapplied: ds.unapplied,
             hidden: ds.hidden,
             patches,
+            cover: ds.cover,
+            renamed: ds.renamed,
         })
     }
 }
@@ -117,12 +178,30 @@ impl serde::Serialize for super::state::StackState<'_> {
             pub applied: &'a Vec<PatchName>,
             pub unapplied: &'a Vec<PatchName>,
             pub hidden: &'a Vec<PatchName>,
-            pub patches: BTreeMap<&'a PatchName, SerializablePatchState>,
+            pub patches: BTreeMap<&'a PatchName, SerializablePatchState<'a>>,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub cover: Option<&'a CoverLetter>,
+            #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+            pub renamed: &'a BTreeMap<PatchName, PatchName>,
+        }
+
+        #[derive(serde::Serialize)]
+        struct SerializablePatchState<'a> {
+            pub oid: String,
+            #[serde(skip_serializing_if = "BTreeMap::is_empty")]
+            pub meta: &'a BTreeMap<String, String>,
+            #[serde(skip_serializing_if = "Vec::is_empty")]
+            pub sent: Vec<SerializableSentRecord<'a>>,
         }
 
         #[derive(serde::Serialize)]
-        struct SerializablePatchState {
+        struct SerializableSentRecord<'a> {
             pub oid: String,
+            #[serde(rename = "message-id")]
+            pub message_id: &'a str,
+            pub date: String,
+            #[serde(skip_serializing_if = "Option::is_none")]
+            pub version: Option<u32>,
         }
 
         let prev: Option<String> = self.prev.as_ref().map(|commit| commit.id().to_string());
@@ -133,6 +212,17 @@ impl serde::Serialize for super::state::StackState<'_> {
                 patchname,
                 SerializablePatchState {
                     oid: patch_state.commit.id().to_string(),
+                    meta: &patch_state.meta,
+                    sent: patch_state
+                        .sent
+                        .iter()
+                        .map(|record| SerializableSentRecord {
+                            oid: record.commit_id.to_string(),
+                            message_id: &record.message_id,
+                            date: record.time.format(gix::date::time::format::RAW),
+                            version: record.version,
+                        })
+                        .collect(),
                 },
             );
         }
@@ -145,6 +235,8 @@ impl serde::Serialize for super::state::StackState<'_> {
             unapplied: &self.unapplied,
             hidden: &self.hidden,
             patches,
+            cover: self.cover.as_ref(),
+            renamed: &self.renamed,
         };
 
         ss.serialize(serializer)
@@ -8,8 +8,10 @@ use anyhow::{anyhow, Result};
 use bstr::ByteSlice;
 
 use super::{
-    state::StackState, transaction::TransactionBuilder, upgrade::stack_upgrade, PatchState,
-    StackAccess, StackStateAccess,
+    state::{CoverLetter, SentRecord, StackState},
+    transaction::TransactionBuilder,
+    upgrade::stack_upgrade,
+    Operation, PatchState, StackAccess, StackStateAccess,
 };
 use crate::{
     branchloc::BranchLocator,
@@ -59,13 +61,16 @@ pub(crate) enum InitializationPolicy {
 impl<'repo> Stack<'repo> {
     /// Remove StGit stack state from the repository.
     ///
-    /// This removes the reference to the stack state, i.e. `refs/stacks/<name>`, and
-    /// references to the stacks patches found in `refs/patches/<name>/`. StGit specific
+    /// This removes the reference to the stack state, i.e. `refs/stacks/<name>`,
+    /// references to the stacks patches found in `refs/patches/<name>/`, the stack's
+    /// version snapshots found in `refs/stacks/<name>.versions/`, and the stack's checkpoints
+    /// found in `refs/checkpoints/<name>/`. StGit specific
     /// configuration associated with the stack is also removed from the config.
     ///
     /// N.B. stack and patch commits that become unreferenced are subject to git's
     /// normal periodic garbage collection.
     pub(crate) fn deinitialize(self) -> Result<()> {
+        Operation::clear(&self)?;
         let Self {
             repo,
             branch_name,
@@ -74,18 +79,20 @@ impl<'repo> Stack<'repo> {
         } = self;
         let state_ref = repo.find_reference(&stack_refname)?;
         let patch_ref_prefix = get_patch_refname(&branch_name, "");
-        for patch_reference in
-            repo.references()?
-                .all()?
-                .filter_map(Result::ok)
-                .filter(|reference| {
-                    reference
-                        .name()
-                        .as_bstr()
-                        .starts_with(patch_ref_prefix.as_bytes())
-                })
+        let version_ref_prefix = super::version::get_version_refname_prefix(&branch_name);
+        let checkpoint_ref_prefix = super::checkpoint::get_checkpoint_refname_prefix(&branch_name);
+        for reference in repo
+            .references()?
+            .all()?
+            .filter_map(Result::ok)
+            .filter(|reference| {
+                let name = reference.name().as_bstr();
+                name.starts_with(patch_ref_prefix.as_bytes())
+                    || name.starts_with(version_ref_prefix.as_bytes())
+                    || name.starts_with(checkpoint_ref_prefix.as_bytes())
+            })
         {
-            patch_reference.delete()?;
+            reference.delete()?;
         }
         state_ref.delete()?;
 
@@ -336,6 +343,43 @@ impl<'repo> Stack<'repo> {
         Ok(Self { state, ..self })
     }
 
+    /// Append records to the sent history of patches and commit the updated state.
+    ///
+    /// Only the stack state is changed; patch commits, the branch, the index, and the
+    /// work tree are left as-is.
+    pub(crate) fn record_sent(
+        mut self,
+        records: Vec<(PatchName, SentRecord)>,
+        message: &str,
+    ) -> Result<Self> {
+        for (patchname, record) in records {
+            self.state
+                .patches
+                .get_mut(&patchname)
+                .expect("recorded patch exists in stack")
+                .sent
+                .push(record);
+        }
+        let (stack, _) = self.append_state_log(message)?;
+        Ok(stack)
+    }
+
+    /// Commit the stack's current state as a new entry in the stack state log.
+    ///
+    /// The id of the new stack state commit is returned along with the updated stack.
+    pub(super) fn append_state_log(self, message: &str) -> Result<(Self, gix::ObjectId)> {
+        let prev_state_commit = self
+            .repo
+            .find_reference(&self.stack_refname)?
+            .into_fully_peeled_id()?
+            .object()?
+            .try_into_commit()?;
+        let head = self.state.head.clone();
+        let state = self.state.advance_head(head, Rc::new(prev_state_commit));
+        let state_commit_id = state.commit(self.repo, Some(&self.stack_refname), message)?;
+        Ok((Self { state, ..self }, state_commit_id))
+    }
+
     /// Start a transaction to modify the stack.
     pub(crate) fn setup_transaction(self) -> TransactionBuilder<'repo> {
         assert!(
@@ -427,6 +471,10 @@ impl<'repo> StackStateAccess<'repo> for Stack<'repo> {
     fn head(&self) -> &Rc<gix::Commit<'repo>> {
         self.state.head()
     }
+
+    fn cover(&self) -> Option<&CoverLetter> {
+        self.state.cover()
+    }
 }
 
 /// Get reference name for StGit stack state for the given branch name.
@@ -45,14 +45,78 @@ pub(crate) struct StackState<'repo> {
 
     /// Mapping of patch names to their state.
     pub(super) patches: BTreeMap<PatchName, PatchState<'repo>>,
+
+    /// Cover letter for the patch series, if any.
+    pub(super) cover: Option<CoverLetter>,
+
+    /// Patches renamed by the operation that recorded this state, mapping each new
+    /// patch name to the patch's previous name.
+    pub(super) renamed: BTreeMap<PatchName, PatchName>,
 }
 
 /// State associated with a patch.
 ///
-/// Currently the only state is a commit object.
+/// Each patch has a commit object, an optional set of user-defined key/value
+/// metadata, and a history of when the patch was sent by email. The metadata and sent
+/// history are recorded in the stack state and are thus subject to `stg undo`, `stg
+/// redo`, and `stg log`.
 #[derive(Clone, Debug)]
 pub(crate) struct PatchState<'repo> {
     pub(crate) commit: Rc<gix::Commit<'repo>>,
+    pub(crate) meta: BTreeMap<String, String>,
+
+    /// Each time the patch was sent by email, oldest first.
+    pub(crate) sent: Vec<SentRecord>,
+}
+
+/// Record of a patch having been sent by email.
+#[derive(Clone, Debug, PartialEq, Eq)]
+pub(crate) struct SentRecord {
+    /// Id of the patch's commit as it was sent.
+    pub(crate) commit_id: gix::ObjectId,
+
+    /// Message id of the patch's email.
+    pub(crate) message_id: String,
+
+    /// Time the email was sent.
+    pub(crate) time: gix::date::Time,
+
+    /// Version of the patch series the patch was sent as, if known.
+    pub(crate) version: Option<u32>,
+}
+
+/// Cover letter for the stack's patch series.
+///
+/// The cover letter is recorded in the stack state and is thus versioned along with
+/// the patches, including in the series version snapshots.
+#[derive(Clone, Debug, Default, PartialEq, Eq, serde::Serialize, serde::Deserialize)]
+pub(crate) struct CoverLetter {
+    /// Title of the patch series, used as the cover letter's subject.
+    pub(crate) title: String,
+
+    /// Description of the patch series.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) body: String,
+
+    /// Description of changes since the previous version of the patch series.
+    #[serde(default, skip_serializing_if = "String::is_empty")]
+    pub(crate) changes: String,
+}
+
+impl<'repo> PatchState<'repo> {
+    /// Create patch state for the given commit with empty metadata.
+    pub(crate) fn new(commit: Rc<gix::Commit<'repo>>) -> Self {
+        Self {
+            commit,
+            meta: BTreeMap::new(),
+            sent: Vec::new(),
+        }
+    }
+
+    /// Get the most recent record of the patch being sent, if any.
+    pub(crate) fn last_sent(&self) -> Option<&SentRecord> {
+        self.sent.last()
+    }
 }
 
 impl<'repo> StackStateAccess<'repo> for StackState<'repo> {
@@ -87,6 +151,10 @@ impl<'repo> StackStateAccess<'repo> for StackState<'repo> {
     fn head(&self) -> &Rc<gix::Commit<'repo>> {
         &self.head
     }
+
+    fn cover(&self) -> Option<&CoverLetter> {
+        self.cover.as_ref()
+    }
 }
 
 /// Maximum number of parents a stack state commit is allowed before parent
@@ -103,6 +171,8 @@ impl<'repo> StackState<'repo> {
             unapplied: vec![],
             hidden: vec![],
             patches: BTreeMap::new(),
+            cover: None,
+            renamed: BTreeMap::new(),
         }
     }
 
@@ -143,6 +213,8 @@ impl<'repo> StackState<'repo> {
                 patchname,
                 PatchState {
                     commit: Rc::new(commit),
+                    meta: raw_state.meta,
+                    sent: raw_state.sent,
                 },
             );
         }
@@ -157,6 +229,8 @@ impl<'repo> StackState<'repo> {
             unapplied: raw_state.unapplied,
             hidden: raw_state.hidden,
             patches,
+            cover: raw_state.cover,
+            renamed: raw_state.renamed,
         })
     }
 
@@ -183,6 +257,7 @@ impl<'repo> StackState<'repo> {
         Self {
             prev: Some(prev_state),
             head: new_head,
+            renamed: BTreeMap::new(),
             ..self
         }
     }
@@ -200,6 +275,23 @@ impl<'repo> StackState<'repo> {
         repo: &'repo gix::Repository,
         update_ref: Option<&str>,
         message: &str,
+    ) -> Result<gix::ObjectId> {
+        let author = repo.get_author()?;
+        let committer = repo.get_committer()?;
+        self.commit_as(repo, update_ref, message, author, committer)
+    }
+
+    /// Commit stack state to repository using the given author and committer.
+    ///
+    /// This is used to recreate existing stack state commits, e.g. when pruning the
+    /// stack state log, such that their original dates are retained.
+    pub(super) fn commit_as(
+        &self,
+        repo: &'repo gix::Repository,
+        update_ref: Option<&str>,
+        message: &str,
+        author: gix::actor::SignatureRef<'_>,
+        committer: gix::actor::SignatureRef<'_>,
     ) -> Result<gix::ObjectId> {
         let (state_tree_id, prev_state) = if let Some(prev_commit) = self.prev.as_ref() {
             let prev_state = Self::from_tree(repo, prev_commit.tree()?)?;
@@ -209,8 +301,6 @@ impl<'repo> StackState<'repo> {
             (self.make_tree(repo, None)?, None)
         };
         let config = repo.config_snapshot();
-        let committer = repo.get_committer()?;
-        let author = repo.get_author()?;
 
         let simplified_parents: Vec<gix::ObjectId> = match &self.prev {
             Some(prev_commit) => {
@@ -386,12 +476,16 @@ impl<'repo> StackState<'repo> {
         prev_state: Option<&StackState>,
         prev_patches_tree: &Option<gix::Tree>,
     ) -> Result<gix::ObjectId> {
-        let commit = &self.patches[patchname].commit;
+        let patch_state = &self.patches[patchname];
+        let commit = &patch_state.commit;
         let commit_ref = commit.decode()?;
 
         if let Some(prev_state) = prev_state {
             if let Some(prev_patch) = prev_state.patches.get(patchname) {
-                if prev_patch.commit.id == commit.id {
+                if prev_patch.commit.id == commit.id
+                    && prev_patch.meta == patch_state.meta
+                    && prev_patch.sent == patch_state.sent
+                {
                     if let Some(prev_patches_tree) = prev_patches_tree {
                         let patchname_str: &str = patchname.as_ref();
                         if let Some(prev_patch_entry) = prev_patches_tree
@@ -426,7 +520,22 @@ impl<'repo> StackState<'repo> {
         patch_meta.push_str(">\n");
         patch_meta.push_str("Date:   ");
         patch_meta.push_str(date);
-        patch_meta.push_str("\n\n");
+        patch_meta.push_str("\n");
+        for (key, value) in &patch_state.meta {
+            patch_meta.push_str(format!("Meta:   {key}={value}\n"));
+        }
+        for record in &patch_state.sent {
+            patch_meta.push_str("Sent:   ");
+            if let Some(version) = record.version {
+                patch_meta.push_str(format!("v{version} "));
+            }
+            patch_meta.push_str(format!(
+                "{} {}\n",
+                record.time.format(gix::date::time::format::ISO8601),
+                record.message_id
+            ));
+        }
+        patch_meta.push_str("\n");
         patch_meta.push_str(commit.message_raw_sloppy());
 
         let patch_meta_id = repo.write_blob(patch_meta)?;
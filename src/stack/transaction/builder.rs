@@ -9,7 +9,7 @@ use super::{
     ui::TransactionUserInterface,
     ExecuteContext, StackTransaction,
 };
-use crate::stack::{Stack, StackAccess, StackStateAccess};
+use crate::stack::{Operation, Stack, StackAccess, StackStateAccess};
 
 /// Builder used to setup a stack transaction.
 pub(crate) struct TransactionBuilder<'repo> {
@@ -117,6 +117,18 @@ impl<'repo> TransactionBuilder<'repo> {
         self
     }
 
+    /// Record the operation if a push results in conflicts.
+    ///
+    /// When pushing a patch results in conflicts, the operation is saved along with the
+    /// conflicting patch and the patches that remain to be pushed such that the
+    /// operation may later be continued, skipped, or aborted. Any saved operation is
+    /// cleared when the transaction executes without conflicts.
+    #[must_use]
+    pub(crate) fn record_operation(mut self, operation: Operation) -> Self {
+        self.options.operation = Some(operation);
+        self
+    }
+
     /// Perform stack transaction operations.
     ///
     /// The closure provided to this method may call various methods on the provided
@@ -161,7 +173,10 @@ impl<'repo> TransactionBuilder<'repo> {
             updated_patches: BTreeMap::new(),
             updated_head: None,
             updated_base: None,
+            updated_cover: None,
+            renamed: BTreeMap::new(),
             current_tree_id,
+            halted_push: None,
             error: None,
         };
 
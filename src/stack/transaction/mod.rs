@@ -40,11 +40,11 @@ use self::{
     options::{ConflictMode, TransactionOptions},
     ui::TransactionUserInterface,
 };
-use super::{state::StackState, StackAccess};
+use super::{deps::DEPENDS_META_KEY, state::StackState, StackAccess};
 use crate::{
     ext::{CommitExtended, RepositoryExtended},
     patch::PatchName,
-    stack::{PatchState, Stack, StackStateAccess},
+    stack::{ConflictingPatch, CoverLetter, Operation, PatchState, Stack, StackStateAccess},
     stupid::{Stupid, StupidContext},
     wrap::Branch,
 };
@@ -70,8 +70,18 @@ pub(crate) struct StackTransaction<'repo> {
     updated_patches: BTreeMap<PatchName, Option<PatchState<'repo>>>,
     updated_head: Option<Rc<gix::Commit<'repo>>>,
     updated_base: Option<Rc<gix::Commit<'repo>>>,
+    updated_cover: Option<Option<CoverLetter>>,
+
+    /// Patches renamed by the transaction, mapping each new patch name to the patch's
+    /// name before the transaction.
+    renamed: BTreeMap<PatchName, PatchName>,
 
     current_tree_id: gix::ObjectId,
+
+    /// Conflicting patch and the patches that remained to be pushed when
+    /// [`StackTransaction::push_patches()`] halted due to conflicts.
+    halted_push: Option<(ConflictingPatch, Vec<PatchName>, bool)>,
+
     error: Option<anyhow::Error>,
 }
 
@@ -140,7 +150,10 @@ impl<'repo> ExecuteContext<'repo> {
             unapplied,
             hidden,
             updated_patches,
+            updated_cover,
+            mut renamed,
             current_tree_id,
+            halted_push,
             error,
             ..
         } = transaction;
@@ -204,7 +217,7 @@ impl<'repo> ExecuteContext<'repo> {
             .map_err(|e| rollback(current_tree_id, e))?;
         }
 
-        crate::signal::critical(|| {
+        let state_commit_id = crate::signal::critical(|| {
             // Commit updated stack state
             let conflict_msg;
             let state_reflog_msg = if has_conflicts {
@@ -232,6 +245,11 @@ impl<'repo> ExecuteContext<'repo> {
             state.applied = applied;
             state.unapplied = unapplied;
             state.hidden = hidden;
+            if let Some(cover) = updated_cover {
+                state.cover = cover;
+            }
+            renamed.retain(|patchname, _| state.patches.contains_key(patchname));
+            state.renamed = renamed;
             let state_commit_id = state.commit(repo, None, state_reflog_msg)?;
 
             // Update various refs as a single transaction. This reference transaction is
@@ -302,13 +320,27 @@ impl<'repo> ExecuteContext<'repo> {
                 );
             }
 
-            Ok(())
+            Ok(state_commit_id)
         })
         .map_err(|e| rollback(trans_head_tree_id, e))?;
 
+        if let Some(mut operation) = options.operation {
+            if let Some((conflicting, remaining, check_merged)) = halted_push {
+                operation.halted_state = state_commit_id;
+                operation.conflicting = Some(conflicting);
+                operation.remaining = remaining;
+                operation.check_merged = check_merged;
+                operation.committer_date_is_author_date = options.committer_date_is_author_date;
+                operation.save(&stack)?;
+            } else if error.is_none() {
+                Operation::clear(&stack)?;
+            }
+        }
+
         if let Some(err) = error {
             Err(err)
         } else {
+            let stack = stack.auto_prune_state_log()?;
             if !ui.printed_top() {
                 if let Some(top_patchname) = trans_top_patchname.as_ref() {
                     ui.print_top(top_patchname)?;
@@ -375,6 +407,8 @@ impl<'repo> StackTransaction<'repo> {
             unapplied,
             hidden,
             patches,
+            cover,
+            renamed: _renamed,
         } = state;
         self.updated_base = Some(if let Some(pn) = applied.first() {
             Rc::new(patches[pn].commit.get_parent_commit()?)
@@ -388,6 +422,8 @@ impl<'repo> StackTransaction<'repo> {
         self.applied = applied;
         self.unapplied = unapplied;
         self.hidden = hidden;
+        self.updated_cover = Some(cover);
+        self.renamed.clear();
         Ok(())
     }
 
@@ -419,7 +455,11 @@ impl<'repo> StackTransaction<'repo> {
             .patches
             .iter()
             .filter_map(|(pn, patch_state)| {
-                if self.has_patch(pn) && self.get_patch_commit_id(pn) == patch_state.commit.id {
+                if self.has_patch(pn)
+                    && self.get_patch_commit_id(pn) == patch_state.commit.id
+                    && self.get_patch(pn).meta == patch_state.meta
+                    && self.get_patch(pn).sent == patch_state.sent
+                {
                     Some(pn)
                 } else {
                     None
@@ -474,23 +514,39 @@ impl<'repo> StackTransaction<'repo> {
         commit_id: gix::ObjectId,
     ) -> Result<()> {
         let commit = self.stack.repo.find_commit(commit_id)?;
-        let old_commit = self.get_patch_commit(patchname);
+        let old_patch = self.get_patch(patchname);
         // Failure to copy is okay. The old commit may not have a note to copy.
         self.stack
             .repo
             .stupid()
-            .notes_copy(old_commit.id, commit_id)
+            .notes_copy(old_patch.commit.id, commit_id)
             .ok();
-        self.updated_patches.insert(
-            patchname.clone(),
-            Some(PatchState {
-                commit: Rc::new(commit),
-            }),
-        );
+        let mut patch = old_patch.clone();
+        patch.commit = Rc::new(commit);
+        self.updated_patches.insert(patchname.clone(), Some(patch));
         self.ui.print_updated(patchname, self.applied())?;
         Ok(())
     }
 
+    /// Set or remove the cover letter for the patch series.
+    pub(crate) fn set_cover(&mut self, cover: Option<CoverLetter>) {
+        self.updated_cover = Some(cover);
+    }
+
+    /// Set or remove a metadata value for a patch.
+    ///
+    /// The metadata entry for `key` is removed when `value` is `None`. The patch's
+    /// commit is unaffected.
+    pub(crate) fn set_patch_meta(&mut self, patchname: &PatchName, key: &str, value: Option<&str>) {
+        let mut patch = self.get_patch(patchname).clone();
+        if let Some(value) = value {
+            patch.meta.insert(key.to_string(), value.to_string());
+        } else {
+            patch.meta.remove(key);
+        }
+        self.updated_patches.insert(patchname.clone(), Some(patch));
+    }
+
     /// Add new patch to the top of the stack.
     ///
     /// The commit for the new patch must be parented by the former top commit of the
@@ -499,12 +555,8 @@ impl<'repo> StackTransaction<'repo> {
         let commit = self.stack.repo.find_commit(oid)?;
         assert_eq!(commit.parent_ids().next().unwrap().detach(), self.top().id);
         self.applied.push(patchname.clone());
-        self.updated_patches.insert(
-            patchname.clone(),
-            Some(PatchState {
-                commit: Rc::new(commit),
-            }),
-        );
+        self.updated_patches
+            .insert(patchname.clone(), Some(PatchState::new(Rc::new(commit))));
         self.ui.print_pushed(patchname, PushStatus::New, true)?;
         Ok(())
     }
@@ -520,12 +572,8 @@ impl<'repo> StackTransaction<'repo> {
     ) -> Result<()> {
         let commit = self.stack.repo.find_commit(commit_id)?;
         self.unapplied.insert(insert_pos, patchname.clone());
-        self.updated_patches.insert(
-            patchname.clone(),
-            Some(PatchState {
-                commit: Rc::new(commit),
-            }),
-        );
+        self.updated_patches
+            .insert(patchname.clone(), Some(PatchState::new(Rc::new(commit))));
         self.ui.print_popped(&[patchname.clone()])?;
         Ok(())
     }
@@ -580,12 +628,9 @@ impl<'repo> StackTransaction<'repo> {
             repo.stupid()
                 .notes_copy(patch_commit.id, new_commit_id)
                 .ok();
-            self.updated_patches.insert(
-                patchname.clone(),
-                Some(PatchState {
-                    commit: Rc::new(commit),
-                }),
-            );
+            let mut patch = self.get_patch(patchname).clone();
+            patch.commit = Rc::new(commit);
+            self.updated_patches.insert(patchname.clone(), Some(patch));
 
             PushStatus::Modified
         };
@@ -735,12 +780,8 @@ impl<'repo> StackTransaction<'repo> {
         let mut new_applied: Vec<_> = Vec::with_capacity(self.applied.len());
         for (patchname, commit_id) in patches {
             let commit = self.stack.repo.find_commit(commit_id)?;
-            self.updated_patches.insert(
-                patchname.clone(),
-                Some(PatchState {
-                    commit: Rc::new(commit),
-                }),
-            );
+            self.updated_patches
+                .insert(patchname.clone(), Some(PatchState::new(Rc::new(commit))));
             new_applied.push(patchname.clone());
         }
         self.ui.print_uncommitted(new_applied.as_ref())?;
@@ -830,11 +871,44 @@ impl<'repo> StackTransaction<'repo> {
             panic!("old `{old_patchname}` not found in applied, unapplied, or hidden");
         }
 
-        let patch = self.stack.get_patch(old_patchname).clone();
+        let original_patchname = self
+            .renamed
+            .remove(old_patchname)
+            .unwrap_or_else(|| old_patchname.clone());
+        if &original_patchname != new_patchname {
+            self.renamed
+                .insert(new_patchname.clone(), original_patchname);
+        }
+
+        let patch = self.get_patch(old_patchname).clone();
         self.updated_patches.insert(old_patchname.clone(), None);
         self.updated_patches
             .insert(new_patchname.clone(), Some(patch));
 
+        // Keep declared dependencies on the renamed patch intact.
+        let old_name: &str = old_patchname.as_ref();
+        let new_name: &str = new_patchname.as_ref();
+        let dependents: Vec<(PatchName, String)> = self
+            .all_patches()
+            .filter_map(|pn| {
+                let depends = self.get_patch(pn).meta.get(DEPENDS_META_KEY)?;
+                depends
+                    .split_whitespace()
+                    .any(|dep| dep == old_name)
+                    .then(|| {
+                        let depends = depends
+                            .split_whitespace()
+                            .map(|dep| if dep == old_name { new_name } else { dep })
+                            .collect::<Vec<_>>()
+                            .join(" ");
+                        (pn.clone(), depends)
+                    })
+            })
+            .collect();
+        for (pn, depends) in dependents {
+            self.set_patch_meta(&pn, DEPENDS_META_KEY, Some(&depends));
+        }
+
         self.ui.print_rename(old_patchname, new_patchname)
     }
 
@@ -979,13 +1053,31 @@ impl<'repo> StackTransaction<'repo> {
                 let already_merged = merged
                     .as_ref()
                     .map_or(false, |merged| merged.contains(&patchname));
+                let orig_commit_id = self.get_patch(patchname).commit.id;
                 self.push_patch(
                     patchname,
                     already_merged,
                     is_last,
                     stupid_temp,
                     &mut temp_index_tree_id,
-                )?;
+                )
+                .map_err(|e| {
+                    if let Some(Error::TransactionHalt {
+                        conflicts: true, ..
+                    }) = e.downcast_ref::<Error>()
+                    {
+                        let remaining = patchnames[i + 1..]
+                            .iter()
+                            .map(|pn| pn.as_ref().clone())
+                            .collect();
+                        let conflicting = ConflictingPatch {
+                            patchname: patchname.clone(),
+                            orig_commit_id,
+                        };
+                        self.halted_push = Some((conflicting, remaining, check_merged));
+                    }
+                    e
+                })?;
             }
 
             Ok(())
@@ -1131,8 +1223,9 @@ impl<'repo> StackTransaction<'repo> {
                 push_status = PushStatus::Empty;
             }
 
-            self.updated_patches
-                .insert(patchname.clone(), Some(PatchState { commit }));
+            let mut patch = self.get_patch(patchname).clone();
+            patch.commit = commit;
+            self.updated_patches.insert(patchname.clone(), Some(patch));
         }
 
         if push_status == PushStatus::Conflict {
@@ -1150,11 +1243,23 @@ impl<'repo> StackTransaction<'repo> {
         self.ui.print_pushed(patchname, push_status, is_last)?;
 
         if push_status == PushStatus::Conflict {
+            let msg = if let Some(operation) = self.options.operation.as_ref() {
+                let command = &operation.command;
+                format!(
+                    "merge conflicts; \
+                     resolve conflicts manually and `stg add` the resolved files, then \
+                     continue with `stg {command} --continue`, skip the conflicting \
+                     patch with `stg {command} --skip`, or abort with \
+                     `stg {command} --abort`."
+                )
+            } else {
+                "merge conflicts; \
+                 resolve conflicts manually then refresh or \
+                 undo the operation with `stg undo --hard`."
+                    .to_string()
+            };
             Err(Error::TransactionHalt {
-                msg: "merge conflicts; \
-                      resolve conflicts manually then refresh or \
-                      undo the operation with `stg undo --hard`."
-                    .to_string(),
+                msg,
                 conflicts: true,
             }
             .into())
@@ -1283,4 +1388,12 @@ impl<'repo> StackStateAccess<'repo> for StackTransaction<'repo> {
             self.top()
         }
     }
+
+    fn cover(&self) -> Option<&CoverLetter> {
+        if let Some(cover) = self.updated_cover.as_ref() {
+            cover.as_ref()
+        } else {
+            self.stack.cover()
+        }
+    }
 }
@@ -150,7 +150,14 @@ fn stack_upgrade_from_4(repo: &gix::Repository, branch_name: &str) -> Result<()>
                                     format!("converting `{oid_str}` for `{patchname}`")
                                 })?;
                             patch_list.push(patchname.clone());
-                            patches.insert(patchname, RawPatchState { oid: commit_id });
+                            patches.insert(
+                                patchname,
+                                RawPatchState {
+                                    oid: commit_id,
+                                    meta: BTreeMap::new(),
+                                    sent: Vec::new(),
+                                },
+                            );
                         }
                     } else {
                         return Err(anyhow!("malformed metadata"));
@@ -194,6 +201,8 @@ fn stack_upgrade_from_4(repo: &gix::Repository, branch_name: &str) -> Result<()>
                 unapplied,
                 hidden,
                 patches,
+                cover: None,
+                renamed: BTreeMap::new(),
             };
 
             let state = StackState::from_raw_state(repo, raw_stack_state)?;
@@ -266,7 +275,14 @@ fn stack_upgrade_from_3(repo: &gix::Repository, branch_name: &str) -> Result<()>
                     .with_context(|| format!("converting `{}` to patchname", &pn))?;
                 patch_list.push(patchname.clone());
                 cleanup.push(format!("refs/patches/{branch_name}/{pn}.log"));
-                patches.insert(patchname, RawPatchState { oid: commit_id });
+                patches.insert(
+                    patchname,
+                    RawPatchState {
+                        oid: commit_id,
+                        meta: BTreeMap::new(),
+                        sent: Vec::new(),
+                    },
+                );
             }
         }
     }
@@ -278,6 +294,8 @@ fn stack_upgrade_from_3(repo: &gix::Repository, branch_name: &str) -> Result<()>
         unapplied,
         hidden,
         patches,
+        cover: None,
+        renamed: BTreeMap::new(),
     };
 
     let state = StackState::from_raw_state(repo, raw_stack_state)?;
@@ -0,0 +1,105 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Snapshots of a stack's patch series for each version of the series.
+//!
+//! Each version snapshot is a reference to a stack state commit, i.e. the same kind of
+//! commit referred to by `refs/stacks/<branch>`. Snapshots are stored alongside the
+//! stack state reference as `refs/stacks/<branch>.versions/v<N>`. They cannot be stored
+//! under `refs/stacks/<branch>/` because that name is itself a reference.
+
+use anyhow::{anyhow, Result};
+use bstr::ByteSlice;
+
+use super::{Stack, StackAccess, StackState};
+
+/// Get the reference name prefix for the version snapshots of the given branch.
+pub(crate) fn get_version_refname_prefix(branch_name: &str) -> String {
+    format!("refs/stacks/{branch_name}.versions/")
+}
+
+/// Get the reference name for a version snapshot of the given branch.
+pub(crate) fn get_version_refname(branch_name: &str, version: u32) -> String {
+    format!("{}v{version}", get_version_refname_prefix(branch_name))
+}
+
+/// Parse a series version of the form `v<N>` or `<N>` where N is a positive integer.
+pub(crate) fn parse_version(s: &str) -> Result<u32> {
+    let digits = s.strip_prefix('v').unwrap_or(s);
+    match digits.parse::<u32>() {
+        Ok(version) if version > 0 && !digits.starts_with('+') => Ok(version),
+        _ => Err(anyhow!("invalid series version `{s}`, expected `v<N>`")),
+    }
+}
+
+impl<'repo> Stack<'repo> {
+    /// Get this stack's version snapshots, ordered by version number.
+    ///
+    /// Each version number is paired with the id of the snapshot's stack state commit.
+    pub(crate) fn get_versions(&self) -> Result<Vec<(u32, gix::ObjectId)>> {
+        let prefix = get_version_refname_prefix(self.get_branch_name());
+        let mut versions = Vec::new();
+        for mut reference in self
+            .repo
+            .references()?
+            .prefixed(prefix.as_str())?
+            .filter_map(Result::ok)
+        {
+            let Some(version) = reference
+                .name()
+                .as_bstr()
+                .strip_prefix(prefix.as_bytes())
+                .and_then(|name| name.to_str().ok())
+                .filter(|name| name.starts_with('v'))
+                .and_then(|name| parse_version(name).ok())
+            else {
+                continue;
+            };
+            versions.push((version, reference.peel_to_id_in_place()?.detach()));
+        }
+        versions.sort_by_key(|(version, _)| *version);
+        Ok(versions)
+    }
+
+    /// Get the stack state recorded by a version snapshot, if the snapshot exists.
+    pub(crate) fn get_version_state(&self, version: u32) -> Result<Option<StackState<'repo>>> {
+        let refname = get_version_refname(self.get_branch_name(), version);
+        if let Some(reference) = self.repo.try_find_reference(refname.as_str())? {
+            let commit = reference
+                .into_fully_peeled_id()?
+                .object()?
+                .try_into_commit()?;
+            Ok(Some(StackState::from_commit(self.repo, &commit)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Record the stack's current state as a version snapshot.
+    ///
+    /// An existing snapshot for the version is replaced only when `force` is true.
+    pub(crate) fn snapshot_version(&self, version: u32, force: bool) -> Result<()> {
+        let refname = get_version_refname(self.get_branch_name(), version);
+        if !force && self.repo.try_find_reference(refname.as_str())?.is_some() {
+            return Err(anyhow!("version `v{version}` already exists"));
+        }
+        let state_commit_id = self
+            .repo
+            .find_reference(self.get_stack_refname())?
+            .into_fully_peeled_id()?
+            .detach();
+        self.repo.edit_reference(gix::refs::transaction::RefEdit {
+            change: gix::refs::transaction::Change::Update {
+                log: gix::refs::transaction::LogChange {
+                    mode: gix::refs::transaction::RefLog::AndReference,
+                    force_create_reflog: false,
+                    message: format!("snapshot v{version}").into(),
+                },
+                expected: gix::refs::transaction::PreviousValue::Any,
+                new: gix::refs::Target::Peeled(state_commit_id),
+            },
+            name: gix::refs::FullName::try_from(refname)?,
+            deref: false,
+        })?;
+        Ok(())
+    }
+}
@@ -7,7 +7,7 @@
 use std::{
     cell::RefCell,
     ffi::{OsStr, OsString},
-    io::Write,
+    io::{Read, Write},
     path::Path,
     process::{Command, Stdio},
 };
@@ -18,6 +18,7 @@ use bstr::{BStr, BString, ByteSlice, ByteVec};
 use super::{
     command::{git_command_error, StupidCommand, StupidExitStatus, StupidOutput},
     diff::DiffFiles,
+    log::LogFilter,
     oid::parse_oid,
     status::{StatusOptions, Statuses},
     tempindex::TempIndex,
@@ -121,6 +122,28 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Apply a patch (diff) in reverse to the specified index using `git apply --cached
+    /// -R`.
+    pub(crate) fn apply_reverse_to_index(&self, diff: &BStr) -> Result<()> {
+        self.git_in_work_root()?
+            .args(["apply", "--cached", "-R"])
+            .stdout(Stdio::null())
+            .in_and_out(diff)?
+            .require_success("apply")?;
+        Ok(())
+    }
+
+    /// Apply diff with zero lines of context to the index using `git apply --cached
+    /// --unidiff-zero`.
+    pub(crate) fn apply_unidiff_zero_to_index(&self, diff: &BStr) -> Result<()> {
+        self.git_in_work_root()?
+            .args(["apply", "--cached", "--unidiff-zero"])
+            .stdout(Stdio::null())
+            .in_and_out(diff)?
+            .require_success("apply")?;
+        Ok(())
+    }
+
     pub(crate) fn apply_to_worktree_and_index(
         &self,
         diff: &BStr,
@@ -497,13 +520,28 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
 
     /// Generate diff between specified tree and the working tree or index with
     /// `git diff-index`.
-    pub(crate) fn diff_index(&self, tree_id: gix::ObjectId) -> Result<BString> {
-        let output = self
-            .git()
+    pub(crate) fn diff_index<OptIter, OptArg, SpecIter, SpecArg>(
+        &self,
+        tree_id: gix::ObjectId,
+        diff_opts: OptIter,
+        pathspecs: Option<SpecIter>,
+    ) -> Result<BString>
+    where
+        OptIter: IntoIterator<Item = OptArg>,
+        OptArg: AsRef<OsStr>,
+        SpecIter: IntoIterator<Item = SpecArg>,
+        SpecArg: AsRef<OsStr>,
+    {
+        let mut command = self.git();
+        command
             .args(["diff-index", "-p", "--full-index"])
-            .arg(tree_id.to_string())
-            .output_git()?
-            .require_success("diff-index")?;
+            .args(diff_opts)
+            .arg(tree_id.to_string());
+        if let Some(pathspecs) = pathspecs {
+            command.arg("--");
+            command.args(pathspecs);
+        }
+        let output = command.output_git()?.require_success("diff-index")?;
         Ok(BString::from(output.stdout))
     }
 
@@ -623,6 +661,47 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(BString::from(output.stdout))
     }
 
+    /// Generate diffstat and summary between two trees using `git diff-tree`.
+    ///
+    /// The diffstat is formatted with the same width as used for emails.
+    pub(crate) fn diff_tree_stat(
+        &self,
+        tree1: gix::ObjectId,
+        tree2: gix::ObjectId,
+    ) -> Result<BString> {
+        let output = self
+            .git()
+            .args(["diff-tree", "-M", "--stat=72", "--summary", "--color=never"])
+            .args([tree1.to_string(), tree2.to_string()])
+            .output_git()?
+            .require_success("diff-tree")?;
+        Ok(BString::from(output.stdout))
+    }
+
+    /// Generate diff between two files using `git diff --no-index`.
+    ///
+    /// The files need not be part of the repository.
+    pub(crate) fn diff_no_index(
+        &self,
+        path1: &Path,
+        path2: &Path,
+        use_color: bool,
+    ) -> Result<BString> {
+        let output = self
+            .git()
+            .args(["diff", "--no-index", "--no-ext-diff"])
+            .arg(if use_color {
+                "--color=always"
+            } else {
+                "--color=never"
+            })
+            .arg(path1)
+            .arg(path2)
+            .output_git()?
+            .require_code_less_than("diff --no-index", 2)?;
+        Ok(BString::from(output.stdout))
+    }
+
     /// Get unmerged path list using `git diff --name-only --diff-filter=U`.
     ///
     /// The returned unmerged paths are relative to the work tree root regardless of the
@@ -643,8 +722,8 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(paths)
     }
 
-    /// Run `git format-patch` with arbitrary arguments.
-    pub(crate) fn format_patch<OptIter, OptArg>(&self, args: OptIter) -> Result<()>
+    /// Run `git format-patch` with arbitrary arguments, returning its captured output.
+    pub(crate) fn format_patch<OptIter, OptArg>(&self, args: OptIter) -> Result<BString>
     where
         OptIter: IntoIterator<Item = OptArg>,
         OptArg: AsRef<OsStr>,
@@ -652,12 +731,11 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         let mut command = self.git();
         command.arg("format-patch");
         command.args(args);
-        command
+        let output = command
             .stdin(Stdio::inherit())
-            .stdout(Stdio::inherit())
             .output_git()?
             .require_success("format-patch")?;
-        Ok(())
+        Ok(BString::from(output.stdout))
     }
 
     /// Show log in `gitk`
@@ -665,6 +743,7 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         &self,
         commit_id: gix::ObjectId,
         pathspecs: Option<SpecIter>,
+        filter: &LogFilter,
     ) -> Result<()>
     where
         SpecIter: IntoIterator<Item = SpecArg>,
@@ -672,7 +751,7 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
     {
         let mut command = Command::new("gitk");
         self.setup_git_env(&mut command);
-        command.arg(commit_id.to_string());
+        command.args(filter.args(commit_id));
         if let Some(pathspecs) = pathspecs {
             command.arg("--");
             command.args(pathspecs);
@@ -710,12 +789,27 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(output.stdout)
     }
 
+    /// Parse the trailers of a commit message.
+    ///
+    /// The trailers are output one per line, with folded values unfolded.
+    pub(crate) fn interpret_trailers_parse(&self, message: &[u8]) -> Result<Vec<u8>> {
+        let output = self
+            .git()
+            .args(["interpret-trailers", "--parse"])
+            .stdout(Stdio::piped())
+            .in_and_out(message)?
+            .require_success("interpret-trailers")?;
+        Ok(output.stdout)
+    }
+
     /// Interactively show log
+    #[allow(clippy::too_many_arguments)]
     pub(crate) fn log<SpecIter, SpecArg>(
         &self,
         commit_id: gix::ObjectId,
         pathspecs: Option<SpecIter>,
         num_commits: Option<usize>,
+        filter: &LogFilter,
         use_color: bool,
         full_index: bool,
         show_diff: bool,
@@ -739,7 +833,7 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         } else if !full_index {
             command.arg("--pretty=tformat:%C(auto)%h   %C(auto,blue)%aD   %C(auto)%s");
         }
-        command.arg(commit_id.to_string());
+        command.args(filter.args(commit_id));
         if let Some(pathspecs) = pathspecs {
             command.arg("--");
             command.args(pathspecs);
@@ -755,6 +849,40 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Get ids of the commits that would be shown by [`StupidContext::log()`].
+    pub(crate) fn log_ids<SpecIter, SpecArg>(
+        &self,
+        commit_id: gix::ObjectId,
+        pathspecs: Option<SpecIter>,
+        num_commits: Option<usize>,
+        filter: &LogFilter,
+    ) -> Result<Vec<gix::ObjectId>>
+    where
+        SpecIter: IntoIterator<Item = SpecArg>,
+        SpecArg: AsRef<OsStr>,
+    {
+        let mut command = self.git_in_work_root()?;
+        command.arg("rev-list");
+        if let Some(n) = num_commits {
+            command.arg(format!("-{n}"));
+        }
+        command.args(filter.args(commit_id));
+        if let Some(pathspecs) = pathspecs {
+            command.arg("--");
+            command.args(pathspecs);
+        }
+        let output = command.output_git()?.require_success("rev-list")?;
+        let mut oids: Vec<gix::ObjectId> = Vec::new();
+        for line in output
+            .stdout
+            .split_str("\n")
+            .filter(|line| !line.is_empty())
+        {
+            oids.push(parse_oid(line)?);
+        }
+        Ok(oids)
+    }
+
     pub(crate) fn mailinfo(
         &self,
         input: Option<std::fs::File>,
@@ -912,6 +1040,80 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(())
     }
 
+    /// Get the note for an object using `git notes show`.
+    ///
+    /// The notes from `notes_ref` are used if specified, otherwise the default notes
+    /// ref. `None` is returned if the object has no note.
+    pub(crate) fn notes_show(
+        &self,
+        oid: gix::ObjectId,
+        notes_ref: Option<&str>,
+    ) -> Result<Option<BString>> {
+        let mut command = self.git();
+        command.arg("notes");
+        if let Some(notes_ref) = notes_ref {
+            command.arg(format!("--ref={notes_ref}"));
+        }
+        let output = command
+            .arg("show")
+            .arg(oid.to_string())
+            .stderr(Stdio::null())
+            .output_git()?;
+        if output.status.success() {
+            Ok(Some(BString::from(output.stdout)))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Get the stable patch id of a commit using `git patch-id --stable`.
+    ///
+    /// `None` is returned if the commit's diff is empty.
+    pub(crate) fn patch_id(&self, commit_id: gix::ObjectId) -> Result<Option<gix::ObjectId>> {
+        let diff = self
+            .git()
+            .args(["diff-tree", "-p", "--color=never"])
+            .arg(commit_id.to_string())
+            .output_git()?
+            .require_success("diff-tree")?
+            .stdout;
+        let output = self
+            .git()
+            .args(["patch-id", "--stable"])
+            .stdout(Stdio::piped())
+            .in_and_out(&diff)?
+            .require_success("patch-id")?;
+        if let Some(patch_id) = output
+            .stdout
+            .split_str(" ")
+            .next()
+            .filter(|s| !s.is_empty())
+        {
+            Ok(Some(parse_oid(patch_id)?))
+        } else {
+            Ok(None)
+        }
+    }
+
+    /// Compare two commit ranges using `git range-diff`.
+    pub(crate) fn range_diff(
+        &self,
+        old_range: &str,
+        new_range: &str,
+        creation_factor: Option<&str>,
+    ) -> Result<BString> {
+        let mut command = self.git();
+        command.args(["range-diff", "--no-color"]);
+        if let Some(creation_factor) = creation_factor {
+            command.arg(format!("--creation-factor={creation_factor}"));
+        }
+        let output = command
+            .args([old_range, new_range])
+            .output_git()?
+            .require_success("range-diff")?;
+        Ok(BString::from(output.stdout))
+    }
+
     /// Read content of a tree into specified index using `git read-tree`.
     pub(crate) fn read_tree(&self, tree_id: gix::ObjectId) -> Result<()> {
         self.git_in_work_root()?
@@ -1032,7 +1234,15 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         }
     }
 
-    pub(crate) fn send_email<OptIter, OptArg>(&self, args: OptIter) -> Result<()>
+    /// Run `git send-email`, collecting its output into `output`.
+    ///
+    /// The output is also passed through to stdout as it is produced. Whatever output
+    /// was produced is collected even if `git send-email` fails.
+    pub(crate) fn send_email<OptIter, OptArg>(
+        &self,
+        args: OptIter,
+        output: &mut Vec<u8>,
+    ) -> Result<()>
     where
         OptIter: IntoIterator<Item = OptArg>,
         OptArg: AsRef<OsStr>,
@@ -1040,11 +1250,23 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         let mut command = self.git();
         command.arg("send-email");
         command.args(args);
-        command
+        let mut child = command
             .stdin(Stdio::inherit())
-            .stdout(Stdio::inherit())
-            .output_git()?
-            .require_success("send-email")?;
+            .stdout(Stdio::piped())
+            .spawn_git()?;
+        let mut child_stdout = child.stdout.take().unwrap();
+        let mut stdout = std::io::stdout();
+        let mut buf = [0u8; 4096];
+        loop {
+            let n = child_stdout.read(&mut buf)?;
+            if n == 0 {
+                break;
+            }
+            stdout.write_all(&buf[..n])?;
+            stdout.flush()?;
+            output.extend_from_slice(&buf[..n]);
+        }
+        child.require_success("send-email")?;
         Ok(())
     }
 
@@ -1370,6 +1592,46 @@ impl<'repo, 'index> StupidContext<'repo, 'index> {
         Ok(version_line)
     }
 
+    /// Add a linked worktree with a detached HEAD using `git worktree add`.
+    pub(crate) fn worktree_add(&self, path: &Path, commit_id: gix::ObjectId) -> Result<()> {
+        self.git()
+            .args(["worktree", "add", "--detach", "--quiet"])
+            .arg(path)
+            .arg(commit_id.to_string())
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("worktree add")?;
+        Ok(())
+    }
+
+    /// Check out commit with a detached HEAD in a linked worktree using `git checkout`.
+    ///
+    /// Any changes to tracked files in the linked worktree are discarded.
+    pub(crate) fn worktree_checkout(&self, path: &Path, commit_id: gix::ObjectId) -> Result<()> {
+        Command::new("git")
+            .current_dir(path)
+            .env_remove("GIT_DIR")
+            .env_remove("GIT_WORK_TREE")
+            .env_remove("GIT_INDEX_FILE")
+            .args(["checkout", "--detach", "--force", "--quiet"])
+            .arg(commit_id.to_string())
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("checkout")?;
+        Ok(())
+    }
+
+    /// Remove a linked worktree using `git worktree remove`.
+    pub(crate) fn worktree_remove(&self, path: &Path) -> Result<()> {
+        self.git()
+            .args(["worktree", "remove", "--force"])
+            .arg(path)
+            .stdout(Stdio::null())
+            .output_git()?
+            .require_success("worktree remove")?;
+        Ok(())
+    }
+
     /// Write tree object from content of specified index using `git write-tree`.
     pub(crate) fn write_tree(&self) -> Result<gix::ObjectId> {
         let output = self
@@ -4,7 +4,8 @@
 
 use std::path::Path;
 
-use bstr::ByteSlice;
+use anyhow::{anyhow, Result};
+use bstr::{BStr, ByteSlice};
 
 /// Diff output containing only names of differing files.
 ///
@@ -52,6 +53,146 @@ impl<'a> Iterator for DiffFilesIter<'a> {
     }
 }
 
+/// A single file's portion of a unified diff.
+///
+/// E.g. from `git diff-tree -p`
+pub(crate) struct FileDiff<'a> {
+    /// Header lines for the file, from the `diff --git` line up to the first hunk.
+    pub(crate) header: &'a BStr,
+
+    /// Path of the file as it appears in the `diff --git` line.
+    ///
+    /// The path is the source path, including any source prefix (e.g. `a/`) and
+    /// quoting. Renames must be disabled (i.e. `--no-renames`) for this path to
+    /// reliably identify the file.
+    pub(crate) path: &'a BStr,
+
+    /// Whether the file's diff is for binary content, in which case there are no
+    /// hunks.
+    pub(crate) binary: bool,
+
+    /// Hunks of changed lines.
+    pub(crate) hunks: Vec<DiffHunk<'a>>,
+}
+
+/// A hunk from a unified diff.
+pub(crate) struct DiffHunk<'a> {
+    /// Starting line number in the old file.
+    pub(crate) old_start: usize,
+
+    /// Number of lines from the old file in the hunk.
+    pub(crate) old_len: usize,
+
+    /// Starting line number in the new file.
+    pub(crate) new_start: usize,
+
+    /// Number of lines from the new file in the hunk.
+    pub(crate) new_len: usize,
+
+    /// Full text of the hunk, including the `@@` header line.
+    pub(crate) text: &'a BStr,
+}
+
+/// Parse unified diff output from `git` into per-file diffs.
+pub(crate) fn parse_diff(diff: &[u8]) -> Result<Vec<FileDiff<'_>>> {
+    let mut files: Vec<FileDiff> = Vec::new();
+    let mut pos = 0;
+    let mut hunk_start: Option<(usize, DiffHunk)> = None;
+    let mut header_start = 0;
+    let mut in_header = false;
+
+    while pos < diff.len() {
+        let line_end = diff[pos..]
+            .find_byte(b'\n')
+            .map_or(diff.len(), |offset| pos + offset + 1);
+        let line = &diff[pos..line_end];
+
+        if let Some(rest) = line.strip_prefix(b"diff --git ") {
+            finish_hunk(diff, &mut files, &mut hunk_start, pos);
+            if in_header {
+                files.last_mut().unwrap().header = diff[header_start..pos].as_bstr();
+            }
+            let rest = rest.trim_end_with(|c| c == '\n');
+            let path = rest[..rest.len() / 2].as_bstr();
+            files.push(FileDiff {
+                header: BStr::new(b""),
+                path,
+                binary: false,
+                hunks: Vec::new(),
+            });
+            header_start = pos;
+            in_header = true;
+        } else if line.starts_with(b"@@ ") {
+            if files.is_empty() {
+                return Err(anyhow!("hunk without file header in diff"));
+            }
+            finish_hunk(diff, &mut files, &mut hunk_start, pos);
+            if in_header {
+                files.last_mut().unwrap().header = diff[header_start..pos].as_bstr();
+                in_header = false;
+            }
+            hunk_start = Some((pos, parse_hunk_header(line)?));
+        } else if in_header
+            && (line.starts_with(b"Binary files ") || line.starts_with(b"GIT binary patch"))
+        {
+            files.last_mut().unwrap().binary = true;
+        }
+
+        pos = line_end;
+    }
+
+    finish_hunk(diff, &mut files, &mut hunk_start, diff.len());
+    if in_header {
+        files.last_mut().unwrap().header = diff[header_start..].as_bstr();
+    }
+
+    Ok(files)
+}
+
+/// Move pending hunk, if any, into the last file diff.
+fn finish_hunk<'a>(
+    diff: &'a [u8],
+    files: &mut [FileDiff<'a>],
+    hunk_start: &mut Option<(usize, DiffHunk<'a>)>,
+    end: usize,
+) {
+    if let Some((start, mut hunk)) = hunk_start.take() {
+        hunk.text = diff[start..end].as_bstr();
+        files
+            .last_mut()
+            .expect("hunks only follow file headers")
+            .hunks
+            .push(hunk);
+    }
+}
+
+/// Parse hunk header line of the form `@@ -<start>[,<len>] +<start>[,<len>] @@`.
+fn parse_hunk_header(line: &[u8]) -> Result<DiffHunk<'_>> {
+    let bad_header = || anyhow!("invalid hunk header `{}`", line.trim().as_bstr());
+    let ranges = line
+        .strip_prefix(b"@@ -")
+        .and_then(|rest| rest.find(b" @@").map(|end| &rest[..end]))
+        .ok_or_else(bad_header)?;
+    let ranges = ranges.to_str().map_err(|_| bad_header())?;
+    let (old_range, new_range) = ranges.split_once(" +").ok_or_else(bad_header)?;
+    let parse_range = |range: &str| -> Option<(usize, usize)> {
+        if let Some((start, len)) = range.split_once(',') {
+            Some((start.parse().ok()?, len.parse().ok()?))
+        } else {
+            Some((range.parse().ok()?, 1))
+        }
+    };
+    let (old_start, old_len) = parse_range(old_range).ok_or_else(bad_header)?;
+    let (new_start, new_len) = parse_range(new_range).ok_or_else(bad_header)?;
+    Ok(DiffHunk {
+        old_start,
+        old_len,
+        new_start,
+        new_len,
+        text: BStr::new(b""),
+    })
+}
+
 #[cfg(test)]
 mod tests {
     use super::*;
@@ -65,4 +206,68 @@ mod tests {
         assert_eq!(it.next(), Some(Path::new("jkl")));
         assert!(it.next().is_none());
     }
+
+    #[test]
+    fn parse_unified_diff() {
+        let diff = b"\
+diff --git a/foo.txt b/foo.txt
+index 1111111..2222222 100644
+--- a/foo.txt
++++ b/foo.txt
+@@ -1,2 +1,3 @@ context
+ one
++two
+ three
+@@ -10 +11,0 @@
+-ten
+diff --git a/bar baz.bin b/bar baz.bin
+index 3333333..4444444 100644
+Binary files a/bar baz.bin and b/bar baz.bin differ
+diff --git a/new.txt b/new.txt
+new file mode 100644
+index 0000000..5555555
+--- /dev/null
++++ b/new.txt
+@@ -0,0 +1 @@
++new
+\\ No newline at end of file
+";
+        let files = parse_diff(diff).unwrap();
+        assert_eq!(files.len(), 3);
+
+        assert_eq!(files[0].path, "a/foo.txt");
+        assert!(!files[0].binary);
+        assert!(files[0].header.starts_with(b"diff --git a/foo.txt"));
+        assert!(files[0].header.ends_with(b"+++ b/foo.txt\n"));
+        assert_eq!(files[0].hunks.len(), 2);
+        let hunk = &files[0].hunks[0];
+        assert_eq!(
+            (hunk.old_start, hunk.old_len, hunk.new_start, hunk.new_len),
+            (1, 2, 1, 3)
+        );
+        assert_eq!(hunk.text, "@@ -1,2 +1,3 @@ context\n one\n+two\n three\n");
+        let hunk = &files[0].hunks[1];
+        assert_eq!(
+            (hunk.old_start, hunk.old_len, hunk.new_start, hunk.new_len),
+            (10, 1, 11, 0)
+        );
+        assert_eq!(hunk.text, "@@ -10 +11,0 @@\n-ten\n");
+
+        assert_eq!(files[1].path, "a/bar baz.bin");
+        assert!(files[1].binary);
+        assert!(files[1].hunks.is_empty());
+
+        assert_eq!(files[2].path, "a/new.txt");
+        assert_eq!(files[2].hunks.len(), 1);
+        assert_eq!(
+            files[2].hunks[0].text,
+            "@@ -0,0 +1 @@\n+new\n\\ No newline at end of file\n"
+        );
+    }
+
+    #[test]
+    fn parse_bad_hunk_header() {
+        assert!(parse_diff(b"diff --git a/x b/x\n@@ -1,z +1 @@\n").is_err());
+        assert!(parse_diff(b"@@ -1 +1 @@\n").is_err());
+    }
 }
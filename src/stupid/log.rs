@@ -0,0 +1,71 @@
+// SPDX-License-Identifier: GPL-2.0-only
+
+//! Filter commits shown by `git log` and `git rev-list`.
+
+use std::ffi::OsString;
+
+/// Criteria for selecting commits from a stack's state log.
+///
+/// Commits are selected when they match any of the given authors and are within the
+/// given date range. When specific commits are given, only those commits are
+/// considered instead of walking the history.
+#[derive(Default)]
+pub(crate) struct LogFilter {
+    pub(super) commit_ids: Option<Vec<gix::ObjectId>>,
+    pub(super) authors: Vec<String>,
+    pub(super) since: Option<String>,
+    pub(super) until: Option<String>,
+}
+
+impl LogFilter {
+    /// Select only the provided commits, in the provided order.
+    pub(crate) fn commit_ids(&mut self, commit_ids: Vec<gix::ObjectId>) -> &mut Self {
+        self.commit_ids = Some(commit_ids);
+        self
+    }
+
+    /// Select commits whose author matches one of the provided patterns.
+    pub(crate) fn authors<I, S>(&mut self, authors: I) -> &mut Self
+    where
+        I: IntoIterator<Item = S>,
+        S: Into<String>,
+    {
+        self.authors.extend(authors.into_iter().map(Into::into));
+        self
+    }
+
+    /// Select commits more recent than the provided date.
+    pub(crate) fn since(&mut self, date: Option<&str>) -> &mut Self {
+        self.since = date.map(ToString::to_string);
+        self
+    }
+
+    /// Select commits older than the provided date.
+    pub(crate) fn until(&mut self, date: Option<&str>) -> &mut Self {
+        self.until = date.map(ToString::to_string);
+        self
+    }
+
+    /// Get the `git log` or `git rev-list` arguments implementing this filter.
+    ///
+    /// The history is walked from `commit_id` unless specific commits were selected.
+    pub(super) fn args(&self, commit_id: gix::ObjectId) -> Vec<OsString> {
+        let mut args: Vec<OsString> = Vec::new();
+        for author in &self.authors {
+            args.push(format!("--author={author}").into());
+        }
+        if let Some(since) = self.since.as_ref() {
+            args.push(format!("--since={since}").into());
+        }
+        if let Some(until) = self.until.as_ref() {
+            args.push(format!("--until={until}").into());
+        }
+        if let Some(commit_ids) = self.commit_ids.as_ref() {
+            args.push("--no-walk=unsorted".into());
+            args.extend(commit_ids.iter().map(|id| id.to_string().into()));
+        } else {
+            args.push(commit_id.to_string().into());
+        }
+        args
+    }
+}
@@ -9,6 +9,7 @@
 mod command;
 mod context;
 mod diff;
+mod log;
 mod oid;
 mod status;
 mod tempindex;
@@ -18,6 +19,8 @@ use std::cell::RefCell;
 
 pub(crate) use self::{
     context::StupidContext,
+    diff::{parse_diff, DiffHunk, FileDiff},
+    log::LogFilter,
     status::{Status, StatusOptions, Statuses},
 };
 
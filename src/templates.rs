@@ -147,3 +147,6 @@ From: %(authname)s <%(authemail)s>
 ---
 %(diffstat)s
 ";
+
+/// Default email subject template.
+pub(crate) const EMAILSUBJECT_TMPL: &str = "[%(prefix)s] %(shortdescr)s";
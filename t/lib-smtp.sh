@@ -0,0 +1,23 @@
+# Helpers for tests sending email via SMTP.
+
+# Start an SMTP server that records received emails in the given directory, which
+# defaults to "sink". The server is stopped when the test script exits, and
+# sendemail.smtpServer and sendemail.smtpServerPort are configured to use it.
+start_smtp_sink () {
+	smtp_sink_dir=${1:-sink}
+	"$PERL_PATH" "$TEST_DIRECTORY"/test-smtp-sink.perl "$smtp_sink_dir" &
+	for i in 1 2 3 4 5 6 7 8 9 10
+	do
+		test -f "$smtp_sink_dir/port" && break
+		sleep 1
+	done &&
+	test -f "$smtp_sink_dir/port" &&
+	test_atexit "kill $(cat "$smtp_sink_dir/pid")" &&
+	git config sendemail.smtpServer 127.0.0.1 &&
+	git config sendemail.smtpServerPort "$(cat "$smtp_sink_dir/port")"
+}
+
+test_lazy_prereq SMTPSINK '
+	test_have_prereq PERL &&
+	"$PERL_PATH" -MIO::Socket::INET -e 0
+'
@@ -35,6 +35,12 @@ test_expect_success 'Test empty series count' '
     test "$(stg series --count)" = "0"
 '
 
+test_expect_success 'Test empty series JSON' '
+    echo "[]" >expected.txt &&
+    stg series --json >series.txt &&
+    test_cmp expected.txt series.txt
+'
+
 test_expect_success 'Add patches' '
     echo "a" >>file.txt &&
     stg new -m "message 0" p0 &&
@@ -160,6 +166,87 @@ test_expect_success 'Test effects' '
     test_line_count = 3 series.txt
 '
 
+test_expect_success 'Test JSON' '
+    stg series --json >series.json &&
+    test "$(grep -c "\"name\": \"p[0-9]\"" series.json)" = "4" &&
+    grep -e "\"name\": \"p1\"" series.json &&
+    grep -e "\"state\": \"applied\"" series.json &&
+    grep -e "\"state\": \"unapplied\"" series.json &&
+    test "$(grep -c "\"top\": true" series.json)" = "1" &&
+    grep -e "\"offset\": -2" series.json &&
+    grep -e "\"name\": \"B Author\"" series.json &&
+    grep -e "\"description\": \"message 3\"" series.json &&
+    test "$(grep -c "\"empty\": true" series.json)" = "1" &&
+    grep -e "\"commit\": \"$(stg id p2)\"" series.json
+'
+
+test_expect_success 'Test JSON selection and order' '
+    stg series --json --reverse --applied >series.json &&
+    test "$(grep -c "\"state\": \"applied\"" series.json)" = "3" &&
+    grep -e "\"name\": \"p[0-9]\"" series.json >names.txt &&
+    cat >expected.txt <<-\EOF &&
+	    "name": "p2",
+	    "name": "p1",
+	    "name": "p0",
+	EOF
+    test_cmp expected.txt names.txt
+'
+
+test_expect_success 'Test JSON metadata' '
+    stg meta set -p p1 status reviewed &&
+    stg series --json p1 >series.json &&
+    grep -e "\"status\": \"reviewed\"" series.json &&
+    stg meta unset -p p1 status
+'
+
+test_expect_success 'Test invalid JSON and display options' '
+    general_error stg series --json --description &&
+    general_error stg series --json --count
+'
+
+test_expect_success 'Test format' '
+    stg series --format="%(prefix)%(name) %(state) %(index) %(offset)" >series.txt &&
+    cat >expected.txt <<-\EOF &&
+	+p0 applied 0 -2
+	+p1 applied 1 -1
+	>p2 applied 2 +0
+	-p3 unapplied 3 +1
+	EOF
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test format commit and author atoms' '
+    stg series --applied --format="%(author-name)|%(author-email)|%(description)" >series.txt &&
+    cat >expected.txt <<-EOF &&
+	$GIT_AUTHOR_NAME|$GIT_AUTHOR_EMAIL|message 0
+	B Author|$GIT_AUTHOR_EMAIL|message 1
+	$GIT_AUTHOR_NAME|$GIT_AUTHOR_EMAIL|message 2
+	EOF
+    test_cmp expected.txt series.txt &&
+    test "$(stg series --format="%(commit)" p1)" = "$(stg id p1)" &&
+    test "$(stg series --format="%(commit:short=8)" p1)" = "$(git rev-parse --short=8 $(stg id p1))" &&
+    test "$(stg series --format="%(committer-date:unix)" p1)" = "$(git log -1 --format=%ct $(stg id p1))"
+'
+
+test_expect_success 'Test format empty, meta, and escapes' '
+    stg meta set -p p1 status reviewed &&
+    test_when_finished "stg meta unset -p p1 status" &&
+    stg series --reverse --format="%(name)%09%(meta:status)%(empty)%%" >series.txt &&
+    printf "p3\tempty%%\np2\t%%\np1\treviewed%%\np0\t%%\n" >expected.txt &&
+    test_cmp expected.txt series.txt
+'
+
+test_expect_success 'Test invalid format' '
+    general_error stg series --format="%(bogus)" 2>err &&
+    grep -e "invalid format atom \`%(bogus)\`" err &&
+    general_error stg series --format="%(name" 2>err &&
+    grep -e "unterminated format atom" err &&
+    general_error stg series --format="%(commit:short=2)" &&
+    general_error stg series --format="%(author-date:bogus)" &&
+    general_error stg series --format="%q" &&
+    general_error stg series --format="%(name)" --description
+'
+
 test_expect_success 'Test missing' '
     stg branch --clone -- other &&
     test "$(stg branch)" = "other" &&
@@ -0,0 +1,133 @@
+#!/bin/sh
+
+test_description='Test filtering and JSON output of the log command'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    test_tick &&
+    stg pop &&
+    stg refresh -m "p3 changed" -p p3 &&
+    test_tick &&
+    GIT_AUTHOR_NAME="Other Person" GIT_AUTHOR_EMAIL="other@example.com" \
+        stg push &&
+    stg new -m "new patch" p4
+'
+
+test_expect_success 'Filter by command' '
+    stg log --command push >log.txt &&
+    test_line_count = 1 log.txt &&
+    grep -e "push$" log.txt &&
+    stg log --command new --command refresh >log.txt &&
+    test_line_count = 3 log.txt &&
+    grep -e "new: p4$" log.txt &&
+    grep -e "refresh" log.txt &&
+    stg log --command ne >log.txt &&
+    test_must_be_empty log.txt
+'
+
+test_expect_success 'Filter by author' '
+    stg log --author "Other Person" >log.txt &&
+    test_line_count = 1 log.txt &&
+    grep -e "push$" log.txt &&
+    stg log --author other@example.com --command new >log.txt &&
+    test_must_be_empty log.txt &&
+    stg log --author "Perso+n" --command push >log.txt &&
+    test_must_be_empty log.txt
+'
+
+test_expect_success 'Filter by date' '
+    stg log --since "$(($test_tick - 30)) -0700" >log.txt &&
+    test_line_count = 2 log.txt &&
+    stg log --until "$(($test_tick - 30)) -0700" >log.txt &&
+    test_line_count = 5 log.txt &&
+    grep -e "uncommit$" log.txt
+'
+
+test_expect_success 'Filter by patch and command' '
+    stg log --command refresh p1 >log.txt &&
+    test_must_be_empty log.txt &&
+    stg log --command refresh p3 >log.txt &&
+    test_line_count = 1 log.txt
+'
+
+test_expect_success 'Invalid option combinations' '
+    general_error stg log --json --diff &&
+    general_error stg log --json --graphical &&
+    general_error stg log --clear --command push &&
+    general_error stg log --prune --since now
+'
+
+test_expect_success 'JSON output' '
+    stg log --json -n 2 >log.json &&
+    test_line_count = 60 log.json &&
+    cat >expected <<-EOF &&
+	[
+	  {
+	    "commit": "$(stg log -n 1 --full | sed -n -e "s/^commit //p")",
+	    "command": "new",
+	    "message": "new: p4",
+	    "author": {
+	      "name": "A $(printf "\303\232") Thor",
+	      "email": "author@example.com"
+	    },
+	    "timestamp": "2005-04-07T15:17:13-07:00",
+	    "before": {
+	      "applied": [
+	        "p1",
+	        "p2",
+	        "p3"
+	      ],
+	      "unapplied": [],
+	      "hidden": []
+	    },
+	    "after": {
+	      "applied": [
+	        "p1",
+	        "p2",
+	        "p3",
+	        "p4"
+	      ],
+	      "unapplied": [],
+	      "hidden": []
+	    }
+	  },
+	EOF
+    head -n 30 log.json >actual &&
+    test_cmp expected actual &&
+    grep -e "\"name\": \"Other Person\"" log.json
+'
+
+test_expect_success 'JSON output of first entry' '
+    stg log --json --command initialize >log.json &&
+    grep -e "\"before\": null" log.json
+'
+
+test_expect_success 'Filter by command matches only the first message line' '
+    state=$(git rev-parse refs/stacks/master) &&
+    simplified=$(git rev-parse $state^1) &&
+    new_simplified=$(
+        {
+            git cat-file commit $simplified &&
+            printf "\n\npush p4\n"
+        } | git hash-object -t commit -w --stdin
+    ) &&
+    new_state=$(
+        {
+            git cat-file commit $state |
+            sed -e "s/^parent $simplified\$/parent $new_simplified/" &&
+            printf "\n\npush p4\n"
+        } | git hash-object -t commit -w --stdin
+    ) &&
+    git update-ref refs/stacks/master $new_state $state &&
+    stg log --command push >log.txt &&
+    test_line_count = 1 log.txt &&
+    grep -e "push$" log.txt &&
+    stg log --command new >log.txt &&
+    test_line_count = 1 log.txt &&
+    grep -e "new: p4$" log.txt
+'
+
+test_done
@@ -0,0 +1,97 @@
+#!/bin/sh
+
+test_description='Test the patch-log command'
+
+. ./test-lib.sh
+
+test_expect_success 'Attempt patch-log with no patches' '
+    stg init &&
+    command_error stg patch-log 2>err &&
+    grep -e "no patches applied" err
+'
+
+test_expect_success 'Initialize StGit stack' '
+    test_commit_bulk --message="p%s" 2 &&
+    stg uncommit -n 2 &&
+    test_tick &&
+    stg goto p1 &&
+    echo "change" >>1.t &&
+    stg refresh &&
+    stg edit -m "p1 edited" &&
+    stg rename p1 first &&
+    stg push
+'
+
+test_expect_success 'Show patch versions' '
+    stg patch-log first >out &&
+    test_line_count = 3 out &&
+    cut -d " " -f 1 out >numbers &&
+    test_write_lines 3 2 1 >expected &&
+    test_cmp expected numbers &&
+    head -n 1 out | grep -e "$(stg id first | cut -c 1-7) .*edit: p1 (as p1)$" &&
+    sed -n -e 2p out | grep -e "refresh p1 (as p1)$" &&
+    sed -n -e 3p out | grep -e "uncommit (as p1)$"
+'
+
+test_expect_success 'Pushing without changes is not a new version' '
+    stg pop -a &&
+    stg push -a &&
+    stg patch-log first >out &&
+    test_line_count = 3 out
+'
+
+test_expect_success 'Limit number of versions' '
+    stg patch-log -n 2 first >out &&
+    test_line_count = 2 out &&
+    head -n 1 out | grep -e "^3 "
+'
+
+test_expect_success 'Topmost patch is the default' '
+    stg patch-log >out &&
+    test_line_count = 2 out &&
+    head -n 1 out | grep -e "$(stg id p2 | cut -c 1-7) .*push$"
+'
+
+test_expect_success 'Rebased patch is a new version' '
+    stg pop -a &&
+    test_commit base &&
+    stg push -a &&
+    stg patch-log first >out &&
+    test_line_count = 4 out &&
+    head -n 1 out | grep -e "^4 .*push$"
+'
+
+test_expect_success 'Show diffs between versions' '
+    stg patch-log --diff first >out &&
+    grep -e "^    ++change" out &&
+    grep -e "^    -    p1$" out &&
+    grep -e "^    +    p1 edited$" out &&
+    stg patch-log --diff p2 >out &&
+    test_line_count = 3 out
+'
+
+test_expect_success 'Split patch history follows only the renamed part' '
+    stg new -m "two files" both &&
+    echo x >x.t &&
+    echo y >y.t &&
+    stg add x.t y.t &&
+    stg refresh &&
+    write_script name-editor <<-\EOF &&
+	n=$(($(cat count 2>/dev/null || echo 0) + 1))
+	echo $n >count
+	sed -i -e "s/^Patch: .*/Patch: part$n/" "$1"
+	EOF
+    EDITOR=./name-editor stg split --by-file both &&
+    stg patch-log part1 >out &&
+    test_line_count = 3 out &&
+    tail -n 1 out | grep -e "new: both (as both)$" &&
+    stg patch-log part2 >out &&
+    test_line_count = 1 out
+'
+
+test_expect_success 'Unknown patch' '
+    command_error stg patch-log bogus 2>err &&
+    grep -e "patch \`bogus\` does not exist" err
+'
+
+test_done
@@ -0,0 +1,96 @@
+#!/bin/sh
+
+test_description='Test revisions of earlier patch versions'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack' '
+    test_commit_bulk --message="p%s" 2 &&
+    stg uncommit -n 2 &&
+    stg id p1 >v0 &&
+    test_tick &&
+    stg goto p1 &&
+    echo "change" >>1.t &&
+    stg refresh &&
+    stg id p1 >v1 &&
+    test_tick &&
+    stg edit -m "p1 edited" &&
+    stg rename p1 first &&
+    stg push &&
+    stg id first >v2
+'
+
+test_expect_success 'Versions by number' '
+    test "$(stg id first@{0})" = "$(cat v2)" &&
+    test "$(stg id first@{1})" = "$(cat v1)" &&
+    test "$(stg id first@{2})" = "$(cat v0)" &&
+    command_error stg id first@{3} 2>err &&
+    grep -e "only 3 versions of patch .first. are recorded in the stack log" err
+'
+
+test_expect_success 'Versions by date' '
+    test "$(stg id "first@{$test_tick -0700}")" = "$(cat v2)" &&
+    test "$(stg id "first@{$(($test_tick - 1)) -0700}")" = "$(cat v1)" &&
+    test "$(stg id "first@{$(($test_tick - 61)) -0700}")" = "$(cat v0)" &&
+    command_error stg id "first@{2000-01-01}" 2>err &&
+    grep -e "no version of patch .first. is recorded in the stack log as of .2000-01-01." err
+'
+
+test_expect_success 'Version with git revision suffix' '
+    test "$(stg id first@{1}^)" = "$(git rev-parse $(cat v1)^)" &&
+    test "$(stg id {base}+@{2})" = "$(cat v0)"
+'
+
+test_expect_success 'Version of a patch in another branch' '
+    stg branch --create other &&
+    test "$(stg id master:first@{2})" = "$(cat v0)" &&
+    stg branch master
+'
+
+test_expect_success 'Invalid versions' '
+    command_error stg id first@{bogus} 2>err &&
+    grep -e "\`bogus\` is not a version number or date" err &&
+    command_error stg id {base}@{1} 2>err &&
+    grep -e "\`@{1}\` must follow a patch" err
+'
+
+test_expect_success 'Show earlier version' '
+    stg show first@{1} >out &&
+    grep -e "^commit $(cat v1)" out &&
+    grep -e "^    p1$" out &&
+    grep -e "^+change$" out
+'
+
+test_expect_success 'Diff against earlier version' '
+    stg diff -r first@{2} --stat >out &&
+    grep -e "1.t | 1 +" out &&
+    grep -e "2.t | 1 +" out
+'
+
+test_expect_success 'Files of earlier version' '
+    stg files first@{2} >out &&
+    echo "A 1.t" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Pick earlier version' '
+    stg pop -a &&
+    stg pick first@{1} &&
+    test "$(stg top)" = "p1" &&
+    test "$(git rev-parse HEAD^{tree})" = "$(git rev-parse $(cat v1)^{tree})"
+'
+
+test_expect_success 'Versions by number after rename' '
+    stg rename first renamed &&
+    test "$(stg id renamed@{0})" = "$(cat v2)" &&
+    test "$(stg id renamed@{1})" = "$(cat v1)" &&
+    test "$(stg id renamed@{2})" = "$(cat v0)" &&
+    command_error stg id renamed@{3} 2>err &&
+    grep -e "only 3 versions of patch .renamed. are recorded in the stack log" err &&
+    stg patch-log renamed >out &&
+    test_line_count = 3 out &&
+    head -n 1 out | grep -e "^3  $(cut -c 1-7 v2) " &&
+    tail -n 1 out | grep -e "^1  $(cut -c 1-7 v0) "
+'
+
+test_done
@@ -0,0 +1,166 @@
+#!/bin/sh
+
+test_description='Test continuing, skipping, and aborting conflicted operations'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup' '
+    echo base >file &&
+    echo other >other &&
+    stg add file other &&
+    git commit -m "add files" &&
+    stg init &&
+    stg new -m p1 p1 &&
+    echo p1 >file &&
+    stg refresh &&
+    stg new -m p2 p2 &&
+    echo p2 >file &&
+    stg refresh &&
+    stg new -m p3 p3 &&
+    echo p3 >other &&
+    stg refresh &&
+    stg pop -a
+'
+
+test_expect_success 'Attempt continue with no operation in progress' '
+    command_error stg push --continue 2>err &&
+    grep -e "no operation in progress" err
+'
+
+test_expect_success 'Push with conflict halts operation' '
+    conflict stg push p2 p3 2>err &&
+    grep -e "stg push --continue" err &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2" &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p1 p3"
+'
+
+test_expect_success 'Attempt continue with unresolved conflicts' '
+    command_error stg push --continue 2>err &&
+    grep -e "resolve outstanding conflicts first" err
+'
+
+test_expect_success 'Continue after resolving conflict' '
+    echo resolved >file &&
+    stg add file &&
+    stg push --continue &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2 p3" &&
+    test "$(stg top)" = "p3" &&
+    test "$(cat file)" = "resolved" &&
+    test "$(git show $(stg id p2):file)" = "resolved" &&
+    git diff --quiet HEAD &&
+    command_error stg push --continue 2>err &&
+    grep -e "no operation in progress" err
+'
+
+test_expect_success 'Skip conflicting patch' '
+    stg pop -a &&
+    stg push p1 &&
+    conflict stg push p2 p3 &&
+    stg push --skip &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p3" &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p2" &&
+    stg show p2 | grep -e "^+resolved\$" &&
+    test "$(cat file)" = "p1" &&
+    git diff --quiet HEAD
+'
+
+test_expect_success 'Abort restores original stack state' '
+    stg pop -a &&
+    stg push p1 &&
+    conflict stg push -a &&
+    test "$(stg top)" = "p2" &&
+    stg push --abort &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1" &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p3 p2" &&
+    test "$(cat file)" = "p1" &&
+    git diff --quiet HEAD &&
+    command_error stg push --abort 2>err &&
+    grep -e "no operation in progress" err
+'
+
+test_expect_success 'Goto with conflict may be continued' '
+    conflict stg goto p2 2>err &&
+    grep -e "stg goto --continue" err &&
+    echo resolved >file &&
+    stg add file &&
+    stg goto --continue &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p3 p2"
+'
+
+test_expect_success 'Operation may be resumed by another command' '
+    stg pop -a &&
+    conflict stg push p2 p3 &&
+    stg goto --skip &&
+    test "$(echo $(stg series --noprefix --applied))" = "p3"
+'
+
+test_expect_success 'Undo discards halted operation' '
+    stg pop -a &&
+    conflict stg push p2 p3 &&
+    stg undo --hard &&
+    stg new -m p4 p4 &&
+    echo edit >other &&
+    command_error stg push --abort 2>err &&
+    grep -e "no operation in progress" err &&
+    test "$(stg top)" = "p4" &&
+    test "$(cat other)" = "edit" &&
+    git checkout other &&
+    stg delete p4
+'
+
+test_expect_success 'Halted operation is not resumed after stack changes' '
+    conflict stg push p2 p3 &&
+    echo resolved >file &&
+    stg add file &&
+    stg refresh &&
+    echo edit >other &&
+    command_error stg push --abort 2>err &&
+    grep -e "the stack has changed since .push. halted" err &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2" &&
+    test "$(cat other)" = "edit" &&
+    command_error stg push --continue 2>err &&
+    grep -e "no operation in progress" err &&
+    git checkout other
+'
+
+test_expect_success 'Rebase with conflict may be continued' '
+    stg pop -a &&
+    git checkout -q -b upstream &&
+    echo upstream >file &&
+    git commit -q -a -m upstream &&
+    git checkout -q master &&
+    stg push p1 p3 &&
+    conflict stg rebase upstream 2>err &&
+    grep -e "stg rebase --continue" err &&
+    echo resolved >file &&
+    stg add file &&
+    stg rebase --continue &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p3" &&
+    test "$(git rev-parse $(stg id p1)~)" = "$(git rev-parse upstream)" &&
+    test "$(cat file)" = "resolved"
+'
+
+test_expect_success 'Interactive rebase stops are performed after continuing' '
+    stg branch --create exec-stops upstream~ &&
+    stg new -m a a &&
+    echo a >file &&
+    stg refresh &&
+    stg new -m c c &&
+    echo c >c.txt &&
+    stg add c.txt &&
+    stg refresh &&
+    write_script fake-editor <<-\EOF &&
+	printf "keep a\nexec echo one >>exec.log\nkeep c\nexec echo two >>exec.log\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    conflict stg rebase --interactive upstream &&
+    test_path_is_missing exec.log &&
+    echo resolved >file &&
+    stg add file &&
+    stg rebase --continue &&
+    test "$(echo $(cat exec.log))" = "one two" &&
+    test "$(echo $(stg series --noprefix --applied))" = "a c"
+'
+
+test_done
@@ -0,0 +1,157 @@
+#!/bin/sh
+
+test_description='Test patch dependency tracking'
+
+. ./test-lib.sh
+
+test_expect_success 'Attempt deps on uninitialized stack' '
+    command_error stg deps 2>err &&
+    grep -e "error: no patches applied" err
+'
+
+test_expect_success 'Setup patches' '
+    test_seq 1 30 >file &&
+    stg add file &&
+    git commit -m "add file" &&
+    stg new -m "change line 5" p1 &&
+    sed -i "s/^5\$/five/" file &&
+    stg refresh &&
+    stg new -m "change line 20" p2 &&
+    sed -i "s/^20\$/twenty/" file &&
+    stg refresh &&
+    stg new -m "change line 6" p3 &&
+    sed -i "s/^6\$/six/" file &&
+    stg refresh &&
+    stg new -m "add other file" p4 &&
+    echo other >other &&
+    stg add other &&
+    stg refresh
+'
+
+test_expect_success 'Show dependencies of all patches' '
+    cat >expected <<-\EOF &&
+	p1:
+	p2:
+	p3: p1
+	p4:
+	EOF
+    stg deps --all >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Show dependency tree' '
+    cat >expected <<-\EOF &&
+	p3
+	  p1 [overlap]
+	EOF
+    stg deps p3 >out &&
+    test_cmp expected out &&
+    cat >expected <<-\EOF &&
+	p1
+	  p3 [overlap]
+	EOF
+    stg deps --dependents p1 >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Pop refuses to strand dependent patch' '
+    command_error stg pop --deps=check p1 2>err &&
+    grep -e "patch \`p3\` depends on \`p1\` (overlap) which would not be applied" err &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p2 p3 p4"
+'
+
+test_expect_success 'Pop carries dependent patch' '
+    stg pop --deps p1 &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2 p4" &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p1 p3"
+'
+
+test_expect_success 'Dependencies of unapplied patches' '
+    stg deps --all >out &&
+    grep -e "^p3: p1\$" out
+'
+
+test_expect_success 'Push refuses to push without dependency' '
+    command_error stg push --deps=check p3 2>err &&
+    grep -e "patch \`p3\` depends on \`p1\` (overlap) which would not be applied" err &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p1 p3"
+'
+
+test_expect_success 'Push carries dependency' '
+    stg push --deps p3 &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2 p4 p1 p3" &&
+    test -z "$(stg series --noprefix --unapplied)"
+'
+
+test_expect_success 'Float refuses to reorder dependency above dependent' '
+    command_error stg float --deps=check p1 2>err &&
+    grep -e "patch \`p3\` depends on \`p1\` (overlap) but would be applied below it" err
+'
+
+test_expect_success 'Float carries dependent patch' '
+    stg float --deps p2 p1 &&
+    test "$(echo $(stg series --noprefix --applied))" = "p4 p2 p1 p3"
+'
+
+test_expect_success 'Sink carries dependency' '
+    stg sink --deps p3 &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p3 p4 p2"
+'
+
+test_expect_success 'Sink refuses to sink below dependency' '
+    command_error stg sink --deps -t p1 p3 2>err &&
+    grep -e "cannot sink below \`p1\` since patches being sunk depend on it" err
+'
+
+test_expect_success 'Default policy from config' '
+    test_config stgit.deps check &&
+    command_error stg pop p1 2>err &&
+    grep -e "patch \`p3\` depends on \`p1\`" err &&
+    stg pop --deps=carry p1 &&
+    stg push --deps=carry p3 &&
+    test_config stgit.deps bogus &&
+    command_error stg pop p1 2>err &&
+    grep -e "invalid dependency policy \`bogus\`" err
+'
+
+test_expect_success 'Declare dependency' '
+    stg deps --add p2 p4 &&
+    test "$(stg meta get -p p4 depends)" = "p2" &&
+    cat >expected <<-\EOF &&
+	p4
+	  p2 [declared]
+	EOF
+    stg deps p4 >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Attempt self dependency' '
+    command_error stg deps --add p4 p4 2>err &&
+    grep -e "patch \`p4\` cannot depend on itself" err
+'
+
+test_expect_success 'Declared dependency follows rename' '
+    stg rename p2 two &&
+    test "$(stg meta get -p p4 depends)" = "two" &&
+    stg rename two p2 &&
+    test "$(stg meta get -p p4 depends)" = "p2"
+'
+
+test_expect_success 'Pop carries declared dependent' '
+    stg pop --deps p2 &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p3" &&
+    stg push --deps p4 &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p3 p2 p4"
+'
+
+test_expect_success 'Remove declared dependency' '
+    command_error stg deps --remove p1 p4 2>err &&
+    grep -e "patch \`p4\` does not declare a dependency on \`p1\`" err &&
+    stg deps --remove p2 p4 &&
+    command_error stg meta get -p p4 depends &&
+    stg deps p4 >out &&
+    echo p4 >expected &&
+    test_cmp expected out
+'
+
+test_done
@@ -0,0 +1,143 @@
+#!/bin/sh
+
+test_description='Test importing review trailers from email replies'
+
+. ./test-lib.sh
+
+# Write an mbox entry with the given In-Reply-To, References, and Subject headers,
+# followed by a body with the remaining arguments as lines.
+write_reply () {
+    in_reply_to="$1" &&
+    references="$2" &&
+    subject="$3" &&
+    shift 3 &&
+    echo "From reviewer@example.com Mon Sep 17 00:00:00 2001" &&
+    echo "From: Rev Iewer <reviewer@example.com>" &&
+    echo "Date: Mon, 1 Jan 2024 12:00:00 +0000" &&
+    echo "Subject: $subject" &&
+    if test -n "$in_reply_to"
+    then
+        echo "In-Reply-To: $in_reply_to"
+    fi &&
+    if test -n "$references"
+    then
+        echo "References: $references"
+    fi &&
+    echo &&
+    printf "%s\n" "$@" &&
+    echo
+}
+
+test_expect_success 'Setup stack and send version 1' '
+    test_commit_bulk --message="base%s" 1 &&
+    stg init &&
+    for i in 1 2 3
+    do
+        echo "line $i" >file$i.txt &&
+        git add file$i.txt &&
+        stg new -m "Add file$i" p$i &&
+        stg refresh --index || return 1
+    done &&
+    stg email format -o v1 --cover-letter --all &&
+    for i in 0 1 2 3
+    do
+        sed -n -e "s/^Message-Id: //p" v1/000$i-*.patch >id$i || return 1
+    done &&
+    test_line_count = 1 id1
+'
+
+test_expect_success 'Modify a patch after sending' '
+    stg goto p2 &&
+    echo "more" >>file2.txt &&
+    stg refresh &&
+    stg goto p3
+'
+
+test_expect_success 'Import trailers from replies' '
+    {
+        write_reply "$(cat id1)" "$(cat id0) $(cat id1)" "Re: [PATCH 1/3] Add file1" \
+            "> Signed-off-by: Quo Ted <quoted@example.com>" \
+            "> Acked-by: Quo Ted <quoted@example.com>" \
+            "" \
+            "Looks good." \
+            "" \
+            "Reviewed-by: Rev Iewer <reviewer@example.com>" \
+            "" \
+            "-- " \
+            "Tested-by: Sig Nature <signature@example.com>" &&
+        write_reply "$(cat id2)" "$(cat id0) $(cat id2)" "Re: [PATCH 2/3] Add file2" \
+            "Acked-by: Ack Er <acker@example.com>" \
+            "Tested-by: Tes Ter <tester@example.com>" &&
+        write_reply "<reply.nested@example.com>" "$(cat id0) $(cat id3) <reply.nested@example.com>" \
+            "Re: [PATCH 3/3] Add file3" \
+            "Reported-by: Re Porter <reporter@example.com>" &&
+        write_reply "$(cat id0)" "$(cat id0)" "Re: [PATCH 0/3] *** SUBJECT HERE ***" \
+            "Reviewed-by: Cov Er <cover@example.com>" &&
+        write_reply "" "" "Re: [PATCH v1 1/3] Add file1" \
+            "Tested-by: Sub Ject <subject@example.com>" &&
+        write_reply "<unknown@example.com>" "" "Re: [PATCH] Something else" \
+            "Acked-by: Un Known <unknown@example.com>"
+    } >replies.mbox &&
+    old_tree=$(git rev-parse HEAD^{tree}) &&
+    stg import --trailers-from replies.mbox 2>err &&
+    grep -e "info: ignoring reply .Something else." err &&
+    test "$(git rev-parse HEAD^{tree})" = "$old_tree" &&
+    test "$(stg top)" = "p3" &&
+    stg series --applied --noprefix >applied &&
+    test_line_count = 3 applied &&
+
+    git log -n 1 --format=%B "$(stg id p1)" >msg1 &&
+    cat >expected <<-\EOF &&
+	Add file1
+
+	Reviewed-by: Rev Iewer <reviewer@example.com>
+	Tested-by: Sub Ject <subject@example.com>
+
+	EOF
+    test_cmp expected msg1 &&
+
+    git log -n 1 --format=%B "$(stg id p2)" >msg2 &&
+    cat >expected <<-\EOF &&
+	Add file2
+
+	Acked-by: Ack Er <acker@example.com>
+	Tested-by: Tes Ter <tester@example.com>
+
+	EOF
+    test_cmp expected msg2 &&
+
+    git log -n 1 --format=%B "$(stg id p3)" >msg3 &&
+    cat >expected <<-\EOF &&
+	Add file3
+
+	Reported-by: Re Porter <reporter@example.com>
+
+	EOF
+    test_cmp expected msg3
+'
+
+test_expect_success 'Existing trailers are not added again' '
+    git rev-parse refs/patches/master/p1 refs/patches/master/p2 refs/patches/master/p3 >before &&
+    stg import --trailers-from replies.mbox 2>err &&
+    grep -e "info: no new trailers found" err &&
+    git rev-parse refs/patches/master/p1 refs/patches/master/p2 refs/patches/master/p3 >after &&
+    test_cmp before after
+'
+
+test_expect_success 'Trailers are added to unapplied patches' '
+    stg pop p2 p3 &&
+    write_reply "$(cat id3)" "" "Re: [PATCH 3/3] Add file3" \
+        "Acked-by: Ack Er <acker@example.com>" >more.mbox &&
+    stg import --trailers-from more.mbox &&
+    stg series --unapplied --noprefix >unapplied &&
+    test_line_count = 2 unapplied &&
+    git log -n 1 --format=%B "$(stg id p3)" >msg3 &&
+    grep -e "^Acked-by: Ack Er <acker@example.com>" msg3
+'
+
+test_expect_success 'Trailers cannot be imported with a patch source' '
+    general_error stg import --trailers-from more.mbox some.patch 2>err &&
+    grep -e "the argument .--trailers-from <mbox>. cannot be used with .\[source\]." err
+'
+
+test_done
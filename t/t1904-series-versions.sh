@@ -0,0 +1,162 @@
+#!/bin/sh
+
+test_description='Test patch series version snapshots'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3
+'
+
+test_expect_success 'No versions listed initially' '
+    stg snapshot --list >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Snapshot first version' '
+    stg snapshot &&
+    test "$(git rev-parse refs/stacks/master.versions/v1)" = "$(git rev-parse refs/stacks/master)" &&
+    stg snapshot --list >out &&
+    echo v1 >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Attempt to snapshot existing version' '
+    command_error stg snapshot v1 2>err &&
+    grep -e "version \`v1\` already exists" err
+'
+
+test_expect_success 'Attempt to snapshot invalid version' '
+    general_error stg snapshot v0 2>err &&
+    grep -e "invalid series version \`v0\`" err
+'
+
+test_expect_success 'Snapshot next and explicit versions' '
+    stg edit -m "p2 changed" p2 &&
+    stg snapshot &&
+    stg snapshot 5 &&
+    stg snapshot --list >out &&
+    cat >expected <<-\EOF &&
+	v1
+	v2
+	v5
+	EOF
+    test_cmp expected out &&
+    test "$(git rev-parse refs/stacks/master.versions/v5)" = "$(git rev-parse refs/stacks/master)"
+'
+
+test_expect_success 'Force replace existing version' '
+    stg edit -m "p3 changed" p3 &&
+    stg snapshot --force v5 &&
+    test "$(git rev-parse refs/stacks/master.versions/v5)" = "$(git rev-parse refs/stacks/master)"
+'
+
+test_expect_success 'Series list still works with patch arguments' '
+    stg series --noprefix p1..p2 >out &&
+    printf "p1\np2\n" >expected &&
+    test_cmp expected out
+'
+
+test_expect_success 'Series lists a patch named snapshot' '
+    stg new -m "snapshot patch" snapshot &&
+    stg series --noprefix snapshot >out &&
+    echo snapshot >expected &&
+    test_cmp expected out &&
+    stg snapshot --list >out &&
+    printf "v1\nv2\nv5\n" >expected &&
+    test_cmp expected out &&
+    stg delete snapshot
+'
+
+test_expect_success 'Interdiff between versions' '
+    stg interdiff v1 v2 >out &&
+    grep -e "^1:  [0-9a-f]\{7\} = 1:  [0-9a-f]\{7\} p1\$" out &&
+    grep -e "^2:  [0-9a-f]\{7\} ! 2:  [0-9a-f]\{7\} p2\$" out &&
+    grep -e "^3:  [0-9a-f]\{7\} = 3:  [0-9a-f]\{7\} p3\$" out
+'
+
+test_expect_success 'Rename branch keeps versions' '
+    stg branch --rename master renamed &&
+    stg snapshot --list >out &&
+    printf "v1\nv2\nv5\n" >expected &&
+    test_cmp expected out &&
+    test_must_fail git rev-parse --verify -q refs/stacks/master.versions/v1 &&
+    stg branch --rename renamed master
+'
+
+test_expect_success 'Delete branch removes versions' '
+    stg branch --clone cloned &&
+    stg snapshot &&
+    git rev-parse --verify -q refs/stacks/cloned.versions/v1 &&
+    stg branch master &&
+    stg branch --delete --force cloned &&
+    test_must_fail git rev-parse --verify -q refs/stacks/cloned.versions/v1
+'
+
+test_expect_success 'Setup fresh stack for email' '
+    stg branch --create email &&
+    test_commit_bulk --filename=e%s.txt --message="e%s" 2 &&
+    stg uncommit -n 2
+'
+
+test_expect_success 'Format first version' '
+    stg email format -o patches --all &&
+    test_path_exists patches/0001-e1.patch &&
+    stg snapshot --list >versions &&
+    echo v1 >expected &&
+    test_cmp expected versions &&
+    rm -r patches
+'
+
+test_expect_success 'Format unchanged series reuses version' '
+    stg email format -o patches --all &&
+    test_path_exists patches/0001-e1.patch &&
+    stg snapshot --list >versions &&
+    test_cmp expected versions &&
+    rm -r patches
+'
+
+test_expect_success 'Format changed series as next version' '
+    stg edit -m "e2 revised" e2 &&
+    stg email format -o patches --cover-letter --all &&
+    test_path_exists patches/v2-0001-e1.patch &&
+    grep -e "^Subject: \[PATCH v2 2/2\] e2 revised" patches/v2-0002-e2.patch &&
+    grep -e "^Range-diff against v1:" patches/v2-0000-cover-letter.patch &&
+    printf "v1\nv2\n" >expected &&
+    stg snapshot --list >versions &&
+    test_cmp expected versions &&
+    test "$(git rev-parse refs/stacks/email.versions/v2)" = "$(git rev-parse refs/stacks/email)" &&
+    rm -r patches
+'
+
+test_expect_success 'Format with explicit range-diff' '
+    stg edit -m "e1 revised" e1 &&
+    stg email format -o patches --cover-letter --range-diff=HEAD~1 --all &&
+    test_path_exists patches/v3-0000-cover-letter.patch &&
+    test "$(grep -c -e "^Range-diff" patches/v3-0000-cover-letter.patch)" = "1" &&
+    rm -r patches
+'
+
+test_expect_success 'Format with explicit reroll count' '
+    stg email format -o patches -v 7 --all &&
+    test_path_exists patches/v7-0001-e1.patch &&
+    git rev-parse --verify -q refs/stacks/email.versions/v7 &&
+    rm -r patches
+'
+
+test_expect_success 'Format without snapshot' '
+    stg edit -m "e1 revised again" e1 &&
+    stg email format -o patches --no-snapshot --all &&
+    test_path_exists patches/0001-e1.patch &&
+    test_must_fail git rev-parse --verify -q refs/stacks/email.versions/v8 &&
+    rm -r patches
+'
+
+test_expect_success GITSENDEMAIL 'Send dry run does not snapshot' '
+    stg email send --dry-run --to someone@example.com --all >out &&
+    grep -e "Subject: \[PATCH v8 1/2\]" out &&
+    test_must_fail git rev-parse --verify -q refs/stacks/email.versions/v8
+'
+
+test_done
@@ -0,0 +1,129 @@
+#!/bin/sh
+
+test_description='Test stg cover'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack' '
+    test_commit_bulk --message="p%s" 2 &&
+    stg uncommit -n 2
+'
+
+test_expect_success 'Attempt show without cover letter' '
+    command_error stg cover show 2>err &&
+    grep -e "no cover letter for branch \`master\`" err
+'
+
+test_expect_success 'Edit cover letter from file' '
+    cat >cover.txt <<-\EOF &&
+
+	The series title
+
+	The series description.
+
+	Second paragraph.
+
+	Changes:
+	- first change
+	EOF
+    stg cover edit --file cover.txt &&
+    stg cover show >out &&
+    cat >expected <<-\EOF &&
+	The series title
+
+	The series description.
+
+	Second paragraph.
+
+	Changes:
+	- first change
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Cover letter is recorded in stack state' '
+    git show refs/stacks/master:stack.json >stack.json &&
+    grep -e "\"title\": \"The series title\"" stack.json &&
+    stg log -n 1 >log &&
+    grep -e "cover edit" log
+'
+
+test_expect_success 'Edit cover letter with editor' '
+    write_script fake-editor <<-\EOF &&
+	sed -i -e "s/The series title/Edited title/" -e "/^- first change/a - second change" "$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    stg cover edit &&
+    stg cover show >out &&
+    head -n 1 out >title &&
+    echo "Edited title" >expected &&
+    test_cmp expected title &&
+    grep -e "^- second change" out &&
+    test_path_is_missing .stgit-cover.txt
+'
+
+test_expect_success 'Editor instructions are stripped' '
+    write_script fake-editor <<-\EOF &&
+	cat "$1" >editor-input
+	EOF
+    stg cover edit &&
+    grep -e "^# Please enter the cover letter" editor-input &&
+    stg cover show >out &&
+    ! grep -e "^#" out
+'
+
+test_expect_success 'Empty cover letter aborts edit' '
+    write_script fake-editor <<-\EOF &&
+	grep -v -e "^[^#]" "$1" >"$1.tmp" && mv "$1.tmp" "$1"
+	EOF
+    command_error stg cover edit 2>err &&
+    grep -e "aborting due to empty cover letter" err &&
+    stg cover show >out &&
+    grep -e "^Edited title" out
+'
+
+test_expect_success 'Undo cover letter edit' '
+    stg undo &&
+    stg cover show >out &&
+    grep -e "^The series title" out &&
+    ! grep -e "second change" out &&
+    stg redo &&
+    stg cover show >out &&
+    grep -e "^Edited title" out
+'
+
+test_expect_success 'Cover letter fills in format-patch cover letter' '
+    stg email format -o patches --cover-letter --all &&
+    grep -e "^Subject: \[PATCH 0/2\] Edited title" patches/0000-cover-letter.patch &&
+    grep -e "^The series description." patches/0000-cover-letter.patch &&
+    grep -e "^Changes:" patches/0000-cover-letter.patch &&
+    ! grep -e "\*\*\*" patches/0000-cover-letter.patch &&
+    rm -r patches
+'
+
+test_expect_success 'Changes are relative to the previous version' '
+    stg edit -m "p2 changed" p2 &&
+    stg email format -o patches --cover-letter --all >out &&
+    grep -e "^Subject: \[PATCH v2 0/2\] Edited title" patches/v2-0000-cover-letter.patch &&
+    grep -e "^Changes since v1:" patches/v2-0000-cover-letter.patch &&
+    grep -e "patches/v2-0000-cover-letter.patch" out &&
+    rm -r patches
+'
+
+test_expect_success 'Quiet format with cover letter' '
+    stg email format -o patches --cover-letter --quiet --all >out &&
+    test_must_be_empty out &&
+    grep -e "^Subject: \[PATCH v2 0/2\] Edited title" patches/v2-0000-cover-letter.patch &&
+    rm -r patches
+'
+
+test_expect_success 'Delete cover letter' '
+    stg cover delete &&
+    command_error stg cover show &&
+    command_error stg cover delete 2>err &&
+    grep -e "no cover letter" err &&
+    stg email format -o patches --cover-letter --all &&
+    grep -e "\*\*\* SUBJECT HERE \*\*\*" patches/v2-0000-cover-letter.patch
+'
+
+test_done
@@ -0,0 +1,126 @@
+#!/bin/sh
+
+test_description='Test native email generation by stg email format'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup StGit stack' '
+    test_commit_bulk --message="base%s" 1 &&
+    stg init &&
+    echo "one" >one.txt &&
+    git add one.txt &&
+    stg new -m "Añadir función uno
+
+Description of the first patch." first &&
+    stg refresh --index &&
+    git notes add -m "Note for reviewers" &&
+    echo "two" >two.txt &&
+    git add two.txt &&
+    stg new -m "Add file two" second &&
+    stg refresh --index
+'
+
+test_expect_success 'File names use patch names' '
+    stg email format -o patches --no-snapshot --all >out &&
+    cat >expected <<-\EOF &&
+	patches/0001-first.patch
+	patches/0002-second.patch
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Headers are encoded' '
+    grep -e "^Subject: \[PATCH 1/2\] =?UTF-8?q?A=C3=B1adir=20funci=C3=B3n=20uno?=" patches/0001-first.patch &&
+    grep -e "^Content-Type: text/plain; charset=UTF-8" patches/0001-first.patch &&
+    grep -e "^Subject: \[PATCH 2/2\] Add file two" patches/0002-second.patch &&
+    ! grep -e "^MIME-Version" patches/0002-second.patch
+'
+
+test_expect_success 'Notes are included after three-dash line' '
+    sed -n -e "/^---\$/,/^diff/p" patches/0001-first.patch >section &&
+    grep -e "^Notes:" section &&
+    grep -e "^    Note for reviewers" section &&
+    stg email format -o no-notes --no-snapshot --no-notes --all &&
+    ! grep -e "Note for reviewers" no-notes/0001-first.patch
+'
+
+test_expect_success 'Message ids are deterministic' '
+    grep -e "^Message-Id: <stgit\.$(stg id first)@" patches/0001-first.patch &&
+    grep -e "^Message-Id:" patches/0002-second.patch >id-before &&
+    rm -r patches &&
+    stg email format -o patches --no-snapshot --all &&
+    grep -e "^Message-Id:" patches/0002-second.patch >id-after &&
+    test_cmp id-before id-after &&
+    stg email format -o rerolled --no-snapshot -v 2 --all &&
+    grep -e "^Message-Id: <stgit\.$(stg id second)\.v2@" rerolled/v2-0002-second.patch
+'
+
+test_expect_success 'Formatted patches apply with git am' '
+    git checkout -b applied "$(stg id {base})" &&
+    test_when_finished "git checkout master && git branch -D applied" &&
+    git am patches/0001-first.patch patches/0002-second.patch &&
+    git log -n 2 --format="%an <%ae> %ad%n%B" >applied-log &&
+    git log -n 2 --format="%an <%ae> %ad%n%B" master >expected-log &&
+    test_cmp expected-log applied-log &&
+    test "$(git rev-parse HEAD^{tree})" = "$(git rev-parse master^{tree})"
+'
+
+test_expect_success 'Subject template' '
+    echo "[%(prefix)s] %(patchname)s: %(shortdescr)s" >.git/emailsubject.tmpl &&
+    test_when_finished "rm .git/emailsubject.tmpl" &&
+    stg email format -o templated --no-snapshot second &&
+    grep -e "^Subject: \[PATCH\] second: Add file two" templated/0001-second.patch
+'
+
+test_expect_success 'Keep subject bypasses template' '
+    stg email format -o kept --no-snapshot -k second &&
+    grep -e "^Subject: Add file two" kept/0001-second.patch
+'
+
+test_expect_success 'Deep threading' '
+    stg email format -o threaded --no-snapshot --thread=deep --cover-letter --all &&
+    cover_id=$(sed -n -e "s/^Message-Id: //p" threaded/0000-cover-letter.patch) &&
+    first_id=$(sed -n -e "s/^Message-Id: //p" threaded/0001-first.patch) &&
+    grep -e "^In-Reply-To: $cover_id" threaded/0001-first.patch &&
+    grep -e "^In-Reply-To: $first_id" threaded/0002-second.patch
+'
+
+test_expect_success 'Cover letter shortlog and diffstat' '
+    grep -e "^Subject: \[PATCH 0/2\] \*\*\* SUBJECT HERE \*\*\*" threaded/0000-cover-letter.patch &&
+    grep -e "^$GIT_AUTHOR_NAME (2):" threaded/0000-cover-letter.patch &&
+    grep -e "^  Add file two" threaded/0000-cover-letter.patch &&
+    grep -e "^ 2 files changed, 2 insertions(+)" threaded/0000-cover-letter.patch
+'
+
+test_expect_success 'Recipients, sign-off, signature, and base' '
+    test_config format.cc "config-cc@example.com" &&
+    stg email format -o addressed --no-snapshot -s --signature "my sig" \
+        --to to@example.com --cc cc@example.com --base=auto second &&
+    grep -e "^To: to@example.com" addressed/0001-second.patch &&
+    grep -e "^Cc: config-cc@example.com," addressed/0001-second.patch &&
+    grep -e "^    cc@example.com" addressed/0001-second.patch &&
+    grep -e "^Signed-off-by: $GIT_COMMITTER_NAME <$GIT_COMMITTER_EMAIL>" addressed/0001-second.patch &&
+    grep -e "^base-commit: $(stg id first)" addressed/0001-second.patch &&
+    tail -n 3 addressed/0001-second.patch >tail &&
+    printf -- "-- \nmy sig\n\n" >expected &&
+    test_cmp expected tail &&
+    stg email format -o no-cc --no-snapshot --no-cc second &&
+    ! grep -e "^Cc:" no-cc/0001-second.patch
+'
+
+test_expect_success 'Single patch range-diff commentary' '
+    old_second=$(stg id second) &&
+    stg edit -m "Add file two, revised" second &&
+    stg email format -o single --no-snapshot --range-diff="$old_second" second &&
+    sed -n -e "/^---\$/,/^diff/p" single/0001-second.patch >section &&
+    grep -e "^Range-diff:" section &&
+    grep -e "^1:  [0-9a-f]\{7\} ! 1:  [0-9a-f]\{7\} Add file two" section
+'
+
+test_expect_success 'Attach falls back to git format-patch' '
+    stg email format -o attached --no-snapshot --attach second &&
+    test_path_exists attached/0001-Add-file-two-revised.patch &&
+    grep -e "^Content-Disposition: attachment" attached/0001-Add-file-two-revised.patch
+'
+
+test_done
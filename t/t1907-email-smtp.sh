@@ -0,0 +1,184 @@
+#!/bin/sh
+
+test_description='Test stg email send with the built-in transports'
+
+. ./test-lib.sh
+. "$TEST_DIRECTORY"/lib-smtp.sh
+
+if ! test_have_prereq SMTPSINK
+then
+	skip_all='skipping SMTP tests; IO::Socket::INET not available'
+	test_done
+fi
+
+test_expect_success 'Setup StGit stack and SMTP sink' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    start_smtp_sink &&
+    git config stgit.email.transport smtp
+'
+
+test_expect_success 'Invalid transport' '
+    git config stgit.email.transport carrier-pigeon &&
+    test_when_finished "git config stgit.email.transport smtp" &&
+    command_error stg email send --to to@example.com --no-snapshot --all 2>err &&
+    grep -e "invalid stgit.email.transport \`carrier-pigeon\`" err
+'
+
+test_expect_success 'Dry run does not send' '
+    stg email send --dry-run --to to@example.com --all >out &&
+    grep "Subject: " out >subjects &&
+    cat >expected <<-\EOF &&
+	Subject: [PATCH 1/3] p1
+	Subject: [PATCH 2/3] p2
+	Subject: [PATCH 3/3] p3
+	EOF
+    test_cmp expected subjects &&
+    grep -e "^Dry-OK. Log says:" out &&
+    grep -e "^RCPT TO:<to@example.com>" out &&
+    test_path_is_missing sink/msg-1 &&
+    stg snapshot --list >versions &&
+    test_must_be_empty versions
+'
+
+test_expect_success 'Send patches over SMTP' '
+    stg email send --to to@example.com --cc cc@example.com --bcc bcc@example.com \
+        --quiet --all >out &&
+    cat >expected <<-\EOF &&
+	Sent [PATCH 1/3] p1
+	Sent [PATCH 2/3] p2
+	Sent [PATCH 3/3] p3
+	EOF
+    test_cmp expected out &&
+    test_path_exists sink/msg-3 &&
+    test_path_is_missing sink/msg-4 &&
+    cat >expected-envelope <<-\EOF &&
+	MAIL FROM: committer@example.com
+	RCPT TO: to@example.com
+	RCPT TO: cc@example.com
+	RCPT TO: bcc@example.com
+
+	EOF
+    head -n 5 sink/msg-1 >envelope &&
+    test_cmp expected-envelope envelope &&
+    grep -e "^To: to@example.com" sink/msg-1 &&
+    grep -e "^Cc: cc@example.com" sink/msg-1 &&
+    ! grep -e "^Bcc:" sink/msg-1 &&
+    grep -e "^Subject: \[PATCH 1/3\] p1" sink/msg-1 &&
+    stg snapshot --list >versions &&
+    test_line_count = 1 versions
+'
+
+test_expect_success 'Sent emails are threaded' '
+    first_id=$(sed -n -e "s/^Message-Id: //p" sink/msg-1) &&
+    test -n "$first_id" &&
+    ! grep -e "^In-Reply-To:" sink/msg-1 &&
+    grep -e "^In-Reply-To: $first_id" sink/msg-2 &&
+    grep -e "^In-Reply-To: $first_id" sink/msg-3
+'
+
+test_expect_success 'Author is preserved in body' '
+    sed -e "1,/^\$/d" sink/msg-1 >message &&
+    sed -e "1,/^\$/d" message | head -n 1 >body-from &&
+    echo "From: $GIT_AUTHOR_NAME <$GIT_AUTHOR_EMAIL>" >expected &&
+    test_cmp expected body-from
+'
+
+test_expect_success 'Sent emails apply with git am' '
+    git checkout -b applied "$(stg id {base})" &&
+    test_when_finished "git checkout master && git branch -D applied" &&
+    for i in 1 2 3
+    do
+        sed -e "1,/^\$/d" sink/msg-$i >msg-$i || return 1
+    done &&
+    git am msg-1 msg-2 msg-3 &&
+    git log -n 3 --format="%an <%ae>%n%B" >applied-log &&
+    git log -n 3 --format="%an <%ae>%n%B" master >expected-log &&
+    test_cmp expected-log applied-log &&
+    test "$(git rev-parse HEAD^{tree})" = "$(git rev-parse master^{tree})"
+'
+
+test_expect_success 'Refuse authentication over unencrypted connection' '
+    rm -f sink/msg-* sink/auth &&
+    stg email format -o outgoing --no-snapshot p2 &&
+    test_config sendemail.smtpUser user &&
+    test_config sendemail.smtpPass secret &&
+    test_config sendemail.to config-to@example.com &&
+    command_error stg email send outgoing 2>err &&
+    grep -e "refusing to send SMTP credentials over an unencrypted connection" err &&
+    test_path_is_missing sink/msg-1 &&
+    test_path_is_missing sink/auth
+'
+
+test_expect_success 'Send email files with authentication' '
+    rm -f sink/msg-* &&
+    stg email format -o outgoing --no-snapshot p2 &&
+    test_config sendemail.smtpUser user &&
+    test_config sendemail.smtpPass secret &&
+    test_config stgit.email.smtp-plaintext-auth true &&
+    test_config sendemail.to config-to@example.com &&
+    test_config sendemail.from "Sender <sender@example.com>" &&
+    stg email send --in-reply-to "<parent@example.com>" outgoing >out &&
+    grep -e "^OK. Log says:" out &&
+    grep -e "^MAIL FROM: sender@example.com" sink/msg-1 &&
+    grep -e "^RCPT TO: config-to@example.com" sink/msg-1 &&
+    grep -e "^From: Sender <sender@example.com>" sink/msg-1 &&
+    grep -e "^In-Reply-To: <parent@example.com>" sink/msg-1 &&
+    printf "\0user\0secret" | base64 >expected-auth &&
+    test_cmp expected-auth sink/auth
+'
+
+test_expect_success 'Confirmation prompt' '
+    rm -f sink/msg-* &&
+    printf "n\ny\nq\n" |
+    stg email send --confirm=always --no-snapshot --to to@example.com --all >out &&
+    grep -e "^Send this email?" out &&
+    test_path_exists sink/msg-1 &&
+    test_path_is_missing sink/msg-2 &&
+    grep -e "^Subject: \[PATCH 2/3\] p2" sink/msg-1
+'
+
+test_expect_success 'Options requiring git send-email are rejected' '
+    command_error stg email send --compose --to to@example.com --all 2>err &&
+    grep -e "'"'"'--compose'"'"' is not supported with the \`smtp\` email transport" err
+'
+
+test_expect_success 'Missing recipients' '
+    command_error stg email send --no-snapshot --all 2>err &&
+    grep -e "no recipients for email" err
+'
+
+test_expect_success 'Encrypted SMTP is not supported' '
+    test_config sendemail.smtpEncryption tls &&
+    command_error stg email send --no-snapshot --to to@example.com --all 2>err &&
+    grep -e "is not supported by the built-in SMTP client" err
+'
+
+test_expect_success 'Send with sendmail program' '
+    write_script fake-sendmail <<-\EOF &&
+	printf "%s\n" "$@" >sendmail-args
+	cat >sendmail-message
+	EOF
+    git config stgit.email.transport sendmail &&
+    test_when_finished "git config stgit.email.transport smtp" &&
+    test_config sendemail.sendmailCmd "\"$(pwd)/fake-sendmail\"" &&
+    stg email send --no-snapshot --to to@example.com --cc cc@example.com p3 &&
+    cat >expected <<-\EOF &&
+	-i
+	to@example.com
+	cc@example.com
+	EOF
+    test_cmp expected sendmail-args &&
+    grep -e "^Subject: \[PATCH\] p3" sendmail-message
+'
+
+test_expect_success 'Absolute smtpServer path is a sendmail program' '
+    rm -f sendmail-args &&
+    git config sendemail.smtpServer "$(pwd)/fake-sendmail" &&
+    stg email send --no-snapshot --to to@example.com p1 &&
+    echo "-i" >expected &&
+    echo "to@example.com" >>expected &&
+    test_cmp expected sendmail-args
+'
+
+test_done
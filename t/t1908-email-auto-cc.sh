@@ -0,0 +1,122 @@
+#!/bin/sh
+
+test_description='Test automatic Cc addresses for stg email'
+
+. ./test-lib.sh
+. "$TEST_DIRECTORY"/lib-smtp.sh
+
+# Output the Cc header, including continuation lines, of the given email file.
+email_cc () {
+    sed -n -e "/^\$/q" -e "/^Cc:/,/^[^ ]/p" "$1" | grep -e "^Cc:" -e "^ "
+}
+
+test_expect_success 'Setup StGit stack' '
+    test_commit_bulk --message="base%s" 1 &&
+    stg init &&
+    echo "one" >one.txt &&
+    git add one.txt &&
+    stg new -m "Add file one
+
+Signed-off-by: Dev One <dev1@example.com>
+Reviewed-by: Rev Iewer <reviewer@example.com>
+Tested-by: Tes Ter <tester@example.com>
+Signed-off-by: $GIT_COMMITTER_NAME <$GIT_COMMITTER_EMAIL>" first &&
+    stg refresh --index &&
+    echo "two" >two.txt &&
+    git add two.txt &&
+    stg new -m "Add file two
+
+Acked-by: Ack Er <acker@example.com>
+Signed-off-by: Dev One <DEV1@example.com>" second &&
+    stg refresh --index &&
+    write_script get-maintainer <<-\EOF
+	if grep -q "^+++ b/two.txt" "$1"
+	then
+	    echo "Main Tainer <maintainer@example.com> (maintainer:TWO)"
+	    echo "list@example.com (open list)"
+	fi
+	EOF
+'
+
+test_expect_success 'No automatic Cc by default' '
+    stg email format -o default --no-snapshot --all &&
+    ! grep -e "^Cc:" default/0001-first.patch &&
+    ! grep -e "^Cc:" default/0002-second.patch
+'
+
+test_expect_success 'Cc addresses from trailers' '
+    stg email format -o trailers --no-snapshot --auto-cc --all &&
+    email_cc trailers/0001-first.patch >cc &&
+    grep -e "Dev One <dev1@example.com>" cc &&
+    grep -e "Rev Iewer <reviewer@example.com>" cc &&
+    ! grep -e "tester@example.com" cc &&
+    ! grep -e "committer@example.com" cc &&
+    email_cc trailers/0002-second.patch >cc &&
+    grep -e "Ack Er <acker@example.com>" cc &&
+    grep -i -e "dev1@example.com" cc >dev1 &&
+    test_line_count = 1 dev1
+'
+
+test_expect_success 'Configured trailers and Cc command' '
+    test_config stgit.email.auto-cc true &&
+    test_config stgit.email.auto-cc-trailers "Tested-by" &&
+    test_config stgit.email.cc-cmd "\"$(pwd)/get-maintainer\"" &&
+    stg email format -o configured --no-snapshot --cc dev1@example.com --all &&
+    email_cc configured/0001-first.patch >cc &&
+    grep -e "Tes Ter <tester@example.com>" cc &&
+    ! grep -e "reviewer@example.com" cc &&
+    ! grep -e "maintainer@example.com" cc &&
+    email_cc configured/0002-second.patch >cc &&
+    grep -e "Main Tainer <maintainer@example.com>" cc &&
+    grep -e "list@example.com" cc &&
+    ! grep -e "(maintainer:TWO)" cc &&
+    grep -e "dev1@example.com" cc >dev1 &&
+    test_line_count = 1 dev1 &&
+    stg email format -o disabled --no-snapshot --no-auto-cc --all &&
+    ! grep -e "^Cc:" disabled/0002-second.patch
+'
+
+test_expect_success 'Cover letter is Cc-ed to all addresses' '
+    stg email format -o cover --no-snapshot --cover-letter \
+        --cc-cmd "\"$(pwd)/get-maintainer\"" --all &&
+    email_cc cover/0000-cover-letter.patch >cc &&
+    grep -e "dev1@example.com" cc &&
+    grep -e "reviewer@example.com" cc &&
+    grep -e "acker@example.com" cc &&
+    grep -e "maintainer@example.com" cc
+'
+
+test_expect_success 'Failing Cc command' '
+    command_error stg email format -o failed --no-snapshot --cc-cmd false --all 2>err &&
+    grep -e "\`false\` failed" err
+'
+
+test_expect_success 'Cc addresses with git format-patch' '
+    stg email format -o attached --no-snapshot --attach --auto-cc second &&
+    grep -e "^Content-Disposition: attachment" attached/0001-Add-file-two.patch &&
+    email_cc attached/0001-Add-file-two.patch >cc &&
+    grep -e "Ack Er <acker@example.com>" cc
+'
+
+test_expect_success SMTPSINK 'Send with automatic Cc addresses' '
+    start_smtp_sink &&
+    test_config stgit.email.transport smtp &&
+    stg email send --no-snapshot --confirm=never --to to@example.com --auto-cc --all &&
+    grep -e "^RCPT TO: reviewer@example.com" sink/msg-1 &&
+    ! grep -e "^RCPT TO: acker@example.com" sink/msg-1 &&
+    grep -e "^RCPT TO: acker@example.com" sink/msg-2 &&
+    ! grep -e "^RCPT TO: committer@example.com" sink/msg-2
+'
+
+test_expect_success SMTPSINK 'Automatic Cc addresses are confirmed' '
+    rm -f sink/msg-* &&
+    test_config stgit.email.transport smtp &&
+    echo q | stg email send --no-snapshot --to to@example.com --auto-cc --all >out &&
+    grep -e "^Send this email?" out &&
+    test_path_is_missing sink/msg-1 &&
+    stg email send --no-snapshot --to to@example.com --all </dev/null >out &&
+    ! grep -e "^Send this email?" out &&
+    test_path_exists sink/msg-2
+'
+
+test_done
@@ -0,0 +1,155 @@
+#!/bin/sh
+
+test_description='Test recording the history of sent patches'
+
+. ./test-lib.sh
+. "$TEST_DIRECTORY"/lib-smtp.sh
+
+if ! test_have_prereq SMTPSINK
+then
+	skip_all='skipping SMTP tests; IO::Socket::INET not available'
+	test_done
+fi
+
+test_expect_success 'Setup StGit stack and SMTP sink' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    start_smtp_sink &&
+    git config stgit.email.transport smtp
+'
+
+test_expect_success 'Unsent patches have no history' '
+    stg series --sent >series &&
+    cat >expected <<-\EOF &&
+	+ p1
+	+ p2
+	> p3
+	EOF
+    test_cmp expected series &&
+    stg series --json >series.json &&
+    ! grep -e "\"message-id\"" series.json
+'
+
+test_expect_success 'Dry run does not record history' '
+    stg email send --dry-run --to to@example.com --all >out &&
+    stg series --sent >series &&
+    test_cmp expected series
+'
+
+test_expect_success 'Sending records history' '
+    stg email send --to to@example.com --quiet p1 p2 &&
+    stg series --sent >series &&
+    cat >expected <<-\EOF &&
+	+ p1 [sent v1]
+	+ p2 [sent v1]
+	> p3
+	EOF
+    test_cmp expected series &&
+    sed -n -e "s/^Message-Id: //p" sink/msg-1 >id1 &&
+    test_line_count = 1 id1 &&
+    stg series --json p1 >series.json &&
+    grep -e "\"message-id\": \"$(cat id1)\"" series.json &&
+    grep -e "\"commit\": \"$(stg id p1)\"" series.json &&
+    grep -e "\"version\": 1" series.json &&
+    git show refs/stacks/master:stack.json >stack.json &&
+    grep -e "\"message-id\": \"$(cat id1)\"" stack.json
+'
+
+test_expect_success 'Changed patches are marked' '
+    stg goto p2 &&
+    echo "more" >>2.t &&
+    stg refresh &&
+    stg goto p3 &&
+    stg series --sent >series &&
+    cat >expected <<-\EOF &&
+	+ p1 [sent v1]
+	+ p2 [sent v1, changed]
+	> p3
+	EOF
+    test_cmp expected series
+'
+
+test_expect_success 'History survives patch operations' '
+    stg rename p1 first &&
+    stg pop -a &&
+    stg push -a &&
+    stg series --sent >series &&
+    grep -e "^+ first \[sent v1\]\$" series &&
+    grep -e "^+ p2  *\[sent v1, changed\]\$" series &&
+    grep -e "^> p3  *\$" series &&
+    stg rename first p1
+'
+
+test_expect_success 'New version is not chained by default' '
+    stg email send --to to@example.com --quiet p1 p2 &&
+    ! grep -e "^In-Reply-To:" sink/msg-3 &&
+    stg series --sent >series &&
+    cat >expected <<-\EOF &&
+	+ p1 [sent v2]
+	+ p2 [sent v2]
+	> p3
+	EOF
+    test_cmp expected series
+'
+
+test_expect_success 'New version is chained to previous version' '
+    test_config stgit.email.chain-versions true &&
+    sed -n -e "s/^Message-Id: //p" sink/msg-3 >id3 &&
+    stg goto p2 &&
+    echo "even more" >>2.t &&
+    stg refresh &&
+    stg goto p3 &&
+    stg email send --to to@example.com --quiet p1 p2 &&
+    grep -e "^Subject: \[PATCH v3 1/2\]" sink/msg-5 &&
+    grep -e "^In-Reply-To: $(cat id3)" sink/msg-5
+'
+
+test_expect_success 'Explicit in-reply-to takes precedence' '
+    test_config stgit.email.chain-versions true &&
+    stg goto p2 &&
+    echo "yet more" >>2.t &&
+    stg refresh &&
+    stg goto p3 &&
+    stg email send --to to@example.com --quiet --in-reply-to "<other@example.com>" p1 p2 &&
+    grep -e "^In-Reply-To: <other@example.com>" sink/msg-7
+'
+
+test_expect_success 'Emails delivered before a failure are recorded' '
+    stg new -m p4 &&
+    echo "4" >4.t &&
+    stg add 4.t &&
+    stg refresh &&
+    write_script fake-sendmail <<-\EOF &&
+	n=$(($(cat sendmail-count 2>/dev/null || echo 0) + 1))
+	echo $n >sendmail-count
+	cat >sendmail-msg-$n
+	test $n -lt 2
+	EOF
+    test_config stgit.email.transport sendmail &&
+    test_config sendemail.sendmailCmd "\"$(pwd)/fake-sendmail\"" &&
+    command_error stg email send --no-snapshot --to to@example.com --quiet p3 p4 2>err &&
+    grep -e "fake-sendmail.* failed" err &&
+    test_path_exists sendmail-msg-2 &&
+    stg series --sent p3 p4 >series &&
+    cat >expected <<-\EOF &&
+	+ p3 [sent]
+	> p4
+	EOF
+    test_cmp expected series
+'
+
+test_expect_success GITSENDEMAIL 'Emails declined at the git send-email prompt are not recorded' '
+    test_config stgit.email.transport git &&
+    printf "n\n" |
+    GIT_SEND_EMAIL_NOTTY=1 stg email send --no-snapshot --confirm=always --to to@example.com p4 &&
+    stg series --sent p4 >series &&
+    echo "> p4" >expected &&
+    test_cmp expected series &&
+    printf "y\n" |
+    GIT_SEND_EMAIL_NOTTY=1 stg email send --no-snapshot --confirm=always --to to@example.com p4 &&
+    stg series --sent p4 >series &&
+    echo "> p4 [sent]" >expected &&
+    test_cmp expected series
+'
+
+test_done
@@ -272,6 +272,132 @@ test_expect_success 'Fixup after hide works correctly' '
     git diff-index --quiet HEAD
 '
 
+test_expect_success 'Reorder applied and unapplied patches' '
+    stg delete $(stg series --all --noprefix --no-description) &&
+    stg new -m p0 &&
+    stg new -m p1 &&
+    stg new -m p2 &&
+    stg new -m p3 &&
+    stg pop p3 &&
+    write_script fake-editor <<-\EOF &&
+	printf "keep p2\nkeep p0\n# --- APPLY_LINE ---\nkeep p3\nkeep p1\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2 p0" &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p3 p1" &&
+    git diff-index --quiet HEAD
+'
+
+test_expect_success 'Reword a patch' '
+    stg push -a &&
+    write_script fake-editor <<-\EOF &&
+	if [ ! -f .fake-editor-has-run-once ]
+	then
+	    printf "keep p2\nkeep p0\nreword p3\nkeep p1\n" >"$1" &&
+	    touch .fake-editor-has-run-once
+	else
+	    ! grep -e "^diff --git" "$1" &&
+	    sed "s/^p3\$/p3 reworded/" "$1" >"$1".tmp && mv "$1".tmp "$1"
+	fi
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    test_when_finished rm -f .fake-editor-has-run-once &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2 p0 p3 p1" &&
+    test "$(git log -1 --format=%s $(stg id p3))" = "p3 reworded"
+'
+
+test_expect_success 'Rename a patch' '
+    write_script fake-editor <<-\EOF &&
+	printf "keep p2\nrename p0 first\nkeep p3\nkeep p1\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2 first p3 p1"
+'
+
+test_expect_success 'Rename to existing patch name throws error' '
+    write_script fake-editor <<-\EOF &&
+	printf "keep p2\nrename first p3\nkeep p3\nkeep p1\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    command_error stg rebase --interactive 2>err &&
+    grep -e "cannot rename \`first\` to \`p3\`: patch \`p3\` already exists" err &&
+    stg push -a
+'
+
+test_expect_success 'Exec commands after pushing patches' '
+    write_script fake-editor <<-\EOF &&
+	cat >"$1" <<-\EOT
+	exec echo start >exec.log
+	keep p2
+	keep first
+	exec stg top >>exec.log # comment is part of command
+	keep p3
+	x stg top >>exec.log
+	keep p1
+	EOT
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(echo $(cat exec.log))" = "start first p3" &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2 first p3 p1"
+'
+
+test_expect_success 'Failed exec command stops rebase' '
+    write_script fake-editor <<-\EOF &&
+	printf "keep p2\nkeep first\nexec false\nkeep p3\nkeep p1\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    command_error stg rebase --interactive 2>err &&
+    grep -e "\`false\` failed" err &&
+    grep -e "stopped at \`first\`; resume with \`stg goto p1\`" err &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2 first" &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p3 p1" &&
+    stg goto p1
+'
+
+test_expect_success 'Break stops rebase' '
+    write_script fake-editor <<-\EOF &&
+	printf "keep p2\nbreak\nkeep first\nkeep p3\n# --- APPLY_LINE ---\nkeep p1\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive 2>err &&
+    grep -e "Stopped at \`p2\`; resume with \`stg goto p3\`" err &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2" &&
+    stg goto p3 &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p1"
+'
+
+test_expect_success 'Exec below APPLY_LINE throws error' '
+    write_script fake-editor <<-\EOF &&
+	printf "keep p2\n# --- APPLY_LINE ---\nkeep p1\nexec true\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    command_error stg rebase --interactive 2>err &&
+    grep -e "exec and break are not allowed below the APPLY_LINE" err
+'
+
+test_expect_success 'Exec command separated by tab' '
+    write_script fake-editor <<-\EOF &&
+	printf "keep p2\n  exec\techo tab >exec.log\nkeep first\nkeep p3\n" >"$1"
+	EOF
+    test_set_editor "$(pwd)/fake-editor" &&
+    test_when_finished test_set_editor false &&
+    stg rebase --interactive &&
+    test "$(cat exec.log)" = "tab" &&
+    test "$(echo $(stg series --noprefix --applied))" = "p2 first p3"
+'
+
 test_expect_success 'No patches exits early' '
     stg delete $(stg series --all --noprefix --no-description) &&
     stg rebase --interactive
@@ -0,0 +1,109 @@
+#!/bin/sh
+
+test_description='Test stg absorb'
+
+. ./test-lib.sh
+
+test_expect_success 'Attempt absorb on uninitialized stack' '
+    command_error stg absorb 2>err &&
+    grep -e "error: no patches applied" err
+'
+
+test_expect_success 'Setup patches' '
+    test_seq 1 30 >file &&
+    echo other >other &&
+    stg add file other &&
+    git commit -m "add files" &&
+    stg new -m "change line 5" p1 &&
+    sed -i "s/^5\$/five/" file &&
+    stg refresh &&
+    stg new -m "change line 20" p2 &&
+    sed -i "s/^20\$/twenty/" file &&
+    stg refresh &&
+    stg new -m "change line 10" p3 &&
+    sed -i "s/^10\$/ten/" file &&
+    stg refresh
+'
+
+test_expect_success 'Attempt absorb without local changes' '
+    command_error stg absorb 2>err &&
+    grep -e "no local changes to absorb" err
+'
+
+test_expect_success 'Dry run shows assignments' '
+    p1_id=$(stg id p1) &&
+    sed -i -e "s/^five\$/FIVE/" -e "s/^twenty\$/TWENTY/" file &&
+    cat >expected <<-\EOF &&
+	file:5 p1
+	file:20 p2
+	EOF
+    stg absorb --dry-run >out &&
+    test_cmp expected out &&
+    test "$(git diff --name-only)" = "file" &&
+    test "$(stg id p1)" = "$p1_id"
+'
+
+test_expect_success 'Absorb modified lines' '
+    stg absorb &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p2 p3" &&
+    git diff --quiet &&
+    git diff --cached --quiet &&
+    stg show p1 | grep -e "^+FIVE\$" &&
+    stg show p2 | grep -e "^+TWENTY\$" &&
+    stg show p3 | grep -e "^+ten\$" &&
+    test "$(git log -1 --format=%s $(stg id p1))" = "change line 5"
+'
+
+test_expect_success 'Absorb is undoable' '
+    stg undo --hard &&
+    stg show p1 | grep -e "^+five\$" &&
+    git diff --quiet &&
+    stg redo --hard &&
+    stg show p1 | grep -e "^+FIVE\$"
+'
+
+test_expect_success 'Absorb added line adjacent to patch line' '
+    sed -i "s/^ten\$/ten\nten and a half/" file &&
+    stg absorb &&
+    git diff --quiet &&
+    stg show p3 | grep -e "^+ten and a half\$"
+'
+
+test_expect_success 'Ambiguous and unowned hunks remain in worktree' '
+    sed -i -e "s/^1\$/one/" -e "s/^FIVE\$/5ive/" file &&
+    echo changed >other &&
+    stg absorb 2>err &&
+    grep -e "2 hunks could not be absorbed and remain in the worktree" err &&
+    stg show p1 | grep -e "^+5ive\$" &&
+    cat >expected <<-\EOF &&
+	file
+	other
+	EOF
+    git diff --name-only >out &&
+    test_cmp expected out &&
+    git diff --cached --quiet &&
+    git diff | grep -e "^+one\$"
+'
+
+test_expect_success 'Attempt absorb with nothing absorbable' '
+    command_error stg absorb 2>err &&
+    grep -e "no hunks could be absorbed" err &&
+    git checkout file other
+'
+
+test_expect_success 'Hunk spanning lines from multiple patches remains' '
+    sed -i -e "4,6d" file &&
+    command_error stg absorb --dry-run >out 2>err &&
+    test_must_be_empty out &&
+    grep -e "1 hunk could not be absorbed and remains in the worktree" err &&
+    git checkout file
+'
+
+test_expect_success 'Attempt absorb with dirty index' '
+    sed -i "s/^5ive\$/five/" file &&
+    stg add file &&
+    command_error stg absorb 2>err &&
+    grep -e "index not clean" err
+'
+
+test_done
@@ -0,0 +1,99 @@
+#!/bin/sh
+
+test_description='Test interactive hunk selection for refresh, new, and spill'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup' '
+    test_seq 1 20 >file &&
+    echo other >other &&
+    stg add file other &&
+    git commit -m "add files" &&
+    stg init &&
+    stg new -m p1 p1
+'
+
+test_expect_success 'Refresh selected hunks' '
+    sed -i -e "s/^2\$/two/" -e "s/^15\$/fifteen/" file &&
+    echo changed >other &&
+    printf "n\ny\ny\n" | stg refresh --interactive >out &&
+    grep -e "Refresh this hunk \[y,n,q,a,d,?\]?" out &&
+    stg files p1 >files &&
+    test "$(cat files)" = "M file
+M other" &&
+    stg show p1 >show &&
+    grep -e "^+fifteen\$" show &&
+    ! grep -e "^+two\$" show &&
+    git diff --cached --quiet &&
+    git diff >diff &&
+    grep -e "^+two\$" diff &&
+    ! grep -e "^+fifteen\$" diff
+'
+
+test_expect_success 'Refresh remaining hunk' '
+    printf "y\n" | stg refresh -I &&
+    git diff --quiet &&
+    stg show p1 | grep -e "^+two\$"
+'
+
+test_expect_success 'Split hunk' '
+    sed -i -e "s/^5\$/five/" -e "s/^9\$/nine/" file &&
+    printf "s\nn\ny\n" | stg refresh -I >out &&
+    grep -e "Split into 2 hunks" out &&
+    stg show p1 >show &&
+    grep -e "^+nine\$" show &&
+    ! grep -e "^+five\$" show &&
+    git diff | grep -e "^+five\$" &&
+    git diff | grep -e "^-5\$"
+'
+
+test_expect_success 'Quit leaves remaining hunks' '
+    sed -i -e "s/^20\$/twenty/" file &&
+    printf "q\n" | stg refresh -I &&
+    git diff | grep -e "^+five\$" &&
+    git diff | grep -e "^+twenty\$" &&
+    git diff --cached --quiet
+'
+
+test_expect_success 'Attempt interactive refresh with dirty index' '
+    stg add file &&
+    command_error stg refresh -I </dev/null 2>err &&
+    grep -e "the index is dirty; consider using \`--force\`" err &&
+    git reset -q file
+'
+
+test_expect_success 'New patch from selected hunks' '
+    printf "y\nn\n" | stg new -I -m p2 p2 &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p2" &&
+    stg show p2 >show &&
+    grep -e "^+five\$" show &&
+    ! grep -e "^+twenty\$" show &&
+    git diff | grep -e "^+twenty\$" &&
+    git checkout file
+'
+
+test_expect_success 'Spill selected hunks' '
+    stg pop &&
+    printf "s\nn\ny\nn\nn\n" | stg spill --interactive >out &&
+    grep -e "Spill this hunk" out &&
+    stg show p1 >show &&
+    grep -e "^+two\$" show &&
+    ! grep -e "^+nine\$" show &&
+    grep -e "^+fifteen\$" show &&
+    git diff --cached | grep -e "^+nine\$" &&
+    grep -e "^nine\$" file
+'
+
+test_expect_success 'Spill split hunk with reset' '
+    git reset -q --hard &&
+    sed -i -e "s/^9\$/nine/" -e "s/^12\$/twelve/" file &&
+    stg refresh &&
+    printf "s\nn\ny\nn\nn\nn\n" | stg spill -I --reset &&
+    stg show p1 >show &&
+    ! grep -e "^+nine\$" show &&
+    grep -e "^+twelve\$" show &&
+    git diff --cached --quiet &&
+    git diff | grep -e "^+nine\$"
+'
+
+test_done
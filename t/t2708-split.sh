@@ -0,0 +1,122 @@
+#!/bin/sh
+
+test_description='Test stg split'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup' '
+    test_seq 1 20 >file &&
+    echo a >a &&
+    echo b >b &&
+    stg add file a b &&
+    git commit -m "add files" &&
+    stg init &&
+    stg new -m p0 p0 &&
+    echo p0 >>b &&
+    stg refresh &&
+    stg new -m "change message" change &&
+    sed -i -e "s/^2\$/two/" -e "s/^15\$/fifteen/" file &&
+    echo a1 >>a &&
+    stg refresh &&
+    stg new -m p2 p2 &&
+    echo p2 >>b &&
+    stg refresh
+'
+
+test_expect_success 'Attempt split with no applied patches' '
+    stg pop -a &&
+    command_error stg split 2>err &&
+    grep -e "no patches applied" err &&
+    stg push -a
+'
+
+test_expect_success 'Attempt split of single file patch by file' '
+    command_error stg split --by-file p2 2>err &&
+    grep -e "must be split into at least two patches" err &&
+    test "$(echo $(stg series --noprefix))" = "p0 change p2"
+'
+
+test_expect_success 'Split by file' '
+    stg split --by-file change &&
+    test "$(echo $(stg series --noprefix --applied))" = "p0 change change-1 p2" &&
+    test "$(stg files change)" = "M a" &&
+    test "$(stg files change-1)" = "M file" &&
+    test "$(git log -1 --format=%B $(stg id change-1))" = "change message" &&
+    test "$(stg top)" = "p2" &&
+    git diff --quiet HEAD
+'
+
+test_expect_success 'Undo split' '
+    stg undo &&
+    test "$(echo $(stg series --noprefix --applied))" = "p0 change p2" &&
+    test "$(echo $(stg files change))" = "M a M file"
+'
+
+test_expect_success 'Split by file with new names and messages' '
+    write_script name-editor <<-\EOF &&
+	n=$(($(cat count 2>/dev/null || echo 0) + 1))
+	echo $n >count
+	sed -i -e "s/^Patch: .*/Patch: part$n/" -e "s/^change message/part $n/" "$1"
+	EOF
+    EDITOR=./name-editor stg split --by-file change &&
+    rm count &&
+    test "$(echo $(stg series --noprefix --applied))" = "p0 part1 part2 p2" &&
+    test "$(git log -1 --format=%s $(stg id part1))" = "part 1" &&
+    test "$(git log -1 --format=%s $(stg id part2))" = "part 2" &&
+    stg undo
+'
+
+test_expect_success 'Split keeps metadata and dependencies on first patch' '
+    stg meta set -p change ticket ABC-123 &&
+    stg meta set -p p2 depends change &&
+    EDITOR=./name-editor stg split --by-file change &&
+    rm count &&
+    test "$(stg meta get -p part1 ticket)" = "ABC-123" &&
+    command_error stg meta get -p part2 ticket &&
+    test "$(stg meta get -p p2 depends)" = "part1" &&
+    stg undo &&
+    stg meta unset -p p2 depends &&
+    stg meta unset -p change ticket
+'
+
+test_expect_success 'Attempt split with local changes' '
+    echo dirty >>b &&
+    test_when_finished "stg reset --hard" &&
+    command_error stg split --by-file change 2>err &&
+    grep -e "worktree not clean" err &&
+    test "$(echo $(stg series --noprefix --applied))" = "p0 change p2"
+'
+
+test_expect_success 'Split by hunk' '
+    printf "n\nn\ny\ny\nn\n" | stg split change >out &&
+    grep -e "Select changes for patch 1" out &&
+    grep -e "Select changes for patch 2" out &&
+    test "$(echo $(stg series --noprefix --applied))" = "p0 change change-1 change-2 p2" &&
+    test "$(stg files change)" = "M file" &&
+    stg show change >show &&
+    grep -e "^+fifteen\$" show &&
+    ! grep -e "^+two\$" show &&
+    test "$(echo $(stg files change-1))" = "M a" &&
+    stg show change-2 | grep -e "^+two\$" &&
+    git diff --quiet HEAD &&
+    stg undo
+'
+
+test_expect_success 'Split unapplied patch by hunk' '
+    stg pop change &&
+    printf "y\nd\n" | stg split change &&
+    test "$(echo $(stg series --noprefix --applied))" = "p0 p2" &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "change change-1" &&
+    test "$(stg files change)" = "M a" &&
+    test "$(stg files change-1)" = "M file" &&
+    stg push -a &&
+    test "$(cat a)" = "a
+a1"
+'
+
+test_expect_success 'Attempt split without selecting any hunks' '
+    command_error stg split change-1 </dev/null 2>err &&
+    grep -e "must be split into at least two patches" err
+'
+
+test_done
@@ -0,0 +1,98 @@
+#!/bin/sh
+
+test_description='Test stg interdiff'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup' '
+    test_seq 1 10 >file &&
+    stg add file &&
+    git commit -m "add file" &&
+    stg init &&
+    stg new -m "first patch" p1 &&
+    echo p1 >a &&
+    stg add a &&
+    stg refresh &&
+    stg new -m "second patch" p2 &&
+    sed -i -e "s/^3\$/three/" file &&
+    stg refresh &&
+    stg new -m "third patch" p3 &&
+    echo p3 >c &&
+    stg add c &&
+    stg refresh &&
+    git rev-parse refs/stacks/master >v1
+'
+
+test_expect_success 'Attempt interdiff with invalid state' '
+    command_error stg interdiff HEAD 2>err &&
+    grep -e "\`HEAD\` is not a stack state" err
+'
+
+test_expect_success 'Interdiff against same state' '
+    stg interdiff $(cat v1) >out &&
+    cat >expected <<-EOF &&
+	1:  $(stg id p1 | cut -c1-7) = 1:  $(stg id p1 | cut -c1-7) p1
+	2:  $(stg id p2 | cut -c1-7) = 2:  $(stg id p2 | cut -c1-7) p2
+	3:  $(stg id p3 | cut -c1-7) = 3:  $(stg id p3 | cut -c1-7) p3
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Setup new series version' '
+    stg goto p2 &&
+    sed -i -e "s/^7\$/seven/" file &&
+    stg refresh &&
+    stg edit -m "second patch, revised" p2 &&
+    stg delete p3 &&
+    stg new -m "new patch" p4 &&
+    echo p4 >d &&
+    stg add d &&
+    stg refresh &&
+    git rev-parse refs/stacks/master >v2
+'
+
+test_expect_success 'Interdiff shows changed, removed, and added patches' '
+    stg interdiff $(cat v1) >out &&
+    grep -e "^1:  [0-9a-f]\{7\} = 1:  [0-9a-f]\{7\} p1\$" out &&
+    grep -e "^2:  [0-9a-f]\{7\} ! 2:  [0-9a-f]\{7\} p2\$" out &&
+    grep -e "^3:  [0-9a-f]\{7\} < -:  ------- p3\$" out &&
+    grep -e "^-:  ------- > 3:  [0-9a-f]\{7\} p4\$" out &&
+    grep -e "^    -    second patch\$" out &&
+    grep -e "^    +    second patch, revised\$" out &&
+    grep -e "^    ++seven\$" out
+'
+
+test_expect_success 'Interdiff with stat only' '
+    stg interdiff --stat $(cat v1) >out &&
+    test_line_count = 4 out
+'
+
+test_expect_success 'Interdiff between two old states' '
+    stg delete p4 &&
+    stg interdiff $(cat v1) $(cat v2) >out &&
+    grep -e "^-:  ------- > 3:  [0-9a-f]\{7\} p4\$" out
+'
+
+test_expect_success 'Rebased patch compares as unchanged' '
+    git rev-parse refs/stacks/master >v3 &&
+    stg id p1 >p1-before &&
+    stg pop -a &&
+    echo base >base &&
+    stg add base &&
+    git commit -m "new base" &&
+    stg push -a &&
+    test "$(stg id p1)" != "$(cat p1-before)" &&
+    stg interdiff $(cat v3) >out &&
+    grep -e "^1:  [0-9a-f]\{7\} = 1:  [0-9a-f]\{7\} p1\$" out &&
+    grep -e "^2:  [0-9a-f]\{7\} = 2:  [0-9a-f]\{7\} p2\$" out
+'
+
+test_expect_success 'Interdiff with unapplied patches' '
+    stg pop p2 &&
+    stg interdiff $(cat v3) >out &&
+    grep -e "^2:  [0-9a-f]\{7\} < -:  ------- p2\$" out &&
+    stg interdiff --all $(cat v3) >out &&
+    grep -e "^2:  [0-9a-f]\{7\} = 2:  [0-9a-f]\{7\} p2\$" out
+'
+
+test_done
@@ -0,0 +1,105 @@
+#!/bin/sh
+
+test_description='Test named checkpoints of the stack state'
+
+. ./test-lib.sh
+
+test_expect_success 'Initialize StGit stack with three patches' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    stg pop
+'
+
+test_expect_success 'Record a checkpoint' '
+    stg checkpoint start 2>err &&
+    grep -e "Recorded checkpoint .start." err &&
+    stg checkpoint --list >list &&
+    echo start >expected &&
+    test_cmp expected list &&
+    test "$(git rev-parse refs/checkpoints/master/start)" = "$(git rev-parse refs/stacks/master)" &&
+    stg log -n 1 >log &&
+    grep -e "checkpoint start" log
+'
+
+test_expect_success 'Existing checkpoint is not replaced' '
+    command_error stg checkpoint start 2>err &&
+    grep -e "checkpoint .start. already exists" err
+'
+
+test_expect_success 'Undo does not count checkpoints' '
+    stg push &&
+    stg checkpoint pushed &&
+    stg undo &&
+    test "$(echo $(stg series))" = "+ p1 > p2 - p3" &&
+    stg redo &&
+    test "$(echo $(stg series))" = "+ p1 + p2 > p3"
+'
+
+test_expect_success 'Undo to a checkpoint' '
+    stg pop -a &&
+    stg delete p1 &&
+    stg new -m "new patch" p4 &&
+    stg undo --to start &&
+    test "$(echo $(stg series))" = "+ p1 > p2 - p3" &&
+    test "$(stg top)" = "p2" &&
+    stg undo &&
+    test "$(echo $(stg series))" = "> p4 - p2 - p3"
+'
+
+test_expect_success 'Reset to a checkpoint' '
+    stg reset --checkpoint pushed &&
+    test "$(echo $(stg series))" = "+ p1 + p2 > p3"
+'
+
+test_expect_success 'Unknown checkpoint' '
+    command_error stg undo --to bogus 2>err &&
+    grep -e "checkpoint .bogus. not found" err &&
+    command_error stg reset --checkpoint bogus 2>err &&
+    grep -e "checkpoint .bogus. not found" err
+'
+
+test_expect_success 'Checkpoint cannot be combined with a commit' '
+    general_error stg reset --checkpoint start HEAD &&
+    general_error stg undo --to start -n 2
+'
+
+test_expect_success 'Replace a checkpoint' '
+    stg pop &&
+    stg checkpoint --force start &&
+    stg push &&
+    stg undo --to start &&
+    test "$(echo $(stg series))" = "+ p1 > p2 - p3"
+'
+
+test_expect_success 'Checkpoints survive clearing the log' '
+    stg log --clear 2>err &&
+    grep -e "checkpoints .pushed., .start. are kept" err &&
+    stg checkpoint --list >list &&
+    test_line_count = 2 list &&
+    stg undo --to pushed &&
+    test "$(echo $(stg series))" = "+ p1 + p2 > p3"
+'
+
+test_expect_success 'Delete a checkpoint' '
+    stg checkpoint --delete start 2>err &&
+    grep -e "Deleted checkpoint .start." err &&
+    test_must_fail git rev-parse --verify -q refs/checkpoints/master/start &&
+    command_error stg checkpoint --delete start 2>err &&
+    grep -e "checkpoint .start. not found" err
+'
+
+test_expect_success 'Checkpoints follow branch rename' '
+    stg branch --rename master renamed &&
+    stg checkpoint --list >list &&
+    echo pushed >expected &&
+    test_cmp expected list &&
+    test_must_fail git rev-parse --verify -q refs/checkpoints/master/pushed &&
+    stg branch --rename renamed master
+'
+
+test_expect_success 'Checkpoints are removed with the stack' '
+    stg branch --cleanup --force &&
+    test_must_fail git rev-parse --verify -q refs/checkpoints/master/pushed
+'
+
+test_done
@@ -0,0 +1,108 @@
+#!/bin/sh
+
+test_description='Test pruning the stack log'
+
+. ./test-lib.sh
+
+log_count () {
+    stg log >log.txt &&
+    test_line_count = "$1" log.txt
+}
+
+test_expect_success 'Initialize StGit stack with a long log' '
+    test_commit_bulk --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    for i in 1 2 3 4
+    do
+        test_tick &&
+        stg pop &&
+        stg push || return 1
+    done &&
+    log_count 10
+'
+
+test_expect_success 'Keep and keep-since require prune' '
+    general_error stg log --keep 3 &&
+    general_error stg log --keep-since now &&
+    general_error stg log --prune --clear
+'
+
+test_expect_success 'Prune with nothing to remove' '
+    stg log >expected &&
+    stg log --prune 2>err &&
+    grep -e "Pruned 0 stack log entries" err &&
+    stg log >log.txt &&
+    test_cmp expected log.txt
+'
+
+test_expect_success 'Prune keeping recent entries since a date' '
+    stg log -n 4 | cut -d " " -f 2- >expected &&
+    stg log --prune --keep 1 --keep-since "$(($test_tick - 60)) -0700" 2>err &&
+    grep -e "Pruned 6 stack log entries" err &&
+    stg log | cut -d " " -f 2- >log.txt &&
+    test_cmp expected log.txt &&
+    test "$(echo $(stg series))" = "+ p1 + p2 > p3"
+'
+
+test_expect_success 'Undo after pruning' '
+    stg undo &&
+    test "$(echo $(stg series))" = "+ p1 > p2 - p3" &&
+    stg redo &&
+    test "$(echo $(stg series))" = "+ p1 + p2 > p3"
+'
+
+test_expect_success 'Checkpoints and snapshots are retained' '
+    stg checkpoint start &&
+    stg snapshot &&
+    stg pop &&
+    stg push &&
+    stg pop &&
+    stg log --prune --keep 2 2>err &&
+    grep -e "Pruned" err &&
+    stg log >log.txt &&
+    grep -e "checkpoint start" log.txt &&
+    test "$(git rev-parse refs/checkpoints/master/start)" = "$(git rev-parse refs/stacks/master.versions/v1)" &&
+    test "$(git log -1 --format=%s refs/checkpoints/master/start^)" = "checkpoint start" &&
+    stg undo --to start &&
+    test "$(echo $(stg series))" = "+ p1 + p2 > p3"
+'
+
+test_expect_success 'Prune with configured policy' '
+    test_config stgit.log.keep 1 &&
+    stg log --prune 2>err &&
+    grep -e "Pruned 2 stack log entries" err &&
+    log_count 2 &&
+    stg log -n 1 >log.txt &&
+    grep -e "undo --to start" log.txt
+'
+
+test_expect_success 'Automatic pruning' '
+    test_config stgit.log.auto 3 &&
+    test_config stgit.log.keep 2 &&
+    stg pop &&
+    log_count 3 &&
+    stg push &&
+    log_count 3 &&
+    stg log -n 1 >log.txt &&
+    grep -e "push" log.txt
+'
+
+test_expect_success 'Automatic pruning retains configured number of entries' '
+    test_config stgit.log.auto 2 &&
+    test_config stgit.log.keep 4 &&
+    stg pop &&
+    stg push &&
+    stg pop &&
+    stg push &&
+    log_count 5 &&
+    stg log >log.txt &&
+    grep -e "checkpoint start" log.txt
+'
+
+test_expect_success 'Invalid configured policy' '
+    test_config stgit.log.keep-since bogus &&
+    command_error stg log --prune 2>err &&
+    grep -e "invalid stgit.log.keep-since" err
+'
+
+test_done
@@ -0,0 +1,124 @@
+#!/bin/sh
+
+test_description='Test "stg meta"'
+
+. ./test-lib.sh
+
+test_expect_success 'Attempt meta on uninitialized stack' '
+    command_error stg meta list 2>err &&
+    grep -e "error: no patches applied" err
+'
+
+test_expect_success 'Attempt meta without subcommand' '
+    general_error stg meta 2>err
+'
+
+test_expect_success 'Add some patches' '
+    test_commit_bulk --start=0 --message="p%s" 3 &&
+    stg uncommit -n 3 &&
+    stg rename p2 top &&
+    stg rename p1 middle &&
+    stg rename p0 bottom
+'
+
+test_expect_success 'Empty metadata' '
+    stg meta list >out &&
+    test_must_be_empty out &&
+    command_error stg meta get status 2>err &&
+    grep -e "patch \`top\` does not have metadata \`status\`" err
+'
+
+test_expect_success 'Attempt invalid keys' '
+    general_error stg meta set "" value 2>err &&
+    grep -e "key must not be empty" err &&
+    general_error stg meta set -foo value 2>err &&
+    general_error stg meta set "foo bar" value 2>err &&
+    grep -e "key may only contain" err
+'
+
+test_expect_success 'Attempt invalid values' '
+    general_error stg meta set notes "$(printf "line1\nMeta:   forged=value")" 2>err &&
+    grep -e "value must not contain control characters" err &&
+    general_error stg meta set notes "$(printf "tab\tseparated")" 2>err &&
+    grep -e "value must not contain control characters" err &&
+    stg meta list >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Set metadata on topmost patch' '
+    stg meta set status needs-review &&
+    stg meta set ticket ABC-123 &&
+    test "$(stg meta get status)" = "needs-review" &&
+    test "$(stg meta get ticket)" = "ABC-123" &&
+    cat >expected <<-\EOF &&
+	status=needs-review
+	ticket=ABC-123
+	EOF
+    stg meta list >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Set metadata on other patch' '
+    stg meta set -p bottom notes "some free-form notes" &&
+    test "$(stg meta get --patch=bottom notes)" = "some free-form notes" &&
+    stg meta list -p middle >out &&
+    test_must_be_empty out
+'
+
+test_expect_success 'Overwrite metadata' '
+    stg meta set status reviewed &&
+    test "$(stg meta get status)" = "reviewed"
+'
+
+test_expect_success 'Metadata in stack log' '
+    stg log -n1 | grep -e "meta set top status" &&
+    stg log top | grep -e "meta set top ticket"
+'
+
+test_expect_success 'Undo and redo metadata change' '
+    stg undo &&
+    test "$(stg meta get status)" = "needs-review" &&
+    stg redo &&
+    test "$(stg meta get status)" = "reviewed"
+'
+
+test_expect_success 'Metadata survives pop, push, and edit' '
+    stg pop -a &&
+    stg push -a &&
+    stg edit -m "new message" bottom &&
+    test "$(stg meta get -p bottom notes)" = "some free-form notes" &&
+    test "$(stg meta get status)" = "reviewed"
+'
+
+test_expect_success 'Metadata survives reordering' '
+    stg float bottom &&
+    test "$(stg meta get notes)" = "some free-form notes" &&
+    stg sink bottom &&
+    test "$(stg meta get -p top ticket)" = "ABC-123"
+'
+
+test_expect_success 'Metadata follows rename' '
+    stg rename top renamed &&
+    test "$(stg meta get -p renamed ticket)" = "ABC-123" &&
+    stg rename renamed top
+'
+
+test_expect_success 'Show metadata in series' '
+    cat >expected <<-\EOF &&
+	+ bottom notes=some free-form notes
+	+ middle
+	> top    status=reviewed ticket=ABC-123
+	EOF
+    stg series --meta notes --meta status --meta ticket >out &&
+    test_cmp expected out
+'
+
+test_expect_success 'Unset metadata' '
+    command_error stg meta unset ticket bogus 2>err &&
+    grep -e "patch \`top\` does not have metadata \`bogus\`" err &&
+    stg meta unset ticket status &&
+    stg meta list >out &&
+    test_must_be_empty out
+'
+
+test_done
@@ -0,0 +1,108 @@
+#!/bin/sh
+
+test_description='Test stg bisect run'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup' '
+    echo good >status &&
+    stg add status &&
+    git commit -m "add status" &&
+    stg init &&
+    for i in 1 2 3 4 5 6 7; do
+        stg new -m p$i p$i &&
+        echo $i >>file &&
+        stg add file &&
+        stg refresh || return 1
+    done &&
+    stg goto p5 &&
+    echo bad >status &&
+    stg refresh &&
+    stg goto p7 &&
+    write_script check <<-\EOF
+	test "$(cat status)" = good
+	EOF
+'
+
+test_expect_success 'Attempt bisect with no applied patches' '
+    stg pop -a &&
+    command_error stg bisect run ./check 2>err &&
+    grep -e "no patches applied" err &&
+    stg push -a
+'
+
+test_expect_success 'Bisect applied patches' '
+    stg bisect run ./check >out &&
+    grep -e "^\`p5\` is the first failing patch" out &&
+    test "$(stg top)" = "p7" &&
+    test "$(cat status)" = "bad" &&
+    git diff --quiet HEAD
+'
+
+test_expect_success 'Bisect with shell command' '
+    stg bisect run "test \"\$(cat status)\" = good" >out &&
+    grep -e "^\`p5\` is the first failing patch" out &&
+    test "$(stg top)" = "p7"
+'
+
+test_expect_success 'Bisect with command arguments' '
+    stg bisect run grep -q good status >out &&
+    grep -e "^\`p5\` is the first failing patch" out
+'
+
+test_expect_success 'Bisect patch range including unapplied patches' '
+    stg goto p3 &&
+    stg bisect run --range p2..p6 ./check >out &&
+    grep -e "^\`p5\` is the first failing patch" out &&
+    test "$(echo $(stg series --noprefix --applied))" = "p1 p2 p3" &&
+    test "$(echo $(stg series --noprefix --unapplied))" = "p4 p5 p6 p7"
+'
+
+test_expect_success 'Bisect when all patches in range fail' '
+    stg bisect run -r p5..p7 ./check >out &&
+    grep -e "^\`p5\` is the first failing patch" out &&
+    test "$(stg top)" = "p3"
+'
+
+test_expect_success 'Attempt bisect when last patch succeeds' '
+    command_error stg bisect run -r p1..p4 ./check 2>err &&
+    grep -e "command succeeds for the last patch in the range, \`p4\`" err &&
+    test "$(stg top)" = "p3"
+'
+
+test_expect_success 'Aborted bisect restores original top' '
+    stg goto p7 &&
+    command_error stg bisect run "exit 200" 2>err &&
+    grep -e "\`exit 200\` failed (exit status: 200) at \`p7\`; bisect aborted" err &&
+    test "$(stg top)" = "p7"
+'
+
+test_expect_success 'Bisect skips untestable patches' '
+    write_script check-skip <<-\EOF &&
+	test "$(wc -l <file)" -eq 6 && exit 125
+	test "$(cat status)" = good
+	EOF
+    stg bisect run ./check-skip >out 2>err &&
+    grep -e "^\`p5\` is the first failing patch" out &&
+    grep -e "Skipping \`p6\`" err &&
+    test "$(stg top)" = "p7"
+'
+
+test_expect_success 'Bisect with only skipped patches left' '
+    write_script check-skip-all <<-\EOF &&
+	test "$(wc -l <file)" -eq 7 || exit 125
+	exit 1
+	EOF
+    command_error stg bisect run -r p4..p7 ./check-skip-all 2>err &&
+    grep -e "the first failing patch could be any of: \`p4\`, \`p5\`, \`p6\`, \`p7\`" err &&
+    test "$(stg top)" = "p7"
+'
+
+test_expect_success 'Attempt bisect with dirty worktree' '
+    echo dirty >>file &&
+    command_error stg bisect run ./check 2>err &&
+    grep -e "worktree not clean" err &&
+    git checkout file
+'
+
+test_done
@@ -0,0 +1,133 @@
+#!/bin/sh
+
+test_description='Test stg exec'
+
+. ./test-lib.sh
+
+test_expect_success 'Setup' '
+    echo good >status &&
+    stg add status &&
+    git commit -m "add status" &&
+    stg init &&
+    for i in 1 2 3 4; do
+        stg new -m p$i p$i &&
+        echo $i >>file &&
+        stg add file &&
+        stg refresh || return 1
+    done &&
+    stg goto p2 &&
+    echo bad >status &&
+    stg refresh &&
+    stg goto p3 &&
+    echo good >status &&
+    stg refresh &&
+    stg goto p4
+'
+
+test_expect_success 'Attempt exec with no applied patches' '
+    stg pop -a &&
+    command_error stg exec true 2>err &&
+    grep -e "no patches applied" err &&
+    stg push -a
+'
+
+test_expect_success 'Exec passing command' '
+    stg exec "test -f status" >out &&
+    cat >expected <<-\EOF &&
+	pass p1
+	pass p2
+	pass p3
+	pass p4
+	EOF
+    test_cmp expected out
+'
+
+test_expect_success 'Exec failing command' '
+    command_error stg exec "test \"\$(cat status)\" = good" >out 2>err &&
+    cat >expected <<-\EOF &&
+	pass p1
+	fail p2 (exit status: 1)
+	pass p3
+	pass p4
+	EOF
+    test_cmp expected out &&
+    grep -e "failed for 1 of 4 patches" err
+'
+
+test_expect_success 'Exec uses each patch tree' '
+    stg exec "echo \$(wc -l <file) >>\"$(pwd)/lines\"" &&
+    test "$(echo $(cat lines))" = "1 2 3 4"
+'
+
+test_expect_success 'Exec runs command in linked worktree' '
+    stg exec "git rev-parse HEAD >>\"$(pwd)/seq-heads\"" &&
+    for p in p1 p2 p3 p4; do
+        grep -e "$(stg id $p)" seq-heads || return 1
+    done &&
+    test "$(git rev-parse HEAD)" = "$(stg id p4)" &&
+    test -z "$(git worktree list --porcelain | grep -e "^worktree .*exec-")"
+'
+
+test_expect_success 'Exec with command arguments' '
+    command_error stg exec grep -q good status >out &&
+    grep -e "^fail p2" out
+'
+
+test_expect_success 'Exec does not touch worktree or stack' '
+    echo dirty >>file &&
+    stg exec "echo changed >file && rm status" &&
+    test "$(tail -n 1 file)" = "dirty" &&
+    test "$(cat status)" = "good" &&
+    test "$(stg top)" = "p4" &&
+    git checkout file
+'
+
+test_expect_success 'Exec with patch range' '
+    stg pop p3 p4 &&
+    stg exec -r p3..p4 "test \$(wc -l <file) -ge 3" >out &&
+    cat >expected <<-\EOF &&
+	pass p3
+	pass p4
+	EOF
+    test_cmp expected out &&
+    test "$(stg top)" = "p2" &&
+    stg push -a
+'
+
+test_expect_success 'Exec with record' '
+    command_error stg exec --record tested "test \"\$(cat status)\" = good" &&
+    test "$(stg meta -p p1 get tested)" = "pass" &&
+    test "$(stg meta -p p2 get tested)" = "fail" &&
+    test "$(stg meta -p p4 get tested)" = "pass"
+'
+
+test_expect_success 'Attempt exec with invalid record key' '
+    general_error stg exec --record "bad key" true 2>err &&
+    grep -e "key may only contain ASCII letters" err
+'
+
+test_expect_success 'Exec in parallel' '
+    command_error stg exec -j 3 "test \"\$(cat status)\" = good" >out 2>err &&
+    grep -e "^pass p1\$" out &&
+    grep -e "^fail p2 (exit status: 1)\$" out &&
+    grep -e "^pass p3\$" out &&
+    grep -e "^pass p4\$" out &&
+    grep -e "failed for 1 of 4 patches" err &&
+    test -z "$(git worktree list --porcelain | grep -e "^worktree .*exec-")" &&
+    ! test -e .git/stgit/worktrees/exec-*
+'
+
+test_expect_success 'Exec in parallel reports output in stack order' '
+    stg exec -j 4 "echo lines \$(wc -l <file)" >out &&
+    test "$(grep -e "^lines" out | tr -d " ")" = "$(printf "lines1\nlines2\nlines3\nlines4")"
+'
+
+test_expect_success 'Exec in parallel with linked worktree checkout' '
+    stg exec --jobs 2 "git rev-parse HEAD >>\"$(pwd)/heads\"" &&
+    for p in p1 p2 p3 p4; do
+        grep -e "$(stg id $p)" heads || return 1
+    done &&
+    test "$(git rev-parse HEAD)" = "$(stg id p4)"
+'
+
+test_done